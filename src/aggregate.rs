@@ -0,0 +1,151 @@
+use crate::{constant::Constant, scan::Scan, transaction::Transaction};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum AggregateKind {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl AggregateKind {
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            AggregateKind::Count => "count",
+            AggregateKind::Sum => "sum",
+            AggregateKind::Avg => "avg",
+            AggregateKind::Min => "min",
+            AggregateKind::Max => "max",
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "count" => Some(AggregateKind::Count),
+            "sum" => Some(AggregateKind::Sum),
+            "avg" => Some(AggregateKind::Avg),
+            "min" => Some(AggregateKind::Min),
+            "max" => Some(AggregateKind::Max),
+            _ => None,
+        }
+    }
+}
+
+/// An aggregate function applied to a field, or to whole records for
+/// `COUNT(*)`, which has no field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct AggregateFn {
+    pub(crate) kind: AggregateKind,
+    pub(crate) field: Option<String>,
+}
+
+impl AggregateFn {
+    pub(crate) fn new(kind: AggregateKind, field: Option<&str>) -> Self {
+        Self {
+            kind,
+            field: field.map(|f| f.to_string()),
+        }
+    }
+
+    /// The name of the field holding the result, which is the text of the
+    /// call itself, such as `count(*)` or `sum(B)`.
+    pub(crate) fn field_name(&self) -> String {
+        format!(
+            "{}({})",
+            self.kind.name(),
+            self.field.as_deref().unwrap_or("*")
+        )
+    }
+}
+
+/// Accumulates an aggregate over the records of a group. NULL values are
+/// skipped; over no values SUM, AVG, MIN and MAX are NULL and COUNT is 0.
+/// AVG is the integer quotient of the sum and the count.
+#[derive(Debug, Clone)]
+pub(crate) struct Aggregator {
+    function: AggregateFn,
+    count: i64,
+    sum: i64,
+    best: Constant,
+}
+
+impl Aggregator {
+    pub(crate) fn new(function: AggregateFn) -> Self {
+        Self {
+            function,
+            count: 0,
+            sum: 0,
+            best: Constant::Null,
+        }
+    }
+
+    pub(crate) fn function(&self) -> &AggregateFn {
+        &self.function
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.count = 0;
+        self.sum = 0;
+        self.best = Constant::Null;
+    }
+
+    /// Adds the current record of the scan.
+    pub(crate) fn add(
+        &mut self,
+        scan: &mut dyn Scan,
+        tx: &mut Transaction,
+    ) -> Result<(), std::io::Error> {
+        let Some(fldname) = &self.function.field else {
+            self.count += 1;
+            return Ok(());
+        };
+        let value = scan.get_val(tx, fldname)?;
+        if value.is_null() {
+            return Ok(());
+        }
+        self.count += 1;
+        match self.function.kind {
+            AggregateKind::Count => {}
+            AggregateKind::Sum | AggregateKind::Avg => {
+                let Constant::Int(i) = value else {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        format!(
+                            "{} of non-integer value {}",
+                            self.function.field_name(),
+                            value
+                        ),
+                    ));
+                };
+                self.sum = self.sum.checked_add(i).ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("{} overflows", self.function.field_name()),
+                    )
+                })?;
+            }
+            AggregateKind::Min => {
+                if self.best.is_null() || value < self.best {
+                    self.best = value;
+                }
+            }
+            AggregateKind::Max => {
+                if value > self.best {
+                    self.best = value;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn value(&self) -> Constant {
+        match self.function.kind {
+            AggregateKind::Count => Constant::Int(self.count),
+            _ if self.count == 0 => Constant::Null,
+            AggregateKind::Sum => Constant::Int(self.sum),
+            AggregateKind::Avg => Constant::Int(self.sum / self.count),
+            AggregateKind::Min | AggregateKind::Max => self.best.clone(),
+        }
+    }
+}
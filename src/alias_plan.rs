@@ -0,0 +1,79 @@
+use crate::{
+    alias_scan::AliasScan, constant::Constant, plan::Plan, scan::Scan, schema::Schema,
+    transaction::Transaction,
+};
+
+/// The records of a table or derived table of the FROM clause, whose fields
+/// can also be referred to as `alias.field`.
+pub(crate) struct AliasPlan {
+    plan: Box<dyn Plan>,
+    alias: String,
+    schema: Schema,
+}
+
+impl AliasPlan {
+    pub(crate) fn new(plan: Box<dyn Plan>, alias: &str) -> Self {
+        let schema = aliased_schema(plan.schema(), alias);
+        Self {
+            plan,
+            alias: alias.to_string(),
+            schema,
+        }
+    }
+
+    /// The field of the underlying plan that the name refers to.
+    fn resolve<'f>(&self, fldname: &'f str) -> &'f str {
+        match fldname.split_once('.') {
+            Some((alias, field)) if alias == self.alias => field,
+            _ => fldname,
+        }
+    }
+}
+
+impl Plan for AliasPlan {
+    fn open(&self, tx: &mut Transaction) -> Result<Box<dyn Scan>, std::io::Error> {
+        let scan = self.plan.open(tx)?;
+        Ok(Box::new(AliasScan::new(scan, &self.alias)))
+    }
+
+    fn blocks_accessed(&self) -> u64 {
+        self.plan.blocks_accessed()
+    }
+
+    fn records_output(&self) -> u64 {
+        self.plan.records_output()
+    }
+
+    fn distinct_values(&self, fldname: &str) -> u64 {
+        self.plan.distinct_values(self.resolve(fldname))
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn eq_selectivity(&self, fldname: &str, value: &Constant) -> f64 {
+        self.plan.eq_selectivity(self.resolve(fldname), value)
+    }
+
+    fn range_selectivity(
+        &self,
+        fldname: &str,
+        low: Option<&Constant>,
+        high: Option<&Constant>,
+    ) -> f64 {
+        self.plan
+            .range_selectivity(self.resolve(fldname), low, high)
+    }
+}
+
+/// The fields of the schema, each also under its name qualified by the
+/// alias.
+pub(crate) fn aliased_schema(schema: &Schema, alias: &str) -> Schema {
+    let mut result = schema.clone();
+    for fldname in schema.fields() {
+        let qualified = format!("{}.{}", alias, fldname);
+        result.add_field(&qualified, schema.ctype(fldname), schema.length(fldname));
+    }
+    result
+}
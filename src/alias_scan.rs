@@ -0,0 +1,137 @@
+use crate::{
+    constant::Constant,
+    overflow::LargeValue,
+    query_data::QueryData,
+    rid::Rid,
+    scan::{Scan, UpdateScan},
+    transaction::Transaction,
+};
+
+/// The records of a table's scan, whose fields can also be referred to as
+/// `alias.field`.
+pub(crate) struct AliasScan<S: Scan> {
+    scan: S,
+    alias: String,
+}
+
+impl<S: Scan> AliasScan<S> {
+    pub(crate) fn new(scan: S, alias: &str) -> Self {
+        Self {
+            scan,
+            alias: alias.to_string(),
+        }
+    }
+
+    /// The field of the underlying scan that the name refers to.
+    fn resolve<'f>(&self, fldname: &'f str) -> &'f str {
+        match fldname.split_once('.') {
+            Some((alias, field)) if alias == self.alias => field,
+            _ => fldname,
+        }
+    }
+}
+
+impl<S: Scan> Scan for AliasScan<S> {
+    fn before_first(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        self.scan.before_first(tx)
+    }
+
+    fn next(&mut self, tx: &mut Transaction) -> Result<bool, std::io::Error> {
+        self.scan.next(tx)
+    }
+
+    fn get_int(&mut self, tx: &mut Transaction, fldname: &str) -> Result<i64, std::io::Error> {
+        let field = self.resolve(fldname);
+        self.scan.get_int(tx, field)
+    }
+
+    fn get_string(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+    ) -> Result<String, std::io::Error> {
+        let field = self.resolve(fldname);
+        self.scan.get_string(tx, field)
+    }
+
+    fn get_val(&mut self, tx: &mut Transaction, fldname: &str) -> Result<Constant, std::io::Error> {
+        let field = self.resolve(fldname);
+        self.scan.get_val(tx, field)
+    }
+
+    fn has_field(&self, fldname: &str) -> bool {
+        match fldname.split_once('.') {
+            Some((alias, field)) => alias == self.alias && self.scan.has_field(field),
+            None => self.scan.has_field(fldname),
+        }
+    }
+
+    fn get_large(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+    ) -> Result<Option<LargeValue>, std::io::Error> {
+        let field = self.resolve(fldname);
+        self.scan.get_large(tx, field)
+    }
+
+    fn close(&mut self, tx: &mut Transaction) {
+        self.scan.close(tx)
+    }
+
+    fn subquery_values(
+        &mut self,
+        tx: &mut Transaction,
+        query: &QueryData,
+    ) -> Result<Vec<Constant>, std::io::Error> {
+        self.scan.subquery_values(tx, query)
+    }
+}
+
+impl<S: UpdateScan> UpdateScan for AliasScan<S> {
+    fn set_int(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+        value: i64,
+    ) -> Result<(), std::io::Error> {
+        let field = self.resolve(fldname);
+        self.scan.set_int(tx, field, value)
+    }
+
+    fn set_string(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+        value: &str,
+    ) -> Result<(), std::io::Error> {
+        let field = self.resolve(fldname);
+        self.scan.set_string(tx, field, value)
+    }
+
+    fn set_val(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+        value: &Constant,
+    ) -> Result<(), std::io::Error> {
+        let field = self.resolve(fldname);
+        self.scan.set_val(tx, field, value)
+    }
+
+    fn insert(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        self.scan.insert(tx)
+    }
+
+    fn delete(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        self.scan.delete(tx)
+    }
+
+    fn get_rid(&self) -> Rid {
+        self.scan.get_rid()
+    }
+
+    fn move_to_rid(&mut self, tx: &mut Transaction, rid: Rid) -> Result<(), std::io::Error> {
+        self.scan.move_to_rid(tx, rid)
+    }
+}
@@ -0,0 +1,49 @@
+use crate::{
+    join_plan::JoinPlan,
+    plan::Plan,
+    planner::{check_fields, PlanContext, QueryPlanner},
+    predicate::Predicate,
+    product_plan::{combined_schema, ProductPlan},
+    query_data::FromItem,
+};
+
+/// Plans a FROM clause the way it is written: each item with its joins in
+/// order, the product of the items left to right, and a selection over all
+/// of it. The plans can be far from the cheapest, but they are easy to
+/// follow.
+pub(crate) struct BasicQueryPlanner;
+
+impl QueryPlanner for BasicQueryPlanner {
+    fn plan_from(
+        &self,
+        from: &[FromItem],
+        predicate: &Predicate,
+        ctx: &PlanContext,
+    ) -> Result<Box<dyn Plan>, std::io::Error> {
+        let mut plan: Option<Box<dyn Plan>> = None;
+        for item in from {
+            let item_plan = plan_item(item, ctx)?;
+            plan = Some(match plan {
+                Some(plan) => Box::new(ProductPlan::new(plan, item_plan)),
+                None => item_plan,
+            });
+        }
+        let plan = plan.expect("a FROM clause has at least one item");
+        ctx.select(plan, predicate)
+    }
+}
+
+/// The table of the FROM item joined to each of its joined tables in turn.
+pub(crate) fn plan_item(
+    item: &FromItem,
+    ctx: &PlanContext,
+) -> Result<Box<dyn Plan>, std::io::Error> {
+    let mut plan = ctx.plan_table(&item.table)?;
+    for join in &item.joins {
+        let right = ctx.plan_table(&join.table)?;
+        let schema = combined_schema(plan.schema(), right.schema());
+        check_fields(&join.on.fields(), &schema)?;
+        plan = Box::new(JoinPlan::new(plan, right, join.kind, join.on.clone()));
+    }
+    Ok(plan)
+}
@@ -0,0 +1,309 @@
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{
+    command::{
+        AlterAction, AlterTableData, AnalyzeData, CreateIndexData, CreateTableData, CreateViewData,
+        DeleteData, DropIndexData, DropTableData, DropViewData, InsertData, ModifyData,
+    },
+    constant::Constant,
+    expression::Expression,
+    metadata_manager::MetadataManager,
+    planner::{check_fields, plan_query, PlanContext, UpdatePlanner},
+    predicate::Predicate,
+    rid::Rid,
+    scan::{Scan, ScanRow, UpdateScan},
+    select_scan::SelectScan,
+    sql_exception::SQLException,
+    subquery_scan::{SubqueryRunner, SubqueryScan},
+    table_manager::{check_name, MAX_NAME},
+    table_scan::TableScan,
+    table_writer::TableWriter,
+    transaction::Transaction,
+};
+
+/// Runs a command by finding the records it changes with a scan of the
+/// whole table, then changing them through a [`TableWriter`], which checks
+/// the constraints and keeps the indexes up to date.
+pub(crate) struct BasicUpdatePlanner;
+
+impl UpdatePlanner for BasicUpdatePlanner {
+    fn execute_insert(
+        &self,
+        data: &InsertData,
+        _ctx: &PlanContext,
+        tx: &mut Transaction,
+        mdm: &mut MetadataManager,
+    ) -> Result<u64, SQLException> {
+        let values = data
+            .fields
+            .iter()
+            .cloned()
+            .zip(data.values.iter().cloned())
+            .collect::<HashMap<_, _>>();
+        let mut writer = TableWriter::new(&data.tblname, mdm, tx)?;
+        if let Err(e) = writer.insert(tx, &values) {
+            writer.close(tx);
+            return Err(e);
+        }
+        writer.finish(tx)?;
+        mdm.record_updates(1);
+        Ok(1)
+    }
+
+    fn execute_delete(
+        &self,
+        data: &DeleteData,
+        ctx: &PlanContext,
+        tx: &mut Transaction,
+        mdm: &mut MetadataManager,
+    ) -> Result<u64, SQLException> {
+        let found = find_records(&data.tblname, &data.predicate, &[], ctx, tx, mdm)?;
+        let mut writer = TableWriter::new(&data.tblname, mdm, tx)?;
+        for (rid, _) in &found {
+            if let Err(e) = writer.delete(tx, *rid) {
+                writer.close(tx);
+                return Err(e);
+            }
+        }
+        writer.finish(tx)?;
+        mdm.record_updates(found.len() as u64);
+        Ok(found.len() as u64)
+    }
+
+    fn execute_modify(
+        &self,
+        data: &ModifyData,
+        ctx: &PlanContext,
+        tx: &mut Transaction,
+        mdm: &mut MetadataManager,
+    ) -> Result<u64, SQLException> {
+        let found = find_records(
+            &data.tblname,
+            &data.predicate,
+            &data.assignments,
+            ctx,
+            tx,
+            mdm,
+        )?;
+        let mut writer = TableWriter::new(&data.tblname, mdm, tx)?;
+        for (rid, changes) in &found {
+            if let Err(e) = writer.update(tx, *rid, changes) {
+                writer.close(tx);
+                return Err(e);
+            }
+        }
+        writer.finish(tx)?;
+        mdm.record_updates(found.len() as u64);
+        Ok(found.len() as u64)
+    }
+
+    fn execute_create_table(
+        &self,
+        data: &CreateTableData,
+        _ctx: &PlanContext,
+        tx: &mut Transaction,
+        mdm: &mut MetadataManager,
+    ) -> Result<u64, SQLException> {
+        check_unused(&data.tblname, tx, mdm)?;
+        mdm.create_table(&data.tblname, &data.schema, tx)?;
+        for (fldname, expr) in &data.defaults {
+            mdm.set_default(&data.tblname, fldname, Some(&expr.to_string()), tx)?;
+        }
+        for (i, constraint) in data.constraints.iter().enumerate() {
+            let mut constraint = constraint.clone();
+            if constraint.name.is_empty() {
+                constraint.name = constraint_name(&data.tblname, i);
+            }
+            mdm.add_constraint(&data.tblname, &constraint, tx)?;
+        }
+        Ok(0)
+    }
+
+    fn execute_create_view(
+        &self,
+        data: &CreateViewData,
+        ctx: &PlanContext,
+        tx: &mut Transaction,
+        mdm: &mut MetadataManager,
+    ) -> Result<u64, SQLException> {
+        check_name(&data.viewname)?;
+        check_unused(&data.viewname, tx, mdm)?;
+        // A view is planned when it is used, but one that cannot be is
+        // rejected now
+        plan_query(&data.query, ctx)?;
+        mdm.create_view(&data.viewname, &data.view_def(), tx)?;
+        Ok(0)
+    }
+
+    fn execute_create_index(
+        &self,
+        data: &CreateIndexData,
+        _ctx: &PlanContext,
+        tx: &mut Transaction,
+        mdm: &mut MetadataManager,
+    ) -> Result<u64, SQLException> {
+        mdm.create_index(
+            &data.idxname,
+            &data.tblname,
+            &data.fldname,
+            data.index_type,
+            tx,
+        )?;
+        Ok(0)
+    }
+    fn execute_drop_table(
+        &self,
+        data: &DropTableData,
+        _ctx: &PlanContext,
+        tx: &mut Transaction,
+        mdm: &mut MetadataManager,
+    ) -> Result<u64, SQLException> {
+        mdm.drop_table(&data.tblname, tx)?;
+        Ok(0)
+    }
+
+    fn execute_drop_view(
+        &self,
+        data: &DropViewData,
+        _ctx: &PlanContext,
+        tx: &mut Transaction,
+        mdm: &mut MetadataManager,
+    ) -> Result<u64, SQLException> {
+        mdm.drop_view(&data.viewname, tx)?;
+        Ok(0)
+    }
+
+    fn execute_drop_index(
+        &self,
+        data: &DropIndexData,
+        _ctx: &PlanContext,
+        tx: &mut Transaction,
+        mdm: &mut MetadataManager,
+    ) -> Result<u64, SQLException> {
+        mdm.drop_index(&data.idxname, tx)?;
+        Ok(0)
+    }
+
+    fn execute_alter_table(
+        &self,
+        data: &AlterTableData,
+        _ctx: &PlanContext,
+        tx: &mut Transaction,
+        mdm: &mut MetadataManager,
+    ) -> Result<u64, SQLException> {
+        let tblname = &data.tblname;
+        match &data.action {
+            AlterAction::AddColumn {
+                fldname,
+                ctype,
+                length,
+                default,
+            } => mdm.add_column(tblname, fldname, *ctype, *length, default.clone(), tx)?,
+            AlterAction::DropColumn(fldname) => mdm.drop_column(tblname, fldname, tx)?,
+            AlterAction::RenameColumn { old, new } => mdm.rename_column(tblname, old, new, tx)?,
+            AlterAction::RenameTable(new) => {
+                // A view may not share the new name either
+                check_unused(new, tx, mdm)?;
+                mdm.rename_table(tblname, new, tx)?
+            }
+        }
+        Ok(0)
+    }
+
+    fn execute_analyze(
+        &self,
+        data: &AnalyzeData,
+        _ctx: &PlanContext,
+        tx: &mut Transaction,
+        mdm: &mut MetadataManager,
+    ) -> Result<u64, SQLException> {
+        mdm.analyze(data.tblname.as_deref(), tx)?;
+        Ok(0)
+    }
+}
+
+/// A record to change and the values of its fields that change.
+type Change = (Rid, HashMap<String, Constant>);
+
+/// The records of the table that satisfy the predicate, with the values the
+/// assignments give their fields. They are all found before any is changed,
+/// so that the subqueries of the command see the table as it was.
+fn find_records(
+    tblname: &str,
+    predicate: &Predicate,
+    assignments: &[(String, Expression)],
+    ctx: &PlanContext,
+    tx: &mut Transaction,
+    mdm: &mut MetadataManager,
+) -> Result<Vec<Change>, SQLException> {
+    let layout = mdm.get_layout(tblname, tx)?.ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("table {} does not exist", tblname),
+        )
+    })?;
+    let mut fields = predicate.fields();
+    let mut subqueries = predicate.subqueries();
+    for (fldname, expr) in assignments {
+        fields.push(fldname.clone());
+        expr.collect_fields(&mut fields);
+        expr.collect_subqueries(&mut subqueries);
+    }
+    for subquery in subqueries {
+        fields.extend(ctx.outer_fields(subquery)?);
+    }
+    check_fields(&fields, layout.schema())?;
+
+    let ts = TableScan::new(tx, tblname, layout)?;
+    let mut scan = SelectScan::new(
+        SubqueryScan::new(ts, Rc::new(ctx.clone())),
+        predicate.clone(),
+    );
+    let result = collect_changes(&mut scan, assignments, tx);
+    scan.close(tx);
+    Ok(result?)
+}
+
+fn collect_changes(
+    scan: &mut impl UpdateScan,
+    assignments: &[(String, Expression)],
+    tx: &mut Transaction,
+) -> Result<Vec<Change>, std::io::Error> {
+    let mut found = Vec::new();
+    while scan.next(tx)? {
+        let mut changes = HashMap::new();
+        for (fldname, expr) in assignments {
+            let value = expr.evaluate(&mut ScanRow {
+                scan: &mut *scan,
+                tx: &mut *tx,
+            })?;
+            changes.insert(fldname.clone(), value);
+        }
+        found.push((scan.get_rid(), changes));
+    }
+    Ok(found)
+}
+
+/// Fails if a table or view already has the name.
+fn check_unused(
+    name: &str,
+    tx: &mut Transaction,
+    mdm: &mut MetadataManager,
+) -> Result<(), std::io::Error> {
+    if mdm.get_layout(name, tx)?.is_some() || mdm.get_view_def(name, tx)?.is_some() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("table {} already exists", name),
+        ));
+    }
+    Ok(())
+}
+
+/// The name of a constraint declared without one: the table's, cut short
+/// if need be, and the constraint's position.
+fn constraint_name(tblname: &str, position: usize) -> String {
+    let suffix = format!("_c{}", position + 1);
+    let keep = (MAX_NAME as usize).saturating_sub(suffix.len());
+    let prefix = tblname.chars().take(keep).collect::<String>();
+    format!("{}{}", prefix, suffix)
+}
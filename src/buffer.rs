@@ -1,4 +1,4 @@
-use crate::{file_manager::{self, BlockId, FileManager}, log_manager::LogManager, page::Page};
+use crate::{file_manager::{BlockId, FileManager}, log_manager::LogManager, page::Page};
 
 
 pub(crate) struct Buffer {
@@ -24,12 +24,19 @@ impl Buffer {
 
     pub(crate) fn set_modified(&mut self, lsn: Option<u64>, txnum: u64) {
         self.transaction_number = Some(txnum);
-        self.log_sequence_number = lsn;
+        // Unlogged changes keep the lsn of the latest logged one
+        if lsn.is_some() {
+            self.log_sequence_number = lsn;
+        }
+    }
+
+    pub(crate) fn is_pinned(&self) -> bool {
+        self.pins > 0
     }
 
     pub(crate) fn assign_to_block(&mut self, block: BlockId, file_manager: &FileManager, log_manager: &mut LogManager) -> Result<(), std::io::Error> {
         self.flush(file_manager, log_manager)?;
-        file_manager.read(&block, &mut self.contents).unwrap();
+        file_manager.read(&block, &mut self.contents)?;
         self.block = Some(block);
         self.pins = 0;
 
@@ -37,9 +44,11 @@ impl Buffer {
     }
 
     pub(crate) fn flush(&mut self, file_manager: &FileManager, log_manager: &mut LogManager) -> Result<(), std::io::Error> {
-        if self.transaction_number.is_some() && self.log_sequence_number.is_some() && self.block.is_some() {
-            log_manager.flush_with_lsn(file_manager, self.log_sequence_number.unwrap())?;
-            file_manager.write(self.block.as_ref().unwrap(), &mut self.contents)?;
+        if let (Some(_), Some(block)) = (self.transaction_number, self.block.as_ref()) {
+            if let Some(lsn) = self.log_sequence_number {
+                log_manager.flush_with_lsn(file_manager, lsn)?;
+            }
+            file_manager.write(block, &mut self.contents)?;
             self.transaction_number = None;
         }
 
@@ -58,28 +67,35 @@ impl Buffer {
 
 #[cfg(test)]
 mod tests {
-    use crate::{file_manager::BlockId, log_manager::LogManager, page::Page, simpledb::SimpleDB};
-    use expect_test::{expect, Expect};
-    use pretty_hex::PrettyHex;
+    use crate::{file_manager::BlockId, simpledb::SimpleDB};
 
     #[test]
     fn test_buffer() {
-        let db = SimpleDB::new("buffertest", 400, 3).unwrap();
-        let buff1 = db.buffer_manager.pin(&BlockId::new("testfile".to_string(), 1));
-        let p = buff1.contents();
+        let _ = std::fs::remove_dir_all("buffertest");
+        let mut db = SimpleDB::new("buffertest", 400, 3).unwrap();
+        let fm = &db.file_manager;
+        let lm = &mut db.log_manager;
+        let bm = &mut db.buffer_manager;
+        fm.append("testfile").unwrap();
+
+        let buff1 = bm.pin(&BlockId::new("testfile".to_string(), 1), fm, lm).unwrap();
+        let p = &mut bm.bufferpool[buff1].contents;
         let n = p.get_int(80);
         p.set_int(80, n + 1);
-        buff1.set_modified(1, 0);
-        db.buffer_manager.unpin(buff1);
+        bm.bufferpool[buff1].set_modified(Some(1), 0);
+        bm.unpin(buff1);
 
-        let buff2 = db.buffer_manager.pin(&BlockId::new("testfile".to_string(), 2));
-        let buff3 = db.buffer_manager.pin(&BlockId::new("testfile".to_string(), 3));
-        let buff4 = db.buffer_manager.pin(&BlockId::new("testfile".to_string(), 4));
-        let p = buff2.contents();
+        // One of these pins flushes buff1
+        let buff2 = bm.pin(&BlockId::new("testfile".to_string(), 2), fm, lm).unwrap();
+        bm.pin(&BlockId::new("testfile".to_string(), 3), fm, lm).unwrap();
+        bm.pin(&BlockId::new("testfile".to_string(), 4), fm, lm).unwrap();
+
+        bm.unpin(buff2);
+        let buff2 = bm.pin(&BlockId::new("testfile".to_string(), 1), fm, lm).unwrap();
+        let p = &mut bm.bufferpool[buff2].contents;
         p.set_int(80, 9999);
-        buff2.set_modified(1, 0);
-        db.buffer_manager.unpin(buff2);
+        assert_eq!(p.get_int(80), 9999);
+        bm.bufferpool[buff2].set_modified(Some(1), 0);
+        bm.unpin(buff2);
     }
-
 }
-
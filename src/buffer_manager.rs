@@ -1,4 +1,8 @@
-use crate::{buffer::Buffer, file_manager::FileManager, log_manager::LogManager};
+use crate::{
+    buffer::Buffer,
+    file_manager::{BlockId, FileManager},
+    log_manager::LogManager,
+};
 
 pub(crate) struct BufferManager {
     pub(crate) bufferpool: Vec<Buffer>,
@@ -10,7 +14,7 @@ impl BufferManager {
         let mut bufferpool = Vec::with_capacity(numbuffs as usize);
 
         for _ in 0..numbuffs {
-            bufferpool.push(Buffer::new(&file_manager));
+            bufferpool.push(Buffer::new(file_manager));
         }
 
         Self {
@@ -31,47 +35,111 @@ impl BufferManager {
             .try_for_each(|b| b.flush(file_manager, log_manager))
     }
 
-    pub(crate) fn unpin(&mut self, buff: &mut Buffer) {
+    /// Pins the block to a buffer and returns the buffer's index in the pool.
+    /// There is no one to wait for, so running out of buffers is an error.
+    pub(crate) fn pin(
+        &mut self,
+        block: &BlockId,
+        file_manager: &FileManager,
+        log_manager: &mut LogManager,
+    ) -> Result<usize, std::io::Error> {
+        let index = match self.find_existing_buffer(block) {
+            Some(index) => index,
+            None => {
+                let index = self.choose_unpinned_buffer().ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::WouldBlock,
+                        format!("no available buffers to pin {}", block),
+                    )
+                })?;
+                self.bufferpool[index].assign_to_block(block.clone(), file_manager, log_manager)?;
+                index
+            }
+        };
+
+        if !self.bufferpool[index].is_pinned() {
+            self.available -= 1;
+        }
+        self.bufferpool[index].pin();
+
+        Ok(index)
+    }
+
+    pub(crate) fn unpin(&mut self, index: usize) {
+        let buff = &mut self.bufferpool[index];
         buff.unpin();
         if buff.pins == 0 {
             self.available += 1;
+        }
+    }
 
+    fn find_existing_buffer(&self, block: &BlockId) -> Option<usize> {
+        self.bufferpool
+            .iter()
+            .position(|b| b.block.as_ref() == Some(block))
+    }
+
+    /// Forgets the blocks of a deleted file, so they are neither written back
+    /// nor mistaken for blocks of a new file with the same name.
+    pub(crate) fn discard_file(&mut self, filename: &str) {
+        for buffer in &mut self.bufferpool {
+            if buffer.block.as_ref().is_some_and(|b| b.filename() == filename) {
+                buffer.block = None;
+                buffer.transaction_number = None;
+                buffer.log_sequence_number = None;
+            }
         }
+    }
 
+    fn choose_unpinned_buffer(&self) -> Option<usize> {
+        self.bufferpool.iter().position(|b| !b.is_pinned())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{file_manager::BlockId, log_manager::LogManager, page::Page, simpledb::SimpleDB};
-    use expect_test::{expect, Expect};
-    use pretty_hex::PrettyHex;
+    use crate::{file_manager::BlockId, simpledb::SimpleDB};
 
     #[test]
     fn test_buffer() {
-        let db = SimpleDB::new("buffermgrtest", 400, 3).unwrap();
-        let bm = &db.buffer_manager;
-        let mut buff = Vec::with_capacity(6);
-        buff.push(bm.pin(&BlockId::new("testfile".to_string(), 0)));
-        buff.push(bm.pin(&BlockId::new("testfile".to_string(), 1)));
-        buff.push(bm.pin(&BlockId::new("testfile".to_string(), 2)));
-
-        bm.unpin(buff[1]);
+        let _ = std::fs::remove_dir_all("buffermgrtest");
+        let mut db = SimpleDB::new("buffermgrtest", 400, 3).unwrap();
+        let fm = &db.file_manager;
+        let lm = &mut db.log_manager;
+        let bm = &mut db.buffer_manager;
+        fm.append("testfile").unwrap();
+        let block = |n| BlockId::new("testfile".to_string(), n);
+        let mut buff = [None; 6];
+        buff[0] = Some(bm.pin(&block(0), fm, lm).unwrap());
+        buff[1] = Some(bm.pin(&block(1), fm, lm).unwrap());
+        buff[2] = Some(bm.pin(&block(2), fm, lm).unwrap());
+
+        bm.unpin(buff[1].unwrap());
         buff[1] = None;
-        buff[3] = bm.pin(BlockId::new("testfile".to_string(), 0)); // block 0 pinned twice
-        buff[4] = bm.pin(BlockId::new("testfile".to_string(), 1)); // block 1 repinned
+        buff[3] = Some(bm.pin(&block(0), fm, lm).unwrap()); // block 0 pinned twice
+        buff[4] = Some(bm.pin(&block(1), fm, lm).unwrap()); // block 1 repinned
+        assert_eq!(bm.available, 0);
 
-        assert!(bm.pin(BlockId::new("testfile".to_string(), 3)).is_err()); // no available buffers
+        assert!(bm.pin(&block(3), fm, lm).is_err()); // no available buffers
 
-        bm.unpin(buff[2]);
+        bm.unpin(buff[2].unwrap());
         buff[2] = None;
-        buff[5] = bm.pin(BlockId::new("testfile".to_string(), 3)); // now this works
+        buff[5] = Some(bm.pin(&block(3), fm, lm).unwrap()); // now this works
 
-        println!("Final Buffer Allocation:");
-        for (i, b) in buff.iter().enumerate() {
-            if let Some(b) = b {
-                println!("buff[{}] pinned to block {}", i, b.block());
-            }
-        }
+        let allocation = buff
+            .iter()
+            .map(|b| b.map(|b| bm.bufferpool[b].block.as_ref().unwrap().to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            allocation,
+            vec![
+                Some("[file testfile, block 0]".to_string()),
+                None,
+                None,
+                Some("[file testfile, block 0]".to_string()),
+                Some("[file testfile, block 1]".to_string()),
+                Some("[file testfile, block 3]".to_string()),
+            ]
+        );
     }
 }
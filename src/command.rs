@@ -0,0 +1,119 @@
+use crate::{
+    constant::Constant, constraint::Constraint, expression::Expression, index::IndexType,
+    predicate::Predicate, query_data::QueryData, resultset::ColumnType, schema::Schema,
+};
+
+/// A parsed statement other than a query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Command {
+    Insert(InsertData),
+    Delete(DeleteData),
+    Modify(ModifyData),
+    CreateTable(CreateTableData),
+    CreateView(CreateViewData),
+    CreateIndex(CreateIndexData),
+    DropTable(DropTableData),
+    DropView(DropViewData),
+    DropIndex(DropIndexData),
+    AlterTable(AlterTableData),
+    Analyze(AnalyzeData),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct InsertData {
+    pub(crate) tblname: String,
+    pub(crate) fields: Vec<String>,
+    pub(crate) values: Vec<Constant>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct DeleteData {
+    pub(crate) tblname: String,
+    pub(crate) predicate: Predicate,
+}
+
+/// An UPDATE statement, which sets each field to the value of its
+/// expression in the records satisfying the predicate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ModifyData {
+    pub(crate) tblname: String,
+    pub(crate) assignments: Vec<(String, Expression)>,
+    pub(crate) predicate: Predicate,
+}
+
+/// A CREATE TABLE statement. Constraints declared without a name have an
+/// empty one, which the table's name and their position stand in for when
+/// the statement is executed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CreateTableData {
+    pub(crate) tblname: String,
+    pub(crate) schema: Schema,
+    pub(crate) constraints: Vec<Constraint>,
+    pub(crate) defaults: Vec<(String, Expression)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CreateViewData {
+    pub(crate) viewname: String,
+    pub(crate) query: QueryData,
+}
+
+impl CreateViewData {
+    /// The definition kept in the catalog.
+    pub(crate) fn view_def(&self) -> String {
+        self.query.to_string()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CreateIndexData {
+    pub(crate) idxname: String,
+    pub(crate) tblname: String,
+    pub(crate) fldname: String,
+    pub(crate) index_type: IndexType,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct DropTableData {
+    pub(crate) tblname: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct DropViewData {
+    pub(crate) viewname: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct DropIndexData {
+    pub(crate) idxname: String,
+}
+
+/// An ALTER TABLE statement, or a RENAME TABLE, which is the same as
+/// renaming through ALTER TABLE.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct AlterTableData {
+    pub(crate) tblname: String,
+    pub(crate) action: AlterAction,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum AlterAction {
+    AddColumn {
+        fldname: String,
+        ctype: ColumnType,
+        length: u64,
+        default: Option<Constant>,
+    },
+    DropColumn(String),
+    RenameColumn {
+        old: String,
+        new: String,
+    },
+    RenameTable(String),
+}
+
+/// An ANALYZE statement, of one table or, without a name, of all of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct AnalyzeData {
+    pub(crate) tblname: Option<String>,
+}
@@ -0,0 +1,65 @@
+use std::fmt::Display;
+
+// NULL sorts before every other value
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) enum Constant {
+    Null,
+    Int(i64),
+    Str(String),
+    Bytes(Vec<u8>),
+}
+
+impl Constant {
+    pub(crate) fn is_null(&self) -> bool {
+        matches!(self, Constant::Null)
+    }
+
+    pub(crate) fn as_int(&self) -> i64 {
+        match self {
+            Constant::Int(i) => *i,
+            _ => panic!("{} is not an integer", self),
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> &str {
+        match self {
+            Constant::Str(s) => s,
+            _ => panic!("{} is not a string", self),
+        }
+    }
+
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        match self {
+            Constant::Bytes(b) => b,
+            _ => panic!("{} is not a byte string", self),
+        }
+    }
+}
+
+/// Reads the digits of a hex literal, two per byte.
+pub(crate) fn parse_hex(digits: &str) -> Option<Vec<u8>> {
+    if !digits.len().is_multiple_of(2) || !digits.is_ascii() {
+        return None;
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).ok())
+        .collect()
+}
+
+impl Display for Constant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Constant::Null => write!(f, "NULL"),
+            Constant::Int(i) => write!(f, "{}", i),
+            Constant::Str(s) => write!(f, "'{}'", s.replace('\'', "''")),
+            Constant::Bytes(b) => {
+                write!(f, "X'")?;
+                for byte in b {
+                    write!(f, "{:02x}", byte)?;
+                }
+                write!(f, "'")
+            }
+        }
+    }
+}
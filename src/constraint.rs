@@ -0,0 +1,182 @@
+use crate::{constant::Constant, predicate::Predicate, sql_exception::SQLException};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ConstraintKind {
+    /// The fields are NOT NULL and UNIQUE together; a table has one at most.
+    PrimaryKey,
+    NotNull,
+    /// No two records share the same values for the fields, unless one of
+    /// them is NULL.
+    Unique,
+    /// Every record whose fields are all set matches a record of the
+    /// referenced table.
+    ForeignKey(Reference),
+    /// The predicate, kept as text, is not false for any record.
+    Check(String),
+}
+
+impl ConstraintKind {
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            ConstraintKind::PrimaryKey => "primary",
+            ConstraintKind::NotNull => "notnull",
+            ConstraintKind::Unique => "unique",
+            ConstraintKind::ForeignKey(_) => "foreign",
+            ConstraintKind::Check(_) => "check",
+        }
+    }
+
+    /// The kinds that need nothing but their name, foreign keys and checks
+    /// are read along with their reference or predicate.
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "primary" => Some(ConstraintKind::PrimaryKey),
+            "notnull" => Some(ConstraintKind::NotNull),
+            "unique" => Some(ConstraintKind::Unique),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OnDelete {
+    /// The delete fails while records still refer to the deleted one.
+    Restrict,
+    /// The referring records are deleted too.
+    Cascade,
+    /// The referring records' fields are set to NULL.
+    SetNull,
+}
+
+impl OnDelete {
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            OnDelete::Restrict => "restrict",
+            OnDelete::Cascade => "cascade",
+            OnDelete::SetNull => "setnull",
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "restrict" => Some(OnDelete::Restrict),
+            "cascade" => Some(OnDelete::Cascade),
+            "setnull" => Some(OnDelete::SetNull),
+            _ => None,
+        }
+    }
+}
+
+/// The table and fields a foreign key refers to, which are the fields of a
+/// PRIMARY KEY or UNIQUE constraint of that table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Reference {
+    pub(crate) table: String,
+    pub(crate) fields: Vec<String>,
+    pub(crate) on_delete: OnDelete,
+}
+
+impl Reference {
+    pub(crate) fn new(table: &str, fields: &[&str], on_delete: OnDelete) -> Self {
+        Self {
+            table: table.to_string(),
+            fields: fields.iter().map(|f| f.to_string()).collect(),
+            on_delete,
+        }
+    }
+}
+
+/// A named rule on the records of one table, as declared in its
+/// `CREATE TABLE` statement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Constraint {
+    pub(crate) name: String,
+    pub(crate) kind: ConstraintKind,
+    pub(crate) fields: Vec<String>,
+}
+
+impl Constraint {
+    pub(crate) fn new(name: &str, kind: ConstraintKind, fields: &[&str]) -> Self {
+        Self {
+            name: name.to_string(),
+            kind,
+            fields: fields.iter().map(|f| f.to_string()).collect(),
+        }
+    }
+
+    /// A CHECK constraint on the fields the predicate names.
+    pub(crate) fn check(name: &str, predicate: &Predicate) -> Self {
+        Self {
+            name: name.to_string(),
+            kind: ConstraintKind::Check(predicate.to_string()),
+            fields: predicate.fields(),
+        }
+    }
+
+    pub(crate) fn rejects_null(&self) -> bool {
+        matches!(
+            self.kind,
+            ConstraintKind::PrimaryKey | ConstraintKind::NotNull
+        )
+    }
+
+    pub(crate) fn is_unique(&self) -> bool {
+        matches!(
+            self.kind,
+            ConstraintKind::PrimaryKey | ConstraintKind::Unique
+        )
+    }
+
+    pub(crate) fn reference(&self) -> Option<&Reference> {
+        match &self.kind {
+            ConstraintKind::ForeignKey(reference) => Some(reference),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn predicate(&self) -> Option<&str> {
+        match &self.kind {
+            ConstraintKind::Check(predicate) => Some(predicate),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn null_violation(&self, tblname: &str, fldname: &str) -> SQLException {
+        self.violation(tblname, format!("{} is NULL", fldname))
+    }
+
+    pub(crate) fn duplicate_violation(&self, tblname: &str, key: &[Constant]) -> SQLException {
+        let values = key.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+        self.violation(tblname, format!("duplicate key ({})", values.join(", ")))
+    }
+
+    pub(crate) fn check_violation(&self, tblname: &str) -> SQLException {
+        let predicate = self.predicate().unwrap_or_default();
+        self.violation(tblname, format!("CHECK ({}) is false", predicate))
+    }
+
+    /// The key of a record of `tblname` has no match in the referenced
+    /// table, or a removed key of the referenced table is still in use.
+    pub(crate) fn reference_violation(
+        &self,
+        tblname: &str,
+        key: &[Constant],
+        detail: &str,
+    ) -> SQLException {
+        let values = key.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+        SQLException::ForeignKeyViolation {
+            constraint: self.name.clone(),
+            table: tblname.to_string(),
+            referenced: self.reference().map_or(String::new(), |r| r.table.clone()),
+            detail: format!("key ({}) {}", values.join(", "), detail),
+        }
+    }
+
+    fn violation(&self, tblname: &str, detail: String) -> SQLException {
+        SQLException::ConstraintViolation {
+            constraint: self.name.clone(),
+            table: tblname.to_string(),
+            detail,
+        }
+    }
+}
@@ -0,0 +1,300 @@
+use crate::{
+    constraint::{Constraint, ConstraintKind, OnDelete, Reference},
+    layout::Layout,
+    schema::Schema,
+    table_manager::{check_name, TableManager, MAX_NAME},
+    table_scan::TableScan,
+    transaction::Transaction,
+};
+
+/// Keeps constraint definitions in the `concat` catalog table, a row per
+/// constraint with its table, kind and the comma separated fields it covers.
+/// Foreign keys also record the table and fields they refer to and what
+/// happens on delete, checks the text of their predicate. The `defcat`
+/// table holds the text of each field's DEFAULT expression.
+pub(crate) struct ConstraintManager {
+    ccat_layout: Layout,
+    dcat_layout: Layout,
+}
+
+impl ConstraintManager {
+    pub(crate) fn new(
+        is_new: bool,
+        table_manager: &TableManager,
+        tx: &mut Transaction,
+    ) -> Result<Self, std::io::Error> {
+        let mut schema = Schema::new();
+        schema.add_string_field("conname", MAX_NAME);
+        schema.add_string_field("tblname", MAX_NAME);
+        schema.add_string_field("contype", MAX_NAME);
+        schema.add_text_field("fields");
+        schema.add_string_field("reftable", MAX_NAME);
+        schema.add_text_field("reffields");
+        schema.add_string_field("ondelete", MAX_NAME);
+        schema.add_text_field("expr");
+        let mut dcat_schema = Schema::new();
+        dcat_schema.add_string_field("tblname", MAX_NAME);
+        dcat_schema.add_string_field("fldname", MAX_NAME);
+        dcat_schema.add_text_field("expr");
+        if is_new {
+            table_manager.create_table("concat", &schema, tx)?;
+            table_manager.create_table("defcat", &dcat_schema, tx)?;
+        }
+
+        Ok(Self {
+            ccat_layout: Layout::new(schema),
+            dcat_layout: Layout::new(dcat_schema),
+        })
+    }
+
+    pub(crate) fn add_constraint(
+        &self,
+        tblname: &str,
+        constraint: &Constraint,
+        tx: &mut Transaction,
+    ) -> Result<(), std::io::Error> {
+        check_name(&constraint.name)?;
+        let mut ccat = TableScan::new(tx, "concat", self.ccat_layout.clone())?;
+        while ccat.next(tx)? {
+            if ccat.get_string(tx, "conname")? == constraint.name {
+                ccat.close(tx);
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::AlreadyExists,
+                    format!("constraint {} already exists", constraint.name),
+                ));
+            }
+        }
+        ccat.insert(tx)?;
+        ccat.set_string(tx, "conname", &constraint.name)?;
+        ccat.set_string(tx, "tblname", tblname)?;
+        ccat.set_string(tx, "contype", constraint.kind.name())?;
+        ccat.set_string(tx, "fields", &constraint.fields.join(","))?;
+        if let Some(reference) = constraint.reference() {
+            ccat.set_string(tx, "reftable", &reference.table)?;
+            ccat.set_string(tx, "reffields", &reference.fields.join(","))?;
+            ccat.set_string(tx, "ondelete", reference.on_delete.name())?;
+        }
+        if let Some(predicate) = constraint.predicate() {
+            ccat.set_string(tx, "expr", predicate)?;
+        }
+        ccat.close(tx);
+        Ok(())
+    }
+
+    pub(crate) fn get_constraints(
+        &self,
+        tblname: &str,
+        tx: &mut Transaction,
+    ) -> Result<Vec<Constraint>, std::io::Error> {
+        let mut result = Vec::new();
+        let mut ccat = TableScan::new(tx, "concat", self.ccat_layout.clone())?;
+        while ccat.next(tx)? {
+            if ccat.get_string(tx, "tblname")? == tblname {
+                result.push(read_constraint(&mut ccat, tx)?);
+            }
+        }
+        ccat.close(tx);
+        Ok(result)
+    }
+
+    /// The foreign keys of any table that refer to this one, with the name
+    /// of the table they belong to.
+    pub(crate) fn get_referencing(
+        &self,
+        tblname: &str,
+        tx: &mut Transaction,
+    ) -> Result<Vec<(String, Constraint)>, std::io::Error> {
+        let mut result = Vec::new();
+        let mut ccat = TableScan::new(tx, "concat", self.ccat_layout.clone())?;
+        while ccat.next(tx)? {
+            if ccat.get_string(tx, "contype")? == "foreign"
+                && ccat.get_string(tx, "reftable")? == tblname
+            {
+                let table = ccat.get_string(tx, "tblname")?;
+                result.push((table, read_constraint(&mut ccat, tx)?));
+            }
+        }
+        ccat.close(tx);
+        Ok(result)
+    }
+
+    pub(crate) fn remove_table(
+        &self,
+        tblname: &str,
+        tx: &mut Transaction,
+    ) -> Result<(), std::io::Error> {
+        let mut ccat = TableScan::new(tx, "concat", self.ccat_layout.clone())?;
+        while ccat.next(tx)? {
+            if ccat.get_string(tx, "tblname")? == tblname {
+                ccat.delete(tx)?;
+            }
+        }
+        ccat.close(tx);
+        let mut dcat = TableScan::new(tx, "defcat", self.dcat_layout.clone())?;
+        while dcat.next(tx)? {
+            if dcat.get_string(tx, "tblname")? == tblname {
+                dcat.delete(tx)?;
+            }
+        }
+        dcat.close(tx);
+        Ok(())
+    }
+
+    pub(crate) fn rename_table(
+        &self,
+        old: &str,
+        new: &str,
+        tx: &mut Transaction,
+    ) -> Result<(), std::io::Error> {
+        let mut ccat = TableScan::new(tx, "concat", self.ccat_layout.clone())?;
+        while ccat.next(tx)? {
+            if ccat.get_string(tx, "tblname")? == old {
+                ccat.set_string(tx, "tblname", new)?;
+            }
+            if ccat.get_string(tx, "contype")? == "foreign"
+                && ccat.get_string(tx, "reftable")? == old
+            {
+                ccat.set_string(tx, "reftable", new)?;
+            }
+        }
+        ccat.close(tx);
+        let mut dcat = TableScan::new(tx, "defcat", self.dcat_layout.clone())?;
+        while dcat.next(tx)? {
+            if dcat.get_string(tx, "tblname")? == old {
+                dcat.set_string(tx, "tblname", new)?;
+            }
+        }
+        dcat.close(tx);
+        Ok(())
+    }
+
+    pub(crate) fn rename_field(
+        &self,
+        tblname: &str,
+        old: &str,
+        new: &str,
+        tx: &mut Transaction,
+    ) -> Result<(), std::io::Error> {
+        let mut ccat = TableScan::new(tx, "concat", self.ccat_layout.clone())?;
+        while ccat.next(tx)? {
+            if ccat.get_string(tx, "tblname")? == tblname {
+                rename_in_list(&mut ccat, "fields", old, new, tx)?;
+            }
+            if ccat.get_string(tx, "contype")? == "foreign"
+                && ccat.get_string(tx, "reftable")? == tblname
+            {
+                rename_in_list(&mut ccat, "reffields", old, new, tx)?;
+            }
+        }
+        ccat.close(tx);
+        let mut dcat = TableScan::new(tx, "defcat", self.dcat_layout.clone())?;
+        while dcat.next(tx)? {
+            if dcat.get_string(tx, "tblname")? == tblname && dcat.get_string(tx, "fldname")? == old
+            {
+                dcat.set_string(tx, "fldname", new)?;
+            }
+        }
+        dcat.close(tx);
+        Ok(())
+    }
+
+    /// Replaces the field's DEFAULT expression, or removes it when there is
+    /// none.
+    pub(crate) fn set_default(
+        &self,
+        tblname: &str,
+        fldname: &str,
+        expr: Option<&str>,
+        tx: &mut Transaction,
+    ) -> Result<(), std::io::Error> {
+        let mut dcat = TableScan::new(tx, "defcat", self.dcat_layout.clone())?;
+        while dcat.next(tx)? {
+            if dcat.get_string(tx, "tblname")? == tblname
+                && dcat.get_string(tx, "fldname")? == fldname
+            {
+                dcat.delete(tx)?;
+            }
+        }
+        if let Some(expr) = expr {
+            dcat.insert(tx)?;
+            dcat.set_string(tx, "tblname", tblname)?;
+            dcat.set_string(tx, "fldname", fldname)?;
+            dcat.set_string(tx, "expr", expr)?;
+        }
+        dcat.close(tx);
+        Ok(())
+    }
+
+    /// The fields of the table that have a DEFAULT, with its expression.
+    pub(crate) fn get_defaults(
+        &self,
+        tblname: &str,
+        tx: &mut Transaction,
+    ) -> Result<Vec<(String, String)>, std::io::Error> {
+        let mut defaults = Vec::new();
+        let mut dcat = TableScan::new(tx, "defcat", self.dcat_layout.clone())?;
+        while dcat.next(tx)? {
+            if dcat.get_string(tx, "tblname")? == tblname {
+                let fldname = dcat.get_string(tx, "fldname")?;
+                defaults.push((fldname, dcat.get_string(tx, "expr")?));
+            }
+        }
+        dcat.close(tx);
+        Ok(defaults)
+    }
+}
+
+fn read_constraint(
+    ccat: &mut TableScan,
+    tx: &mut Transaction,
+) -> Result<Constraint, std::io::Error> {
+    let name = ccat.get_string(tx, "conname")?;
+    let type_name = ccat.get_string(tx, "contype")?;
+    let unknown = |what: &str, value: &str| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("unknown {} {} for constraint {}", what, value, name),
+        )
+    };
+    let kind = if type_name == "check" {
+        ConstraintKind::Check(ccat.get_string(tx, "expr")?)
+    } else if type_name == "foreign" {
+        let action = ccat.get_string(tx, "ondelete")?;
+        ConstraintKind::ForeignKey(Reference {
+            table: ccat.get_string(tx, "reftable")?,
+            fields: split_list(&ccat.get_string(tx, "reffields")?),
+            on_delete: OnDelete::from_name(&action)
+                .ok_or_else(|| unknown("delete action", &action))?,
+        })
+    } else {
+        ConstraintKind::from_name(&type_name).ok_or_else(|| unknown("type", &type_name))?
+    };
+    let fields = split_list(&ccat.get_string(tx, "fields")?);
+    Ok(Constraint { name, kind, fields })
+}
+
+fn split_list(list: &str) -> Vec<String> {
+    if list.is_empty() {
+        return Vec::new();
+    }
+    list.split(',').map(|f| f.to_string()).collect()
+}
+
+fn rename_in_list(
+    ccat: &mut TableScan,
+    fldname: &str,
+    old: &str,
+    new: &str,
+    tx: &mut Transaction,
+) -> Result<(), std::io::Error> {
+    let list = split_list(&ccat.get_string(tx, fldname)?);
+    if list.iter().any(|f| f == old) {
+        let renamed = list
+            .iter()
+            .map(|f| if f == old { new } else { f })
+            .collect::<Vec<_>>()
+            .join(",");
+        ccat.set_string(tx, fldname, &renamed)?;
+    }
+    Ok(())
+}
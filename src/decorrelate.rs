@@ -0,0 +1,358 @@
+use crate::{
+    expression::Expression,
+    predicate::Predicate,
+    query_data::{Join, JoinKind, QueryData, SelectItem, TableRef},
+    schema::Schema,
+    term::{Operator, Term},
+};
+
+/// Where the fields of a table of a FROM clause are found, which the
+/// planner knows.
+pub(crate) type SchemaOf<'a> = dyn Fn(&TableRef) -> Result<Schema, std::io::Error> + 'a;
+
+/// The tables of a FROM clause, by the names that qualify their fields.
+struct Scope {
+    tables: Vec<(String, Schema)>,
+    // Names of aggregates and select items, which are fields of the query
+    // too
+    names: Vec<String>,
+}
+
+impl Scope {
+    fn new<'t>(
+        tables: impl IntoIterator<Item = &'t TableRef>,
+        schema_of: &SchemaOf,
+    ) -> Result<Self, std::io::Error> {
+        let mut scope = Self {
+            tables: Vec::new(),
+            names: Vec::new(),
+        };
+        for table in tables {
+            let schema = schema_of(table)?;
+            scope.tables.push((table.range_name().to_string(), schema));
+        }
+        Ok(scope)
+    }
+
+    fn of_query(query: &QueryData, schema_of: &SchemaOf) -> Result<Self, std::io::Error> {
+        let mut scope = Self::new(query.tables(), schema_of)?;
+        scope
+            .names
+            .extend(query.aggregates.iter().map(|a| a.field_name()));
+        scope
+            .names
+            .extend(query.items.iter().filter_map(|i| i.alias.clone()));
+        Ok(scope)
+    }
+
+    /// Whether the field is one of the scope's, which is where a name
+    /// found in both a subquery and the query around it refers.
+    fn has(&self, fldname: &str) -> bool {
+        if self.names.iter().any(|n| n == fldname) {
+            return true;
+        }
+        match fldname.split_once('.') {
+            Some((range, field)) => self
+                .tables
+                .iter()
+                .any(|(r, schema)| r == range && schema.has_field(field)),
+            None => self
+                .tables
+                .iter()
+                .any(|(_, schema)| schema.has_field(fldname)),
+        }
+    }
+}
+
+/// The fields the query refers to that none of its tables have, which are
+/// those of the records of an outer query. A derived table cannot refer to
+/// the query it is in, the subqueries of conditions and select items can.
+pub(crate) fn outer_fields(
+    query: &QueryData,
+    schema_of: &SchemaOf,
+) -> Result<Vec<String>, std::io::Error> {
+    let scope = Scope::of_query(query, schema_of)?;
+    let mut fields = Vec::new();
+    let mut subqueries = Vec::new();
+    for item in &query.items {
+        item.expr.collect_fields(&mut fields);
+        item.expr.collect_subqueries(&mut subqueries);
+    }
+    let joins = query.from.iter().flat_map(|item| &item.joins);
+    for predicate in [&query.predicate, &query.having]
+        .into_iter()
+        .chain(joins.map(|j| &j.on))
+    {
+        fields.extend(predicate.fields());
+        subqueries.extend(predicate.subqueries());
+    }
+    fields.extend(query.group_by.iter().cloned());
+    fields.extend(query.order_by.iter().map(|k| k.field.clone()));
+    for subquery in subqueries {
+        fields.extend(outer_fields(subquery, schema_of)?);
+    }
+    for (_, part) in &query.compound {
+        fields.extend(outer_fields(part, schema_of)?);
+    }
+
+    let mut outer = Vec::new();
+    for fldname in fields {
+        if !scope.has(&fldname) && !outer.contains(&fldname) {
+            outer.push(fldname);
+        }
+    }
+    Ok(outer)
+}
+
+/// Rewrites the subqueries among the terms of the WHERE clause that can be
+/// into joins: `EXISTS` and `IN` into semi joins, `NOT EXISTS` into an anti
+/// join. The subquery becomes a derived table joined to the FROM item whose
+/// fields it refers to, on the terms of its WHERE clause that refer to
+/// them. That takes the subquery to be a single ungrouped SELECT without a
+/// LIMIT, and to refer to outer fields only in those terms; other
+/// subqueries are left to be run for each record. `NOT IN` stays too, as a
+/// NULL among the values of its subquery makes it unknown rather than true.
+pub(crate) fn decorrelate(
+    query: &QueryData,
+    schema_of: &SchemaOf,
+) -> Result<QueryData, std::io::Error> {
+    let mut result = query.clone();
+    let mut kept = Vec::new();
+    for term in query.predicate.terms() {
+        let range_names = result
+            .tables()
+            .iter()
+            .map(|t| t.range_name().to_string())
+            .collect::<Vec<_>>();
+        let alias = (0..)
+            .map(|n| format!("subquery{}", n))
+            .find(|name| !range_names.contains(name))
+            .unwrap();
+        match semi_join(&result, term, &alias, schema_of)? {
+            Some((index, join)) => result.from[index].joins.push(join),
+            None => kept.push(term.clone()),
+        }
+    }
+    result.predicate = Predicate::from_terms(kept);
+    Ok(result)
+}
+
+/// The join a term of the query's WHERE clause becomes, with the index of
+/// the FROM item it joins.
+fn semi_join(
+    query: &QueryData,
+    term: &Term,
+    alias: &str,
+    schema_of: &SchemaOf,
+) -> Result<Option<(usize, Join)>, std::io::Error> {
+    let (subquery, expr, kind) = match term {
+        Term::Exists(subquery) => (subquery, None, JoinKind::Semi),
+        Term::InQuery {
+            expr,
+            query,
+            negated: false,
+        } if query.items.len() == 1 && !expr.has_subquery() => (query, Some(expr), JoinKind::Semi),
+        Term::Not(predicate) => match predicate.terms() {
+            [Term::Exists(subquery)] => (subquery, None, JoinKind::Anti),
+            _ => return Ok(None),
+        },
+        _ => return Ok(None),
+    };
+    if subquery.is_grouped() || !subquery.compound.is_empty() || subquery.limit.is_some() {
+        return Ok(None);
+    }
+
+    // Split the subquery's conditions into those on its own fields and
+    // those that correlate it
+    let scope = Scope::of_query(subquery, schema_of)?;
+    let (mut correlated, mut local) = (Vec::new(), Vec::new());
+    for term in subquery.predicate.terms() {
+        let mut fields = Vec::new();
+        term.collect_fields(&mut fields);
+        if fields.iter().all(|f| scope.has(f)) {
+            local.push(term.clone());
+        } else if term.has_subquery() {
+            return Ok(None);
+        } else {
+            correlated.push(term.clone());
+        }
+    }
+    let mut derived = QueryData {
+        predicate: Predicate::from_terms(local),
+        order_by: Vec::new(),
+        ..(**subquery).clone()
+    };
+    if !outer_fields(&derived, schema_of)?.is_empty() {
+        return Ok(None);
+    }
+
+    let mut fields = Vec::new();
+    for term in &correlated {
+        term.collect_fields(&mut fields);
+    }
+    let (inner, mut outer): (Vec<_>, Vec<_>) = fields.into_iter().partition(|f| scope.has(f));
+    if let Some(expr) = expr {
+        expr.collect_fields(&mut outer);
+    }
+    if outer.is_empty() {
+        return Ok(None);
+    }
+    let mut index = None;
+    for (i, item) in query.from.iter().enumerate() {
+        let tables = std::iter::once(&item.table).chain(item.joins.iter().map(|j| &j.table));
+        let item_scope = Scope::new(tables, schema_of)?;
+        if outer.iter().all(|f| item_scope.has(f)) {
+            index = Some(i);
+            break;
+        }
+    }
+    let Some(index) = index else {
+        return Ok(None);
+    };
+
+    // The derived table gives the inner fields the join needs as c0, c1..
+    let column = |n: usize| Expression::Field(format!("{}.c{}", alias, n));
+    derived.items = inner
+        .iter()
+        .enumerate()
+        .map(|(n, f)| SelectItem::new(Expression::Field(f.clone()), Some(&format!("c{}", n))))
+        .collect();
+    let rename = |f: &str| inner.iter().position(|i| i == f).map(column);
+    let mut on = correlated
+        .iter()
+        .map(|t| t.map_fields(&rename))
+        .collect::<Vec<_>>();
+    if let Some(expr) = expr {
+        let n = derived.items.len();
+        let value = subquery.items[0].expr.clone();
+        derived
+            .items
+            .push(SelectItem::new(value, Some(&format!("c{}", n))));
+        on.push(Term::new(expr.clone(), Operator::Eq, column(n)));
+    }
+    let join = Join {
+        kind,
+        table: TableRef::derived(derived, alias),
+        on: Predicate::from_terms(on),
+    };
+    Ok(Some((index, join)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parser::parse_query, query_data::TableRef, schema::Schema};
+
+    use super::{decorrelate, outer_fields};
+
+    fn schema_of(table: &TableRef) -> Result<Schema, std::io::Error> {
+        let mut schema = Schema::new();
+        let fields: &[&str] = match table.tblname.as_str() {
+            "EMP" => &["ID", "N", "DID", "S"],
+            "DEPT" => &["ID", "B"],
+            _ => match &table.subquery {
+                Some(query) => return Ok(derived_schema(&query.field_names())),
+                None => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::NotFound,
+                        format!("no table {}", table.tblname),
+                    ))
+                }
+            },
+        };
+        for f in fields {
+            schema.add_int_field(f);
+        }
+        Ok(schema)
+    }
+
+    fn derived_schema(fields: &[String]) -> Schema {
+        let mut schema = Schema::new();
+        for f in fields {
+            schema.add_int_field(f);
+        }
+        schema
+    }
+
+    #[test]
+    fn test_decorrelate() {
+        let outer = |text: &str| outer_fields(&parse_query(text).unwrap(), &schema_of).unwrap();
+        assert_eq!(
+            outer("select ID from DEPT where B > e.S and ID = DID"),
+            ["e.S", "DID"]
+        );
+        assert_eq!(
+            outer("select max(B) m from DEPT d where B > X group by ID order by m"),
+            ["X"]
+        );
+        assert_eq!(
+            outer(
+                "select ID from DEPT d where exists (select N from EMP where DID = d.ID and S = Y)"
+            ),
+            ["Y"]
+        );
+        assert!(outer("select q.ID from (select ID from DEPT) q where q.ID > 2").is_empty());
+
+        for (text, expected) in [
+            (
+                "select N from EMP e where exists (select ID from DEPT d where d.ID = e.DID and d.B > 10) and S > 5",
+                "select N from EMP e semi join (select d.ID as c0 from DEPT d where d.B > 10) subquery0 \
+                 on subquery0.c0 = e.DID where S > 5",
+            ),
+            (
+                "select N from EMP where not exists (select ID from DEPT where B = S)",
+                "select N from EMP anti join (select B as c0 from DEPT) subquery0 on subquery0.c0 = S",
+            ),
+            (
+                "select N from EMP where DID in (select ID from DEPT where B > 10)",
+                "select N from EMP semi join (select ID as c0 from DEPT where B > 10) subquery0 \
+                 on DID = subquery0.c0",
+            ),
+            (
+                "select N from DEPT, EMP e where e.DID + 1 in (select ID from DEPT d where d.B = e.S)",
+                "select N from DEPT, EMP e semi join (select d.B as c0, ID as c1 from DEPT d) subquery0 \
+                 on subquery0.c0 = e.S and e.DID + 1 = subquery0.c1",
+            ),
+            // Left alone: NOT IN, grouped or uncorrelated subqueries, and
+            // outer fields outside the WHERE clause or across FROM items
+            (
+                "select N from EMP where DID not in (select ID from DEPT)",
+                "select N from EMP where DID not in (select ID from DEPT)",
+            ),
+            (
+                "select N from EMP where exists (select max(B) from DEPT where ID = DID)",
+                "select N from EMP where exists (select max(B) from DEPT where ID = DID)",
+            ),
+            (
+                "select N from EMP where exists (select ID from DEPT where B > 1)",
+                "select N from EMP where exists (select ID from DEPT where B > 1)",
+            ),
+            (
+                "select N from EMP where exists (select S from DEPT where ID = DID)",
+                "select N from EMP where exists (select S from DEPT where ID = DID)",
+            ),
+            (
+                "select N from EMP e, DEPT d where exists (select N from EMP where ID = e.ID and S = d.B)",
+                "select N from EMP e, DEPT d where exists (select N from EMP where ID = e.ID and S = d.B)",
+            ),
+        ] {
+            let query = parse_query(text).unwrap();
+            let result = decorrelate(&query, &schema_of).unwrap();
+            assert_eq!(result.to_string(), expected);
+        }
+
+        // Each joined subquery gets a name of its own
+        let query = parse_query(
+            "select N from EMP where exists (select ID from DEPT where ID = DID) \
+             and not exists (select ID from DEPT where B = S)",
+        )
+        .unwrap();
+        let result = decorrelate(&query, &schema_of).unwrap();
+        let names = result.from[0]
+            .joins
+            .iter()
+            .map(|j| j.table.range_name())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["subquery0", "subquery1"]);
+        assert!(result.predicate.terms().is_empty());
+    }
+}
@@ -0,0 +1,50 @@
+use crate::{
+    distinct_scan::DistinctScan, plan::Plan, record_comparator::SortKey, scan::Scan,
+    schema::Schema, sort_plan::SortPlan, transaction::Transaction,
+};
+
+/// The records of the underlying plan, each set of values once. They come
+/// sorted on all their fields in ascending order, which is how duplicates
+/// are found.
+pub(crate) struct DistinctPlan {
+    sorted: SortPlan,
+    fields: Vec<String>,
+}
+
+impl DistinctPlan {
+    pub(crate) fn new(plan: Box<dyn Plan>, block_size: u64) -> Self {
+        let fields = plan.schema().fields().to_vec();
+        let keys = fields.iter().map(|f| SortKey::new(f, false)).collect();
+        Self {
+            sorted: SortPlan::new(plan, keys, block_size),
+            fields,
+        }
+    }
+}
+
+impl Plan for DistinctPlan {
+    fn open(&self, tx: &mut Transaction) -> Result<Box<dyn Scan>, std::io::Error> {
+        let scan = self.sorted.open(tx)?;
+        Ok(Box::new(DistinctScan::new(scan, self.fields.clone())))
+    }
+
+    fn blocks_accessed(&self) -> u64 {
+        self.sorted.blocks_accessed()
+    }
+
+    fn records_output(&self) -> u64 {
+        let mut combinations = 1u64;
+        for fldname in &self.fields {
+            combinations = combinations.saturating_mul(self.sorted.distinct_values(fldname));
+        }
+        combinations.min(self.sorted.records_output())
+    }
+
+    fn distinct_values(&self, fldname: &str) -> u64 {
+        self.sorted.distinct_values(fldname)
+    }
+
+    fn schema(&self) -> &Schema {
+        self.sorted.schema()
+    }
+}
@@ -0,0 +1,131 @@
+use crate::{constant::Constant, scan::Scan, transaction::Transaction};
+
+/// The records of a scan sorted on the fields, each set of values once.
+/// Duplicates are next to each other in sorted order, so only the values of
+/// the last record returned are kept; the sort itself goes through temporary
+/// tables, and memory use does not grow with the number of records. NULLs
+/// count as equal to each other.
+pub(crate) struct DistinctScan<S: Scan> {
+    scan: S,
+    fields: Vec<String>,
+    last: Option<Vec<Constant>>,
+}
+
+impl<S: Scan> DistinctScan<S> {
+    pub(crate) fn new(scan: S, fields: Vec<String>) -> Self {
+        Self {
+            scan,
+            fields,
+            last: None,
+        }
+    }
+}
+
+impl<S: Scan> Scan for DistinctScan<S> {
+    fn before_first(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        self.last = None;
+        self.scan.before_first(tx)
+    }
+
+    fn next(&mut self, tx: &mut Transaction) -> Result<bool, std::io::Error> {
+        while self.scan.next(tx)? {
+            let mut values = Vec::with_capacity(self.fields.len());
+            for fldname in &self.fields {
+                values.push(self.scan.get_val(tx, fldname)?);
+            }
+            if self.last.as_ref() != Some(&values) {
+                self.last = Some(values);
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn get_int(&mut self, tx: &mut Transaction, fldname: &str) -> Result<i64, std::io::Error> {
+        self.scan.get_int(tx, fldname)
+    }
+
+    fn get_string(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+    ) -> Result<String, std::io::Error> {
+        self.scan.get_string(tx, fldname)
+    }
+
+    fn get_val(&mut self, tx: &mut Transaction, fldname: &str) -> Result<Constant, std::io::Error> {
+        self.scan.get_val(tx, fldname)
+    }
+
+    fn has_field(&self, fldname: &str) -> bool {
+        self.scan.has_field(fldname)
+    }
+
+    fn close(&mut self, tx: &mut Transaction) {
+        self.scan.close(tx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        layout::Layout,
+        record_comparator::{RecordComparator, SortKey},
+        scan::Scan,
+        schema::Schema,
+        simpledb::SimpleDB,
+        sort_scan::SortScan,
+        table_scan::TableScan,
+        transaction::Transaction,
+    };
+
+    use super::DistinctScan;
+
+    #[test]
+    fn test_distinct_scan() {
+        let _ = std::fs::remove_dir_all("distincttest");
+        let mut db = SimpleDB::new("distincttest", 400, 8).unwrap();
+        let mut tx = Transaction::new(
+            &db.file_manager,
+            &mut db.log_manager,
+            &mut db.buffer_manager,
+        );
+
+        let mut schema = Schema::new();
+        schema.add_int_field("A");
+        schema.add_string_field("B", 5);
+        let layout = Layout::new(schema.clone());
+        let mut ts = TableScan::new(&mut tx, "T", layout.clone()).unwrap();
+        // More records than fit in the buffer pool at once
+        for i in 0..300 {
+            ts.insert(&mut tx).unwrap();
+            match i % 4 {
+                3 => ts.set_null(&mut tx, "A").unwrap(),
+                a => ts.set_int(&mut tx, "A", a).unwrap(),
+            }
+            ts.set_string(&mut tx, "B", if i % 3 == 0 { "x" } else { "y" })
+                .unwrap();
+        }
+        ts.close(&mut tx);
+
+        let fields = vec!["A".to_string(), "B".to_string()];
+        let keys = fields.iter().map(|f| SortKey::new(f, false)).collect();
+        let ts = TableScan::new(&mut tx, "T", layout).unwrap();
+        let sorted = SortScan::new(ts, &schema, RecordComparator::new(keys), &mut tx).unwrap();
+        let mut scan = DistinctScan::new(sorted, fields);
+        for _ in 0..2 {
+            let mut rows = Vec::new();
+            scan.before_first(&mut tx).unwrap();
+            while scan.next(&mut tx).unwrap() {
+                let a = scan.get_val(&mut tx, "A").unwrap();
+                rows.push(format!("{} {}", a, scan.get_string(&mut tx, "B").unwrap()));
+            }
+            assert_eq!(
+                rows,
+                ["NULL x", "NULL y", "0 x", "0 y", "1 x", "1 y", "2 x", "2 y"]
+            );
+        }
+        scan.close(&mut tx);
+        tx.commit().unwrap();
+    }
+}
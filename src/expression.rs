@@ -0,0 +1,421 @@
+use std::collections::HashMap;
+
+use crate::{
+    constant::Constant, predicate::Predicate, query_data::QueryData, resultset::ColumnType,
+    schema::Schema,
+};
+
+/// Where an expression finds the values of the fields it names.
+pub(crate) trait Row {
+    fn get_val(&mut self, fldname: &str) -> Result<Constant, std::io::Error>;
+
+    /// The values of the single field of the query's records, with the
+    /// fields of this row it refers to taking their current values.
+    fn subquery_values(&mut self, query: &QueryData) -> Result<Vec<Constant>, std::io::Error> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            format!("subquery ({}) cannot run here", query),
+        ))
+    }
+}
+
+impl Row for &HashMap<String, Constant> {
+    fn get_val(&mut self, fldname: &str) -> Result<Constant, std::io::Error> {
+        self.get(fldname).cloned().ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("no field {} in the record", fldname),
+            )
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ArithOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+}
+
+impl ArithOp {
+    pub(crate) fn symbol(&self) -> &'static str {
+        match self {
+            ArithOp::Add => "+",
+            ArithOp::Sub => "-",
+            ArithOp::Mul => "*",
+            ArithOp::Div => "/",
+            ArithOp::Mod => "%",
+        }
+    }
+
+    pub(crate) fn from_symbol(symbol: &str) -> Option<Self> {
+        match symbol {
+            "+" => Some(ArithOp::Add),
+            "-" => Some(ArithOp::Sub),
+            "*" => Some(ArithOp::Mul),
+            "/" => Some(ArithOp::Div),
+            "%" => Some(ArithOp::Mod),
+            _ => None,
+        }
+    }
+
+    /// Operators of higher precedence bind tighter.
+    fn precedence(&self) -> u8 {
+        match self {
+            ArithOp::Add | ArithOp::Sub => 1,
+            ArithOp::Mul | ArithOp::Div | ArithOp::Mod => 2,
+        }
+    }
+
+    fn apply(&self, lhs: i64, rhs: i64) -> Result<i64, std::io::Error> {
+        let result = match self {
+            ArithOp::Add => lhs.checked_add(rhs),
+            ArithOp::Sub => lhs.checked_sub(rhs),
+            ArithOp::Mul => lhs.checked_mul(rhs),
+            ArithOp::Div | ArithOp::Mod if rhs == 0 => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "division by zero",
+                ))
+            }
+            ArithOp::Div => lhs.checked_div(rhs),
+            ArithOp::Mod => lhs.checked_rem(rhs),
+        };
+        result.ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("{} {} {} overflows", lhs, self.symbol(), rhs),
+            )
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Expression {
+    Constant(Constant),
+    Field(String),
+    Arith(Box<Expression>, ArithOp, Box<Expression>),
+    Negate(Box<Expression>),
+    /// The value of the first branch whose condition holds, else that of
+    /// `otherwise`, else NULL.
+    Case {
+        branches: Vec<(Predicate, Expression)>,
+        otherwise: Option<Box<Expression>>,
+    },
+    /// The value of the single field of the query's single record, NULL
+    /// when it has none.
+    Subquery(Box<QueryData>),
+}
+
+impl Expression {
+    /// Arithmetic is on integers, and gives NULL when an operand is NULL.
+    pub(crate) fn evaluate(&self, row: &mut dyn Row) -> Result<Constant, std::io::Error> {
+        match self {
+            Expression::Constant(value) => Ok(value.clone()),
+            Expression::Field(fldname) => row.get_val(fldname),
+            Expression::Arith(lhs, op, rhs) => match (lhs.evaluate(row)?, rhs.evaluate(row)?) {
+                (Constant::Null, _) | (_, Constant::Null) => Ok(Constant::Null),
+                (Constant::Int(l), Constant::Int(r)) => Ok(Constant::Int(op.apply(l, r)?)),
+                (l, r) => Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("{} {} {} needs integers", l, op.symbol(), r),
+                )),
+            },
+            Expression::Negate(expr) => match expr.evaluate(row)? {
+                Constant::Null => Ok(Constant::Null),
+                Constant::Int(i) => ArithOp::Sub.apply(0, i).map(Constant::Int),
+                value => Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("-{} needs an integer", value),
+                )),
+            },
+            Expression::Case {
+                branches,
+                otherwise,
+            } => {
+                for (condition, value) in branches {
+                    if condition.evaluate(row)? == Some(true) {
+                        return value.evaluate(row);
+                    }
+                }
+                match otherwise {
+                    Some(value) => value.evaluate(row),
+                    None => Ok(Constant::Null),
+                }
+            }
+            Expression::Subquery(query) => {
+                let mut values = row.subquery_values(query)?;
+                if values.len() > 1 {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        format!("subquery ({}) gives more than one record", query),
+                    ));
+                }
+                Ok(values.pop().unwrap_or(Constant::Null))
+            }
+        }
+    }
+
+    pub(crate) fn as_field(&self) -> Option<&str> {
+        match self {
+            Expression::Field(fldname) => Some(fldname),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_constant(&self) -> Option<&Constant> {
+        match self {
+            Expression::Constant(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Whether every field the expression names is in the schema. Never so
+    /// for an expression with a subquery, which may name others.
+    pub(crate) fn applies_to(&self, schema: &Schema) -> bool {
+        if self.has_subquery() {
+            return false;
+        }
+        let mut fields = Vec::new();
+        self.collect_fields(&mut fields);
+        fields.iter().all(|f| schema.has_field(f))
+    }
+
+    pub(crate) fn has_subquery(&self) -> bool {
+        match self {
+            Expression::Constant(_) | Expression::Field(_) => false,
+            Expression::Arith(lhs, _, rhs) => lhs.has_subquery() || rhs.has_subquery(),
+            Expression::Negate(expr) => expr.has_subquery(),
+            Expression::Case {
+                branches,
+                otherwise,
+            } => {
+                branches
+                    .iter()
+                    .any(|(c, v)| c.has_subquery() || v.has_subquery())
+                    || otherwise.as_ref().is_some_and(|v| v.has_subquery())
+            }
+            Expression::Subquery(_) => true,
+        }
+    }
+
+    /// A copy with each field the function gives an expression for replaced
+    /// by it, subqueries included.
+    pub(crate) fn map_fields(&self, f: &dyn Fn(&str) -> Option<Expression>) -> Expression {
+        match self {
+            Expression::Constant(_) => self.clone(),
+            Expression::Field(fldname) => f(fldname).unwrap_or_else(|| self.clone()),
+            Expression::Arith(lhs, op, rhs) => Expression::Arith(
+                Box::new(lhs.map_fields(f)),
+                *op,
+                Box::new(rhs.map_fields(f)),
+            ),
+            Expression::Negate(expr) => Expression::Negate(Box::new(expr.map_fields(f))),
+            Expression::Case {
+                branches,
+                otherwise,
+            } => Expression::Case {
+                branches: branches
+                    .iter()
+                    .map(|(c, v)| (c.map_fields(f), v.map_fields(f)))
+                    .collect(),
+                otherwise: otherwise.as_ref().map(|v| Box::new(v.map_fields(f))),
+            },
+            Expression::Subquery(query) => Expression::Subquery(Box::new(query.map_fields(f))),
+        }
+    }
+
+    /// Adds the fields the expression names to `fields`, each once.
+    pub(crate) fn collect_fields(&self, fields: &mut Vec<String>) {
+        match self {
+            Expression::Constant(_) => {}
+            Expression::Field(fldname) => {
+                if !fields.contains(fldname) {
+                    fields.push(fldname.clone());
+                }
+            }
+            Expression::Arith(lhs, _, rhs) => {
+                lhs.collect_fields(fields);
+                rhs.collect_fields(fields);
+            }
+            Expression::Negate(expr) => expr.collect_fields(fields),
+            Expression::Case {
+                branches,
+                otherwise,
+            } => {
+                for (condition, value) in branches {
+                    for fldname in condition.fields() {
+                        if !fields.contains(&fldname) {
+                            fields.push(fldname);
+                        }
+                    }
+                    value.collect_fields(fields);
+                }
+                if let Some(value) = otherwise {
+                    value.collect_fields(fields);
+                }
+            }
+            // The fields of a subquery are its own, or the outer ones it
+            // refers to, which only the planner can tell apart
+            Expression::Subquery(_) => {}
+        }
+    }
+
+    /// Adds the subqueries of the expression to `subqueries`, but not those
+    /// nested in them.
+    pub(crate) fn collect_subqueries<'q>(&'q self, subqueries: &mut Vec<&'q QueryData>) {
+        match self {
+            Expression::Constant(_) | Expression::Field(_) => {}
+            Expression::Arith(lhs, _, rhs) => {
+                lhs.collect_subqueries(subqueries);
+                rhs.collect_subqueries(subqueries);
+            }
+            Expression::Negate(expr) => expr.collect_subqueries(subqueries),
+            Expression::Case {
+                branches,
+                otherwise,
+            } => {
+                for (condition, value) in branches {
+                    subqueries.extend(condition.subqueries());
+                    value.collect_subqueries(subqueries);
+                }
+                if let Some(value) = otherwise {
+                    value.collect_subqueries(subqueries);
+                }
+            }
+            Expression::Subquery(query) => subqueries.push(query),
+        }
+    }
+
+    /// The type and length of the values over records of the schema, none
+    /// when the expression can only be NULL. The branches of a CASE must
+    /// agree, a VARCHAR and a TEXT giving TEXT.
+    pub(crate) fn column_type(
+        &self,
+        schema: &Schema,
+    ) -> Result<Option<(ColumnType, u64)>, std::io::Error> {
+        match self {
+            Expression::Constant(Constant::Null) => Ok(None),
+            Expression::Constant(Constant::Int(_)) => Ok(Some((ColumnType::Integer, 0))),
+            Expression::Constant(Constant::Str(s)) => {
+                Ok(Some((ColumnType::VarChar, s.chars().count() as u64)))
+            }
+            Expression::Constant(Constant::Bytes(_)) => Ok(Some((ColumnType::Blob, 0))),
+            Expression::Field(fldname) => {
+                if !schema.has_field(fldname) {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::NotFound,
+                        format!("no field {}", fldname),
+                    ));
+                }
+                Ok(Some((schema.ctype(fldname), schema.length(fldname))))
+            }
+            Expression::Arith(lhs, _, rhs) => {
+                lhs.column_type(schema)?;
+                rhs.column_type(schema)?;
+                Ok(Some((ColumnType::Integer, 0)))
+            }
+            Expression::Negate(expr) => {
+                expr.column_type(schema)?;
+                Ok(Some((ColumnType::Integer, 0)))
+            }
+            Expression::Case {
+                branches,
+                otherwise,
+            } => {
+                let values = branches.iter().map(|(_, v)| v).chain(otherwise.as_deref());
+                let mut result: Option<(ColumnType, u64)> = None;
+                for value in values {
+                    let Some((ctype, length)) = value.column_type(schema)? else {
+                        continue;
+                    };
+                    result = Some(match result {
+                        None => (ctype, length),
+                        Some((t, l)) if t == ctype => (t, l.max(length)),
+                        Some((t, _))
+                            if [t, ctype].contains(&ColumnType::VarChar)
+                                && [t, ctype].contains(&ColumnType::Text) =>
+                        {
+                            (ColumnType::Text, 0)
+                        }
+                        Some((t, _)) => {
+                            return Err(std::io::Error::new(
+                                std::io::ErrorKind::InvalidInput,
+                                format!("CASE mixes {:?} and {:?} values", t, ctype),
+                            ))
+                        }
+                    });
+                }
+                Ok(result)
+            }
+            // Typed by the planner, which adds the field the subquery's
+            // values would go in to the schema
+            Expression::Subquery(query) => {
+                let fldname = self.to_string();
+                if query.items.len() != 1 {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        format!("subquery {} must give a single field", fldname),
+                    ));
+                }
+                if !schema.has_field(&fldname) {
+                    return Ok(None);
+                }
+                Ok(Some((schema.ctype(&fldname), schema.length(&fldname))))
+            }
+        }
+    }
+
+    fn fmt_operand(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        op: ArithOp,
+        right: bool,
+    ) -> std::fmt::Result {
+        // Parenthesized when it would otherwise group differently
+        match self {
+            Expression::Arith(_, inner, _)
+                if inner.precedence() < op.precedence()
+                    || (right && inner.precedence() == op.precedence()) =>
+            {
+                write!(f, "({})", self)
+            }
+            _ => write!(f, "{}", self),
+        }
+    }
+}
+
+// The text parses back into the same expression
+impl std::fmt::Display for Expression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expression::Constant(value) => write!(f, "{}", value),
+            Expression::Field(fldname) => write!(f, "{}", fldname),
+            Expression::Arith(lhs, op, rhs) => {
+                lhs.fmt_operand(f, *op, false)?;
+                write!(f, " {} ", op.symbol())?;
+                rhs.fmt_operand(f, *op, true)
+            }
+            Expression::Negate(expr) => match **expr {
+                Expression::Field(_) => write!(f, "-{}", expr),
+                _ => write!(f, "-({})", expr),
+            },
+            Expression::Case {
+                branches,
+                otherwise,
+            } => {
+                write!(f, "case")?;
+                for (condition, value) in branches {
+                    write!(f, " when {} then {}", condition, value)?;
+                }
+                if let Some(value) = otherwise {
+                    write!(f, " else {}", value)?;
+                }
+                write!(f, " end")
+            }
+            Expression::Subquery(query) => write!(f, "({})", query),
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use crate::{
+    constant::Constant, expression::Expression, extend_scan::ExtendScan, plan::Plan,
+    resultset::ColumnType, scan::Scan, schema::Schema, transaction::Transaction,
+};
+
+/// The records of the underlying plan with fields computed from
+/// expressions, typed by what the expressions give.
+pub(crate) struct ExtendPlan {
+    plan: Box<dyn Plan>,
+    computed: Vec<(String, Expression)>,
+    schema: Schema,
+}
+
+impl ExtendPlan {
+    /// The types of scalar subqueries are found in `subquery_types`, under
+    /// the text of the subquery. A field that can only be NULL is taken to
+    /// be a VARCHAR, as in the metadata of a result.
+    pub(crate) fn new(
+        plan: Box<dyn Plan>,
+        computed: Vec<(String, Expression)>,
+        subquery_types: &Schema,
+    ) -> Result<Self, std::io::Error> {
+        let mut typing = plan.schema().clone();
+        typing.add_all(subquery_types);
+        let mut schema = plan.schema().clone();
+        for (fldname, expr) in &computed {
+            match expr.column_type(&typing)? {
+                Some((ctype, length)) => schema.add_field(fldname, ctype, length),
+                None => schema.add_field(fldname, ColumnType::VarChar, "NULL".len() as u64),
+            }
+        }
+        Ok(Self {
+            plan,
+            computed,
+            schema,
+        })
+    }
+
+    fn is_computed(&self, fldname: &str) -> bool {
+        self.computed.iter().any(|(name, _)| name == fldname)
+    }
+}
+
+impl Plan for ExtendPlan {
+    fn open(&self, tx: &mut Transaction) -> Result<Box<dyn Scan>, std::io::Error> {
+        let scan = self.plan.open(tx)?;
+        Ok(Box::new(ExtendScan::new(scan, self.computed.clone())))
+    }
+
+    fn blocks_accessed(&self) -> u64 {
+        self.plan.blocks_accessed()
+    }
+
+    fn records_output(&self) -> u64 {
+        self.plan.records_output()
+    }
+
+    fn distinct_values(&self, fldname: &str) -> u64 {
+        if self.is_computed(fldname) {
+            self.records_output()
+        } else {
+            self.plan.distinct_values(fldname)
+        }
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn eq_selectivity(&self, fldname: &str, value: &Constant) -> f64 {
+        if self.is_computed(fldname) {
+            1.0 / self.distinct_values(fldname).max(1) as f64
+        } else {
+            self.plan.eq_selectivity(fldname, value)
+        }
+    }
+
+    fn range_selectivity(
+        &self,
+        fldname: &str,
+        low: Option<&Constant>,
+        high: Option<&Constant>,
+    ) -> f64 {
+        if self.is_computed(fldname) {
+            1.0 / 3.0
+        } else {
+            self.plan.range_selectivity(fldname, low, high)
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use crate::{
+    constant::Constant,
+    expression::Expression,
+    overflow::LargeValue,
+    query_data::QueryData,
+    scan::{Scan, ScanRow},
+    transaction::Transaction,
+};
+
+/// The records of the underlying scan with extra fields, each computed from
+/// an expression over the record. A computed field hides any field of the
+/// same name below it.
+pub(crate) struct ExtendScan<S: Scan> {
+    scan: S,
+    computed: Vec<(String, Expression)>,
+}
+
+impl<S: Scan> ExtendScan<S> {
+    pub(crate) fn new(scan: S, computed: Vec<(String, Expression)>) -> Self {
+        Self { scan, computed }
+    }
+
+    fn mismatch(fldname: &str, value: &Constant, wanted: &str) -> std::io::Error {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("field {} is {}, not {}", fldname, value, wanted),
+        )
+    }
+}
+
+impl<S: Scan> Scan for ExtendScan<S> {
+    fn before_first(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        self.scan.before_first(tx)
+    }
+
+    fn next(&mut self, tx: &mut Transaction) -> Result<bool, std::io::Error> {
+        self.scan.next(tx)
+    }
+
+    fn get_int(&mut self, tx: &mut Transaction, fldname: &str) -> Result<i64, std::io::Error> {
+        match self.get_val(tx, fldname)? {
+            Constant::Int(i) => Ok(i),
+            value => Err(Self::mismatch(fldname, &value, "an integer")),
+        }
+    }
+
+    fn get_string(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+    ) -> Result<String, std::io::Error> {
+        match self.get_val(tx, fldname)? {
+            Constant::Str(s) => Ok(s),
+            value => Err(Self::mismatch(fldname, &value, "a string")),
+        }
+    }
+
+    fn get_val(&mut self, tx: &mut Transaction, fldname: &str) -> Result<Constant, std::io::Error> {
+        match self.computed.iter().find(|(name, _)| name == fldname) {
+            Some((_, expr)) => expr.evaluate(&mut ScanRow {
+                scan: &mut self.scan,
+                tx,
+            }),
+            None => self.scan.get_val(tx, fldname),
+        }
+    }
+
+    fn has_field(&self, fldname: &str) -> bool {
+        self.computed.iter().any(|(name, _)| name == fldname) || self.scan.has_field(fldname)
+    }
+
+    fn get_large(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+    ) -> Result<Option<LargeValue>, std::io::Error> {
+        match self.computed.iter().any(|(name, _)| name == fldname) {
+            true => Ok(None),
+            false => self.scan.get_large(tx, fldname),
+        }
+    }
+
+    fn close(&mut self, tx: &mut Transaction) {
+        self.scan.close(tx)
+    }
+
+    fn subquery_values(
+        &mut self,
+        tx: &mut Transaction,
+        query: &QueryData,
+    ) -> Result<Vec<Constant>, std::io::Error> {
+        self.scan.subquery_values(tx, query)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        layout::Layout,
+        parser::parse_query,
+        project_scan::ProjectScan,
+        resultset::{ColumnType, ResultSetMetadata},
+        scan::Scan,
+        schema::Schema,
+        simpledb::SimpleDB,
+        table_scan::TableScan,
+        transaction::Transaction,
+    };
+
+    use super::ExtendScan;
+
+    #[test]
+    fn test_extend_scan() {
+        let _ = std::fs::remove_dir_all("extendtest");
+        let mut db = SimpleDB::new("extendtest", 400, 8).unwrap();
+        let mut tx = Transaction::new(
+            &db.file_manager,
+            &mut db.log_manager,
+            &mut db.buffer_manager,
+        );
+
+        let mut schema = Schema::new();
+        schema.add_int_field("A");
+        schema.add_string_field("B", 5);
+        let mut ts = TableScan::new(&mut tx, "T", Layout::new(schema.clone())).unwrap();
+        for (a, b) in [(Some(7), "x"), (None, "y"), (Some(-2), "z")] {
+            ts.insert(&mut tx).unwrap();
+            match a {
+                Some(a) => ts.set_int(&mut tx, "A", a).unwrap(),
+                None => ts.set_null(&mut tx, "A").unwrap(),
+            }
+            ts.set_string(&mut tx, "B", b).unwrap();
+        }
+        ts.close(&mut tx);
+
+        let query = parse_query(
+            "select B, A * 2 + 1 as D, -A % 4, \
+             case when A > 0 then B when A < 0 then 'negative' end as S, NULL N from T",
+        )
+        .unwrap();
+        let computed = query
+            .items
+            .iter()
+            .map(|i| (i.name(), i.expr.clone()))
+            .collect();
+        let ts = TableScan::new(&mut tx, "T", Layout::new(schema.clone())).unwrap();
+        let mut scan = ProjectScan::new(ExtendScan::new(ts, computed), query.field_names());
+        assert_eq!(query.field_names(), ["B", "D", "-A % 4", "S", "N"]);
+        let mut rows = Vec::new();
+        scan.before_first(&mut tx).unwrap();
+        while scan.next(&mut tx).unwrap() {
+            let values = query
+                .field_names()
+                .iter()
+                .map(|f| scan.get_val(&mut tx, f).unwrap().to_string())
+                .collect::<Vec<_>>();
+            rows.push(values.join(" "));
+        }
+        assert_eq!(
+            rows,
+            [
+                "'x' 15 -3 'x' NULL",
+                "'y' NULL NULL NULL NULL",
+                "'z' -3 2 'negative' NULL"
+            ]
+        );
+        scan.before_first(&mut tx).unwrap();
+        scan.next(&mut tx).unwrap();
+        assert_eq!(scan.get_int(&mut tx, "D").unwrap(), 15);
+        assert!(scan.get_string(&mut tx, "D").is_err());
+        scan.close(&mut tx);
+
+        let metadata = ResultSetMetadata::new(&query.items, &schema).unwrap();
+        assert_eq!(metadata.column_count(), 5);
+        let columns = (1..=5)
+            .map(|i| {
+                (
+                    metadata.column_name(i),
+                    metadata.column_type(i),
+                    metadata.column_display_size(i),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            columns,
+            [
+                ("B", ColumnType::VarChar, 5),
+                ("D", ColumnType::Integer, 6),
+                ("-A % 4", ColumnType::Integer, 6),
+                ("S", ColumnType::VarChar, 8),
+                ("N", ColumnType::VarChar, 4),
+            ]
+        );
+        let query = parse_query("select case when A = 1 then 1 else B end from T").unwrap();
+        assert!(ResultSetMetadata::new(&query.items, &schema).is_err());
+        let query = parse_query("select C + 1 from T").unwrap();
+        assert!(ResultSetMetadata::new(&query.items, &schema).is_err());
+        tx.commit().unwrap();
+    }
+}
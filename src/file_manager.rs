@@ -5,7 +5,7 @@ use std::{
 
 use crate::page::Page;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub(crate) struct BlockId {
     filename: String,
     block_number: u64,
@@ -18,50 +18,131 @@ impl BlockId {
             block_number,
         }
     }
+
+    pub(crate) fn filename(&self) -> &str {
+        &self.filename
+    }
+
+    pub(crate) fn number(&self) -> u64 {
+        self.block_number
+    }
+}
+
+impl std::fmt::Display for BlockId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[file {}, block {}]", self.filename, self.block_number)
+    }
 }
 
 pub(crate) struct FileManager {
     pub(crate) dir: PathBuf,
     pub(crate) blocksize: u64,
+    pub(crate) is_new: bool,
 }
 
 impl FileManager {
     pub(crate) fn new(dirname: &str, blocksize: u64) -> Result<Self, std::io::Error> {
+        let is_new = !std::path::Path::new(dirname).exists();
+
         // Create the directory if it doesn't exist
         std::fs::create_dir_all(dirname)?;
 
         // Remove any leftover temp files
         std::fs::read_dir(dirname)?
-            .map(|entry| entry.unwrap().path())
-            .filter(|path| path.is_file() && path.starts_with("temp"))
-            .try_for_each(|path| std::fs::remove_file(path))?;
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .filter(|path| {
+                path.is_file()
+                    && path
+                        .file_name()
+                        .is_some_and(|name| name.to_string_lossy().starts_with("temp"))
+            })
+            .try_for_each(std::fs::remove_file)?;
 
         Ok(Self {
             dir: PathBuf::from(dirname),
             blocksize,
+            is_new,
         })
     }
 }
 
+/// Every block on disk starts with a header holding the CRC32C of the page
+/// that follows it, so pages that were corrupted or only partly written are
+/// noticed when they are read back.
+pub(crate) const BLOCK_HEADER_SIZE: u64 = 8;
+
+#[derive(Debug)]
+pub(crate) struct CorruptBlockError {
+    pub(crate) block: BlockId,
+}
+
+impl std::fmt::Display for CorruptBlockError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "checksum mismatch in {}", self.block)
+    }
+}
+
+impl std::error::Error for CorruptBlockError {}
+
 impl FileManager {
+    fn disk_block_size(&self) -> u64 {
+        BLOCK_HEADER_SIZE + self.blocksize
+    }
+
+    fn encode_block(&self, contents: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.disk_block_size() as usize);
+        bytes.extend_from_slice(&crc32c::crc32c(contents).to_be_bytes());
+        bytes.resize(BLOCK_HEADER_SIZE as usize, 0);
+        bytes.extend_from_slice(contents);
+        bytes
+    }
+
     pub(crate) fn read(&self, block: &BlockId, page: &mut Page) -> Result<(), std::io::Error> {
         let filename = self.dir.join(&block.filename);
-        let offset = block.block_number * self.blocksize;
+        let offset = block.block_number * self.disk_block_size();
         let mut file = std::fs::File::open(filename)?;
         file.seek(SeekFrom::Start(offset))?;
-        file.read(page.bb.contents())?;
+        let mut bytes = vec![0; self.disk_block_size() as usize];
+        let mut read = 0;
+        while read < bytes.len() {
+            let n = file.read(&mut bytes[read..])?;
+            if n == 0 {
+                break;
+            }
+            read += n;
+        }
+
+        let contents = page.bb.contents();
+        // Blocks past the end of the file read as zeros
+        if read == 0 {
+            contents.fill(0);
+            return Ok(());
+        }
+        let (header, data) = bytes.split_at(BLOCK_HEADER_SIZE as usize);
+        let checksum = u32::from_be_bytes(header[..4].try_into().unwrap());
+        if read < bytes.len() || checksum != crc32c::crc32c(data) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                CorruptBlockError {
+                    block: block.clone(),
+                },
+            ));
+        }
+        contents.copy_from_slice(data);
         Ok(())
     }
 
     pub(crate) fn write(&self, block: &BlockId, page: &mut Page) -> Result<(), std::io::Error> {
         let filename = self.dir.join(&block.filename);
-        let offset = block.block_number * self.blocksize;
+        let offset = block.block_number * self.disk_block_size();
         let mut file = std::fs::OpenOptions::new()
             .create(true)
             .write(true)
             .open(filename)?;
         file.seek(SeekFrom::Start(offset))?;
-        file.write_all(page.bb.contents())
+        file.write_all(&self.encode_block(page.bb.contents()))
     }
 
     pub(crate) fn append(&self, filename: &str) -> Result<BlockId, std::io::Error> {
@@ -71,28 +152,70 @@ impl FileManager {
             .create(true)
             .open(&filepath)?;
 
-        let block_number = self.length(filename)? / self.blocksize;
+        let block_number = self.size(filename)?;
         let block = BlockId::new(filename.to_string(), block_number);
-        let offset = block.block_number * self.blocksize;
+        let offset = block.block_number * self.disk_block_size();
 
         file.seek(SeekFrom::Start(offset))?;
-        file.write_all(&vec![0; self.blocksize as usize])?;
+        file.write_all(&self.encode_block(&vec![0; self.blocksize as usize]))?;
 
         Ok(block)
     }
 
+    /// Reads every block of the file and returns the ones whose checksum does
+    /// not match, including a partly written block at the end.
+    pub(crate) fn verify(&self, filename: &str) -> Result<Vec<BlockId>, std::io::Error> {
+        let mut corrupt = Vec::new();
+        let blocks = self.length(filename)?.div_ceil(self.disk_block_size());
+        let mut page = Page::new(self.blocksize);
+        for block_number in 0..blocks {
+            let block = BlockId::new(filename.to_string(), block_number);
+            match self.read(&block, &mut page) {
+                Ok(()) => {}
+                Err(e) if e.get_ref().is_some_and(|e| e.is::<CorruptBlockError>()) => {
+                    corrupt.push(block)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(corrupt)
+    }
+
     pub(crate) fn length(&self, filename: &str) -> Result<u64, std::io::Error> {
         let filename = self.dir.join(filename);
         let metadata = std::fs::metadata(filename)?;
         Ok(metadata.len())
     }
+
+    /// Removes the file. Deleting a file that is already gone succeeds, so a
+    /// delete can safely be repeated during recovery.
+    pub(crate) fn delete(&self, filename: &str) -> Result<(), std::io::Error> {
+        match std::fs::remove_file(self.dir.join(filename)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Number of blocks in the file, a missing file has none.
+    pub(crate) fn size(&self, filename: &str) -> Result<u64, std::io::Error> {
+        match self.length(filename) {
+            Ok(length) => Ok(length / self.disk_block_size()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
 }
 
 
 
 #[cfg(test)]
 mod tests {
-    use crate::{file_manager::BlockId, log_manager::LogManager, page::Page, simpledb::SimpleDB};
+    use crate::{
+        file_manager::{BlockId, CorruptBlockError},
+        log_manager::LogManager,
+        page::Page,
+        simpledb::SimpleDB,
+    };
     use expect_test::{expect, Expect};
     use pretty_hex::PrettyHex;
 
@@ -116,5 +239,39 @@ mod tests {
         assert_eq!(p2.get_string(pos1), "abcdefghijklm");
         assert_eq!(p2.get_int(pos2), 345);
     }
+
+    #[test]
+    fn test_checksum() {
+        let _ = std::fs::remove_dir_all("checksumtest");
+        let db = SimpleDB::new("checksumtest", 400, 8).unwrap();
+        let fm = db.file_manager;
+        let mut page = Page::new(fm.blocksize);
+        for n in 0..3 {
+            let block = fm.append("testfile").unwrap();
+            page.set_int(0, n);
+            fm.write(&block, &mut page).unwrap();
+        }
+        assert!(fm.verify("testfile").unwrap().is_empty());
+
+        // Flip a byte in the middle block
+        let path = fm.dir.join("testfile");
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[408 + 100] ^= 0xff;
+        std::fs::write(&path, &bytes).unwrap();
+
+        let block = BlockId::new("testfile".to_string(), 1);
+        let err = fm.read(&block, &mut page).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        let corrupt = err.get_ref().unwrap().downcast_ref::<CorruptBlockError>().unwrap();
+        assert_eq!(corrupt.block, block);
+        assert_eq!(err.to_string(), "checksum mismatch in [file testfile, block 1]");
+
+        // A torn write at the end of the file is reported too
+        std::fs::write(&path, &bytes[..408 * 2 + 200]).unwrap();
+        assert_eq!(
+            fm.verify("testfile").unwrap(),
+            vec![block, BlockId::new("testfile".to_string(), 2)]
+        );
+    }
 }
 
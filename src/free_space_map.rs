@@ -0,0 +1,163 @@
+use crate::{file_manager::BlockId, transaction::Transaction};
+
+// One entry per heap block holding its number of empty slots
+const ENTRY_SIZE: u64 = 8;
+
+/// Tracks how many empty slots each block of a heap file has, so inserts can
+/// go straight to a block with room. The map lives in its own `.fsm` file and
+/// is updated through the transaction like the heap blocks it describes.
+pub(crate) struct FreeSpaceMap {
+    filename: String,
+    // Blocks before this one were full the last time we looked
+    hint: u64,
+}
+
+impl FreeSpaceMap {
+    pub(crate) fn new(tblname: &str) -> Self {
+        Self {
+            filename: format!("{}.fsm", tblname),
+            hint: 0,
+        }
+    }
+
+    pub(crate) fn filename(&self) -> &str {
+        &self.filename
+    }
+
+    pub(crate) fn get_free(
+        &self,
+        tx: &mut Transaction,
+        heap_block: u64,
+    ) -> Result<u64, std::io::Error> {
+        let (block, offset) = self.locate(tx, heap_block);
+        if block.number() >= tx.size(&self.filename)? {
+            return Ok(0);
+        }
+        tx.pin(&block)?;
+        let free = tx.get_int(&block, offset);
+        tx.unpin(&block);
+        Ok(free)
+    }
+
+    pub(crate) fn set_free(
+        &mut self,
+        tx: &mut Transaction,
+        heap_block: u64,
+        free: u64,
+    ) -> Result<(), std::io::Error> {
+        let (block, offset) = self.locate(tx, heap_block);
+        while tx.size(&self.filename)? <= block.number() {
+            tx.append(&self.filename)?;
+        }
+        tx.pin(&block)?;
+        tx.set_int(&block, offset, free)?;
+        tx.unpin(&block);
+        if free > 0 && heap_block < self.hint {
+            self.hint = heap_block;
+        }
+        Ok(())
+    }
+
+    /// Finds the first of the heap's `heap_blocks` blocks that has an empty slot.
+    pub(crate) fn find_block_with_room(
+        &mut self,
+        tx: &mut Transaction,
+        heap_blocks: u64,
+    ) -> Result<Option<u64>, std::io::Error> {
+        let per_block = self.entries_per_block(tx);
+        let mut heap_block = self.hint;
+        while heap_block < heap_blocks {
+            let (block, _) = self.locate(tx, heap_block);
+            if block.number() >= tx.size(&self.filename)? {
+                break;
+            }
+            tx.pin(&block)?;
+            let last = heap_blocks.min((block.number() + 1) * per_block);
+            let found =
+                (heap_block..last).find(|&b| tx.get_int(&block, (b % per_block) * ENTRY_SIZE) > 0);
+            tx.unpin(&block);
+            if let Some(found) = found {
+                self.hint = found;
+                return Ok(Some(found));
+            }
+            heap_block = last;
+        }
+        self.hint = heap_blocks;
+        Ok(None)
+    }
+
+    fn entries_per_block(&self, tx: &Transaction) -> u64 {
+        tx.block_size() / ENTRY_SIZE
+    }
+
+    fn locate(&self, tx: &Transaction, heap_block: u64) -> (BlockId, u64) {
+        let per_block = self.entries_per_block(tx);
+        let block = BlockId::new(self.filename.clone(), heap_block / per_block);
+        (block, (heap_block % per_block) * ENTRY_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        layout::Layout, schema::Schema, simpledb::SimpleDB, table_scan::TableScan,
+        transaction::Transaction,
+    };
+
+    use super::FreeSpaceMap;
+
+    #[test]
+    fn test_free_space_map() {
+        let _ = std::fs::remove_dir_all("fsmtest");
+        let mut db = SimpleDB::new("fsmtest", 400, 8).unwrap();
+        let mut tx = Transaction::new(
+            &db.file_manager,
+            &mut db.log_manager,
+            &mut db.buffer_manager,
+        );
+
+        let mut schema = Schema::new();
+        schema.add_int_field("A");
+        schema.add_string_field("B", 24);
+        // 48 byte slots, 8 to a block
+        let layout = Layout::new(schema);
+
+        let mut ts = TableScan::new(&mut tx, "T", layout).unwrap();
+        for i in 0..80 {
+            ts.insert(&mut tx).unwrap();
+            ts.set_int(&mut tx, "A", i).unwrap();
+            ts.set_string(&mut tx, "B", "x").unwrap();
+        }
+        assert_eq!(tx.size("T.tbl").unwrap(), 10);
+
+        // Scattered deletes in blocks 2 and 7
+        ts.before_first(&mut tx).unwrap();
+        while ts.next(&mut tx).unwrap() {
+            let a = ts.get_int(&mut tx, "A").unwrap();
+            if a == 17 || a == 20 || a == 61 {
+                ts.delete(&mut tx).unwrap();
+            }
+        }
+
+        let fsm = FreeSpaceMap::new("T");
+        let free = (0..10)
+            .map(|b| fsm.get_free(&mut tx, b).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(free, vec![0, 0, 2, 0, 0, 0, 0, 1, 0, 0]);
+
+        // The holes are filled before the file grows
+        let mut rids = Vec::new();
+        for i in 100..104 {
+            ts.insert(&mut tx).unwrap();
+            ts.set_int(&mut tx, "A", i).unwrap();
+            rids.push(ts.get_rid());
+        }
+        assert_eq!(
+            rids.iter().map(|r| r.block_number).collect::<Vec<_>>(),
+            vec![2, 2, 7, 10]
+        );
+        assert_eq!(fsm.get_free(&mut tx, 10).unwrap(), 7);
+        ts.close(&mut tx);
+        tx.commit().unwrap();
+    }
+}
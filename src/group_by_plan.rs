@@ -0,0 +1,107 @@
+use crate::{
+    aggregate::{AggregateFn, AggregateKind},
+    group_by_scan::GroupByScan,
+    plan::Plan,
+    record_comparator::SortKey,
+    resultset::ColumnType,
+    scan::Scan,
+    schema::Schema,
+    sort_plan::SortPlan,
+    transaction::Transaction,
+};
+
+/// A record per group of the underlying plan's records, with the group
+/// fields and the aggregates. The records are sorted on the group fields
+/// first, unless there are none and everything is one group.
+pub(crate) struct GroupByPlan {
+    plan: Box<dyn Plan>,
+    group_fields: Vec<String>,
+    aggregates: Vec<AggregateFn>,
+    schema: Schema,
+}
+
+impl GroupByPlan {
+    pub(crate) fn new(
+        plan: Box<dyn Plan>,
+        group_fields: Vec<String>,
+        aggregates: Vec<AggregateFn>,
+        block_size: u64,
+    ) -> Result<Self, std::io::Error> {
+        let mut schema = Schema::new();
+        let fields = group_fields
+            .iter()
+            .chain(aggregates.iter().filter_map(|a| a.field.as_ref()));
+        for fldname in fields {
+            if !plan.schema().has_field(fldname) {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("field {} not found", fldname),
+                ));
+            }
+        }
+        for fldname in &group_fields {
+            schema.add(fldname, plan.schema());
+        }
+        for aggregate in &aggregates {
+            let name = aggregate.field_name();
+            match (aggregate.kind, &aggregate.field) {
+                (AggregateKind::Min | AggregateKind::Max, Some(fldname)) => {
+                    let src = plan.schema();
+                    schema.add_field(&name, src.ctype(fldname), src.length(fldname));
+                }
+                _ => schema.add_field(&name, ColumnType::Integer, 0),
+            }
+        }
+        let plan: Box<dyn Plan> = if group_fields.is_empty() {
+            plan
+        } else {
+            let keys = group_fields
+                .iter()
+                .map(|f| SortKey::new(f, false))
+                .collect();
+            Box::new(SortPlan::new(plan, keys, block_size))
+        };
+        Ok(Self {
+            plan,
+            group_fields,
+            aggregates,
+            schema,
+        })
+    }
+}
+
+impl Plan for GroupByPlan {
+    fn open(&self, tx: &mut Transaction) -> Result<Box<dyn Scan>, std::io::Error> {
+        let scan = self.plan.open(tx)?;
+        Ok(Box::new(GroupByScan::new(
+            scan,
+            self.group_fields.clone(),
+            self.aggregates.clone(),
+            tx,
+        )?))
+    }
+
+    fn blocks_accessed(&self) -> u64 {
+        self.plan.blocks_accessed()
+    }
+
+    fn records_output(&self) -> u64 {
+        let mut groups = 1u64;
+        for fldname in &self.group_fields {
+            groups = groups.saturating_mul(self.plan.distinct_values(fldname));
+        }
+        groups.min(self.plan.records_output().max(1))
+    }
+
+    fn distinct_values(&self, fldname: &str) -> u64 {
+        if self.group_fields.iter().any(|f| f == fldname) {
+            self.plan.distinct_values(fldname)
+        } else {
+            self.records_output()
+        }
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+}
@@ -0,0 +1,251 @@
+use crate::{
+    aggregate::{AggregateFn, Aggregator},
+    constant::Constant,
+    scan::Scan,
+    transaction::Transaction,
+};
+
+/// A record per group of the underlying scan, holding the group fields and
+/// the aggregates over the group's records. The underlying scan has to be
+/// sorted on the group fields so each group's records are adjacent. Without
+/// group fields everything is one group, which exists even when there are
+/// no records.
+pub(crate) struct GroupByScan<S: Scan> {
+    scan: S,
+    group_fields: Vec<String>,
+    aggregators: Vec<Aggregator>,
+    group_values: Vec<Constant>,
+    more_groups: bool,
+    // Whether the single group of an empty scan is still to come
+    empty_group: bool,
+}
+
+impl<S: Scan> GroupByScan<S> {
+    pub(crate) fn new(
+        scan: S,
+        group_fields: Vec<String>,
+        aggregates: Vec<AggregateFn>,
+        tx: &mut Transaction,
+    ) -> Result<Self, std::io::Error> {
+        let mut scan = Self {
+            scan,
+            group_fields,
+            aggregators: aggregates.into_iter().map(Aggregator::new).collect(),
+            group_values: Vec::new(),
+            more_groups: false,
+            empty_group: false,
+        };
+        Scan::before_first(&mut scan, tx)?;
+        Ok(scan)
+    }
+
+    fn read_group(&mut self, tx: &mut Transaction) -> Result<Vec<Constant>, std::io::Error> {
+        let mut values = Vec::new();
+        for fldname in &self.group_fields {
+            values.push(self.scan.get_val(tx, fldname)?);
+        }
+        Ok(values)
+    }
+
+    fn aggregator(&self, fldname: &str) -> Option<&Aggregator> {
+        self.aggregators
+            .iter()
+            .find(|a| a.function().field_name() == fldname)
+    }
+}
+
+impl<S: Scan> Scan for GroupByScan<S> {
+    fn before_first(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        self.scan.before_first(tx)?;
+        self.more_groups = self.scan.next(tx)?;
+        self.empty_group = !self.more_groups && self.group_fields.is_empty();
+        Ok(())
+    }
+
+    fn next(&mut self, tx: &mut Transaction) -> Result<bool, std::io::Error> {
+        for aggregator in &mut self.aggregators {
+            aggregator.reset();
+        }
+        if self.empty_group {
+            self.empty_group = false;
+            return Ok(true);
+        }
+        if !self.more_groups {
+            return Ok(false);
+        }
+        self.group_values = self.read_group(tx)?;
+        loop {
+            for aggregator in &mut self.aggregators {
+                aggregator.add(&mut self.scan, tx)?;
+            }
+            self.more_groups = self.scan.next(tx)?;
+            if !self.more_groups || self.read_group(tx)? != self.group_values {
+                return Ok(true);
+            }
+        }
+    }
+
+    fn get_int(&mut self, tx: &mut Transaction, fldname: &str) -> Result<i64, std::io::Error> {
+        match self.get_val(tx, fldname)? {
+            Constant::Int(i) => Ok(i),
+            value => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("{} of field {} is not an integer", value, fldname),
+            )),
+        }
+    }
+
+    fn get_string(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+    ) -> Result<String, std::io::Error> {
+        match self.get_val(tx, fldname)? {
+            Constant::Str(s) => Ok(s),
+            value => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("{} of field {} is not a string", value, fldname),
+            )),
+        }
+    }
+
+    fn get_val(
+        &mut self,
+        _tx: &mut Transaction,
+        fldname: &str,
+    ) -> Result<Constant, std::io::Error> {
+        if let Some(i) = self.group_fields.iter().position(|f| f == fldname) {
+            return Ok(self.group_values[i].clone());
+        }
+        self.aggregator(fldname).map(|a| a.value()).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("field {} is neither grouped nor aggregated", fldname),
+            )
+        })
+    }
+
+    fn has_field(&self, fldname: &str) -> bool {
+        self.group_fields.iter().any(|f| f == fldname) || self.aggregator(fldname).is_some()
+    }
+
+    fn close(&mut self, tx: &mut Transaction) {
+        self.scan.close(tx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        aggregate::{AggregateFn, AggregateKind},
+        layout::Layout,
+        parser::parse_predicate,
+        record_comparator::{RecordComparator, SortKey},
+        scan::Scan,
+        schema::Schema,
+        select_scan::SelectScan,
+        simpledb::SimpleDB,
+        sort_scan::SortScan,
+        table_scan::TableScan,
+        transaction::Transaction,
+    };
+
+    use super::GroupByScan;
+
+    fn aggregates() -> Vec<AggregateFn> {
+        vec![
+            AggregateFn::new(AggregateKind::Count, None),
+            AggregateFn::new(AggregateKind::Count, Some("S")),
+            AggregateFn::new(AggregateKind::Sum, Some("S")),
+            AggregateFn::new(AggregateKind::Avg, Some("S")),
+            AggregateFn::new(AggregateKind::Min, Some("N")),
+            AggregateFn::new(AggregateKind::Max, Some("S")),
+        ]
+    }
+
+    fn contents(scan: &mut dyn Scan, tx: &mut Transaction, fields: &[&str]) -> Vec<String> {
+        let mut rows = Vec::new();
+        while scan.next(tx).unwrap() {
+            let values = fields
+                .iter()
+                .map(|f| scan.get_val(tx, f).unwrap().to_string())
+                .collect::<Vec<_>>();
+            rows.push(values.join(" "));
+        }
+        rows
+    }
+
+    #[test]
+    fn test_group_by_scan() {
+        let _ = std::fs::remove_dir_all("groupbyscantest");
+        let mut db = SimpleDB::new("groupbyscantest", 400, 8).unwrap();
+        let mut tx = Transaction::new(
+            &db.file_manager,
+            &mut db.log_manager,
+            &mut db.buffer_manager,
+        );
+
+        let mut schema = Schema::new();
+        schema.add_string_field("D", 9);
+        schema.add_int_field("S");
+        schema.add_string_field("N", 9);
+        let layout = Layout::new(schema.clone());
+        let mut ts = TableScan::new(&mut tx, "T", layout.clone()).unwrap();
+        for (d, s, n) in [
+            ("b", Some(10), "x"),
+            ("a", Some(5), "y"),
+            ("b", None, "w"),
+            ("c", None, "z"),
+            ("a", Some(8), "v"),
+            ("b", Some(3), "u"),
+        ] {
+            ts.insert(&mut tx).unwrap();
+            ts.set_string(&mut tx, "D", d).unwrap();
+            match s {
+                Some(s) => ts.set_int(&mut tx, "S", s).unwrap(),
+                None => ts.set_null(&mut tx, "S").unwrap(),
+            }
+            ts.set_string(&mut tx, "N", n).unwrap();
+        }
+        ts.close(&mut tx);
+
+        let fields = [
+            "D", "count(*)", "count(S)", "sum(S)", "avg(S)", "min(N)", "max(S)",
+        ];
+        let ts = TableScan::new(&mut tx, "T", layout.clone()).unwrap();
+        let comparator = RecordComparator::new(vec![SortKey::new("D", false)]);
+        let sorted = SortScan::new(ts, &schema, comparator, &mut tx).unwrap();
+        let mut grouped =
+            GroupByScan::new(sorted, vec!["D".to_string()], aggregates(), &mut tx).unwrap();
+        assert!(grouped.has_field("avg(S)"));
+        assert!(!grouped.has_field("S"));
+        assert_eq!(
+            contents(&mut grouped, &mut tx, &fields),
+            [
+                "'a' 2 2 13 6 'v' 8",
+                "'b' 3 2 13 6 'u' 10",
+                "'c' 1 0 NULL NULL 'z' NULL",
+            ]
+        );
+        assert!(grouped.get_val(&mut tx, "S").is_err());
+        grouped.close(&mut tx);
+
+        // Without group fields there is one group, even over no records
+        let ts = TableScan::new(&mut tx, "T", layout.clone()).unwrap();
+        let mut total = GroupByScan::new(ts, Vec::new(), aggregates(), &mut tx).unwrap();
+        assert_eq!(
+            contents(&mut total, &mut tx, &fields[1..]),
+            ["6 4 26 6 'u' 10"]
+        );
+        total.close(&mut tx);
+        let ts = TableScan::new(&mut tx, "T", layout).unwrap();
+        let none = SelectScan::new(ts, parse_predicate("D = 'q'").unwrap());
+        let mut total = GroupByScan::new(none, Vec::new(), aggregates(), &mut tx).unwrap();
+        assert_eq!(
+            contents(&mut total, &mut tx, &fields[1..]),
+            ["0 0 NULL NULL NULL NULL"]
+        );
+        total.close(&mut tx);
+        tx.commit().unwrap();
+    }
+}
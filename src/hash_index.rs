@@ -0,0 +1,157 @@
+use crate::{
+    constant::Constant, index::Index, layout::Layout, rid::Rid, table_scan::TableScan,
+    transaction::Transaction,
+};
+
+pub(crate) const NUM_BUCKETS: u64 = 100;
+
+/// A static hash index. Each bucket is a table of its own, `idx$<index>$<n>`,
+/// holding the indexed value and the rid of the record it came from.
+pub(crate) struct HashIndex {
+    idxname: String,
+    layout: Layout,
+    search_key: Option<Constant>,
+    ts: Option<TableScan>,
+}
+
+impl HashIndex {
+    pub(crate) fn new(idxname: &str, layout: Layout) -> Self {
+        Self {
+            idxname: idxname.to_string(),
+            layout,
+            search_key: None,
+            ts: None,
+        }
+    }
+
+    /// Blocks read by a lookup in an index of `num_blocks` blocks, which are
+    /// spread evenly over the buckets.
+    pub(crate) fn search_cost(num_blocks: u64, _records_per_block: u64) -> u64 {
+        num_blocks / NUM_BUCKETS
+    }
+
+    fn scan(&mut self) -> &mut TableScan {
+        self.ts
+            .as_mut()
+            .expect("hash index is not positioned on a search key")
+    }
+}
+
+// No table can be named like this, the lexer allows no `$` in names
+fn bucket_table(idxname: &str, bucket: u64) -> String {
+    format!("idx${}${}", idxname, bucket)
+}
+
+// Has to stay the same across runs, so the std hasher is not an option
+fn bucket(key: &Constant) -> u64 {
+    match key {
+        Constant::Null => 0,
+        Constant::Int(i) => i.rem_euclid(NUM_BUCKETS as i64) as u64,
+        Constant::Str(s) => hash_bytes(s.as_bytes()),
+        Constant::Bytes(b) => hash_bytes(b),
+    }
+}
+
+fn hash_bytes(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(0u64, |h, &b| h.wrapping_mul(31).wrapping_add(b as u64))
+        % NUM_BUCKETS
+}
+
+impl Index for HashIndex {
+    fn before_first(
+        &mut self,
+        tx: &mut Transaction,
+        search_key: &Constant,
+    ) -> Result<(), std::io::Error> {
+        self.close(tx);
+        let tblname = bucket_table(&self.idxname, bucket(search_key));
+        self.ts = Some(TableScan::new(tx, &tblname, self.layout.clone())?);
+        self.search_key = Some(search_key.clone());
+        Ok(())
+    }
+
+    fn next(&mut self, tx: &mut Transaction) -> Result<bool, std::io::Error> {
+        let search_key = self
+            .search_key
+            .clone()
+            .expect("hash index is not positioned on a search key");
+        let ts = self.scan();
+        while ts.next(tx)? {
+            if ts.get_val(tx, "dataval")? == search_key {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn get_data_rid(&mut self, tx: &mut Transaction) -> Result<Rid, std::io::Error> {
+        let ts = self.scan();
+        let block_number = ts.get_int(tx, "block")? as u64;
+        let slot = ts.get_int(tx, "id")? as usize;
+        Ok(Rid::new(block_number, slot))
+    }
+
+    fn insert(
+        &mut self,
+        tx: &mut Transaction,
+        value: &Constant,
+        rid: Rid,
+    ) -> Result<(), std::io::Error> {
+        self.before_first(tx, value)?;
+        let ts = self.scan();
+        ts.insert(tx)?;
+        ts.set_int(tx, "block", rid.block_number as i64)?;
+        ts.set_int(tx, "id", rid.slot as i64)?;
+        ts.set_val(tx, "dataval", value)
+    }
+
+    fn delete(
+        &mut self,
+        tx: &mut Transaction,
+        value: &Constant,
+        rid: Rid,
+    ) -> Result<(), std::io::Error> {
+        self.before_first(tx, value)?;
+        while self.next(tx)? {
+            if self.get_data_rid(tx)? == rid {
+                return self.scan().delete(tx);
+            }
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        self.close(tx);
+        for bucket in 0..NUM_BUCKETS {
+            let tblname = bucket_table(&self.idxname, bucket);
+            // Buckets nothing was ever hashed to have no file yet
+            if tx.size(&format!("{}.tbl", tblname))? == 0 {
+                continue;
+            }
+            let mut ts = TableScan::new(tx, &tblname, self.layout.clone())?;
+            while ts.next(tx)? {
+                ts.delete(tx)?;
+            }
+            ts.close(tx);
+        }
+        Ok(())
+    }
+
+    fn drop_files(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        self.close(tx);
+        for bucket in 0..NUM_BUCKETS {
+            for filename in TableScan::files(&bucket_table(&self.idxname, bucket)) {
+                tx.drop_file(&filename)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn close(&mut self, tx: &mut Transaction) {
+        if let Some(mut ts) = self.ts.take() {
+            ts.close(tx);
+        }
+    }
+}
@@ -0,0 +1,373 @@
+use std::rc::Rc;
+
+use crate::{
+    alias_plan::AliasPlan,
+    basic_query_planner::plan_item,
+    index_join_plan::IndexJoinPlan,
+    index_select_plan::IndexSelectPlan,
+    plan::Plan,
+    planner::{check_fields, PlanContext, QueryPlanner, TableInfo},
+    predicate::Predicate,
+    product_plan::{combined_schema, ProductPlan},
+    query_data::{FromItem, JoinKind, TableRef},
+    select_plan::SelectPlan,
+    table_plan::TablePlan,
+};
+
+/// Plans a FROM clause by joining its tables one at a time, starting with
+/// the one that gives the fewest records and then taking the cheapest join
+/// each time. The terms of the predicate that only refer to one table are
+/// applied to it before it is joined, through an index when one matches a
+/// term equating its field with a constant, and a stored table is joined
+/// through an index on a field that a term equates with one joined already.
+/// Tables are only joined without a join term when no table has one. Inner
+/// joins are planned as if their conditions were in the WHERE clause; the
+/// tables of outer, semi and anti joins stay with the FROM item they are in,
+/// which is joined as a whole.
+pub(crate) struct HeuristicQueryPlanner;
+
+impl QueryPlanner for HeuristicQueryPlanner {
+    fn plan_from(
+        &self,
+        from: &[FromItem],
+        predicate: &Predicate,
+        ctx: &PlanContext,
+    ) -> Result<Box<dyn Plan>, std::io::Error> {
+        let mut predicate = predicate.clone();
+        let mut sources = Vec::new();
+        for item in from {
+            if item.joins.iter().all(|j| j.kind == JoinKind::Inner) {
+                sources.push((Some(&item.table), ctx.plan_table(&item.table)?));
+                for join in &item.joins {
+                    sources.push((Some(&join.table), ctx.plan_table(&join.table)?));
+                    predicate.conjoin_with(join.on.clone());
+                }
+            } else {
+                sources.push((None, plan_item(item, ctx)?));
+            }
+        }
+        // Terms are only given to the tables with the fields they name once
+        // no name could refer to two of them
+        let schema = sources
+            .iter()
+            .skip(1)
+            .fold(sources[0].1.schema().clone(), |schema, (_, plan)| {
+                combined_schema(&schema, plan.schema())
+            });
+        check_fields(&predicate.fields(), &schema)?;
+
+        let mut planners = sources
+            .into_iter()
+            .map(|(table, plan)| TablePlanner::new(table, plan, &predicate, ctx))
+            .collect::<Vec<_>>();
+        let first = (0..planners.len())
+            .min_by_key(|&i| planners[i].selected.records_output())
+            .expect("a FROM clause has at least one item");
+        let mut current: Rc<dyn Plan> = planners.remove(first).selected;
+        while !planners.is_empty() {
+            let joins = planners
+                .iter()
+                .map(|p| p.join_plan(&current, &predicate))
+                .collect();
+            let (i, plan) = cheapest(joins).unwrap_or_else(|| {
+                let products = planners.iter().map(|p| Some(p.product_plan(&current)));
+                cheapest(products.collect()).expect("a table is left to join")
+            });
+            planners.remove(i);
+            current = Rc::from(plan);
+        }
+
+        // What is left are the terms with subqueries, which apply to no
+        // schema
+        let rest = predicate
+            .terms()
+            .iter()
+            .filter(|t| !t.applies_to(current.schema()))
+            .cloned()
+            .collect();
+        ctx.select(Box::new(current), &Predicate::from_terms(rest))
+    }
+}
+
+/// Plans the joins of one table or FROM item.
+struct TablePlanner<'a> {
+    // The records that satisfy the terms only referring to them
+    selected: Rc<dyn Plan>,
+    own_terms: Option<Predicate>,
+    // The table and what the catalog knows of it, for a stored table
+    stored: Option<(&'a TableRef, &'a TableInfo)>,
+}
+
+impl<'a> TablePlanner<'a> {
+    fn new(
+        table: Option<&'a TableRef>,
+        plan: Box<dyn Plan>,
+        predicate: &Predicate,
+        ctx: &'a PlanContext,
+    ) -> Self {
+        let stored = table
+            .filter(|t| t.subquery.is_none())
+            .and_then(|t| Some((t, ctx.catalog.table(&t.tblname)?)));
+        let own_terms = predicate.select_sub_pred(plan.schema());
+        let mut planner = Self {
+            selected: Rc::from(plan),
+            own_terms,
+            stored,
+        };
+        if let Some(terms) = &planner.own_terms {
+            let plan = match planner.index_select(terms) {
+                Some(index_plan)
+                    if index_plan.blocks_accessed() < planner.selected.blocks_accessed() =>
+                {
+                    index_plan
+                }
+                _ => Box::new(planner.selected.clone()),
+            };
+            planner.selected = Rc::new(SelectPlan::new(plan, terms.clone()));
+        }
+        planner
+    }
+
+    /// The fields of the table with an index, in order, with their names
+    /// qualified by the table's range name.
+    fn indexed_fields(&self) -> Vec<(&'a str, String)> {
+        let Some((table, info)) = self.stored else {
+            return Vec::new();
+        };
+        let mut fields = info
+            .indexes
+            .keys()
+            .map(|f| (f.as_str(), format!("{}.{}", table.range_name(), f)))
+            .collect::<Vec<_>>();
+        fields.sort();
+        fields
+    }
+
+    fn table_plan(&self) -> TablePlan {
+        let (table, info) = self.stored.expect("only stored tables are indexed");
+        TablePlan::new(&table.tblname, info.layout.clone(), info.stat_info.clone())
+    }
+
+    /// The records found through the index that gives the fewest, for a
+    /// term that equates its field with a constant.
+    fn index_select(&self, terms: &Predicate) -> Option<Box<dyn Plan>> {
+        let (table, info) = self.stored?;
+        let mut best: Option<IndexSelectPlan> = None;
+        for (fldname, qualified) in self.indexed_fields() {
+            let value = match terms
+                .equates_with_constant(fldname)
+                .or_else(|| terms.equates_with_constant(&qualified))
+            {
+                Some(value) if !value.is_null() => value,
+                _ => continue,
+            };
+            let index = info.indexes[fldname].clone();
+            let plan = IndexSelectPlan::new(self.table_plan(), index, value.clone());
+            if best
+                .as_ref()
+                .is_none_or(|b| plan.records_output() < b.records_output())
+            {
+                best = Some(plan);
+            }
+        }
+        let plan = best?;
+        Some(Box::new(AliasPlan::new(Box::new(plan), table.range_name())))
+    }
+
+    /// The cheaper of joining the table to the current plan through an
+    /// index and selecting from their product, if any term joins the two.
+    fn join_plan(&self, current: &Rc<dyn Plan>, predicate: &Predicate) -> Option<Box<dyn Plan>> {
+        let join_terms = predicate.join_sub_pred(current.schema(), self.selected.schema())?;
+        let product = Box::new(SelectPlan::new(
+            self.product_plan(current),
+            join_terms.clone(),
+        ));
+        match self.index_join(current, &join_terms) {
+            Some(index_plan) if index_plan.blocks_accessed() < product.blocks_accessed() => {
+                Some(index_plan)
+            }
+            _ => Some(product),
+        }
+    }
+
+    /// The current plan joined to the table through an index on a field
+    /// that a join term equates with one of the plan's. The table's own
+    /// terms still have to be applied to what the index finds.
+    fn index_join(&self, current: &Rc<dyn Plan>, join_terms: &Predicate) -> Option<Box<dyn Plan>> {
+        let (table, info) = self.stored?;
+        for (fldname, qualified) in self.indexed_fields() {
+            let Some(other) = join_terms
+                .equates_with_field(fldname)
+                .or_else(|| join_terms.equates_with_field(&qualified))
+            else {
+                continue;
+            };
+            if !current.schema().has_field(other) {
+                continue;
+            }
+            let plan = IndexJoinPlan::new(
+                Box::new(current.clone()),
+                self.table_plan(),
+                table.range_name(),
+                info.indexes[fldname].clone(),
+                other,
+            );
+            let mut terms = join_terms.clone();
+            if let Some(own_terms) = &self.own_terms {
+                terms.conjoin_with(own_terms.clone());
+            }
+            return Some(Box::new(SelectPlan::new(Box::new(plan), terms)));
+        }
+        None
+    }
+
+    fn product_plan(&self, current: &Rc<dyn Plan>) -> Box<dyn Plan> {
+        Box::new(ProductPlan::new(
+            Box::new(current.clone()),
+            Box::new(self.selected.clone()),
+        ))
+    }
+}
+
+/// The position and plan of the cheapest of the plans, by the blocks they
+/// read and then the records they give.
+fn cheapest(plans: Vec<Option<Box<dyn Plan>>>) -> Option<(usize, Box<dyn Plan>)> {
+    plans
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, plan)| Some((i, plan?)))
+        .min_by_key(|(_, plan)| (plan.blocks_accessed(), plan.records_output()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::{
+        basic_query_planner::BasicQueryPlanner, basic_update_planner::BasicUpdatePlanner,
+        parser::parse_query, planner::Planner, resultset::ResultSet, simpledb::SimpleDB,
+        statement::Statement, transaction::Transaction,
+    };
+
+    use super::HeuristicQueryPlanner;
+
+    fn rows(result: ResultSet) -> Vec<String> {
+        let columns = (1..=result.metadata().column_count())
+            .map(|i| result.metadata().column_name(i).to_string())
+            .collect::<Vec<_>>();
+        let mut rows = result
+            .map(|record| {
+                let values = columns
+                    .iter()
+                    .map(|c| match record.is_null(c) {
+                        true => "NULL".to_string(),
+                        false => record.get_string(c),
+                    })
+                    .collect::<Vec<_>>();
+                values.join(" ")
+            })
+            .collect::<Vec<_>>();
+        rows.sort();
+        rows
+    }
+
+    #[test]
+    fn test_heuristic_query_planner() {
+        let _ = std::fs::remove_dir_all("heuristictest");
+        let mut db = SimpleDB::new("heuristictest", 400, 8).unwrap();
+        let mut stmt = Statement::new(&mut db);
+        for cmd in [
+            "create table DEPT (DID int, DNAME varchar(9))",
+            "create table EMP (EID int, ENAME varchar(9), DEPTID int)",
+            "create table PROJ (PID int, PNAME varchar(9), LEAD int)",
+            "create index EMPID on EMP (EID) using hash",
+            "create index DEPTDID on DEPT (DID) using hash",
+        ] {
+            stmt.execute_update(cmd).unwrap();
+        }
+        for i in 0..5 {
+            let cmd = format!("insert into DEPT (DID, DNAME) values ({}, 'd{}')", i, i);
+            stmt.execute_update(&cmd).unwrap();
+        }
+        for i in 0..200 {
+            let cmd = format!(
+                "insert into EMP (EID, ENAME, DEPTID) values ({}, 'e{}', {})",
+                i,
+                i,
+                i % 5
+            );
+            stmt.execute_update(&cmd).unwrap();
+        }
+        for i in 0..20 {
+            let cmd = format!(
+                "insert into PROJ (PID, PNAME, LEAD) values ({}, 'p{}', {})",
+                i,
+                i,
+                i * 7
+            );
+            stmt.execute_update(&cmd).unwrap();
+        }
+
+        let queries = [
+            "select ENAME from EMP where EID = 42",
+            "select PNAME, ENAME, DNAME from PROJ, EMP, DEPT \
+             where LEAD = EID and DEPTID = DID and PID < 5",
+            "select PNAME, DNAME from PROJ p join EMP e on p.LEAD = e.EID \
+             join DEPT d on e.DEPTID = d.DID where d.DNAME = 'd2'",
+            "select DNAME, PNAME from DEPT left join PROJ on DID = PID, EMP \
+             where EID = DID and EID > 2",
+            "select DNAME from DEPT where exists (select EID from EMP where DEPTID = DID \
+             and EID > 197)",
+            "select ENAME from EMP where DEPTID = (select DID from DEPT where DNAME = 'd4') \
+             and EID < 20",
+        ];
+        let expected = queries
+            .iter()
+            .map(|q| rows(stmt.execute_query(q).unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(expected[0], ["e42"]);
+        assert_eq!(expected[1].len(), 5);
+        assert_eq!(expected[2], ["p1 d2", "p11 d2", "p16 d2", "p6 d2"]);
+
+        let basic = Planner::new(Rc::new(BasicQueryPlanner), Box::new(BasicUpdatePlanner));
+        let heuristic = Planner::new(Rc::new(HeuristicQueryPlanner), Box::new(BasicUpdatePlanner));
+        db.set_query_planner(Rc::new(HeuristicQueryPlanner));
+        let mut stmt = Statement::new(&mut db);
+        for (query, expected) in queries.iter().zip(&expected) {
+            assert_eq!(
+                &rows(stmt.execute_query(query).unwrap()),
+                expected,
+                "{}",
+                query
+            );
+        }
+
+        // The heuristic plans read fewer blocks, through the indexes
+        let mut tx = Transaction::new(
+            &db.file_manager,
+            &mut db.log_manager,
+            &mut db.buffer_manager,
+        );
+        let mdm = &mut db.metadata_manager;
+        let mut cost = |planner: &Planner, text: &str| {
+            let plan = planner
+                .create_query_plan(&parse_query(text).unwrap(), &mut tx, mdm)
+                .unwrap();
+            (plan.blocks_accessed(), plan.records_output())
+        };
+        let (table_scan, _) = cost(&basic, queries[0]);
+        let (index_scan, records) = cost(&heuristic, queries[0]);
+        assert!(index_scan < table_scan);
+        assert_eq!(records, 1);
+        // The database itself now plans with the heuristic planner
+        assert_eq!(cost(&db.planner, queries[0]).0, index_scan);
+        for query in &queries[1..4] {
+            let (basic_blocks, _) = cost(&basic, query);
+            let (heuristic_blocks, _) = cost(&heuristic, query);
+            assert!(heuristic_blocks < basic_blocks, "{}", query);
+        }
+        let query = parse_query("select ENAME from EMP, DEPT where EID = X").unwrap();
+        assert!(heuristic.create_query_plan(&query, &mut tx, mdm).is_err());
+        tx.commit().unwrap();
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+
+use crate::constant::{parse_hex, Constant};
+
+pub(crate) const NUM_BUCKETS: usize = 10;
+pub(crate) const NUM_MCVS: usize = 5;
+
+/// Sampled statistics for one column: its most common values with their
+/// frequencies, and an equi-depth histogram over the remaining values. Each
+/// histogram bucket holds about the same number of values and is described
+/// by its bounds, so `bounds` has one more entry than there are buckets.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ColumnStats {
+    pub(crate) distinct_values: u64,
+    pub(crate) mcvs: Vec<(Constant, f64)>,
+    pub(crate) bounds: Vec<Constant>,
+}
+
+impl ColumnStats {
+    /// Builds the statistics from a sample of the column's values taken out
+    /// of a table estimated to hold `num_records` records.
+    pub(crate) fn from_sample(mut sample: Vec<Constant>, num_records: u64) -> Self {
+        let n = sample.len();
+        if n == 0 {
+            return Self {
+                distinct_values: 0,
+                mcvs: Vec::new(),
+                bounds: Vec::new(),
+            };
+        }
+
+        let mut counts: HashMap<&Constant, usize> = HashMap::new();
+        for value in &sample {
+            *counts.entry(value).or_default() += 1;
+        }
+        let distinct_values = estimate_distinct(&counts, n, num_records);
+
+        // Values seen more than once and more often than average are common
+        let average = n as f64 / counts.len() as f64;
+        let mut common = counts
+            .iter()
+            .filter(|(_, &c)| c > 1 && c as f64 > average)
+            .map(|(&v, &c)| (v.clone(), c))
+            .collect::<Vec<_>>();
+        common.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        common.truncate(NUM_MCVS);
+        let mcvs = common
+            .into_iter()
+            .map(|(v, c)| (v, c as f64 / n as f64))
+            .collect::<Vec<_>>();
+
+        sample.retain(|v| !mcvs.iter().any(|(m, _)| m == v));
+        sample.sort();
+        let bounds = if sample.is_empty() {
+            Vec::new()
+        } else {
+            let buckets = NUM_BUCKETS.min(sample.len());
+            (0..=buckets)
+                .map(|i| sample[(i * (sample.len() - 1)) / buckets].clone())
+                .collect::<Vec<_>>()
+        };
+
+        Self {
+            distinct_values,
+            mcvs,
+            bounds,
+        }
+    }
+
+    fn mcv_total(&self) -> f64 {
+        self.mcvs.iter().map(|(_, f)| f).sum()
+    }
+
+    /// Fraction of records whose value equals `value`.
+    pub(crate) fn eq_selectivity(&self, value: &Constant) -> f64 {
+        if let Some((_, f)) = self.mcvs.iter().find(|(v, _)| v == value) {
+            return *f;
+        }
+        if self.bounds.is_empty()
+            || value < &self.bounds[0]
+            || value > &self.bounds[self.bounds.len() - 1]
+        {
+            return 0.0;
+        }
+        let rest = self
+            .distinct_values
+            .saturating_sub(self.mcvs.len() as u64)
+            .max(1);
+        (1.0 - self.mcv_total()) / rest as f64
+    }
+
+    /// Fraction of records whose value lies between `low` and `high`, a
+    /// missing bound leaving that side open.
+    pub(crate) fn range_selectivity(&self, low: Option<&Constant>, high: Option<&Constant>) -> f64 {
+        let in_range = |v: &Constant| low.is_none_or(|l| v >= l) && high.is_none_or(|h| v <= h);
+        let mcv_part = self
+            .mcvs
+            .iter()
+            .filter(|(v, _)| in_range(v))
+            .map(|(_, f)| f)
+            .sum::<f64>();
+        if self.bounds.is_empty() {
+            return mcv_part;
+        }
+        let from = low.map_or(0.0, |l| self.position(l));
+        let to = high.map_or(1.0, |h| self.position(h));
+        mcv_part + (to - from).max(0.0) * (1.0 - self.mcv_total())
+    }
+
+    // Where the value falls in the histogram, from 0 below the first bound to
+    // 1 above the last
+    fn position(&self, value: &Constant) -> f64 {
+        let buckets = self.bounds.len() - 1;
+        if value < &self.bounds[0] {
+            return 0.0;
+        }
+        if value >= &self.bounds[buckets] || buckets == 0 {
+            return 1.0;
+        }
+        let i = self.bounds.partition_point(|b| b <= value) - 1;
+        let (lo, hi) = (&self.bounds[i], &self.bounds[i + 1]);
+        let within = match (lo, hi, value) {
+            (Constant::Int(lo), Constant::Int(hi), Constant::Int(v)) if hi > lo => {
+                (v - lo) as f64 / (hi - lo) as f64
+            }
+            _ => 0.5,
+        };
+        (i as f64 + within) / buckets as f64
+    }
+}
+
+// The Duj1 estimator of Haas and Stokes, exact when the whole table was read
+fn estimate_distinct(counts: &HashMap<&Constant, usize>, n: usize, num_records: u64) -> u64 {
+    let d = counts.len() as f64;
+    let total = num_records.max(n as u64) as f64;
+    let n = n as f64;
+    let singles = counts.values().filter(|&&c| c == 1).count() as f64;
+    let estimate = n * d / (n - singles + singles * n / total);
+    estimate.round().clamp(d, total) as u64
+}
+
+pub(crate) fn encode_constants(values: &[Constant]) -> String {
+    values
+        .iter()
+        .map(|v| match v {
+            Constant::Null => "NULL".to_string(),
+            Constant::Int(i) => i.to_string(),
+            Constant::Str(_) | Constant::Bytes(_) => v.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+pub(crate) fn decode_constants(text: &str) -> Result<Vec<Constant>, std::io::Error> {
+    let malformed = |value: &str| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("malformed constant {} in {}", value, text),
+        )
+    };
+    let mut values = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c == '\'' {
+            chars.next();
+            let mut s = String::new();
+            while let Some(c) = chars.next() {
+                if c == '\'' {
+                    if chars.peek() == Some(&'\'') {
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                s.push(c);
+            }
+            values.push(Constant::Str(s));
+        } else {
+            let text = chars.by_ref().take_while(|&c| c != ',').collect::<String>();
+            values.push(match text.as_str() {
+                "NULL" => Constant::Null,
+                hex if hex.starts_with("X'") && hex.ends_with('\'') && hex.len() >= 3 => {
+                    Constant::Bytes(
+                        parse_hex(&hex[2..hex.len() - 1]).ok_or_else(|| malformed(hex))?,
+                    )
+                }
+                digits => Constant::Int(digits.parse().map_err(|_| malformed(digits))?),
+            });
+            continue;
+        }
+        // Skip the separator after a string
+        chars.next();
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_constants, encode_constants, ColumnStats};
+    use crate::constant::Constant;
+
+    #[test]
+    fn test_histogram() {
+        // 0..1000 once each, plus 7 making up a third of the values
+        let mut sample = (0..1000).map(Constant::Int).collect::<Vec<_>>();
+        sample.extend((0..500).map(|_| Constant::Int(7)));
+        let stats = ColumnStats::from_sample(sample, 1500);
+
+        assert_eq!(stats.distinct_values, 1000);
+        assert_eq!(stats.mcvs.len(), 1);
+        assert!((stats.eq_selectivity(&Constant::Int(7)) - 501.0 / 1500.0).abs() < 1e-9);
+        assert!((stats.eq_selectivity(&Constant::Int(8)) - 1.0 / 1500.0).abs() < 1e-4);
+        assert_eq!(stats.eq_selectivity(&Constant::Int(5000)), 0.0);
+
+        let below_100 = stats.range_selectivity(None, Some(&Constant::Int(99)));
+        assert!((below_100 - (99.0 + 501.0) / 1500.0).abs() < 0.01);
+        let above = stats.range_selectivity(Some(&Constant::Int(500)), None);
+        assert!((above - 499.0 / 1500.0).abs() < 0.01);
+        assert_eq!(
+            stats.range_selectivity(Some(&Constant::Int(2000)), None),
+            0.0
+        );
+
+        let values = vec![
+            Constant::Int(-3),
+            Constant::Str("it's, here".to_string()),
+            Constant::Str(String::new()),
+            Constant::Null,
+            Constant::Int(42),
+        ];
+        assert_eq!(
+            decode_constants(&encode_constants(&values)).unwrap(),
+            values
+        );
+        assert!(decode_constants("").unwrap().is_empty());
+        assert!(decode_constants("1,two").is_err());
+    }
+}
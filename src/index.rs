@@ -0,0 +1,52 @@
+use crate::{constant::Constant, rid::Rid, transaction::Transaction};
+
+/// How an index organizes its entries, as named in the `idxcat` catalog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum IndexType {
+    Hash,
+}
+
+impl IndexType {
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            IndexType::Hash => "hash",
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "hash" => Some(IndexType::Hash),
+            _ => None,
+        }
+    }
+}
+
+/// Maps the values of one field to the rids of the records holding them.
+/// A lookup positions the index before the entries for a search key, and
+/// `next` then steps through them.
+pub(crate) trait Index {
+    fn before_first(
+        &mut self,
+        tx: &mut Transaction,
+        search_key: &Constant,
+    ) -> Result<(), std::io::Error>;
+    fn next(&mut self, tx: &mut Transaction) -> Result<bool, std::io::Error>;
+    fn get_data_rid(&mut self, tx: &mut Transaction) -> Result<Rid, std::io::Error>;
+    fn insert(
+        &mut self,
+        tx: &mut Transaction,
+        value: &Constant,
+        rid: Rid,
+    ) -> Result<(), std::io::Error>;
+    fn delete(
+        &mut self,
+        tx: &mut Transaction,
+        value: &Constant,
+        rid: Rid,
+    ) -> Result<(), std::io::Error>;
+    /// Removes every entry, leaving an empty index to be filled again.
+    fn clear(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error>;
+    /// Schedules the index's files for deletion when the transaction commits.
+    fn drop_files(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error>;
+    fn close(&mut self, tx: &mut Transaction);
+}
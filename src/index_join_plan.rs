@@ -0,0 +1,104 @@
+use crate::{
+    alias_plan::aliased_schema, alias_scan::AliasScan, constant::Constant,
+    index_join_scan::IndexJoinScan, index_manager::IndexInfo, plan::Plan,
+    product_plan::combined_schema, scan::Scan, schema::Schema, table_plan::TablePlan,
+    transaction::Transaction,
+};
+
+/// The records of a plan joined to those of a stored table whose indexed
+/// field equals the plan's join field. The table's fields can also be
+/// referred to as `alias.field`. The index is searched once per record of
+/// the plan, and a block read per match.
+pub(crate) struct IndexJoinPlan {
+    lhs: Box<dyn Plan>,
+    rhs: TablePlan,
+    alias: String,
+    index: IndexInfo,
+    join_field: String,
+    schema: Schema,
+}
+
+impl IndexJoinPlan {
+    pub(crate) fn new(
+        lhs: Box<dyn Plan>,
+        rhs: TablePlan,
+        alias: &str,
+        index: IndexInfo,
+        join_field: &str,
+    ) -> Self {
+        let schema = combined_schema(lhs.schema(), &aliased_schema(rhs.schema(), alias));
+        Self {
+            lhs,
+            rhs,
+            alias: alias.to_string(),
+            index,
+            join_field: join_field.to_string(),
+            schema,
+        }
+    }
+
+    /// The field of the table that the name refers to, if it is one of its
+    /// fields rather than the plan's.
+    fn rhs_field<'f>(&self, fldname: &'f str) -> Option<&'f str> {
+        let field = match fldname.split_once('.') {
+            Some((alias, field)) if alias == self.alias => field,
+            _ => fldname,
+        };
+        (!self.lhs.schema().has_field(fldname) && self.rhs.schema().has_field(field))
+            .then_some(field)
+    }
+}
+
+impl Plan for IndexJoinPlan {
+    fn open(&self, tx: &mut Transaction) -> Result<Box<dyn Scan>, std::io::Error> {
+        let lhs = self.lhs.open(tx)?;
+        let rhs = AliasScan::new(self.rhs.open_table(tx)?, &self.alias);
+        Ok(Box::new(IndexJoinScan::new(
+            lhs,
+            self.index.open(),
+            &self.join_field,
+            rhs,
+            tx,
+        )?))
+    }
+
+    fn blocks_accessed(&self) -> u64 {
+        self.lhs.blocks_accessed()
+            + self.lhs.records_output() * self.index.blocks_accessed()
+            + self.records_output()
+    }
+
+    fn records_output(&self) -> u64 {
+        self.lhs.records_output() * self.index.records_output()
+    }
+
+    fn distinct_values(&self, fldname: &str) -> u64 {
+        match self.rhs_field(fldname) {
+            Some(field) => self.rhs.distinct_values(field),
+            None => self.lhs.distinct_values(fldname),
+        }
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn eq_selectivity(&self, fldname: &str, value: &Constant) -> f64 {
+        match self.rhs_field(fldname) {
+            Some(field) => self.rhs.eq_selectivity(field, value),
+            None => self.lhs.eq_selectivity(fldname, value),
+        }
+    }
+
+    fn range_selectivity(
+        &self,
+        fldname: &str,
+        low: Option<&Constant>,
+        high: Option<&Constant>,
+    ) -> f64 {
+        match self.rhs_field(fldname) {
+            Some(field) => self.rhs.range_selectivity(field, low, high),
+            None => self.lhs.range_selectivity(fldname, low, high),
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use crate::{
+    constant::Constant,
+    index::Index,
+    overflow::LargeValue,
+    product_scan::in_first,
+    query_data::QueryData,
+    scan::{Scan, UpdateScan},
+    transaction::Transaction,
+};
+
+/// The pairs of a record of the left scan and a record of the table whose
+/// indexed field equals the left record's join field. For each left record
+/// the index finds the matching records, so the table is never read as a
+/// whole. A NULL join field matches nothing.
+pub(crate) struct IndexJoinScan<S: Scan, T: UpdateScan> {
+    lhs: S,
+    idx: Box<dyn Index>,
+    join_field: String,
+    rhs: T,
+    /// Whether `lhs` is positioned on a record.
+    on_record: bool,
+    /// Whether the index is positioned before the entries for the current
+    /// left record's key, which a NULL key has none of.
+    searching: bool,
+}
+
+impl<S: Scan, T: UpdateScan> IndexJoinScan<S, T> {
+    pub(crate) fn new(
+        lhs: S,
+        idx: Box<dyn Index>,
+        join_field: &str,
+        rhs: T,
+        tx: &mut Transaction,
+    ) -> Result<Self, std::io::Error> {
+        let mut scan = Self {
+            lhs,
+            idx,
+            join_field: join_field.to_string(),
+            rhs,
+            on_record: false,
+            searching: false,
+        };
+        Scan::before_first(&mut scan, tx)?;
+        Ok(scan)
+    }
+
+    fn reset_index(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        let key = self.lhs.get_val(tx, &self.join_field)?;
+        self.searching = !key.is_null();
+        if self.searching {
+            self.idx.before_first(tx, &key)?;
+        }
+        Ok(())
+    }
+}
+
+impl<S: Scan, T: UpdateScan> Scan for IndexJoinScan<S, T> {
+    fn before_first(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        self.lhs.before_first(tx)?;
+        self.on_record = self.lhs.next(tx)?;
+        if self.on_record {
+            self.reset_index(tx)?;
+        }
+        Ok(())
+    }
+
+    fn next(&mut self, tx: &mut Transaction) -> Result<bool, std::io::Error> {
+        while self.on_record {
+            if self.searching && self.idx.next(tx)? {
+                let rid = self.idx.get_data_rid(tx)?;
+                self.rhs.move_to_rid(tx, rid)?;
+                return Ok(true);
+            }
+            self.on_record = self.lhs.next(tx)?;
+            if self.on_record {
+                self.reset_index(tx)?;
+            }
+        }
+        Ok(false)
+    }
+
+    fn get_int(&mut self, tx: &mut Transaction, fldname: &str) -> Result<i64, std::io::Error> {
+        if in_first(&self.lhs, &self.rhs, fldname)? {
+            self.lhs.get_int(tx, fldname)
+        } else {
+            self.rhs.get_int(tx, fldname)
+        }
+    }
+
+    fn get_string(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+    ) -> Result<String, std::io::Error> {
+        if in_first(&self.lhs, &self.rhs, fldname)? {
+            self.lhs.get_string(tx, fldname)
+        } else {
+            self.rhs.get_string(tx, fldname)
+        }
+    }
+
+    fn get_val(&mut self, tx: &mut Transaction, fldname: &str) -> Result<Constant, std::io::Error> {
+        if in_first(&self.lhs, &self.rhs, fldname)? {
+            self.lhs.get_val(tx, fldname)
+        } else {
+            self.rhs.get_val(tx, fldname)
+        }
+    }
+
+    fn has_field(&self, fldname: &str) -> bool {
+        self.lhs.has_field(fldname) || self.rhs.has_field(fldname)
+    }
+
+    fn get_large(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+    ) -> Result<Option<LargeValue>, std::io::Error> {
+        if in_first(&self.lhs, &self.rhs, fldname)? {
+            self.lhs.get_large(tx, fldname)
+        } else {
+            self.rhs.get_large(tx, fldname)
+        }
+    }
+
+    fn close(&mut self, tx: &mut Transaction) {
+        self.lhs.close(tx);
+        self.idx.close(tx);
+        self.rhs.close(tx);
+    }
+
+    fn subquery_values(
+        &mut self,
+        tx: &mut Transaction,
+        query: &QueryData,
+    ) -> Result<Vec<Constant>, std::io::Error> {
+        self.lhs.subquery_values(tx, query)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        alias_scan::AliasScan,
+        index::IndexType,
+        scan::Scan,
+        schema::Schema,
+        simpledb::SimpleDB,
+        table_scan::TableScan,
+        transaction::Transaction,
+    };
+
+    use super::IndexJoinScan;
+
+    #[test]
+    fn test_index_join_scan() {
+        let _ = std::fs::remove_dir_all("indexjointest");
+        let mut db = SimpleDB::new("indexjointest", 400, 8).unwrap();
+        let mdm = &mut db.metadata_manager;
+        let mut tx = Transaction::new(
+            &db.file_manager,
+            &mut db.log_manager,
+            &mut db.buffer_manager,
+        );
+        let mut schema = Schema::new();
+        schema.add_int_field("ID");
+        schema.add_string_field("N", 9);
+        mdm.create_table("DEPT", &schema, &mut tx).unwrap();
+        let dept = mdm.get_layout("DEPT", &mut tx).unwrap().unwrap();
+        let mut schema = Schema::new();
+        schema.add_int_field("DID");
+        schema.add_string_field("N", 9);
+        mdm.create_table("EMP", &schema, &mut tx).unwrap();
+        let emp = mdm.get_layout("EMP", &mut tx).unwrap().unwrap();
+
+        let mut ts = TableScan::new(&mut tx, "DEPT", dept.clone()).unwrap();
+        for i in 0..3 {
+            ts.insert(&mut tx).unwrap();
+            ts.set_int(&mut tx, "ID", i).unwrap();
+            ts.set_string(&mut tx, "N", &format!("d{}", i)).unwrap();
+        }
+        ts.close(&mut tx);
+        let mut ts = TableScan::new(&mut tx, "EMP", emp.clone()).unwrap();
+        for i in 0..10 {
+            ts.insert(&mut tx).unwrap();
+            ts.set_int(&mut tx, "DID", i % 4).unwrap();
+            ts.set_string(&mut tx, "N", &format!("e{}", i)).unwrap();
+        }
+        ts.insert(&mut tx).unwrap();
+        ts.set_null(&mut tx, "DID").unwrap();
+        ts.set_string(&mut tx, "N", "nobody").unwrap();
+        ts.close(&mut tx);
+        mdm.create_index("DEPTID", "DEPT", "ID", IndexType::Hash, &mut tx)
+            .unwrap();
+        let idx = mdm.get_index_info("DEPT", &mut tx).unwrap()["ID"].open();
+
+        // The fields both tables have are told apart by their aliases
+        let lhs = AliasScan::new(TableScan::new(&mut tx, "EMP", emp).unwrap(), "e");
+        let rhs = AliasScan::new(TableScan::new(&mut tx, "DEPT", dept).unwrap(), "d");
+        let mut scan = IndexJoinScan::new(lhs, idx, "DID", rhs, &mut tx).unwrap();
+        let mut pairs = Vec::new();
+        while scan.next(&mut tx).unwrap() {
+            assert_eq!(
+                scan.get_val(&mut tx, "DID").unwrap(),
+                scan.get_val(&mut tx, "ID").unwrap()
+            );
+            pairs.push(format!(
+                "{} {}",
+                scan.get_string(&mut tx, "e.N").unwrap(),
+                scan.get_string(&mut tx, "d.N").unwrap()
+            ));
+        }
+        assert_eq!(
+            pairs,
+            ["e0 d0", "e1 d1", "e2 d2", "e4 d0", "e5 d1", "e6 d2", "e8 d0", "e9 d1"]
+        );
+        assert!(scan.get_string(&mut tx, "N").is_err());
+        scan.close(&mut tx);
+        tx.commit().unwrap();
+    }
+}
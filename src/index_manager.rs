@@ -0,0 +1,480 @@
+use std::collections::HashMap;
+
+use crate::{
+    hash_index::HashIndex,
+    index::{Index, IndexType},
+    layout::Layout,
+    resultset::ColumnType,
+    schema::Schema,
+    stat_manager::{StatInfo, StatManager},
+    table_manager::{check_name, TableManager, MAX_NAME},
+    table_scan::TableScan,
+    transaction::Transaction,
+};
+
+/// Keeps index definitions in the `idxcat` catalog table, a row per index
+/// with its name, the indexed table and field, and the kind of index.
+pub(crate) struct IndexManager {
+    icat_layout: Layout,
+}
+
+impl IndexManager {
+    pub(crate) fn new(
+        is_new: bool,
+        table_manager: &TableManager,
+        tx: &mut Transaction,
+    ) -> Result<Self, std::io::Error> {
+        let mut schema = Schema::new();
+        schema.add_string_field("indexname", MAX_NAME);
+        schema.add_string_field("tablename", MAX_NAME);
+        schema.add_string_field("fieldname", MAX_NAME);
+        schema.add_string_field("indextype", MAX_NAME);
+        if is_new {
+            table_manager.create_table("idxcat", &schema, tx)?;
+        }
+
+        Ok(Self {
+            icat_layout: Layout::new(schema),
+        })
+    }
+
+    /// Records the index in the catalog and fills it with the records the
+    /// table already holds.
+    pub(crate) fn create_index(
+        &self,
+        idxname: &str,
+        tblname: &str,
+        fldname: &str,
+        index_type: IndexType,
+        table_manager: &TableManager,
+        tx: &mut Transaction,
+    ) -> Result<(), std::io::Error> {
+        check_name(idxname)?;
+        let layout = table_manager.get_layout(tblname, tx)?.ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("table {} does not exist", tblname),
+            )
+        })?;
+        match layout
+            .schema()
+            .has_field(fldname)
+            .then(|| layout.schema().ctype(fldname))
+        {
+            Some(ColumnType::Integer | ColumnType::VarChar) => {}
+            Some(ctype) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!(
+                        "field {}.{} of type {:?} cannot be indexed",
+                        tblname, fldname, ctype
+                    ),
+                ))
+            }
+            None => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("field {}.{} does not exist", tblname, fldname),
+                ))
+            }
+        }
+        if self.index_names(tx)?.iter().any(|name| name == idxname) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("index {} already exists", idxname),
+            ));
+        }
+
+        let mut icat = TableScan::new(tx, "idxcat", self.icat_layout.clone())?;
+        icat.insert(tx)?;
+        icat.set_string(tx, "indexname", idxname)?;
+        icat.set_string(tx, "tablename", tblname)?;
+        icat.set_string(tx, "fieldname", fldname)?;
+        icat.set_string(tx, "indextype", index_type.name())?;
+        icat.close(tx);
+
+        let mut index = open_index(idxname, index_type, index_layout(layout.schema(), fldname));
+        fill_index(index.as_mut(), tblname, &layout, fldname, tx)
+    }
+
+    /// Deletes the index's row from `idxcat` and schedules its files for
+    /// deletion. Returns the table it was on, if there was such an index.
+    pub(crate) fn drop_index(
+        &self,
+        idxname: &str,
+        table_manager: &TableManager,
+        tx: &mut Transaction,
+    ) -> Result<Option<String>, std::io::Error> {
+        let mut found = None;
+        let mut icat = TableScan::new(tx, "idxcat", self.icat_layout.clone())?;
+        while icat.next(tx)? {
+            if icat.get_string(tx, "indexname")? == idxname {
+                let tblname = icat.get_string(tx, "tablename")?;
+                let fldname = icat.get_string(tx, "fieldname")?;
+                let type_name = icat.get_string(tx, "indextype")?;
+                icat.delete(tx)?;
+                found = Some((tblname, fldname, type_name));
+                break;
+            }
+        }
+        icat.close(tx);
+        let Some((tblname, fldname, type_name)) = found else {
+            return Ok(None);
+        };
+
+        let index_type = IndexType::from_name(&type_name).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("unknown type {} for index {}", type_name, idxname),
+            )
+        })?;
+        let layout = table_manager.get_layout(&tblname, tx)?.ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("index on missing table {}", tblname),
+            )
+        })?;
+        let layout = index_layout(layout.schema(), &fldname);
+        open_index(idxname, index_type, layout).drop_files(tx)?;
+        Ok(Some(tblname))
+    }
+
+    /// Empties and refills every index on the table, for after its records
+    /// were moved and their rids changed.
+    pub(crate) fn rebuild_indexes(
+        &self,
+        tblname: &str,
+        table_manager: &TableManager,
+        tx: &mut Transaction,
+    ) -> Result<(), std::io::Error> {
+        let indexes = self.index_defs(tblname, tx)?;
+        if indexes.is_empty() {
+            return Ok(());
+        }
+        let layout = table_manager.get_layout(tblname, tx)?.ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("index on missing table {}", tblname),
+            )
+        })?;
+        for (idxname, fldname, index_type) in indexes {
+            let mut index = open_index(
+                &idxname,
+                index_type,
+                index_layout(layout.schema(), &fldname),
+            );
+            index.clear(tx)?;
+            fill_index(index.as_mut(), tblname, &layout, &fldname, tx)?;
+        }
+        Ok(())
+    }
+
+    /// Points the catalog entries of the table's indexes at its new name.
+    pub(crate) fn rename_table(
+        &self,
+        old: &str,
+        new: &str,
+        tx: &mut Transaction,
+    ) -> Result<(), std::io::Error> {
+        let mut icat = TableScan::new(tx, "idxcat", self.icat_layout.clone())?;
+        while icat.next(tx)? {
+            if icat.get_string(tx, "tablename")? == old {
+                icat.set_string(tx, "tablename", new)?;
+            }
+        }
+        icat.close(tx);
+        Ok(())
+    }
+
+    pub(crate) fn rename_field(
+        &self,
+        tblname: &str,
+        old: &str,
+        new: &str,
+        tx: &mut Transaction,
+    ) -> Result<(), std::io::Error> {
+        let mut icat = TableScan::new(tx, "idxcat", self.icat_layout.clone())?;
+        while icat.next(tx)? {
+            if icat.get_string(tx, "tablename")? == tblname
+                && icat.get_string(tx, "fieldname")? == old
+            {
+                icat.set_string(tx, "fieldname", new)?;
+            }
+        }
+        icat.close(tx);
+        Ok(())
+    }
+
+    /// The name, field and type of each index on the table.
+    pub(crate) fn index_defs(
+        &self,
+        tblname: &str,
+        tx: &mut Transaction,
+    ) -> Result<Vec<(String, String, IndexType)>, std::io::Error> {
+        let mut found = Vec::new();
+        let mut icat = TableScan::new(tx, "idxcat", self.icat_layout.clone())?;
+        while icat.next(tx)? {
+            if icat.get_string(tx, "tablename")? == tblname {
+                let idxname = icat.get_string(tx, "indexname")?;
+                let fldname = icat.get_string(tx, "fieldname")?;
+                let type_name = icat.get_string(tx, "indextype")?;
+                let index_type = IndexType::from_name(&type_name).ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("unknown type {} for index {}", type_name, idxname),
+                    )
+                })?;
+                found.push((idxname, fldname, index_type));
+            }
+        }
+        icat.close(tx);
+        Ok(found)
+    }
+
+    pub(crate) fn index_names(&self, tx: &mut Transaction) -> Result<Vec<String>, std::io::Error> {
+        let mut names = Vec::new();
+        let mut icat = TableScan::new(tx, "idxcat", self.icat_layout.clone())?;
+        while icat.next(tx)? {
+            names.push(icat.get_string(tx, "indexname")?);
+        }
+        icat.close(tx);
+        Ok(names)
+    }
+
+    /// The indexes on the table, keyed by the field they index.
+    pub(crate) fn get_index_info(
+        &self,
+        tblname: &str,
+        table_manager: &TableManager,
+        stat_manager: &mut StatManager,
+        tx: &mut Transaction,
+    ) -> Result<HashMap<String, IndexInfo>, std::io::Error> {
+        let found = self.index_defs(tblname, tx)?;
+        let mut result = HashMap::new();
+        if found.is_empty() {
+            return Ok(result);
+        }
+        let layout = table_manager.get_layout(tblname, tx)?.ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("index on missing table {}", tblname),
+            )
+        })?;
+        let stat_info = stat_manager.get_stat_info(tblname, &layout, tx)?;
+        for (idxname, fldname, index_type) in found {
+            let info = IndexInfo {
+                idxname,
+                fldname: fldname.clone(),
+                index_type,
+                idx_layout: index_layout(layout.schema(), &fldname),
+                stat_info: stat_info.clone(),
+                block_size: tx.block_size(),
+            };
+            result.insert(fldname, info);
+        }
+        Ok(result)
+    }
+}
+
+/// What the planner needs to know about an index: how to open it and what
+/// a lookup through it is estimated to cost.
+#[derive(Clone)]
+pub(crate) struct IndexInfo {
+    idxname: String,
+    fldname: String,
+    index_type: IndexType,
+    idx_layout: Layout,
+    stat_info: StatInfo,
+    block_size: u64,
+}
+
+impl IndexInfo {
+    pub(crate) fn index_name(&self) -> &str {
+        &self.idxname
+    }
+
+    pub(crate) fn field_name(&self) -> &str {
+        &self.fldname
+    }
+
+    pub(crate) fn index_type(&self) -> IndexType {
+        self.index_type
+    }
+
+    pub(crate) fn open(&self) -> Box<dyn Index> {
+        open_index(&self.idxname, self.index_type, self.idx_layout.clone())
+    }
+
+    /// Blocks read to find the entries for one search key.
+    pub(crate) fn blocks_accessed(&self) -> u64 {
+        let records_per_block = (self.block_size / self.idx_layout.slot_size()).max(1);
+        let num_blocks = self.stat_info.records_output() / records_per_block;
+        match self.index_type {
+            IndexType::Hash => HashIndex::search_cost(num_blocks, records_per_block),
+        }
+    }
+
+    /// Records expected to share one value of the indexed field.
+    pub(crate) fn records_output(&self) -> u64 {
+        self.stat_info.records_output() / self.stat_info.distinct_values(&self.fldname).max(1)
+    }
+
+    pub(crate) fn distinct_values(&self, fldname: &str) -> u64 {
+        if fldname == self.fldname {
+            1
+        } else {
+            self.stat_info.distinct_values(fldname)
+        }
+    }
+}
+
+fn fill_index(
+    index: &mut dyn Index,
+    tblname: &str,
+    layout: &Layout,
+    fldname: &str,
+    tx: &mut Transaction,
+) -> Result<(), std::io::Error> {
+    let mut ts = TableScan::new(tx, tblname, layout.clone())?;
+    while ts.next(tx)? {
+        let value = ts.get_val(tx, fldname)?;
+        index.insert(tx, &value, ts.get_rid())?;
+    }
+    ts.close(tx);
+    index.close(tx);
+    Ok(())
+}
+
+fn open_index(idxname: &str, index_type: IndexType, layout: Layout) -> Box<dyn Index> {
+    match index_type {
+        IndexType::Hash => Box::new(HashIndex::new(idxname, layout)),
+    }
+}
+
+// Index records hold the rid of a data record and the value indexed
+fn index_layout(table_schema: &Schema, fldname: &str) -> Layout {
+    let mut schema = Schema::new();
+    schema.add_int_field("block");
+    schema.add_int_field("id");
+    schema.add_field(
+        "dataval",
+        table_schema.ctype(fldname),
+        table_schema.length(fldname),
+    );
+    Layout::new(schema)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        constant::Constant, index::IndexType, rid::Rid, schema::Schema, simpledb::SimpleDB,
+        table_scan::TableScan, transaction::Transaction,
+    };
+
+    #[test]
+    fn test_index_manager() {
+        let _ = std::fs::remove_dir_all("idxmgrtest");
+        let mut schema = Schema::new();
+        schema.add_int_field("A");
+        schema.add_string_field("B", 9);
+        schema.add_text_field("C");
+
+        {
+            let mut db = SimpleDB::new("idxmgrtest", 400, 8).unwrap();
+            let mdm = &mut db.metadata_manager;
+            let mut tx = Transaction::new(
+                &db.file_manager,
+                &mut db.log_manager,
+                &mut db.buffer_manager,
+            );
+            mdm.create_table("T", &schema, &mut tx).unwrap();
+            let layout = mdm.get_layout("T", &mut tx).unwrap().unwrap();
+            let mut ts = TableScan::new(&mut tx, "T", layout).unwrap();
+            for i in 0..300 {
+                ts.insert(&mut tx).unwrap();
+                ts.set_int(&mut tx, "A", i % 30).unwrap();
+                ts.set_string(&mut tx, "B", &format!("b{}", i)).unwrap();
+            }
+            ts.close(&mut tx);
+
+            mdm.create_index("TA", "T", "A", IndexType::Hash, &mut tx)
+                .unwrap();
+            mdm.create_index("TB", "T", "B", IndexType::Hash, &mut tx)
+                .unwrap();
+            assert!(mdm
+                .create_index("TA", "T", "B", IndexType::Hash, &mut tx)
+                .is_err());
+            assert!(mdm
+                .create_index("TC", "T", "C", IndexType::Hash, &mut tx)
+                .is_err());
+            assert!(mdm
+                .create_index("TD", "T", "D", IndexType::Hash, &mut tx)
+                .is_err());
+            tx.commit().unwrap();
+        }
+
+        let mut db = SimpleDB::new("idxmgrtest", 400, 8).unwrap();
+        let mdm = &mut db.metadata_manager;
+        let mut tx = Transaction::new(
+            &db.file_manager,
+            &mut db.log_manager,
+            &mut db.buffer_manager,
+        );
+        let layout = mdm.get_layout("T", &mut tx).unwrap().unwrap();
+        let indexes = mdm.get_index_info("T", &mut tx).unwrap();
+        assert_eq!(indexes.len(), 2);
+        let info = &indexes["A"];
+        assert_eq!(info.index_name(), "TA");
+        assert_eq!(info.index_type(), IndexType::Hash);
+        assert_eq!(info.records_output(), 10);
+        assert_eq!(info.distinct_values("A"), 1);
+        assert_eq!(info.distinct_values("B"), 300);
+        // 32 byte index records: 25 blocks spread over 100 buckets
+        assert_eq!(info.blocks_accessed(), 0);
+
+        // Every rid found for a key leads to a record holding it
+        let lookup = |tx: &mut Transaction, key: &Constant| {
+            let mut index = indexes["A"].open();
+            index.before_first(tx, key).unwrap();
+            let mut rids = Vec::new();
+            while index.next(tx).unwrap() {
+                rids.push(index.get_data_rid(tx).unwrap());
+            }
+            index.close(tx);
+            rids
+        };
+        let rids = lookup(&mut tx, &Constant::Int(7));
+        assert_eq!(rids.len(), 10);
+        let mut ts = TableScan::new(&mut tx, "T", layout).unwrap();
+        for rid in &rids {
+            ts.move_to_rid(&mut tx, *rid).unwrap();
+            assert_eq!(ts.get_int(&mut tx, "A").unwrap(), 7);
+        }
+        ts.close(&mut tx);
+
+        let mut index = indexes["B"].open();
+        index
+            .before_first(&mut tx, &Constant::Str("b123".to_string()))
+            .unwrap();
+        assert!(index.next(&mut tx).unwrap());
+        let rid = index.get_data_rid(&mut tx).unwrap();
+        assert!(!index.next(&mut tx).unwrap());
+        index
+            .delete(&mut tx, &Constant::Str("b123".to_string()), rid)
+            .unwrap();
+        index
+            .before_first(&mut tx, &Constant::Str("b123".to_string()))
+            .unwrap();
+        assert!(!index.next(&mut tx).unwrap());
+        index
+            .insert(&mut tx, &Constant::Str("b123".to_string()), Rid::new(3, 4))
+            .unwrap();
+        index
+            .before_first(&mut tx, &Constant::Str("b123".to_string()))
+            .unwrap();
+        assert!(index.next(&mut tx).unwrap());
+        assert_eq!(index.get_data_rid(&mut tx).unwrap(), Rid::new(3, 4));
+        index.close(&mut tx);
+        tx.commit().unwrap();
+    }
+}
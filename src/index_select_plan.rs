@@ -0,0 +1,69 @@
+use crate::{
+    constant::Constant, index_manager::IndexInfo, index_select_scan::IndexSelectScan, plan::Plan,
+    scan::Scan, schema::Schema, table_plan::TablePlan, transaction::Transaction,
+};
+
+/// The records of a stored table whose indexed field equals a value, looked
+/// up in the index. What it costs is the lookup and a block per record
+/// found.
+pub(crate) struct IndexSelectPlan {
+    table: TablePlan,
+    index: IndexInfo,
+    value: Constant,
+}
+
+impl IndexSelectPlan {
+    pub(crate) fn new(table: TablePlan, index: IndexInfo, value: Constant) -> Self {
+        Self {
+            table,
+            index,
+            value,
+        }
+    }
+}
+
+impl Plan for IndexSelectPlan {
+    fn open(&self, tx: &mut Transaction) -> Result<Box<dyn Scan>, std::io::Error> {
+        let ts = self.table.open_table(tx)?;
+        Ok(Box::new(IndexSelectScan::new(
+            ts,
+            self.index.open(),
+            self.value.clone(),
+            tx,
+        )?))
+    }
+
+    fn blocks_accessed(&self) -> u64 {
+        self.index.blocks_accessed() + self.records_output()
+    }
+
+    fn records_output(&self) -> u64 {
+        let selectivity = self
+            .table
+            .eq_selectivity(self.index.field_name(), &self.value);
+        (self.table.records_output() as f64 * selectivity).ceil() as u64
+    }
+
+    fn distinct_values(&self, fldname: &str) -> u64 {
+        self.index
+            .distinct_values(fldname)
+            .min(self.records_output().max(1))
+    }
+
+    fn schema(&self) -> &Schema {
+        self.table.schema()
+    }
+
+    fn eq_selectivity(&self, fldname: &str, value: &Constant) -> f64 {
+        self.table.eq_selectivity(fldname, value)
+    }
+
+    fn range_selectivity(
+        &self,
+        fldname: &str,
+        low: Option<&Constant>,
+        high: Option<&Constant>,
+    ) -> f64 {
+        self.table.range_selectivity(fldname, low, high)
+    }
+}
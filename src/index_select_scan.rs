@@ -0,0 +1,153 @@
+use crate::{
+    constant::Constant,
+    index::Index,
+    overflow::LargeValue,
+    query_data::QueryData,
+    scan::{Scan, UpdateScan},
+    transaction::Transaction,
+};
+
+/// The records of a table whose indexed field equals a value, found through
+/// the index rather than by reading the whole table.
+pub(crate) struct IndexSelectScan<S: UpdateScan> {
+    ts: S,
+    idx: Box<dyn Index>,
+    value: Constant,
+}
+
+impl<S: UpdateScan> IndexSelectScan<S> {
+    pub(crate) fn new(
+        ts: S,
+        idx: Box<dyn Index>,
+        value: Constant,
+        tx: &mut Transaction,
+    ) -> Result<Self, std::io::Error> {
+        let mut scan = Self { ts, idx, value };
+        Scan::before_first(&mut scan, tx)?;
+        Ok(scan)
+    }
+}
+
+impl<S: UpdateScan> Scan for IndexSelectScan<S> {
+    fn before_first(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        self.idx.before_first(tx, &self.value)
+    }
+
+    fn next(&mut self, tx: &mut Transaction) -> Result<bool, std::io::Error> {
+        if !self.idx.next(tx)? {
+            return Ok(false);
+        }
+        let rid = self.idx.get_data_rid(tx)?;
+        self.ts.move_to_rid(tx, rid)?;
+        Ok(true)
+    }
+
+    fn get_int(&mut self, tx: &mut Transaction, fldname: &str) -> Result<i64, std::io::Error> {
+        self.ts.get_int(tx, fldname)
+    }
+
+    fn get_string(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+    ) -> Result<String, std::io::Error> {
+        self.ts.get_string(tx, fldname)
+    }
+
+    fn get_val(&mut self, tx: &mut Transaction, fldname: &str) -> Result<Constant, std::io::Error> {
+        self.ts.get_val(tx, fldname)
+    }
+
+    fn has_field(&self, fldname: &str) -> bool {
+        self.ts.has_field(fldname)
+    }
+
+    fn get_large(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+    ) -> Result<Option<LargeValue>, std::io::Error> {
+        self.ts.get_large(tx, fldname)
+    }
+
+    fn close(&mut self, tx: &mut Transaction) {
+        self.idx.close(tx);
+        self.ts.close(tx);
+    }
+
+    fn subquery_values(
+        &mut self,
+        tx: &mut Transaction,
+        query: &QueryData,
+    ) -> Result<Vec<Constant>, std::io::Error> {
+        self.ts.subquery_values(tx, query)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        constant::Constant,
+        index::IndexType,
+        scan::Scan,
+        schema::Schema,
+        simpledb::SimpleDB,
+        table_scan::TableScan,
+        transaction::Transaction,
+    };
+
+    use super::IndexSelectScan;
+
+    #[test]
+    fn test_index_select_scan() {
+        let _ = std::fs::remove_dir_all("indexselecttest");
+        let mut db = SimpleDB::new("indexselecttest", 400, 8).unwrap();
+        let mdm = &mut db.metadata_manager;
+        let mut tx = Transaction::new(
+            &db.file_manager,
+            &mut db.log_manager,
+            &mut db.buffer_manager,
+        );
+        let mut schema = Schema::new();
+        schema.add_int_field("A");
+        schema.add_string_field("B", 9);
+        mdm.create_table("T", &schema, &mut tx).unwrap();
+        let layout = mdm.get_layout("T", &mut tx).unwrap().unwrap();
+        let mut ts = TableScan::new(&mut tx, "T", layout.clone()).unwrap();
+        for i in 0..100 {
+            ts.insert(&mut tx).unwrap();
+            ts.set_int(&mut tx, "A", i % 7).unwrap();
+            ts.set_string(&mut tx, "B", &format!("b{}", i)).unwrap();
+        }
+        ts.close(&mut tx);
+        mdm.create_index("TA", "T", "A", IndexType::Hash, &mut tx)
+            .unwrap();
+        let indexes = mdm.get_index_info("T", &mut tx).unwrap();
+
+        let ts = TableScan::new(&mut tx, "T", layout).unwrap();
+        let idx = indexes["A"].open();
+        let mut scan = IndexSelectScan::new(ts, idx, Constant::Int(3), &mut tx).unwrap();
+        let mut found = Vec::new();
+        while scan.next(&mut tx).unwrap() {
+            assert_eq!(scan.get_int(&mut tx, "A").unwrap(), 3);
+            found.push(scan.get_string(&mut tx, "B").unwrap());
+        }
+        found.sort();
+        let mut expected = (0..100)
+            .filter(|i| i % 7 == 3)
+            .map(|i| format!("b{}", i))
+            .collect::<Vec<_>>();
+        expected.sort();
+        assert_eq!(found, expected);
+
+        // Scanning again finds the same records
+        scan.before_first(&mut tx).unwrap();
+        let mut count = 0;
+        while scan.next(&mut tx).unwrap() {
+            count += 1;
+        }
+        assert_eq!(count, expected.len());
+        scan.close(&mut tx);
+        tx.commit().unwrap();
+    }
+}
@@ -0,0 +1,107 @@
+use crate::{
+    constant::Constant,
+    join_scan::JoinScan,
+    plan::Plan,
+    predicate::Predicate,
+    product_plan::{combined_schema, side},
+    query_data::JoinKind,
+    scan::Scan,
+    schema::Schema,
+    transaction::Transaction,
+};
+
+/// The records of a join of two plans on a predicate, by nested loops over
+/// the outer side. Semi and anti joins only give the left side's fields,
+/// but estimates for the fields of both, which the predicate needs.
+pub(crate) struct JoinPlan {
+    left: Box<dyn Plan>,
+    right: Box<dyn Plan>,
+    kind: JoinKind,
+    on: Predicate,
+    schema: Schema,
+}
+
+impl JoinPlan {
+    pub(crate) fn new(
+        left: Box<dyn Plan>,
+        right: Box<dyn Plan>,
+        kind: JoinKind,
+        on: Predicate,
+    ) -> Self {
+        let schema = match kind {
+            JoinKind::Semi | JoinKind::Anti => left.schema().clone(),
+            _ => combined_schema(left.schema(), right.schema()),
+        };
+        Self {
+            left,
+            right,
+            kind,
+            on,
+            schema,
+        }
+    }
+
+    fn side(&self, fldname: &str) -> &dyn Plan {
+        side(self.left.as_ref(), self.right.as_ref(), fldname)
+    }
+}
+
+impl Plan for JoinPlan {
+    fn open(&self, tx: &mut Transaction) -> Result<Box<dyn Scan>, std::io::Error> {
+        let left = self.left.open(tx)?;
+        let right = self.right.open(tx)?;
+        Ok(Box::new(JoinScan::new(
+            left,
+            right,
+            self.kind,
+            self.on.clone(),
+            tx,
+        )?))
+    }
+
+    fn blocks_accessed(&self) -> u64 {
+        let (outer, inner) = match self.kind {
+            JoinKind::Right => (&self.right, &self.left),
+            _ => (&self.left, &self.right),
+        };
+        outer.blocks_accessed() + outer.records_output() * inner.blocks_accessed()
+    }
+
+    fn records_output(&self) -> u64 {
+        let (l, r) = (self.left.records_output(), self.right.records_output());
+        let selectivity = self.on.selectivity(self);
+        let matched = (l as f64 * r as f64 * selectivity).ceil() as u64;
+        // The left records with at least one match, taking each pairing to
+        // match independently
+        let semi = (l as f64 * (r as f64 * selectivity).min(1.0)).ceil() as u64;
+        match self.kind {
+            JoinKind::Inner => matched,
+            JoinKind::Left => matched.max(l),
+            JoinKind::Right => matched.max(r),
+            JoinKind::Full => matched.max(l).max(r),
+            JoinKind::Semi => semi,
+            JoinKind::Anti => l - semi.min(l),
+        }
+    }
+
+    fn distinct_values(&self, fldname: &str) -> u64 {
+        self.side(fldname).distinct_values(fldname)
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn eq_selectivity(&self, fldname: &str, value: &Constant) -> f64 {
+        self.side(fldname).eq_selectivity(fldname, value)
+    }
+
+    fn range_selectivity(
+        &self,
+        fldname: &str,
+        low: Option<&Constant>,
+        high: Option<&Constant>,
+    ) -> f64 {
+        self.side(fldname).range_selectivity(fldname, low, high)
+    }
+}
@@ -0,0 +1,383 @@
+use crate::{
+    constant::Constant, expression::Row, overflow::LargeValue, predicate::Predicate,
+    product_scan::in_first, query_data::JoinKind, scan::Scan, transaction::Transaction,
+};
+
+/// The pairs of records of two scans that satisfy the join condition, found
+/// by rescanning the inner side for each record of the outer side. The
+/// outer side is the left one except for right joins. Outer joins also
+/// give the unmatched records of the sides they keep, with NULL for every
+/// field of the other side; for full joins those of the right side come
+/// last, which takes remembering which right records found a match. Semi
+/// and anti joins stop looking at the first match.
+pub(crate) struct JoinScan<S1: Scan, S2: Scan> {
+    left: S1,
+    right: S2,
+    kind: JoinKind,
+    on: Predicate,
+    outer_on_record: bool,
+    inner_done: bool,
+    matched: bool,
+    // For full joins, which records of the right side matched, by position
+    right_matched: Vec<bool>,
+    right_pos: usize,
+    // Whether the records of the outer side are all joined, and a full join
+    // is now giving the unmatched right records
+    outer_done: bool,
+    // The side whose fields are NULL in the current record, true for left
+    null_left: Option<bool>,
+}
+
+impl<S1: Scan, S2: Scan> JoinScan<S1, S2> {
+    pub(crate) fn new(
+        left: S1,
+        right: S2,
+        kind: JoinKind,
+        on: Predicate,
+        tx: &mut Transaction,
+    ) -> Result<Self, std::io::Error> {
+        let mut scan = Self {
+            left,
+            right,
+            kind,
+            on,
+            outer_on_record: false,
+            inner_done: false,
+            matched: false,
+            right_matched: Vec::new(),
+            right_pos: 0,
+            outer_done: false,
+            null_left: None,
+        };
+        Scan::before_first(&mut scan, tx)?;
+        Ok(scan)
+    }
+
+    fn outer(&mut self) -> &mut dyn Scan {
+        match self.kind {
+            JoinKind::Right => &mut self.right,
+            _ => &mut self.left,
+        }
+    }
+
+    fn inner(&mut self) -> &mut dyn Scan {
+        match self.kind {
+            JoinKind::Right => &mut self.left,
+            _ => &mut self.right,
+        }
+    }
+
+    fn next_inner(&mut self, tx: &mut Transaction) -> Result<bool, std::io::Error> {
+        if !self.inner().next(tx)? {
+            return Ok(false);
+        }
+        if self.kind != JoinKind::Right {
+            self.right_pos += 1;
+        }
+        Ok(true)
+    }
+
+    fn restart_inner(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        self.right_pos = 0;
+        self.inner_done = false;
+        self.matched = false;
+        self.inner().before_first(tx)
+    }
+
+    /// Whether the join only picks out records of the left side.
+    fn filters_left(&self) -> bool {
+        matches!(self.kind, JoinKind::Semi | JoinKind::Anti)
+    }
+
+    fn is_match(&mut self, tx: &mut Transaction) -> Result<bool, std::io::Error> {
+        let mut row = JoinRow {
+            left: &mut self.left,
+            right: &mut self.right,
+            tx,
+        };
+        Ok(self.on.evaluate(&mut row)? == Some(true))
+    }
+}
+
+impl<S1: Scan, S2: Scan> Scan for JoinScan<S1, S2> {
+    fn before_first(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        self.outer().before_first(tx)?;
+        self.outer_on_record = self.outer().next(tx)?;
+        self.outer_done = false;
+        self.null_left = None;
+        self.restart_inner(tx)
+    }
+
+    fn next(&mut self, tx: &mut Transaction) -> Result<bool, std::io::Error> {
+        self.null_left = None;
+        while !self.outer_done {
+            if !self.outer_on_record {
+                self.outer_done = true;
+                self.restart_inner(tx)?;
+                break;
+            }
+            if !self.inner_done {
+                if self.next_inner(tx)? {
+                    if self.is_match(tx)? {
+                        self.matched = true;
+                        match self.kind {
+                            JoinKind::Full => {
+                                self.right_matched
+                                    .resize(self.right_pos.max(self.right_matched.len()), false);
+                                self.right_matched[self.right_pos - 1] = true;
+                            }
+                            // One match decides
+                            JoinKind::Semi | JoinKind::Anti => self.inner_done = true,
+                            _ => {}
+                        }
+                        if self.kind != JoinKind::Anti {
+                            return Ok(true);
+                        }
+                    }
+                    continue;
+                }
+                self.inner_done = true;
+                if !self.matched {
+                    match self.kind {
+                        JoinKind::Inner | JoinKind::Semi => {}
+                        JoinKind::Anti => return Ok(true),
+                        _ => {
+                            self.null_left = Some(self.kind == JoinKind::Right);
+                            return Ok(true);
+                        }
+                    }
+                }
+            }
+            self.outer_on_record = self.outer().next(tx)?;
+            self.restart_inner(tx)?;
+        }
+        if self.kind != JoinKind::Full {
+            return Ok(false);
+        }
+        while self.next_inner(tx)? {
+            if !self
+                .right_matched
+                .get(self.right_pos - 1)
+                .copied()
+                .unwrap_or(false)
+            {
+                self.null_left = Some(true);
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn get_int(&mut self, tx: &mut Transaction, fldname: &str) -> Result<i64, std::io::Error> {
+        match self.get_val(tx, fldname)? {
+            Constant::Int(i) => Ok(i),
+            value => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("{} of field {} is not an integer", value, fldname),
+            )),
+        }
+    }
+
+    fn get_string(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+    ) -> Result<String, std::io::Error> {
+        match self.get_val(tx, fldname)? {
+            Constant::Str(s) => Ok(s),
+            value => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("{} of field {} is not a string", value, fldname),
+            )),
+        }
+    }
+
+    fn get_val(&mut self, tx: &mut Transaction, fldname: &str) -> Result<Constant, std::io::Error> {
+        if self.filters_left() {
+            return self.left.get_val(tx, fldname);
+        }
+        let in_left = in_first(&self.left, &self.right, fldname)?;
+        if self.null_left == Some(in_left) {
+            return Ok(Constant::Null);
+        }
+        if in_left {
+            self.left.get_val(tx, fldname)
+        } else {
+            self.right.get_val(tx, fldname)
+        }
+    }
+
+    fn has_field(&self, fldname: &str) -> bool {
+        self.left.has_field(fldname) || (!self.filters_left() && self.right.has_field(fldname))
+    }
+
+    fn get_large(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+    ) -> Result<Option<LargeValue>, std::io::Error> {
+        if self.filters_left() {
+            return self.left.get_large(tx, fldname);
+        }
+        let in_left = in_first(&self.left, &self.right, fldname)?;
+        if self.null_left == Some(in_left) {
+            return Ok(None);
+        }
+        if in_left {
+            self.left.get_large(tx, fldname)
+        } else {
+            self.right.get_large(tx, fldname)
+        }
+    }
+
+    fn close(&mut self, tx: &mut Transaction) {
+        self.left.close(tx);
+        self.right.close(tx);
+    }
+}
+
+/// The pair of current records the join condition is evaluated on.
+struct JoinRow<'s, 't, 'a, S1: Scan, S2: Scan> {
+    left: &'s mut S1,
+    right: &'s mut S2,
+    tx: &'t mut Transaction<'a>,
+}
+
+impl<S1: Scan, S2: Scan> Row for JoinRow<'_, '_, '_, S1, S2> {
+    fn get_val(&mut self, fldname: &str) -> Result<Constant, std::io::Error> {
+        if in_first(self.left, self.right, fldname)? {
+            self.left.get_val(self.tx, fldname)
+        } else {
+            self.right.get_val(self.tx, fldname)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        alias_scan::AliasScan, layout::Layout, parser::parse_predicate, product_scan::ProductScan,
+        query_data::JoinKind, scan::Scan, schema::Schema, simpledb::SimpleDB,
+        table_scan::TableScan, transaction::Transaction,
+    };
+
+    use super::JoinScan;
+
+    fn contents(scan: &mut dyn Scan, tx: &mut Transaction, fields: &[&str]) -> Vec<String> {
+        let mut rows = Vec::new();
+        while scan.next(tx).unwrap() {
+            let values = fields
+                .iter()
+                .map(|f| scan.get_val(tx, f).unwrap().to_string())
+                .collect::<Vec<_>>();
+            rows.push(values.join(" "));
+        }
+        rows
+    }
+
+    #[test]
+    fn test_join_scan() {
+        let _ = std::fs::remove_dir_all("joinscantest");
+        let mut db = SimpleDB::new("joinscantest", 400, 8).unwrap();
+        let mut tx = Transaction::new(
+            &db.file_manager,
+            &mut db.log_manager,
+            &mut db.buffer_manager,
+        );
+
+        let mut schema = Schema::new();
+        schema.add_int_field("ID");
+        schema.add_string_field("N", 9);
+        let layout = Layout::new(schema);
+        for (tblname, rows) in [
+            ("L", [(1, "a"), (2, "b"), (3, "c")]),
+            ("R", [(2, "x"), (3, "y"), (4, "z")]),
+        ] {
+            let mut ts = TableScan::new(&mut tx, tblname, layout.clone()).unwrap();
+            for (id, n) in rows {
+                ts.insert(&mut tx).unwrap();
+                ts.set_int(&mut tx, "ID", id).unwrap();
+                ts.set_string(&mut tx, "N", n).unwrap();
+            }
+            ts.close(&mut tx);
+        }
+
+        let fields = ["l.ID", "l.N", "r.ID", "r.N"];
+        for (kind, expected) in [
+            (JoinKind::Inner, vec!["3 'c' 2 'x'"]),
+            (
+                JoinKind::Left,
+                vec!["1 'a' NULL NULL", "2 'b' NULL NULL", "3 'c' 2 'x'"],
+            ),
+            (
+                JoinKind::Right,
+                vec!["3 'c' 2 'x'", "NULL NULL 3 'y'", "NULL NULL 4 'z'"],
+            ),
+            (
+                JoinKind::Full,
+                vec![
+                    "1 'a' NULL NULL",
+                    "2 'b' NULL NULL",
+                    "3 'c' 2 'x'",
+                    "NULL NULL 3 'y'",
+                    "NULL NULL 4 'z'",
+                ],
+            ),
+        ] {
+            let l = AliasScan::new(TableScan::new(&mut tx, "L", layout.clone()).unwrap(), "l");
+            let r = AliasScan::new(TableScan::new(&mut tx, "R", layout.clone()).unwrap(), "r");
+            let on = parse_predicate("l.ID > r.ID and r.N = 'x'").unwrap();
+            let mut join = JoinScan::new(l, r, kind, on, &mut tx).unwrap();
+            assert_eq!(
+                contents(&mut join, &mut tx, &fields),
+                expected,
+                "{:?}",
+                kind
+            );
+            // The same again after starting over
+            join.before_first(&mut tx).unwrap();
+            assert_eq!(contents(&mut join, &mut tx, &fields).len(), expected.len());
+            assert!(join.get_val(&mut tx, "ID").is_err());
+            join.close(&mut tx);
+        }
+
+        // Semi and anti joins give each left record at most once
+        for (kind, expected) in [
+            (JoinKind::Semi, vec!["2 'b'", "3 'c'"]),
+            (JoinKind::Anti, vec!["1 'a'"]),
+        ] {
+            let l = AliasScan::new(TableScan::new(&mut tx, "L", layout.clone()).unwrap(), "l");
+            let r = AliasScan::new(TableScan::new(&mut tx, "R", layout.clone()).unwrap(), "r");
+            let on = parse_predicate("l.ID >= r.ID").unwrap();
+            let mut join = JoinScan::new(l, r, kind, on, &mut tx).unwrap();
+            assert_eq!(contents(&mut join, &mut tx, &["ID", "N"]), expected);
+            assert!(!join.has_field("r.N"));
+            join.close(&mut tx);
+        }
+
+        // Outer joins keep every record of an empty side's partner
+        let l = AliasScan::new(TableScan::new(&mut tx, "L", layout.clone()).unwrap(), "l");
+        let e = AliasScan::new(TableScan::new(&mut tx, "E", layout.clone()).unwrap(), "e");
+        let on = parse_predicate("l.ID = e.ID").unwrap();
+        let mut join = JoinScan::new(e, l, JoinKind::Full, on, &mut tx).unwrap();
+        assert_eq!(
+            contents(&mut join, &mut tx, &["e.ID", "l.ID"]),
+            ["NULL 1", "NULL 2", "NULL 3"]
+        );
+        join.close(&mut tx);
+
+        // Unqualified names work unless both sides have the field
+        let l = AliasScan::new(TableScan::new(&mut tx, "L", layout.clone()).unwrap(), "L");
+        let r = AliasScan::new(TableScan::new(&mut tx, "R", layout).unwrap(), "R");
+        let mut product = ProductScan::new(l, r, &mut tx).unwrap();
+        assert!(product.next(&mut tx).unwrap());
+        assert!(product.has_field("L.N") && !product.has_field("l.N"));
+        assert_eq!(
+            product.get_val(&mut tx, "N").unwrap_err().to_string(),
+            "field N is ambiguous"
+        );
+        assert_eq!(product.get_string(&mut tx, "R.N").unwrap(), "x");
+        product.close(&mut tx);
+        tx.commit().unwrap();
+    }
+}
@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+
+use crate::{overflow::OverflowPointer, page::Page, resultset::ColumnType, schema::Schema};
+
+// Every slot starts with an 8 byte flag telling whether it is in use
+pub(crate) const FLAG_SIZE: u64 = 8;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Layout {
+    schema: Schema,
+    offsets: HashMap<String, u64>,
+    slot_size: u64,
+}
+
+impl Layout {
+    pub(crate) fn new(schema: Schema) -> Self {
+        let mut offsets = HashMap::new();
+        let mut pos = FLAG_SIZE;
+        for field in schema.fields() {
+            offsets.insert(field.clone(), pos);
+            pos += Self::length_in_bytes(&schema, field);
+        }
+
+        Self {
+            schema,
+            offsets,
+            slot_size: pos,
+        }
+    }
+
+    // Used when the offsets come from the catalog
+    pub(crate) fn from_parts(schema: Schema, offsets: HashMap<String, u64>, slot_size: u64) -> Self {
+        Self {
+            schema,
+            offsets,
+            slot_size,
+        }
+    }
+
+    fn length_in_bytes(schema: &Schema, field: &str) -> u64 {
+        match schema.ctype(field) {
+            ColumnType::Integer => 8,
+            ColumnType::VarChar => Page::max_length(schema.length(field)),
+            // Large values live in overflow blocks, the slot only holds the pointer
+            ColumnType::Text | ColumnType::Blob => OverflowPointer::SIZE,
+        }
+    }
+
+    pub(crate) fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    pub(crate) fn offset(&self, field: &str) -> u64 {
+        self.offsets[field]
+    }
+
+    pub(crate) fn slot_size(&self) -> u64 {
+        self.slot_size
+    }
+}
@@ -0,0 +1,321 @@
+use crate::{
+    constant::{parse_hex, Constant},
+    sql_exception::SQLException,
+};
+
+// Words that can never be used as table or field names
+const KEYWORDS: &[&str] = &[
+    "select",
+    "from",
+    "where",
+    "and",
+    "or",
+    "between",
+    "in",
+    "like",
+    "exists",
+    "distinct",
+    "union",
+    "all",
+    "intersect",
+    "except",
+    "limit",
+    "offset",
+    "insert",
+    "into",
+    "values",
+    "delete",
+    "update",
+    "set",
+    "create",
+    "table",
+    "view",
+    "index",
+    "on",
+    "as",
+    "using",
+    "int",
+    "varchar",
+    "text",
+    "blob",
+    "null",
+    "not",
+    "default",
+    "constraint",
+    "primary",
+    "key",
+    "unique",
+    "foreign",
+    "references",
+    "check",
+    "cascade",
+    "restrict",
+    "group",
+    "by",
+    "having",
+    "order",
+    "asc",
+    "desc",
+    "count",
+    "sum",
+    "avg",
+    "min",
+    "max",
+    "join",
+    "inner",
+    "left",
+    "right",
+    "full",
+    "outer",
+    "case",
+    "when",
+    "then",
+    "else",
+    "end",
+    "alter",
+    "add",
+    "column",
+    "drop",
+    "rename",
+    "to",
+    "analyze",
+];
+
+// Longest first, so `<=` is not read as `<` followed by `=`
+const DELIMITERS: &[&str] = &[
+    "<=", ">=", "<>", "!=", "(", ")", ",", ".", ";", "=", "<", ">", "+", "-", "*", "/", "%",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Delim(&'static str),
+    Int(i64),
+    Str(String),
+    Bytes(Vec<u8>),
+    Keyword(&'static str),
+    Id(String),
+    End,
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Delim(d) => write!(f, "'{}'", d),
+            Token::Int(i) => write!(f, "{}", i),
+            Token::Str(s) => write!(f, "string '{}'", s),
+            Token::Bytes(b) => write!(f, "bytes {}", Constant::Bytes(b.clone())),
+            Token::Keyword(k) => write!(f, "{}", k.to_uppercase()),
+            Token::Id(id) => write!(f, "{}", id),
+            Token::End => write!(f, "end of input"),
+        }
+    }
+}
+
+/// Splits SQL text into tokens, remembering the line and column each one
+/// starts at. Keywords are matched without regard to case, identifiers keep
+/// theirs.
+pub(crate) struct Lexer {
+    tokens: Vec<(Token, usize, usize)>,
+    current: usize,
+}
+
+impl Lexer {
+    pub(crate) fn new(text: &str) -> Result<Self, SQLException> {
+        let mut tokens = Vec::new();
+        let mut chars = text.chars().peekable();
+        let (mut line, mut column) = (1, 1);
+        while let Some(&c) = chars.peek() {
+            let (start_line, start_column) = (line, column);
+            let mut advance = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+                let c = chars.next();
+                if c == Some('\n') {
+                    line += 1;
+                    column = 1;
+                } else {
+                    column += 1;
+                }
+                c
+            };
+            let token = if c.is_whitespace() {
+                advance(&mut chars);
+                continue;
+            } else if c.is_ascii_digit() {
+                let mut digits = String::new();
+                while chars.peek().is_some_and(|c| c.is_ascii_digit()) {
+                    digits.extend(advance(&mut chars));
+                }
+                Token::Int(digits.parse().map_err(|_| SQLException::BadSyntax {
+                    message: format!("integer {} is too large", digits),
+                    line: start_line,
+                    column: start_column,
+                })?)
+            } else if c == '\'' || (matches!(c, 'x' | 'X') && chars.clone().nth(1) == Some('\'')) {
+                let hex = c != '\'';
+                if hex {
+                    advance(&mut chars);
+                }
+                advance(&mut chars);
+                let mut s = String::new();
+                loop {
+                    match advance(&mut chars) {
+                        Some('\'') if chars.peek() == Some(&'\'') => {
+                            advance(&mut chars);
+                            s.push('\'');
+                        }
+                        Some('\'') => break,
+                        Some(c) => s.push(c),
+                        None => {
+                            return Err(SQLException::BadSyntax {
+                                message: "unterminated string".to_string(),
+                                line: start_line,
+                                column: start_column,
+                            })
+                        }
+                    }
+                }
+                if hex {
+                    Token::Bytes(parse_hex(&s).ok_or_else(|| SQLException::BadSyntax {
+                        message: format!("X'{}' is not a hex literal", s),
+                        line: start_line,
+                        column: start_column,
+                    })?)
+                } else {
+                    Token::Str(s)
+                }
+            } else if c.is_alphabetic() || c == '_' {
+                let mut word = String::new();
+                while chars
+                    .peek()
+                    .is_some_and(|c| c.is_alphanumeric() || *c == '_')
+                {
+                    word.extend(advance(&mut chars));
+                }
+                let lower = word.to_lowercase();
+                match KEYWORDS.iter().find(|k| **k == lower) {
+                    Some(keyword) => Token::Keyword(keyword),
+                    None => Token::Id(word),
+                }
+            } else {
+                let rest = chars.clone().take(2).collect::<String>();
+                let Some(delim) = DELIMITERS.iter().find(|d| rest.starts_with(**d)) else {
+                    return Err(SQLException::BadSyntax {
+                        message: format!("unexpected character '{}'", c),
+                        line: start_line,
+                        column: start_column,
+                    });
+                };
+                for _ in 0..delim.len() {
+                    advance(&mut chars);
+                }
+                Token::Delim(delim)
+            };
+            tokens.push((token, start_line, start_column));
+        }
+        tokens.push((Token::End, line, column));
+        Ok(Self { tokens, current: 0 })
+    }
+
+    fn token(&self) -> &Token {
+        &self.tokens[self.current].0
+    }
+
+    pub(crate) fn match_delim(&self, delim: &str) -> bool {
+        matches!(self.token(), Token::Delim(d) if *d == delim)
+    }
+
+    pub(crate) fn match_int_constant(&self) -> bool {
+        matches!(self.token(), Token::Int(_))
+    }
+
+    pub(crate) fn match_string_constant(&self) -> bool {
+        matches!(self.token(), Token::Str(_))
+    }
+
+    pub(crate) fn match_bytes_constant(&self) -> bool {
+        matches!(self.token(), Token::Bytes(_))
+    }
+
+    pub(crate) fn match_keyword(&self, keyword: &str) -> bool {
+        matches!(self.token(), Token::Keyword(k) if *k == keyword)
+    }
+
+    pub(crate) fn match_id(&self) -> bool {
+        matches!(self.token(), Token::Id(_))
+    }
+
+    pub(crate) fn at_end(&self) -> bool {
+        *self.token() == Token::End
+    }
+
+    pub(crate) fn eat_delim(&mut self, delim: &str) -> Result<(), SQLException> {
+        if !self.match_delim(delim) {
+            return Err(self.expected(&format!("'{}'", delim)));
+        }
+        self.current += 1;
+        Ok(())
+    }
+
+    pub(crate) fn eat_int_constant(&mut self) -> Result<i64, SQLException> {
+        let Token::Int(i) = *self.token() else {
+            return Err(self.expected("an integer"));
+        };
+        self.current += 1;
+        Ok(i)
+    }
+
+    pub(crate) fn eat_string_constant(&mut self) -> Result<String, SQLException> {
+        let Token::Str(s) = self.token().clone() else {
+            return Err(self.expected("a string"));
+        };
+        self.current += 1;
+        Ok(s)
+    }
+
+    pub(crate) fn eat_bytes_constant(&mut self) -> Result<Vec<u8>, SQLException> {
+        let Token::Bytes(b) = self.token().clone() else {
+            return Err(self.expected("a hex literal"));
+        };
+        self.current += 1;
+        Ok(b)
+    }
+
+    pub(crate) fn eat_keyword(&mut self, keyword: &str) -> Result<(), SQLException> {
+        if !self.match_keyword(keyword) {
+            return Err(self.expected(&keyword.to_uppercase()));
+        }
+        self.current += 1;
+        Ok(())
+    }
+
+    pub(crate) fn eat_id(&mut self) -> Result<String, SQLException> {
+        let Token::Id(id) = self.token().clone() else {
+            return Err(self.expected("a name"));
+        };
+        self.current += 1;
+        Ok(id)
+    }
+
+    /// Where the lexer is, for going back with [`Lexer::reset`] when a rule
+    /// turns out not to apply.
+    pub(crate) fn position(&self) -> usize {
+        self.current
+    }
+
+    pub(crate) fn reset(&mut self, position: usize) {
+        self.current = position;
+    }
+
+    /// A syntax error at the current token.
+    pub(crate) fn error(&self, message: String) -> SQLException {
+        let (_, line, column) = self.tokens[self.current];
+        SQLException::BadSyntax {
+            message,
+            line,
+            column,
+        }
+    }
+
+    pub(crate) fn expected(&self, what: &str) -> SQLException {
+        self.error(format!("expected {}, found {}", what, self.token()))
+    }
+}
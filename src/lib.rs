@@ -1,14 +1,85 @@
 
 
+mod aggregate;
+mod alias_plan;
+mod alias_scan;
+mod basic_query_planner;
+mod basic_update_planner;
 mod buffer;
 mod buffer_manager;
 mod byte_buffer;
+mod command;
 mod connection;
+mod constant;
+mod constraint;
+mod constraint_manager;
+mod decorrelate;
+mod distinct_plan;
+mod distinct_scan;
 mod driver;
+mod expression;
+mod extend_plan;
+mod extend_scan;
 mod resultset;
 mod sql_exception;
 mod statement;
 mod file_manager;
+mod free_space_map;
+mod group_by_plan;
+mod group_by_scan;
+mod hash_index;
+mod heuristic_query_planner;
+mod histogram;
+mod index;
+mod index_join_plan;
+mod index_join_scan;
+mod index_manager;
+mod index_select_plan;
+mod index_select_scan;
+mod join_plan;
+mod join_scan;
+mod layout;
+mod lexer;
+mod limit_plan;
+mod limit_scan;
 mod log_manager;
+mod log_record;
+mod metadata_manager;
+mod overflow;
 mod page;
+mod parser;
+mod plan;
+mod planner;
+mod predicate;
+mod product_plan;
+mod product_scan;
+mod project_plan;
+mod project_scan;
+mod query_data;
+mod record_comparator;
+mod record_page;
+mod rid;
+mod scan;
+mod schema;
+mod select_plan;
+mod select_scan;
+mod set_op_plan;
+mod set_op_scan;
 mod simpledb;
+mod sort_plan;
+mod sort_scan;
+mod stat_manager;
+mod subquery_plan;
+mod subquery_scan;
+mod table_manager;
+mod table_plan;
+mod table_scan;
+mod table_writer;
+mod temp_table;
+mod term;
+mod top_n_plan;
+mod top_n_scan;
+mod transaction;
+mod union_plan;
+mod union_scan;
+mod view_manager;
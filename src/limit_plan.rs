@@ -0,0 +1,47 @@
+use crate::{
+    limit_scan::LimitScan, plan::Plan, scan::Scan, schema::Schema, transaction::Transaction,
+};
+
+/// The records of the underlying plan after the first `offset`, and at
+/// most `limit` of them.
+pub(crate) struct LimitPlan {
+    plan: Box<dyn Plan>,
+    limit: Option<u64>,
+    offset: u64,
+}
+
+impl LimitPlan {
+    pub(crate) fn new(plan: Box<dyn Plan>, limit: Option<u64>, offset: u64) -> Self {
+        Self {
+            plan,
+            limit,
+            offset,
+        }
+    }
+}
+
+impl Plan for LimitPlan {
+    fn open(&self, tx: &mut Transaction) -> Result<Box<dyn Scan>, std::io::Error> {
+        let scan = self.plan.open(tx)?;
+        Ok(Box::new(LimitScan::new(scan, self.limit, self.offset)))
+    }
+
+    fn blocks_accessed(&self) -> u64 {
+        self.plan.blocks_accessed()
+    }
+
+    fn records_output(&self) -> u64 {
+        let remaining = self.plan.records_output().saturating_sub(self.offset);
+        self.limit.map_or(remaining, |limit| remaining.min(limit))
+    }
+
+    fn distinct_values(&self, fldname: &str) -> u64 {
+        self.plan
+            .distinct_values(fldname)
+            .min(self.records_output())
+    }
+
+    fn schema(&self) -> &Schema {
+        self.plan.schema()
+    }
+}
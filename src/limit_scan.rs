@@ -0,0 +1,193 @@
+use crate::{constant::Constant, overflow::LargeValue, scan::Scan, transaction::Transaction};
+
+/// The records of a scan after the first `offset`, and at most `limit` of
+/// them. Once the limit is reached the underlying scan is not read any
+/// further.
+pub(crate) struct LimitScan<S: Scan> {
+    scan: S,
+    limit: Option<u64>,
+    offset: u64,
+    /// Records returned since `before_first`, not counting those skipped.
+    returned: u64,
+    skipped: bool,
+}
+
+impl<S: Scan> LimitScan<S> {
+    pub(crate) fn new(scan: S, limit: Option<u64>, offset: u64) -> Self {
+        Self {
+            scan,
+            limit,
+            offset,
+            returned: 0,
+            skipped: false,
+        }
+    }
+}
+
+impl<S: Scan> Scan for LimitScan<S> {
+    fn before_first(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        self.returned = 0;
+        self.skipped = false;
+        self.scan.before_first(tx)
+    }
+
+    fn next(&mut self, tx: &mut Transaction) -> Result<bool, std::io::Error> {
+        if self.limit.is_some_and(|limit| self.returned >= limit) {
+            return Ok(false);
+        }
+        if !self.skipped {
+            self.skipped = true;
+            for _ in 0..self.offset {
+                if !self.scan.next(tx)? {
+                    return Ok(false);
+                }
+            }
+        }
+        if !self.scan.next(tx)? {
+            return Ok(false);
+        }
+        self.returned += 1;
+        Ok(true)
+    }
+
+    fn get_int(&mut self, tx: &mut Transaction, fldname: &str) -> Result<i64, std::io::Error> {
+        self.scan.get_int(tx, fldname)
+    }
+
+    fn get_string(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+    ) -> Result<String, std::io::Error> {
+        self.scan.get_string(tx, fldname)
+    }
+
+    fn get_val(&mut self, tx: &mut Transaction, fldname: &str) -> Result<Constant, std::io::Error> {
+        self.scan.get_val(tx, fldname)
+    }
+
+    fn has_field(&self, fldname: &str) -> bool {
+        self.scan.has_field(fldname)
+    }
+
+    fn get_large(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+    ) -> Result<Option<LargeValue>, std::io::Error> {
+        self.scan.get_large(tx, fldname)
+    }
+
+    fn close(&mut self, tx: &mut Transaction) {
+        self.scan.close(tx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        constant::Constant,
+        layout::Layout,
+        scan::Scan,
+        schema::Schema,
+        simpledb::SimpleDB,
+        table_scan::TableScan,
+        transaction::Transaction,
+    };
+
+    use super::LimitScan;
+
+    /// Counts the records read from the table.
+    struct CountingScan {
+        scan: TableScan,
+        reads: usize,
+    }
+
+    impl Scan for CountingScan {
+        fn before_first(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+            self.scan.before_first(tx)
+        }
+
+        fn next(&mut self, tx: &mut Transaction) -> Result<bool, std::io::Error> {
+            self.reads += 1;
+            self.scan.next(tx)
+        }
+
+        fn get_int(&mut self, tx: &mut Transaction, fldname: &str) -> Result<i64, std::io::Error> {
+            self.scan.get_int(tx, fldname)
+        }
+
+        fn get_string(
+            &mut self,
+            tx: &mut Transaction,
+            fldname: &str,
+        ) -> Result<String, std::io::Error> {
+            self.scan.get_string(tx, fldname)
+        }
+
+        fn get_val(
+            &mut self,
+            tx: &mut Transaction,
+            fldname: &str,
+        ) -> Result<Constant, std::io::Error> {
+            self.scan.get_val(tx, fldname)
+        }
+
+        fn has_field(&self, fldname: &str) -> bool {
+            self.scan.has_field(fldname)
+        }
+
+        fn close(&mut self, tx: &mut Transaction) {
+            self.scan.close(tx)
+        }
+    }
+
+    #[test]
+    fn test_limit_scan() {
+        let _ = std::fs::remove_dir_all("limittest");
+        let mut db = SimpleDB::new("limittest", 400, 8).unwrap();
+        let mut tx = Transaction::new(
+            &db.file_manager,
+            &mut db.log_manager,
+            &mut db.buffer_manager,
+        );
+
+        let mut schema = Schema::new();
+        schema.add_int_field("A");
+        let layout = Layout::new(schema);
+        let mut ts = TableScan::new(&mut tx, "T", layout.clone()).unwrap();
+        for a in 0..100 {
+            ts.insert(&mut tx).unwrap();
+            ts.set_int(&mut tx, "A", a).unwrap();
+        }
+        ts.close(&mut tx);
+
+        for (limit, offset, expected, reads) in [
+            (Some(3), 0, vec![0, 1, 2], 3),
+            (Some(3), 10, vec![10, 11, 12], 13),
+            (Some(0), 5, vec![], 0),
+            (Some(5), 98, vec![98, 99], 101),
+            (None, 97, vec![97, 98, 99], 101),
+            (Some(2), 200, vec![], 101),
+        ] {
+            let scan = CountingScan {
+                scan: TableScan::new(&mut tx, "T", layout.clone()).unwrap(),
+                reads: 0,
+            };
+            let mut scan = LimitScan::new(scan, limit, offset);
+            for pass in 0..2 {
+                let mut values = Vec::new();
+                scan.before_first(&mut tx).unwrap();
+                while scan.next(&mut tx).unwrap() {
+                    values.push(scan.get_int(&mut tx, "A").unwrap());
+                }
+                assert_eq!(values, expected, "{:?} {}", limit, offset);
+                if pass == 0 {
+                    assert_eq!(scan.scan.reads, reads, "{:?} {}", limit, offset);
+                }
+            }
+            scan.close(&mut tx);
+        }
+        tx.commit().unwrap();
+    }
+}
@@ -19,7 +19,8 @@ impl LogManager {
     pub(crate) fn new(file_manager: &FileManager, log_file: &str) -> Self {
         let mut log_page = Page::new(file_manager.blocksize);
 
-        let current_block = if let Ok(log_size) = file_manager.length(log_file) {
+        let log_size = file_manager.size(log_file).unwrap();
+        let current_block = if log_size > 0 {
             let block = BlockId::new(log_file.into(), log_size - 1);
             file_manager.read(&block, &mut log_page).unwrap();
             block
@@ -83,6 +84,59 @@ impl LogManager {
     }
 }
 
+impl LogManager {
+    /// Flushes the log and walks it from the latest record back to the first.
+    pub(crate) fn iterator<'a>(
+        &mut self,
+        file_manager: &'a FileManager,
+    ) -> Result<LogIterator<'a>, std::io::Error> {
+        self.flush(file_manager)?;
+        LogIterator::new(file_manager, self.current_block.clone())
+    }
+}
+
+pub(crate) struct LogIterator<'a> {
+    file_manager: &'a FileManager,
+    block: BlockId,
+    page: Page,
+    position: u64,
+}
+
+impl<'a> LogIterator<'a> {
+    fn new(file_manager: &'a FileManager, block: BlockId) -> Result<Self, std::io::Error> {
+        let mut page = Page::new(file_manager.blocksize);
+        file_manager.read(&block, &mut page)?;
+        let position = page.get_int(0);
+        Ok(Self {
+            file_manager,
+            block,
+            page,
+            position,
+        })
+    }
+}
+
+impl Iterator for LogIterator<'_> {
+    type Item = Result<Vec<u8>, std::io::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Records fill a block from its end, so the newest comes first
+        while self.position >= self.file_manager.blocksize {
+            if self.block.number() == 0 {
+                return None;
+            }
+            self.block = BlockId::new(self.block.filename().to_string(), self.block.number() - 1);
+            if let Err(e) = self.file_manager.read(&self.block, &mut self.page) {
+                return Some(Err(e));
+            }
+            self.position = self.page.get_int(0);
+        }
+        let record = self.page.get_bytes(self.position).to_vec();
+        self.position += 8 + record.len() as u64;
+        Some(Ok(record))
+    }
+}
+
 impl Iterator for LogManager {
     type Item = Record;
 
@@ -100,8 +154,10 @@ mod tests {
 
     #[test]
     fn test_log_manager() {
+        let _ = std::fs::remove_dir_all("logtest");
         let db = SimpleDB::new("logtest", 400, 8).unwrap();
-        let mut lm = db.log_manager;
+        // The database's own log already holds the catalog setup
+        let mut lm = LogManager::new(&db.file_manager, "testlog");
         let mut create_records = |start: u64, end: u64| {
             (start..end).for_each(|i| {
                 let s = format!("record{}", i);
@@ -118,62 +174,80 @@ mod tests {
         lm.flush_with_lsn(&db.file_manager, 20).unwrap();
 
 
-        let logfile = std::fs::read("logtest/logfile").unwrap();
+        let logfile = std::fs::read("logtest/testlog").unwrap();
         let s = pretty_hex::pretty_hex(&logfile);
         let expected = expect![[r#"
-            Length: 800 (0x320) bytes
-            0000:   00 00 00 00  00 00 00 19  00 00 00 00  00 00 00 00   ................
+            Length: 816 (0x330) bytes
+            0000:   50 3d e1 c5  00 00 00 00  00 00 00 00  00 00 00 19   P=..............
             0010:   00 00 00 00  00 00 00 00  00 00 00 00  00 00 00 00   ................
-            0020:   18 00 00 00  00 00 00 00  08 72 65 63  6f 72 64 31   .........record1
-            0030:   32 00 00 00  00 00 00 00  70 00 00 00  00 00 00 00   2.......p.......
-            0040:   18 00 00 00  00 00 00 00  08 72 65 63  6f 72 64 31   .........record1
-            0050:   31 00 00 00  00 00 00 00  6f 00 00 00  00 00 00 00   1.......o.......
-            0060:   18 00 00 00  00 00 00 00  08 72 65 63  6f 72 64 31   .........record1
-            0070:   30 00 00 00  00 00 00 00  6e 00 00 00  00 00 00 00   0.......n.......
-            0080:   17 00 00 00  00 00 00 00  07 72 65 63  6f 72 64 39   .........record9
-            0090:   00 00 00 00  00 00 00 6d  00 00 00 00  00 00 00 17   .......m........
-            00a0:   00 00 00 00  00 00 00 07  72 65 63 6f  72 64 38 00   ........record8.
-            00b0:   00 00 00 00  00 00 6c 00  00 00 00 00  00 00 17 00   ......l.........
-            00c0:   00 00 00 00  00 00 07 72  65 63 6f 72  64 37 00 00   .......record7..
-            00d0:   00 00 00 00  00 6b 00 00  00 00 00 00  00 17 00 00   .....k..........
-            00e0:   00 00 00 00  00 07 72 65  63 6f 72 64  36 00 00 00   ......record6...
-            00f0:   00 00 00 00  6a 00 00 00  00 00 00 00  17 00 00 00   ....j...........
-            0100:   00 00 00 00  07 72 65 63  6f 72 64 35  00 00 00 00   .....record5....
-            0110:   00 00 00 69  00 00 00 00  00 00 00 17  00 00 00 00   ...i............
-            0120:   00 00 00 07  72 65 63 6f  72 64 34 00  00 00 00 00   ....record4.....
-            0130:   00 00 68 00  00 00 00 00  00 00 17 00  00 00 00 00   ..h.............
-            0140:   00 00 07 72  65 63 6f 72  64 33 00 00  00 00 00 00   ...record3......
-            0150:   00 67 00 00  00 00 00 00  00 17 00 00  00 00 00 00   .g..............
-            0160:   00 07 72 65  63 6f 72 64  32 00 00 00  00 00 00 00   ..record2.......
-            0170:   66 00 00 00  00 00 00 00  17 00 00 00  00 00 00 00   f...............
-            0180:   07 72 65 63  6f 72 64 31  00 00 00 00  00 00 00 65   .record1.......e
-            0190:   00 00 00 00  00 00 01 70  00 00 00 00  00 00 00 00   .......p........
-            01a0:   00 00 00 00  00 00 00 00  00 00 00 00  00 00 00 00   ................
-            01b0:   18 00 00 00  00 00 00 00  08 72 65 63  6f 72 64 31   .........record1
-            01c0:   32 00 00 00  00 00 00 00  70 00 00 00  00 00 00 00   2.......p.......
-            01d0:   18 00 00 00  00 00 00 00  08 72 65 63  6f 72 64 31   .........record1
-            01e0:   31 00 00 00  00 00 00 00  6f 00 00 00  00 00 00 00   1.......o.......
-            01f0:   18 00 00 00  00 00 00 00  08 72 65 63  6f 72 64 31   .........record1
-            0200:   30 00 00 00  00 00 00 00  6e 00 00 00  00 00 00 00   0.......n.......
-            0210:   17 00 00 00  00 00 00 00  07 72 65 63  6f 72 64 39   .........record9
-            0220:   00 00 00 00  00 00 00 6d  00 00 00 00  00 00 00 17   .......m........
-            0230:   00 00 00 00  00 00 00 07  72 65 63 6f  72 64 38 00   ........record8.
-            0240:   00 00 00 00  00 00 6c 00  00 00 00 00  00 00 17 00   ......l.........
-            0250:   00 00 00 00  00 00 07 72  65 63 6f 72  64 37 00 00   .......record7..
-            0260:   00 00 00 00  00 6b 00 00  00 00 00 00  00 17 00 00   .....k..........
-            0270:   00 00 00 00  00 07 72 65  63 6f 72 64  36 00 00 00   ......record6...
-            0280:   00 00 00 00  6a 00 00 00  00 00 00 00  17 00 00 00   ....j...........
-            0290:   00 00 00 00  07 72 65 63  6f 72 64 35  00 00 00 00   .....record5....
-            02a0:   00 00 00 69  00 00 00 00  00 00 00 17  00 00 00 00   ...i............
-            02b0:   00 00 00 07  72 65 63 6f  72 64 34 00  00 00 00 00   ....record4.....
-            02c0:   00 00 68 00  00 00 00 00  00 00 17 00  00 00 00 00   ..h.............
-            02d0:   00 00 07 72  65 63 6f 72  64 33 00 00  00 00 00 00   ...record3......
-            02e0:   00 67 00 00  00 00 00 00  00 17 00 00  00 00 00 00   .g..............
-            02f0:   00 07 72 65  63 6f 72 64  32 00 00 00  00 00 00 00   ..record2.......
-            0300:   00 00 00 00  00 00 00 18  00 00 00 00  00 00 00 08   ................
-            0310:   72 65 63 6f  72 64 31 33  00 00 00 00  00 00 00 71   record13.......q"#]];
+            0020:   00 00 00 00  00 00 00 00  18 00 00 00  00 00 00 00   ................
+            0030:   08 72 65 63  6f 72 64 31  32 00 00 00  00 00 00 00   .record12.......
+            0040:   70 00 00 00  00 00 00 00  18 00 00 00  00 00 00 00   p...............
+            0050:   08 72 65 63  6f 72 64 31  31 00 00 00  00 00 00 00   .record11.......
+            0060:   6f 00 00 00  00 00 00 00  18 00 00 00  00 00 00 00   o...............
+            0070:   08 72 65 63  6f 72 64 31  30 00 00 00  00 00 00 00   .record10.......
+            0080:   6e 00 00 00  00 00 00 00  17 00 00 00  00 00 00 00   n...............
+            0090:   07 72 65 63  6f 72 64 39  00 00 00 00  00 00 00 6d   .record9.......m
+            00a0:   00 00 00 00  00 00 00 17  00 00 00 00  00 00 00 07   ................
+            00b0:   72 65 63 6f  72 64 38 00  00 00 00 00  00 00 6c 00   record8.......l.
+            00c0:   00 00 00 00  00 00 17 00  00 00 00 00  00 00 07 72   ...............r
+            00d0:   65 63 6f 72  64 37 00 00  00 00 00 00  00 6b 00 00   ecord7.......k..
+            00e0:   00 00 00 00  00 17 00 00  00 00 00 00  00 07 72 65   ..............re
+            00f0:   63 6f 72 64  36 00 00 00  00 00 00 00  6a 00 00 00   cord6.......j...
+            0100:   00 00 00 00  17 00 00 00  00 00 00 00  07 72 65 63   .............rec
+            0110:   6f 72 64 35  00 00 00 00  00 00 00 69  00 00 00 00   ord5.......i....
+            0120:   00 00 00 17  00 00 00 00  00 00 00 07  72 65 63 6f   ............reco
+            0130:   72 64 34 00  00 00 00 00  00 00 68 00  00 00 00 00   rd4.......h.....
+            0140:   00 00 17 00  00 00 00 00  00 00 07 72  65 63 6f 72   ...........recor
+            0150:   64 33 00 00  00 00 00 00  00 67 00 00  00 00 00 00   d3.......g......
+            0160:   00 17 00 00  00 00 00 00  00 07 72 65  63 6f 72 64   ..........record
+            0170:   32 00 00 00  00 00 00 00  66 00 00 00  00 00 00 00   2.......f.......
+            0180:   17 00 00 00  00 00 00 00  07 72 65 63  6f 72 64 31   .........record1
+            0190:   00 00 00 00  00 00 00 65  78 cb 23 06  00 00 00 00   .......ex.#.....
+            01a0:   00 00 00 00  00 00 01 70  00 00 00 00  00 00 00 00   .......p........
+            01b0:   00 00 00 00  00 00 00 00  00 00 00 00  00 00 00 00   ................
+            01c0:   18 00 00 00  00 00 00 00  08 72 65 63  6f 72 64 31   .........record1
+            01d0:   32 00 00 00  00 00 00 00  70 00 00 00  00 00 00 00   2.......p.......
+            01e0:   18 00 00 00  00 00 00 00  08 72 65 63  6f 72 64 31   .........record1
+            01f0:   31 00 00 00  00 00 00 00  6f 00 00 00  00 00 00 00   1.......o.......
+            0200:   18 00 00 00  00 00 00 00  08 72 65 63  6f 72 64 31   .........record1
+            0210:   30 00 00 00  00 00 00 00  6e 00 00 00  00 00 00 00   0.......n.......
+            0220:   17 00 00 00  00 00 00 00  07 72 65 63  6f 72 64 39   .........record9
+            0230:   00 00 00 00  00 00 00 6d  00 00 00 00  00 00 00 17   .......m........
+            0240:   00 00 00 00  00 00 00 07  72 65 63 6f  72 64 38 00   ........record8.
+            0250:   00 00 00 00  00 00 6c 00  00 00 00 00  00 00 17 00   ......l.........
+            0260:   00 00 00 00  00 00 07 72  65 63 6f 72  64 37 00 00   .......record7..
+            0270:   00 00 00 00  00 6b 00 00  00 00 00 00  00 17 00 00   .....k..........
+            0280:   00 00 00 00  00 07 72 65  63 6f 72 64  36 00 00 00   ......record6...
+            0290:   00 00 00 00  6a 00 00 00  00 00 00 00  17 00 00 00   ....j...........
+            02a0:   00 00 00 00  07 72 65 63  6f 72 64 35  00 00 00 00   .....record5....
+            02b0:   00 00 00 69  00 00 00 00  00 00 00 17  00 00 00 00   ...i............
+            02c0:   00 00 00 07  72 65 63 6f  72 64 34 00  00 00 00 00   ....record4.....
+            02d0:   00 00 68 00  00 00 00 00  00 00 17 00  00 00 00 00   ..h.............
+            02e0:   00 00 07 72  65 63 6f 72  64 33 00 00  00 00 00 00   ...record3......
+            02f0:   00 67 00 00  00 00 00 00  00 17 00 00  00 00 00 00   .g..............
+            0300:   00 07 72 65  63 6f 72 64  32 00 00 00  00 00 00 00   ..record2.......
+            0310:   00 00 00 00  00 00 00 18  00 00 00 00  00 00 00 08   ................
+            0320:   72 65 63 6f  72 64 31 33  00 00 00 00  00 00 00 71   record13.......q"#]];
 
         expected.assert_eq(&s);
+
+        // The iterator hands the records back newest first
+        let records = lm
+            .iterator(&db.file_manager)
+            .unwrap()
+            .map(|bytes| {
+                let mut p = Page::from_bytes(bytes.unwrap());
+                let s = p.get_string(0).to_string();
+                let npos = Page::max_length(s.len() as u64);
+                (s, p.get_int(npos))
+            })
+            .collect::<Vec<_>>();
+        let expected = (1..14)
+            .rev()
+            .map(|i| (format!("record{}", i), i + 100))
+            .collect::<Vec<_>>();
+        assert_eq!(records, expected);
     }
 }
 
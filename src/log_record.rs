@@ -0,0 +1,129 @@
+use crate::{file_manager::BlockId, page::Page};
+
+const CHECKPOINT: u64 = 0;
+const START: u64 = 1;
+const COMMIT: u64 = 2;
+const ROLLBACK: u64 = 3;
+const SET_BYTES: u64 = 4;
+const DROP_FILE: u64 = 5;
+const FILES_DELETED: u64 = 6;
+
+/// The records a transaction writes to the log. Updates keep the bytes they
+/// overwrote, which is all undo-only recovery needs to put a block back the
+/// way it was. A dropped file is only deleted once its transaction commits,
+/// and recovery deletes it again in case the crash came in between, unless
+/// the transaction logged that its deletions were done.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum LogRecord {
+    Checkpoint,
+    Start(u64),
+    Commit(u64),
+    Rollback(u64),
+    SetBytes {
+        txnum: u64,
+        block: BlockId,
+        offset: u64,
+        old: Vec<u8>,
+    },
+    DropFile {
+        txnum: u64,
+        filename: String,
+    },
+    FilesDeleted(u64),
+}
+
+impl LogRecord {
+    pub(crate) fn txnum(&self) -> Option<u64> {
+        match self {
+            LogRecord::Checkpoint => None,
+            LogRecord::Start(txnum)
+            | LogRecord::Commit(txnum)
+            | LogRecord::Rollback(txnum)
+            | LogRecord::FilesDeleted(txnum) => Some(*txnum),
+            LogRecord::SetBytes { txnum, .. } | LogRecord::DropFile { txnum, .. } => Some(*txnum),
+        }
+    }
+
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            LogRecord::Checkpoint => {
+                let mut page = Page::new(8);
+                page.set_int(0, CHECKPOINT);
+                page.bb.bytes().to_vec()
+            }
+            LogRecord::Start(txnum) => Self::tx_record(START, *txnum),
+            LogRecord::Commit(txnum) => Self::tx_record(COMMIT, *txnum),
+            LogRecord::Rollback(txnum) => Self::tx_record(ROLLBACK, *txnum),
+            LogRecord::FilesDeleted(txnum) => Self::tx_record(FILES_DELETED, *txnum),
+            LogRecord::SetBytes {
+                txnum,
+                block,
+                offset,
+                old,
+            } => {
+                let filename_pos = 16;
+                let block_pos = filename_pos + Page::max_length(block.filename().len() as u64);
+                let offset_pos = block_pos + 8;
+                let old_pos = offset_pos + 8;
+                let mut page = Page::new(old_pos + Page::max_length(old.len() as u64));
+                page.set_int(0, SET_BYTES);
+                page.set_int(8, *txnum);
+                page.set_string(filename_pos, block.filename());
+                page.set_int(block_pos, block.number());
+                page.set_int(offset_pos, *offset);
+                page.set_bytes(old_pos, old);
+                page.bb.bytes().to_vec()
+            }
+            LogRecord::DropFile { txnum, filename } => {
+                let mut page = Page::new(16 + Page::max_length(filename.len() as u64));
+                page.set_int(0, DROP_FILE);
+                page.set_int(8, *txnum);
+                page.set_string(16, filename);
+                page.bb.bytes().to_vec()
+            }
+        }
+    }
+
+    pub(crate) fn from_bytes(bytes: Vec<u8>) -> Result<Self, std::io::Error> {
+        let mut page = Page::from_bytes(bytes);
+        let record = match page.get_int(0) {
+            CHECKPOINT => LogRecord::Checkpoint,
+            START => LogRecord::Start(page.get_int(8)),
+            COMMIT => LogRecord::Commit(page.get_int(8)),
+            ROLLBACK => LogRecord::Rollback(page.get_int(8)),
+            FILES_DELETED => LogRecord::FilesDeleted(page.get_int(8)),
+            SET_BYTES => {
+                let txnum = page.get_int(8);
+                let filename = page.get_string(16).to_string();
+                let block_pos = 16 + Page::max_length(filename.len() as u64);
+                let block = BlockId::new(filename, page.get_int(block_pos));
+                let offset = page.get_int(block_pos + 8);
+                let old = page.get_bytes(block_pos + 16).to_vec();
+                LogRecord::SetBytes {
+                    txnum,
+                    block,
+                    offset,
+                    old,
+                }
+            }
+            DROP_FILE => LogRecord::DropFile {
+                txnum: page.get_int(8),
+                filename: page.get_string(16).to_string(),
+            },
+            op => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("unknown log record type {}", op),
+                ))
+            }
+        };
+        Ok(record)
+    }
+
+    fn tx_record(op: u64, txnum: u64) -> Vec<u8> {
+        let mut page = Page::new(16);
+        page.set_int(0, op);
+        page.set_int(8, txnum);
+        page.bb.bytes().to_vec()
+    }
+}
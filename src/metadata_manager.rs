@@ -0,0 +1,1208 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    constant::Constant,
+    constraint::{Constraint, ConstraintKind, Reference},
+    constraint_manager::ConstraintManager,
+    index::IndexType,
+    index_manager::{IndexInfo, IndexManager},
+    layout::Layout,
+    parser::{parse_expression, parse_predicate},
+    resultset::ColumnType,
+    schema::Schema,
+    sql_exception::SQLException,
+    stat_manager::{StatInfo, StatManager, DEFAULT_REFRESH_THRESHOLD},
+    table_manager::{check_schema, TableManager},
+    table_scan::TableScan,
+    transaction::Transaction,
+    view_manager::ViewManager,
+};
+
+/// The single entry point for catalog lookups, in front of the table, view,
+/// statistics, index and constraint managers.
+pub(crate) struct MetadataManager {
+    pub(crate) table_manager: TableManager,
+    pub(crate) view_manager: ViewManager,
+    pub(crate) stat_manager: StatManager,
+    pub(crate) index_manager: IndexManager,
+    pub(crate) constraint_manager: ConstraintManager,
+}
+
+impl MetadataManager {
+    pub(crate) fn new(is_new: bool, tx: &mut Transaction) -> Result<Self, std::io::Error> {
+        let table_manager = TableManager::new(is_new, tx)?;
+        let view_manager = ViewManager::new(is_new, &table_manager, tx)?;
+        let stat_manager = StatManager::new(is_new, &table_manager, DEFAULT_REFRESH_THRESHOLD, tx)?;
+
+        let index_manager = IndexManager::new(is_new, &table_manager, tx)?;
+        let constraint_manager = ConstraintManager::new(is_new, &table_manager, tx)?;
+
+        Ok(Self {
+            table_manager,
+            view_manager,
+            stat_manager,
+            index_manager,
+            constraint_manager,
+        })
+    }
+
+    pub(crate) fn create_table(
+        &self,
+        tblname: &str,
+        schema: &Schema,
+        tx: &mut Transaction,
+    ) -> Result<(), std::io::Error> {
+        self.table_manager.create_table(tblname, schema, tx)
+    }
+
+    pub(crate) fn get_layout(
+        &self,
+        tblname: &str,
+        tx: &mut Transaction,
+    ) -> Result<Option<Layout>, std::io::Error> {
+        self.table_manager.get_layout(tblname, tx)
+    }
+
+    pub(crate) fn create_view(
+        &self,
+        viewname: &str,
+        viewdef: &str,
+        tx: &mut Transaction,
+    ) -> Result<(), std::io::Error> {
+        self.view_manager.create_view(viewname, viewdef, tx)
+    }
+
+    /// Adds a field at the end of the table, giving existing records the
+    /// default, or NULL when there is none. The default also becomes the
+    /// field's DEFAULT for later inserts. The heap
+    /// file is rewritten right away and the table's indexes rebuilt.
+    pub(crate) fn add_column(
+        &mut self,
+        tblname: &str,
+        fldname: &str,
+        ctype: ColumnType,
+        length: u64,
+        default: Option<Constant>,
+        tx: &mut Transaction,
+    ) -> Result<(), std::io::Error> {
+        let layout = self.existing_layout(tblname, tx)?;
+        if layout.schema().has_field(fldname) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("field {}.{} already exists", tblname, fldname),
+            ));
+        }
+        let default = default.unwrap_or(Constant::Null);
+        if !ctype.accepts(&default) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "default {} does not fit field {} of type {:?}",
+                    default, fldname, ctype
+                ),
+            ));
+        }
+
+        let mut schema = layout.schema().clone();
+        schema.add_field(fldname, ctype, length);
+        check_schema(tblname, &schema, tx)?;
+        let mut sources = layout
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| (f.clone(), Source::Field(f.clone())))
+            .collect::<Vec<_>>();
+        sources.push((fldname.to_string(), Source::Value(default.clone())));
+        self.rewrite_table(tblname, &layout, schema, &sources, tx)?;
+        if !default.is_null() {
+            self.constraint_manager.set_default(
+                tblname,
+                fldname,
+                Some(&default.to_string()),
+                tx,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Removes a field and rewrites the heap file without it. A field that is
+    /// indexed, constrained or that some view mentions cannot be dropped.
+    pub(crate) fn drop_column(
+        &mut self,
+        tblname: &str,
+        fldname: &str,
+        tx: &mut Transaction,
+    ) -> Result<(), std::io::Error> {
+        let layout = self.existing_field(tblname, fldname, tx)?;
+        if layout.schema().fields().len() == 1 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("cannot drop {}, the only field of {}", fldname, tblname),
+            ));
+        }
+        self.check_not_indexed(tblname, fldname, tx)?;
+        self.check_not_constrained(tblname, fldname, tx)?;
+        self.check_no_field_views(tblname, fldname, tx)?;
+
+        let mut schema = Schema::new();
+        let mut sources = Vec::new();
+        for field in layout.schema().fields() {
+            if field != fldname {
+                schema.add(field, layout.schema());
+                sources.push((field.clone(), Source::Field(field.clone())));
+            }
+        }
+        self.rewrite_table(tblname, &layout, schema, &sources, tx)?;
+        self.constraint_manager
+            .set_default(tblname, fldname, None, tx)
+    }
+
+    /// Renames a field in the catalog. The field keeps its place in the
+    /// record, so the heap file stays as it is. Like views, checks name their
+    /// fields in text, and a field they use cannot be renamed.
+    pub(crate) fn rename_column(
+        &mut self,
+        tblname: &str,
+        old: &str,
+        new: &str,
+        tx: &mut Transaction,
+    ) -> Result<(), std::io::Error> {
+        let layout = self.existing_field(tblname, old, tx)?;
+        if layout.schema().has_field(new) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("field {}.{} already exists", tblname, new),
+            ));
+        }
+        self.check_no_field_views(tblname, old, tx)?;
+        let constraints = self.constraint_manager.get_constraints(tblname, tx)?;
+        let check = constraints
+            .iter()
+            .find(|c| c.predicate().is_some() && c.fields.iter().any(|f| f == old));
+        if let Some(check) = check {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("field {}.{} is used by check {}", tblname, old, check.name),
+            ));
+        }
+
+        let mut schema = Schema::new();
+        for field in layout.schema().fields() {
+            let name = if field == old { new } else { field };
+            schema.add_field(
+                name,
+                layout.schema().ctype(field),
+                layout.schema().length(field),
+            );
+        }
+        self.table_manager.remove_table(tblname, tx)?;
+        self.table_manager.create_table(tblname, &schema, tx)?;
+        self.index_manager.rename_field(tblname, old, new, tx)?;
+        self.constraint_manager
+            .rename_field(tblname, old, new, tx)?;
+        self.stat_manager.forget(tblname, tx)
+    }
+
+    /// Renames a table by copying its records into files under the new name.
+    /// The old files are deleted when the transaction commits.
+    pub(crate) fn rename_table(
+        &mut self,
+        old: &str,
+        new: &str,
+        tx: &mut Transaction,
+    ) -> Result<(), std::io::Error> {
+        let layout = self.existing_layout(old, tx)?;
+        if self.table_manager.get_layout(new, tx)?.is_some() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("table {} already exists", new),
+            ));
+        }
+        self.check_no_views(old, tx)?;
+
+        self.table_manager.create_table(new, layout.schema(), tx)?;
+        let new_layout = self.existing_layout(new, tx)?;
+        let sources = identity(layout.schema());
+        copy_records(old, &layout, new, &new_layout, &sources, tx)?;
+        drop_table_files(old, tx)?;
+        self.table_manager.remove_table(old, tx)?;
+        self.index_manager.rename_table(old, new, tx)?;
+        self.index_manager
+            .rebuild_indexes(new, &self.table_manager, tx)?;
+        self.constraint_manager.rename_table(old, new, tx)?;
+        self.stat_manager.forget(old, tx)
+    }
+
+    /// Removes the table with its indexes, constraints and statistics from the
+    /// catalog. Its files are deleted when the transaction commits. A table
+    /// that some view or another table's foreign key mentions cannot be
+    /// dropped.
+    pub(crate) fn drop_table(
+        &mut self,
+        tblname: &str,
+        tx: &mut Transaction,
+    ) -> Result<(), std::io::Error> {
+        self.existing_layout(tblname, tx)?;
+        self.check_no_views(tblname, tx)?;
+        let referencing = self.constraint_manager.get_referencing(tblname, tx)?;
+        if let Some((table, constraint)) = referencing.iter().find(|(t, _)| t != tblname) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "{} is referenced by foreign key {} of {}",
+                    tblname, constraint.name, table
+                ),
+            ));
+        }
+        for (idxname, _, _) in self.index_manager.index_defs(tblname, tx)? {
+            self.index_manager
+                .drop_index(&idxname, &self.table_manager, tx)?;
+        }
+        self.constraint_manager.remove_table(tblname, tx)?;
+        self.stat_manager.forget(tblname, tx)?;
+        self.table_manager.remove_table(tblname, tx)?;
+        drop_table_files(tblname, tx)
+    }
+
+    pub(crate) fn drop_index(
+        &mut self,
+        idxname: &str,
+        tx: &mut Transaction,
+    ) -> Result<(), std::io::Error> {
+        match self
+            .index_manager
+            .drop_index(idxname, &self.table_manager, tx)?
+        {
+            Some(_) => Ok(()),
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("index {} does not exist", idxname),
+            )),
+        }
+    }
+
+    /// Removes the view, unless another view is defined in terms of it.
+    pub(crate) fn drop_view(
+        &mut self,
+        viewname: &str,
+        tx: &mut Transaction,
+    ) -> Result<(), std::io::Error> {
+        let dependents = self
+            .view_manager
+            .views_referencing(viewname, tx)?
+            .into_iter()
+            .filter(|v| v != viewname)
+            .collect::<Vec<_>>();
+        if !dependents.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("{} is used by views {}", viewname, dependents.join(", ")),
+            ));
+        }
+        if !self.view_manager.drop_view(viewname, tx)? {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("view {} does not exist", viewname),
+            ));
+        }
+        Ok(())
+    }
+
+    // Copies the records out to a scratch heap under the new layout, formats
+    // the table's blocks after that layout and copies them back
+    fn rewrite_table(
+        &mut self,
+        tblname: &str,
+        layout: &Layout,
+        schema: Schema,
+        sources: &[(String, Source)],
+        tx: &mut Transaction,
+    ) -> Result<(), std::io::Error> {
+        let new_layout = Layout::new(schema.clone());
+        let scratch = format!("{}~", tblname);
+        clear_heap(&scratch, new_layout.clone(), tx)?;
+        copy_records(tblname, layout, &scratch, &new_layout, sources, tx)?;
+
+        self.table_manager.remove_table(tblname, tx)?;
+        self.table_manager.create_table(tblname, &schema, tx)?;
+        clear_heap(tblname, new_layout.clone(), tx)?;
+        let sources = identity(&schema);
+        copy_records(&scratch, &new_layout, tblname, &new_layout, &sources, tx)?;
+        drop_table_files(&scratch, tx)?;
+
+        self.index_manager
+            .rebuild_indexes(tblname, &self.table_manager, tx)?;
+        self.stat_manager.forget(tblname, tx)
+    }
+
+    fn existing_layout(
+        &self,
+        tblname: &str,
+        tx: &mut Transaction,
+    ) -> Result<Layout, std::io::Error> {
+        self.table_manager.get_layout(tblname, tx)?.ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("table {} does not exist", tblname),
+            )
+        })
+    }
+
+    fn existing_field(
+        &self,
+        tblname: &str,
+        fldname: &str,
+        tx: &mut Transaction,
+    ) -> Result<Layout, std::io::Error> {
+        let layout = self.existing_layout(tblname, tx)?;
+        if !layout.schema().has_field(fldname) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("field {}.{} does not exist", tblname, fldname),
+            ));
+        }
+        Ok(layout)
+    }
+
+    fn check_not_indexed(
+        &self,
+        tblname: &str,
+        fldname: &str,
+        tx: &mut Transaction,
+    ) -> Result<(), std::io::Error> {
+        let indexes = self.index_manager.index_defs(tblname, tx)?;
+        if let Some((idxname, _, _)) = indexes.iter().find(|(_, f, _)| f == fldname) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("field {}.{} is used by index {}", tblname, fldname, idxname),
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_not_constrained(
+        &self,
+        tblname: &str,
+        fldname: &str,
+        tx: &mut Transaction,
+    ) -> Result<(), std::io::Error> {
+        let constraints = self.constraint_manager.get_constraints(tblname, tx)?;
+        if let Some(c) = constraints
+            .iter()
+            .find(|c| c.fields.iter().any(|f| f == fldname))
+        {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "field {}.{} is used by constraint {}",
+                    tblname, fldname, c.name
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_no_views(&self, name: &str, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        let views = self.view_manager.views_referencing(name, tx)?;
+        if !views.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("{} is used by views {}", name, views.join(", ")),
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_no_field_views(
+        &self,
+        tblname: &str,
+        fldname: &str,
+        tx: &mut Transaction,
+    ) -> Result<(), std::io::Error> {
+        let views = self.view_manager.views_using_field(tblname, fldname, tx)?;
+        if !views.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("{}.{} is used by views {}", tblname, fldname, views.join(", ")),
+            ));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn get_view_def(
+        &self,
+        viewname: &str,
+        tx: &mut Transaction,
+    ) -> Result<Option<String>, std::io::Error> {
+        self.view_manager.get_view_def(viewname, tx)
+    }
+
+    pub(crate) fn create_index(
+        &self,
+        idxname: &str,
+        tblname: &str,
+        fldname: &str,
+        index_type: IndexType,
+        tx: &mut Transaction,
+    ) -> Result<(), std::io::Error> {
+        if self.table_manager.get_layout(idxname, tx)?.is_some()
+            || self.view_manager.get_view_def(idxname, tx)?.is_some()
+        {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("{} is already the name of a table or view", idxname),
+            ));
+        }
+        self.index_manager.create_index(
+            idxname,
+            tblname,
+            fldname,
+            index_type,
+            &self.table_manager,
+            tx,
+        )
+    }
+
+    /// Records the constraint once the table's records are known to satisfy
+    /// it. UNIQUE and PRIMARY KEY constraints get a hash index on their first
+    /// field, named after the constraint, unless the field is indexed already.
+    pub(crate) fn add_constraint(
+        &mut self,
+        tblname: &str,
+        constraint: &Constraint,
+        tx: &mut Transaction,
+    ) -> Result<(), SQLException> {
+        let layout = self.existing_layout(tblname, tx)?;
+        if constraint.fields.is_empty() && constraint.predicate().is_none() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("constraint {} has no fields", constraint.name),
+            )
+            .into());
+        }
+        for fldname in &constraint.fields {
+            let layout = self.existing_field(tblname, fldname, tx)?;
+            let keyed = constraint.is_unique() || constraint.predicate().is_some();
+            if keyed && layout.schema().ctype(fldname) == ColumnType::Blob {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!(
+                        "blob field {}.{} cannot be used by constraint {}",
+                        tblname, fldname, constraint.name
+                    ),
+                )
+                .into());
+            }
+        }
+        let existing = self.constraint_manager.get_constraints(tblname, tx)?;
+        if constraint.kind == ConstraintKind::PrimaryKey
+            && existing
+                .iter()
+                .any(|c| c.kind == ConstraintKind::PrimaryKey)
+        {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("table {} already has a primary key", tblname),
+            )
+            .into());
+        }
+        match constraint.reference() {
+            Some(reference) => self.check_reference(tblname, &layout, constraint, reference, tx)?,
+            None => check_records(tblname, &layout, constraint, tx)?,
+        }
+        self.constraint_manager
+            .add_constraint(tblname, constraint, tx)?;
+
+        if !constraint.is_unique() {
+            return Ok(());
+        }
+        let first = &constraint.fields[0];
+        let indexable = matches!(
+            layout.schema().ctype(first),
+            ColumnType::Integer | ColumnType::VarChar
+        );
+        if indexable
+            && !self
+                .index_manager
+                .index_defs(tblname, tx)?
+                .iter()
+                .any(|(_, f, _)| f == first)
+        {
+            self.create_index(&constraint.name, tblname, first, IndexType::Hash, tx)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn get_constraints(
+        &self,
+        tblname: &str,
+        tx: &mut Transaction,
+    ) -> Result<Vec<Constraint>, std::io::Error> {
+        self.constraint_manager.get_constraints(tblname, tx)
+    }
+
+    /// Gives the field a DEFAULT, which must be a constant expression that
+    /// fits the field, or removes the one it has.
+    pub(crate) fn set_default(
+        &self,
+        tblname: &str,
+        fldname: &str,
+        expr: Option<&str>,
+        tx: &mut Transaction,
+    ) -> Result<(), SQLException> {
+        let layout = self.existing_field(tblname, fldname, tx)?;
+        if let Some(expr) = expr {
+            let value = parse_expression(expr)?.evaluate(&mut &HashMap::new())?;
+            let ctype = layout.schema().ctype(fldname);
+            if !ctype.accepts(&value) {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!(
+                        "default {} does not fit field {} of type {:?}",
+                        value, fldname, ctype
+                    ),
+                )
+                .into());
+            }
+        }
+        self.constraint_manager
+            .set_default(tblname, fldname, expr, tx)?;
+        Ok(())
+    }
+
+    /// The values of the table's DEFAULT expressions.
+    pub(crate) fn get_defaults(
+        &self,
+        tblname: &str,
+        tx: &mut Transaction,
+    ) -> Result<HashMap<String, Constant>, SQLException> {
+        let mut defaults = HashMap::new();
+        for (fldname, expr) in self.constraint_manager.get_defaults(tblname, tx)? {
+            let value = parse_expression(&expr)?.evaluate(&mut &HashMap::new())?;
+            defaults.insert(fldname, value);
+        }
+        Ok(defaults)
+    }
+
+    pub(crate) fn get_referencing(
+        &self,
+        tblname: &str,
+        tx: &mut Transaction,
+    ) -> Result<Vec<(String, Constraint)>, std::io::Error> {
+        self.constraint_manager.get_referencing(tblname, tx)
+    }
+
+    // A foreign key refers to the fields of a key of the other table, with
+    // the same types, and every record already there must find its match
+    fn check_reference(
+        &self,
+        tblname: &str,
+        layout: &Layout,
+        constraint: &Constraint,
+        reference: &Reference,
+        tx: &mut Transaction,
+    ) -> Result<(), SQLException> {
+        let ref_layout = self.existing_layout(&reference.table, tx)?;
+        if reference.fields.len() != constraint.fields.len() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "foreign key {} has {} fields but refers to {}",
+                    constraint.name,
+                    constraint.fields.len(),
+                    reference.fields.len()
+                ),
+            )
+            .into());
+        }
+        for (fldname, ref_fldname) in constraint.fields.iter().zip(&reference.fields) {
+            self.existing_field(&reference.table, ref_fldname, tx)?;
+            let (ctype, ref_ctype) = (
+                layout.schema().ctype(fldname),
+                ref_layout.schema().ctype(ref_fldname),
+            );
+            if ctype != ref_ctype {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!(
+                        "field {}.{} of type {:?} cannot refer to {}.{} of type {:?}",
+                        tblname, fldname, ctype, reference.table, ref_fldname, ref_ctype
+                    ),
+                )
+                .into());
+            }
+        }
+        let mut ref_fields = reference.fields.clone();
+        ref_fields.sort();
+        let is_key = self
+            .constraint_manager
+            .get_constraints(&reference.table, tx)?
+            .into_iter()
+            .filter(|c| c.is_unique())
+            .any(|mut c| {
+                c.fields.sort();
+                c.fields == ref_fields
+            });
+        if !is_key {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "fields ({}) of {} are not a primary key or unique",
+                    reference.fields.join(", "),
+                    reference.table
+                ),
+            )
+            .into());
+        }
+
+        let keys = read_keys(&reference.table, &ref_layout, &reference.fields, tx)?;
+        for key in read_keys(tblname, layout, &constraint.fields, tx)? {
+            if !keys.contains(&key) {
+                let detail = format!("has no match in {}", reference.table);
+                return Err(constraint.reference_violation(tblname, &key, &detail));
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn get_index_info(
+        &mut self,
+        tblname: &str,
+        tx: &mut Transaction,
+    ) -> Result<HashMap<String, IndexInfo>, std::io::Error> {
+        self.index_manager
+            .get_index_info(tblname, &self.table_manager, &mut self.stat_manager, tx)
+    }
+
+    pub(crate) fn get_stat_info(
+        &mut self,
+        tblname: &str,
+        layout: &Layout,
+        tx: &mut Transaction,
+    ) -> Result<StatInfo, std::io::Error> {
+        self.stat_manager.get_stat_info(tblname, layout, tx)
+    }
+
+    pub(crate) fn record_updates(&mut self, count: u64) {
+        self.stat_manager.record_updates(count);
+    }
+
+    pub(crate) fn set_stat_refresh_threshold(&mut self, refresh_threshold: u64) {
+        self.stat_manager.set_refresh_threshold(refresh_threshold);
+    }
+
+    pub(crate) fn set_stat_sample_blocks(&mut self, sample_blocks: u64) {
+        self.stat_manager.set_sample_blocks(sample_blocks);
+    }
+
+    /// Samples the given table, or every table when there is none, and
+    /// stores histograms for their fields in the catalog.
+    pub(crate) fn analyze(
+        &mut self,
+        tblname: Option<&str>,
+        tx: &mut Transaction,
+    ) -> Result<(), std::io::Error> {
+        let tables = match tblname {
+            Some(tblname) => vec![tblname.to_string()],
+            None => self.table_manager.table_names(tx)?,
+        };
+        for tblname in tables {
+            let layout = self
+                .table_manager
+                .get_layout(&tblname, tx)?
+                .ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::NotFound,
+                        format!("table {} does not exist", tblname),
+                    )
+                })?;
+            self.stat_manager.analyze(&tblname, &layout, tx)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn refresh_statistics(
+        &mut self,
+        tx: &mut Transaction,
+    ) -> Result<(), std::io::Error> {
+        self.stat_manager
+            .refresh_statistics(&self.table_manager, tx)
+    }
+}
+
+// Where a field of a rewritten record gets its value
+enum Source {
+    Field(String),
+    Value(Constant),
+}
+
+fn identity(schema: &Schema) -> Vec<(String, Source)> {
+    schema
+        .fields()
+        .iter()
+        .map(|f| (f.clone(), Source::Field(f.clone())))
+        .collect()
+}
+
+fn drop_table_files(tblname: &str, tx: &mut Transaction) -> Result<(), std::io::Error> {
+    for filename in TableScan::files(tblname) {
+        tx.drop_file(&filename)?;
+    }
+    Ok(())
+}
+
+fn clear_heap(tblname: &str, layout: Layout, tx: &mut Transaction) -> Result<(), std::io::Error> {
+    let mut ts = TableScan::new(tx, tblname, layout)?;
+    ts.clear(tx)?;
+    ts.close(tx);
+    Ok(())
+}
+
+// Scans the table for records that break a constraint about to be added
+fn check_records(
+    tblname: &str,
+    layout: &Layout,
+    constraint: &Constraint,
+    tx: &mut Transaction,
+) -> Result<(), SQLException> {
+    let predicate = constraint.predicate().map(parse_predicate).transpose()?;
+    let keyed = constraint.is_unique() || predicate.is_some();
+    let mut seen = HashSet::new();
+    let mut ts = TableScan::new(tx, tblname, layout.clone())?;
+    let result = (|| {
+        while ts.next(tx)? {
+            let mut row = HashMap::new();
+            for fldname in &constraint.fields {
+                if ts.is_null(tx, fldname) && constraint.rejects_null() {
+                    return Err(constraint.null_violation(tblname, fldname));
+                }
+                if keyed {
+                    row.insert(fldname.clone(), ts.get_val(tx, fldname)?);
+                }
+            }
+            if !keyed {
+                continue;
+            }
+            if let Some(predicate) = &predicate {
+                if predicate.evaluate(&mut &row)? == Some(false) {
+                    return Err(constraint.check_violation(tblname));
+                }
+            }
+            let key = constraint
+                .fields
+                .iter()
+                .map(|f| row[f].clone())
+                .collect::<Vec<_>>();
+            if constraint.is_unique()
+                && !key.iter().any(|v| v.is_null())
+                && !seen.insert(key.clone())
+            {
+                return Err(constraint.duplicate_violation(tblname, &key));
+            }
+        }
+        Ok(())
+    })();
+    ts.close(tx);
+    result
+}
+
+// The distinct values of the fields over the records where none is NULL
+fn read_keys(
+    tblname: &str,
+    layout: &Layout,
+    fields: &[String],
+    tx: &mut Transaction,
+) -> Result<HashSet<Vec<Constant>>, std::io::Error> {
+    let mut keys = HashSet::new();
+    let mut ts = TableScan::new(tx, tblname, layout.clone())?;
+    let result = (|| {
+        while ts.next(tx)? {
+            let mut key = Vec::new();
+            for fldname in fields {
+                key.push(ts.get_val(tx, fldname)?);
+            }
+            if !key.iter().any(|v| v.is_null()) {
+                keys.insert(key);
+            }
+        }
+        Ok(())
+    })();
+    ts.close(tx);
+    result.map(|_| keys)
+}
+
+fn copy_records(
+    from: &str,
+    from_layout: &Layout,
+    to: &str,
+    to_layout: &Layout,
+    sources: &[(String, Source)],
+    tx: &mut Transaction,
+) -> Result<(), std::io::Error> {
+    let mut src = TableScan::new(tx, from, from_layout.clone())?;
+    let mut dst = TableScan::new(tx, to, to_layout.clone())?;
+    while src.next(tx)? {
+        dst.insert(tx)?;
+        for (field, source) in sources {
+            match source {
+                Source::Value(value) => dst.set_val(tx, field, value)?,
+                Source::Field(name) if src.is_null(tx, name) => dst.set_null(tx, field)?,
+                Source::Field(name) => match from_layout.schema().ctype(name) {
+                    ColumnType::Integer | ColumnType::VarChar => {
+                        let value = src.get_val(tx, name)?;
+                        dst.set_val(tx, field, &value)?;
+                    }
+                    // Large values are streamed into the destination's own
+                    // overflow file
+                    ColumnType::Text | ColumnType::Blob => {
+                        let mut reader = src.get_reader(tx, name);
+                        dst.set_from_reader(tx, field, &mut reader)?;
+                    }
+                },
+            }
+        }
+    }
+    src.close(tx);
+    dst.close(tx);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use crate::{
+        constant::Constant, index::IndexType, resultset::ColumnType, schema::Schema,
+        simpledb::SimpleDB, table_scan::TableScan, transaction::Transaction,
+    };
+
+    use super::MetadataManager;
+
+    fn lookup(
+        mdm: &mut MetadataManager,
+        tblname: &str,
+        fldname: &str,
+        key: i64,
+        tx: &mut Transaction,
+    ) -> Vec<i64> {
+        let layout = mdm.get_layout(tblname, tx).unwrap().unwrap();
+        let mut index = mdm.get_index_info(tblname, tx).unwrap()[fldname].open();
+        let mut ts = TableScan::new(tx, tblname, layout).unwrap();
+        let mut found = Vec::new();
+        index.before_first(tx, &Constant::Int(key)).unwrap();
+        while index.next(tx).unwrap() {
+            let rid = index.get_data_rid(tx).unwrap();
+            ts.move_to_rid(tx, rid).unwrap();
+            found.push(ts.get_int(tx, fldname).unwrap());
+        }
+        index.close(tx);
+        ts.close(tx);
+        found
+    }
+
+    #[test]
+    fn test_alter_table() {
+        let _ = std::fs::remove_dir_all("altertest");
+        let mut db = SimpleDB::new("altertest", 400, 8).unwrap();
+        let mdm = &mut db.metadata_manager;
+        let mut tx = Transaction::new(
+            &db.file_manager,
+            &mut db.log_manager,
+            &mut db.buffer_manager,
+        );
+
+        let mut schema = Schema::new();
+        schema.add_int_field("A");
+        schema.add_string_field("B", 9);
+        schema.add_text_field("C");
+        mdm.create_table("T", &schema, &mut tx).unwrap();
+        let layout = mdm.get_layout("T", &mut tx).unwrap().unwrap();
+        let mut ts = TableScan::new(&mut tx, "T", layout).unwrap();
+        for i in 0..30 {
+            ts.insert(&mut tx).unwrap();
+            ts.set_int(&mut tx, "A", i).unwrap();
+            ts.set_string(&mut tx, "B", &format!("b{}", i)).unwrap();
+            ts.set_string(&mut tx, "C", &"c".repeat(i as usize * 20))
+                .unwrap();
+        }
+        ts.close(&mut tx);
+        mdm.create_index("TA", "T", "A", IndexType::Hash, &mut tx)
+            .unwrap();
+        mdm.create_view("V", "select B from T", &mut tx).unwrap();
+        mdm.create_view("X", "select B as C from T where B = 'A'", &mut tx)
+            .unwrap();
+        tx.commit().unwrap();
+
+        mdm.add_column(
+            "T",
+            "D",
+            ColumnType::Integer,
+            0,
+            Some(Constant::Int(42)),
+            &mut tx,
+        )
+        .unwrap();
+        mdm.add_column("T", "E", ColumnType::VarChar, 5, None, &mut tx)
+            .unwrap();
+        assert!(mdm
+            .add_column(
+                "T",
+                "F",
+                ColumnType::Integer,
+                0,
+                Some(Constant::Str("x".to_string())),
+                &mut tx
+            )
+            .is_err());
+        assert!(mdm
+            .add_column("T", "D", ColumnType::Integer, 0, None, &mut tx)
+            .is_err());
+        assert!(mdm
+            .add_column("T", "G", ColumnType::VarChar, 500, None, &mut tx)
+            .is_err());
+
+        // Fields that an index or a view depends on stay, but not those a
+        // view only has as an alias or in a literal
+        assert!(mdm.drop_column("T", "A", &mut tx).is_err());
+        assert!(mdm.drop_column("T", "B", &mut tx).is_err());
+        assert!(mdm.rename_column("T", "B", "BB", &mut tx).is_err());
+        assert!(mdm.rename_table("T", "U", &mut tx).is_err());
+        mdm.drop_column("T", "C", &mut tx).unwrap();
+        mdm.rename_column("T", "A", "AA", &mut tx).unwrap();
+        tx.commit().unwrap();
+
+        let layout = mdm.get_layout("T", &mut tx).unwrap().unwrap();
+        assert_eq!(layout.schema().fields(), ["AA", "B", "D", "E"]);
+        let mut ts = TableScan::new(&mut tx, "T", layout).unwrap();
+        let mut count = 0;
+        while ts.next(&mut tx).unwrap() {
+            let a = ts.get_int(&mut tx, "AA").unwrap();
+            assert_eq!(ts.get_string(&mut tx, "B").unwrap(), format!("b{}", a));
+            assert_eq!(ts.get_int(&mut tx, "D").unwrap(), 42);
+            assert!(ts.is_null(&mut tx, "E"));
+            count += 1;
+        }
+        ts.close(&mut tx);
+        assert_eq!(count, 30);
+        // The index followed the rename and the records' new places
+        assert_eq!(lookup(mdm, "T", "AA", 17, &mut tx), vec![17]);
+
+        // Renaming a table moves its records, large values included
+        let mut schema = Schema::new();
+        schema.add_int_field("K");
+        schema.add_blob_field("L");
+        mdm.create_table("S", &schema, &mut tx).unwrap();
+        let layout = mdm.get_layout("S", &mut tx).unwrap().unwrap();
+        let blob = (0..1000u32).map(|i| i as u8).collect::<Vec<_>>();
+        let mut ts = TableScan::new(&mut tx, "S", layout).unwrap();
+        for i in 0..20 {
+            ts.insert(&mut tx).unwrap();
+            ts.set_int(&mut tx, "K", i).unwrap();
+            ts.set_from_reader(&mut tx, "L", &mut blob.as_slice())
+                .unwrap();
+        }
+        ts.close(&mut tx);
+        mdm.create_index("SK", "S", "K", IndexType::Hash, &mut tx)
+            .unwrap();
+        mdm.rename_table("S", "S2", &mut tx).unwrap();
+        tx.commit().unwrap();
+
+        assert!(mdm.get_layout("S", &mut tx).unwrap().is_none());
+        assert_eq!(lookup(mdm, "S2", "K", 5, &mut tx), vec![5]);
+        let layout = mdm.get_layout("S2", &mut tx).unwrap().unwrap();
+        let mut ts = TableScan::new(&mut tx, "S2", layout).unwrap();
+        let mut count = 0;
+        while ts.next(&mut tx).unwrap() {
+            let mut read = Vec::new();
+            ts.get_reader(&mut tx, "L").read_to_end(&mut read).unwrap();
+            assert_eq!(read, blob);
+            count += 1;
+        }
+        ts.close(&mut tx);
+        assert_eq!(count, 20);
+
+        // A rolled back change leaves both the catalog and the records alone
+        mdm.drop_column("T", "E", &mut tx).unwrap();
+        mdm.rename_table("S2", "S3", &mut tx).unwrap();
+        tx.rollback().unwrap();
+        let layout = mdm.get_layout("T", &mut tx).unwrap().unwrap();
+        assert_eq!(layout.schema().fields(), ["AA", "B", "D", "E"]);
+        assert!(mdm.get_layout("S3", &mut tx).unwrap().is_none());
+        assert_eq!(lookup(mdm, "S2", "K", 5, &mut tx), vec![5]);
+        assert_eq!(lookup(mdm, "T", "AA", 17, &mut tx), vec![17]);
+        tx.commit().unwrap();
+    }
+
+    #[test]
+    fn test_drop() {
+        let _ = std::fs::remove_dir_all("droptest");
+        let exists = |f: &str| std::path::Path::new("droptest").join(f).exists();
+        let mut db = SimpleDB::new("droptest", 400, 8).unwrap();
+        let mdm = &mut db.metadata_manager;
+        let mut tx = Transaction::new(
+            &db.file_manager,
+            &mut db.log_manager,
+            &mut db.buffer_manager,
+        );
+
+        let mut schema = Schema::new();
+        schema.add_int_field("A");
+        schema.add_text_field("B");
+        mdm.create_table("T", &schema, &mut tx).unwrap();
+        let layout = mdm.get_layout("T", &mut tx).unwrap().unwrap();
+        let mut ts = TableScan::new(&mut tx, "T", layout).unwrap();
+        for i in 0..20 {
+            ts.insert(&mut tx).unwrap();
+            ts.set_int(&mut tx, "A", i).unwrap();
+            ts.set_string(&mut tx, "B", "b").unwrap();
+        }
+        ts.close(&mut tx);
+        mdm.create_index("TA", "T", "A", IndexType::Hash, &mut tx)
+            .unwrap();
+        mdm.create_view("V", "select A from T", &mut tx).unwrap();
+        mdm.create_view("W", "select A from V", &mut tx).unwrap();
+        // Only the FROM clause makes a view depend on a table
+        let mut other = Schema::new();
+        other.add_int_field("A");
+        other.add_string_field("B", 5);
+        mdm.create_table("U", &other, &mut tx).unwrap();
+        mdm.create_view("X", "select A as T from U where B = 'V'", &mut tx)
+            .unwrap();
+        tx.commit().unwrap();
+
+        assert!(mdm.drop_table("T", &mut tx).is_err());
+        assert!(mdm.drop_view("V", &mut tx).is_err());
+        mdm.drop_view("W", &mut tx).unwrap();
+        mdm.drop_view("V", &mut tx).unwrap();
+        assert!(mdm.drop_view("V", &mut tx).is_err());
+        assert!(mdm.drop_table("Missing", &mut tx).is_err());
+        assert!(mdm.drop_index("Missing", &mut tx).is_err());
+        tx.commit().unwrap();
+
+        // Nothing is deleted before the commit, and a rollback keeps it all
+        mdm.drop_table("T", &mut tx).unwrap();
+        assert!(mdm.get_layout("T", &mut tx).unwrap().is_none());
+        assert!(mdm.create_table("T", &schema, &mut tx).is_err());
+        assert!(exists("T.tbl") && exists("T.fsm") && exists("T.ovf") && exists("idx$TA$7.tbl"));
+        tx.rollback().unwrap();
+        assert!(mdm.get_layout("T", &mut tx).unwrap().is_some());
+        assert_eq!(lookup(mdm, "T", "A", 7, &mut tx), vec![7]);
+
+        mdm.drop_table("T", &mut tx).unwrap();
+        tx.commit().unwrap();
+        assert!(!exists("T.tbl") && !exists("T.fsm") && !exists("T.ovf") && !exists("idx$TA$7.tbl"));
+        assert!(mdm.get_layout("T", &mut tx).unwrap().is_none());
+        assert!(mdm
+            .index_manager
+            .index_defs("T", &mut tx)
+            .unwrap()
+            .is_empty());
+
+        // The name can be used again, starting out empty
+        mdm.create_table("T", &schema, &mut tx).unwrap();
+        let layout = mdm.get_layout("T", &mut tx).unwrap().unwrap();
+        let mut ts = TableScan::new(&mut tx, "T", layout).unwrap();
+        assert!(!ts.next(&mut tx).unwrap());
+        ts.close(&mut tx);
+        mdm.create_index("TA", "T", "A", IndexType::Hash, &mut tx)
+            .unwrap();
+        mdm.drop_index("TA", &mut tx).unwrap();
+        tx.commit().unwrap();
+        assert!(mdm.get_index_info("T", &mut tx).unwrap().is_empty());
+
+        // Rewriting a table leaves no scratch files behind
+        mdm.add_column("T", "C", ColumnType::Integer, 0, None, &mut tx)
+            .unwrap();
+        tx.commit().unwrap();
+        assert!(!exists("T~.tbl"));
+
+        // Bucket files keep clear of the table files, and an index cannot
+        // take the name of a table or view
+        mdm.drop_table("T", &mut tx).unwrap();
+        mdm.create_table("T1", &schema, &mut tx).unwrap();
+        let layout = mdm.get_layout("T1", &mut tx).unwrap().unwrap();
+        let mut ts = TableScan::new(&mut tx, "T1", layout.clone()).unwrap();
+        ts.insert(&mut tx).unwrap();
+        ts.set_int(&mut tx, "A", 1).unwrap();
+        ts.close(&mut tx);
+        tx.commit().unwrap();
+        mdm.create_index("T", "T1", "A", IndexType::Hash, &mut tx)
+            .unwrap();
+        assert_eq!(lookup(mdm, "T1", "A", 1, &mut tx).len(), 1);
+        mdm.drop_index("T", &mut tx).unwrap();
+        tx.commit().unwrap();
+        assert!(exists("T1.tbl"));
+        let mut ts = TableScan::new(&mut tx, "T1", layout).unwrap();
+        assert!(ts.next(&mut tx).unwrap());
+        ts.close(&mut tx);
+        assert!(mdm
+            .create_index("T1", "T1", "A", IndexType::Hash, &mut tx)
+            .is_err());
+        assert!(mdm
+            .create_index("X", "T1", "A", IndexType::Hash, &mut tx)
+            .is_err());
+        tx.commit().unwrap();
+    }
+
+    #[test]
+    fn test_drop_and_recreate_after_restart() {
+        let _ = std::fs::remove_dir_all("recreatetest");
+        let mut schema = Schema::new();
+        schema.add_int_field("A");
+        let insert = |mdm: &mut MetadataManager, tblname: &str, a: i64, tx: &mut Transaction| {
+            let layout = mdm.get_layout(tblname, tx).unwrap().unwrap();
+            let mut ts = TableScan::new(tx, tblname, layout).unwrap();
+            ts.insert(tx).unwrap();
+            ts.set_int(tx, "A", a).unwrap();
+            ts.close(tx);
+        };
+        let values = |mdm: &mut MetadataManager, tblname: &str, tx: &mut Transaction| {
+            let layout = mdm.get_layout(tblname, tx).unwrap().unwrap();
+            let mut ts = TableScan::new(tx, tblname, layout).unwrap();
+            let mut values = Vec::new();
+            while ts.next(tx).unwrap() {
+                values.push(ts.get_int(tx, "A").unwrap());
+            }
+            ts.close(tx);
+            values
+        };
+
+        {
+            let mut db = SimpleDB::new("recreatetest", 400, 8).unwrap();
+            let mdm = &mut db.metadata_manager;
+            let mut tx = Transaction::new(
+                &db.file_manager,
+                &mut db.log_manager,
+                &mut db.buffer_manager,
+            );
+            mdm.create_table("T", &schema, &mut tx).unwrap();
+            insert(mdm, "T", 1, &mut tx);
+            mdm.create_table("R", &schema, &mut tx).unwrap();
+            insert(mdm, "R", 2, &mut tx);
+            tx.commit().unwrap();
+
+            mdm.drop_table("T", &mut tx).unwrap();
+            tx.commit().unwrap();
+            mdm.create_table("T", &schema, &mut tx).unwrap();
+            insert(mdm, "T", 3, &mut tx);
+            tx.commit().unwrap();
+
+            mdm.rename_table("R", "S", &mut tx).unwrap();
+            tx.commit().unwrap();
+            mdm.rename_table("S", "R", &mut tx).unwrap();
+            tx.commit().unwrap();
+        }
+
+        // Recovery does not repeat the deletions that were done
+        let mut db = SimpleDB::new("recreatetest", 400, 8).unwrap();
+        let mdm = &mut db.metadata_manager;
+        let mut tx = Transaction::new(
+            &db.file_manager,
+            &mut db.log_manager,
+            &mut db.buffer_manager,
+        );
+        assert_eq!(values(mdm, "T", &mut tx), vec![3]);
+        assert_eq!(values(mdm, "R", &mut tx), vec![2]);
+        tx.commit().unwrap();
+    }
+}
@@ -0,0 +1,315 @@
+use std::io::Read;
+
+use crate::{
+    file_manager::{BlockId, FileManager},
+    page::Page,
+    transaction::Transaction,
+};
+
+// Each overflow block starts with the number of the next block in the chain
+const NEXT_SIZE: u64 = 8;
+pub(crate) const NO_BLOCK: u64 = u64::MAX;
+// Block 0 of an overflow file holds the first block of its free list, the
+// blocks of chains that were let go, linked through their next numbers. As
+// no chain can start at the header, 0 ends the list, and a freshly appended
+// header holds an empty one.
+const HEADER_BLOCK: u64 = 0;
+const END_OF_FREE_LIST: u64 = 0;
+
+/// What a record stores in place of a large value: where its overflow chain
+/// starts and how many bytes it holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct OverflowPointer {
+    pub(crate) first_block: u64,
+    pub(crate) length: u64,
+}
+
+impl OverflowPointer {
+    pub(crate) const SIZE: u64 = 16;
+
+    pub(crate) fn empty() -> Self {
+        Self {
+            first_block: NO_BLOCK,
+            length: 0,
+        }
+    }
+}
+
+/// A large value left where it is: the overflow file and the chain in it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LargeValue {
+    pub(crate) filename: String,
+    pub(crate) pointer: OverflowPointer,
+}
+
+pub(crate) fn overflow_file(tblname: &str) -> String {
+    format!("{}.ovf", tblname)
+}
+
+/// Copies `data` into a new chain of overflow blocks, taking blocks off the
+/// free list before appending any. The blocks are written through the
+/// transaction, so the chain is logged, and flushed as they are filled,
+/// since `OverflowReader` reads the file directly.
+pub(crate) fn write_overflow(
+    tx: &mut Transaction,
+    filename: &str,
+    data: &mut dyn Read,
+) -> Result<OverflowPointer, std::io::Error> {
+    let chunk_size = (tx.block_size() - NEXT_SIZE) as usize;
+    let mut chunk = vec![0; chunk_size];
+    let mut pointer = OverflowPointer::empty();
+    let mut pending: Option<BlockId> = None;
+
+    loop {
+        let n = read_chunk(data, &mut chunk)?;
+        if n == 0 {
+            break;
+        }
+        let block = allocate(tx, filename)?;
+        match pending.take() {
+            Some(prev) => {
+                tx.set_int(&prev, 0, block.number())?;
+                tx.flush(&prev)?;
+                tx.unpin(&prev);
+            }
+            None => pointer.first_block = block.number(),
+        }
+        tx.pin(&block)?;
+        tx.set_int(&block, 0, NO_BLOCK)?;
+        tx.set_raw_bytes(&block, NEXT_SIZE, &chunk[..n])?;
+        pending = Some(block);
+        pointer.length += n as u64;
+    }
+
+    if let Some(last) = pending {
+        tx.flush(&last)?;
+        tx.unpin(&last);
+    }
+
+    Ok(pointer)
+}
+
+/// Puts the blocks of the chain on the free list of its file.
+pub(crate) fn free_overflow(
+    tx: &mut Transaction,
+    filename: &str,
+    pointer: OverflowPointer,
+) -> Result<(), std::io::Error> {
+    if pointer.first_block == NO_BLOCK {
+        return Ok(());
+    }
+    let header = BlockId::new(filename.to_string(), HEADER_BLOCK);
+    tx.pin(&header)?;
+    let mut block = BlockId::new(filename.to_string(), pointer.first_block);
+    loop {
+        tx.pin(&block)?;
+        let next = tx.get_int(&block, 0);
+        if next == NO_BLOCK {
+            let free = tx.get_int(&header, 0);
+            tx.set_int(&block, 0, free)?;
+            tx.unpin(&block);
+            break;
+        }
+        tx.unpin(&block);
+        block = BlockId::new(filename.to_string(), next);
+    }
+    tx.set_int(&header, 0, pointer.first_block)?;
+    tx.unpin(&header);
+    Ok(())
+}
+
+/// Puts every block of the file on the free list, for when all the records
+/// pointing into it are gone.
+pub(crate) fn free_all_overflow(
+    tx: &mut Transaction,
+    filename: &str,
+) -> Result<(), std::io::Error> {
+    let size = tx.size(filename)?;
+    if size <= 1 {
+        return Ok(());
+    }
+    for number in 1..size {
+        let block = BlockId::new(filename.to_string(), number);
+        let next = if number + 1 < size {
+            number + 1
+        } else {
+            END_OF_FREE_LIST
+        };
+        tx.pin(&block)?;
+        tx.set_int(&block, 0, next)?;
+        tx.unpin(&block);
+    }
+    let header = BlockId::new(filename.to_string(), HEADER_BLOCK);
+    tx.pin(&header)?;
+    tx.set_int(&header, 0, 1)?;
+    tx.unpin(&header);
+    Ok(())
+}
+
+// Takes the first block off the free list, or appends one if it is empty. A
+// new file gets its header first.
+fn allocate(tx: &mut Transaction, filename: &str) -> Result<BlockId, std::io::Error> {
+    if tx.size(filename)? == 0 {
+        tx.append(filename)?;
+    }
+    let header = BlockId::new(filename.to_string(), HEADER_BLOCK);
+    tx.pin(&header)?;
+    let free = tx.get_int(&header, 0);
+    let block = if free == END_OF_FREE_LIST {
+        tx.append(filename)?
+    } else {
+        let block = BlockId::new(filename.to_string(), free);
+        tx.pin(&block)?;
+        let next = tx.get_int(&block, 0);
+        tx.unpin(&block);
+        tx.set_int(&header, 0, next)?;
+        block
+    };
+    tx.unpin(&header);
+    Ok(block)
+}
+
+fn read_chunk(data: &mut dyn Read, chunk: &mut [u8]) -> Result<usize, std::io::Error> {
+    let mut filled = 0;
+    while filled < chunk.len() {
+        match data.read(&mut chunk[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// Streams a large value one overflow block at a time.
+pub(crate) struct OverflowReader<'a> {
+    file_manager: &'a FileManager,
+    filename: String,
+    page: Page,
+    next_block: u64,
+    position: u64,
+    remaining: u64,
+}
+
+impl<'a> OverflowReader<'a> {
+    pub(crate) fn new(
+        file_manager: &'a FileManager,
+        filename: &str,
+        pointer: OverflowPointer,
+    ) -> Self {
+        let page = Page::new(file_manager.blocksize);
+        // Start at the end of an empty page so the first read loads a block
+        Self {
+            file_manager,
+            filename: filename.to_string(),
+            position: page.block_size,
+            page,
+            next_block: pointer.first_block,
+            remaining: pointer.length,
+        }
+    }
+}
+
+impl Read for OverflowReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.remaining == 0 || buf.is_empty() {
+            return Ok(0);
+        }
+        if self.position == self.page.block_size {
+            let block = BlockId::new(self.filename.clone(), self.next_block);
+            self.file_manager.read(&block, &mut self.page)?;
+            self.next_block = self.page.get_int(0);
+            self.position = NEXT_SIZE;
+        }
+        let available = (self.page.block_size - self.position).min(self.remaining);
+        let n = available.min(buf.len() as u64);
+        buf[..n as usize].copy_from_slice(self.page.bb.get_bytes_with_offset(self.position, n));
+        self.position += n;
+        self.remaining -= n;
+        Ok(n as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::{free_all_overflow, free_overflow, write_overflow, OverflowReader};
+    use crate::{simpledb::SimpleDB, transaction::Transaction};
+
+    fn read_all(reader: &mut OverflowReader) -> Vec<u8> {
+        let mut chunk = [0; 100];
+        let mut read = Vec::new();
+        loop {
+            let n = reader.read(&mut chunk).unwrap();
+            if n == 0 {
+                break;
+            }
+            read.extend_from_slice(&chunk[..n]);
+        }
+        read
+    }
+
+    #[test]
+    fn test_overflow_chain() {
+        let _ = std::fs::remove_dir_all("overflowtest");
+        let mut db = SimpleDB::new("overflowtest", 400, 8).unwrap();
+        let fm = &db.file_manager;
+        let mut tx = Transaction::new(fm, &mut db.log_manager, &mut db.buffer_manager);
+
+        let value = (0..5000u32).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        let pointer = write_overflow(&mut tx, "test.ovf", &mut value.as_slice()).unwrap();
+        assert_eq!(pointer.length, 5000);
+        // The header and 392 bytes of data per block
+        assert_eq!(fm.size("test.ovf").unwrap(), 14);
+        // Readable before the commit
+        assert_eq!(
+            read_all(&mut OverflowReader::new(fm, "test.ovf", pointer)),
+            value
+        );
+        tx.commit().unwrap();
+
+        let empty = write_overflow(&mut tx, "test.ovf", &mut [].as_slice()).unwrap();
+        let mut read = Vec::new();
+        OverflowReader::new(fm, "test.ovf", empty)
+            .read_to_end(&mut read)
+            .unwrap();
+        assert!(read.is_empty());
+        free_overflow(&mut tx, "test.ovf", empty).unwrap();
+
+        // A freed chain's blocks are used again, and a rollback takes them
+        // back from the new value
+        free_overflow(&mut tx, "test.ovf", pointer).unwrap();
+        let other = vec![7; 1000];
+        let second = write_overflow(&mut tx, "test.ovf", &mut other.as_slice()).unwrap();
+        assert_eq!(second.first_block, pointer.first_block);
+        assert_eq!(
+            read_all(&mut OverflowReader::new(fm, "test.ovf", second)),
+            other
+        );
+        tx.rollback().unwrap();
+        assert_eq!(
+            read_all(&mut OverflowReader::new(fm, "test.ovf", pointer)),
+            value
+        );
+
+        free_overflow(&mut tx, "test.ovf", pointer).unwrap();
+        for _ in 0..5 {
+            let chain = write_overflow(&mut tx, "test.ovf", &mut value.as_slice()).unwrap();
+            assert_eq!(
+                read_all(&mut OverflowReader::new(fm, "test.ovf", chain)),
+                value
+            );
+            free_overflow(&mut tx, "test.ovf", chain).unwrap();
+        }
+        assert_eq!(fm.size("test.ovf").unwrap(), 14);
+
+        // Clearing the file frees every block
+        write_overflow(&mut tx, "test.ovf", &mut value.as_slice()).unwrap();
+        free_all_overflow(&mut tx, "test.ovf").unwrap();
+        write_overflow(&mut tx, "test.ovf", &mut value.as_slice()).unwrap();
+        assert_eq!(fm.size("test.ovf").unwrap(), 14);
+        tx.commit().unwrap();
+    }
+}
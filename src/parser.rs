@@ -0,0 +1,1773 @@
+use crate::{
+    aggregate::{AggregateFn, AggregateKind},
+    command::{
+        AlterAction, AlterTableData, AnalyzeData, Command, CreateIndexData, CreateTableData,
+        CreateViewData, DeleteData, DropIndexData, DropTableData, DropViewData, InsertData,
+        ModifyData,
+    },
+    constant::Constant,
+    constraint::{Constraint, ConstraintKind, OnDelete, Reference},
+    expression::{ArithOp, Expression},
+    index::IndexType,
+    lexer::Lexer,
+    predicate::Predicate,
+    query_data::{FromItem, Join, JoinKind, QueryData, SelectItem, SetOp, TableRef},
+    record_comparator::SortKey,
+    resultset::ColumnType,
+    schema::Schema,
+    sql_exception::SQLException,
+    term::{Operator, Term},
+};
+
+/// A recursive descent parser over the lexer's tokens, a method per rule of
+/// the grammar.
+pub(crate) struct Parser {
+    lex: Lexer,
+    // The aggregates met so far, where the grammar allows them
+    aggregates: Option<Vec<AggregateFn>>,
+}
+
+impl Parser {
+    pub(crate) fn new(text: &str) -> Result<Self, SQLException> {
+        Ok(Self {
+            lex: Lexer::new(text)?,
+            aggregates: None,
+        })
+    }
+
+    /// Fails unless every token was used.
+    pub(crate) fn end(&self) -> Result<(), SQLException> {
+        if !self.lex.at_end() {
+            return Err(self.lex.expected("end of input"));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn field(&mut self) -> Result<String, SQLException> {
+        self.lex.eat_id()
+    }
+
+    /// A field as a query refers to it, which may be qualified by the name
+    /// of its table as `table.field`.
+    pub(crate) fn field_ref(&mut self) -> Result<String, SQLException> {
+        let name = self.lex.eat_id()?;
+        if !self.lex.match_delim(".") {
+            return Ok(name);
+        }
+        self.lex.eat_delim(".")?;
+        Ok(format!("{}.{}", name, self.lex.eat_id()?))
+    }
+
+    pub(crate) fn constant(&mut self) -> Result<Constant, SQLException> {
+        if self.lex.match_keyword("null") {
+            self.lex.eat_keyword("null")?;
+            Ok(Constant::Null)
+        } else if self.lex.match_string_constant() {
+            Ok(Constant::Str(self.lex.eat_string_constant()?))
+        } else if self.lex.match_bytes_constant() {
+            Ok(Constant::Bytes(self.lex.eat_bytes_constant()?))
+        } else if self.lex.match_delim("-") {
+            self.lex.eat_delim("-")?;
+            Ok(Constant::Int(-self.lex.eat_int_constant()?))
+        } else if self.lex.match_int_constant() {
+            Ok(Constant::Int(self.lex.eat_int_constant()?))
+        } else {
+            Err(self.lex.expected("a constant"))
+        }
+    }
+
+    /// Sums and differences of products, which bind tighter.
+    pub(crate) fn expression(&mut self) -> Result<Expression, SQLException> {
+        let mut expr = self.product()?;
+        while let Some(op) = ["+", "-"].into_iter().find(|d| self.lex.match_delim(d)) {
+            self.lex.eat_delim(op)?;
+            let rhs = self.product()?;
+            expr = Expression::Arith(
+                Box::new(expr),
+                ArithOp::from_symbol(op).unwrap(),
+                Box::new(rhs),
+            );
+        }
+        Ok(expr)
+    }
+
+    fn product(&mut self) -> Result<Expression, SQLException> {
+        let mut expr = self.unary()?;
+        while let Some(op) = ["*", "/", "%"]
+            .into_iter()
+            .find(|d| self.lex.match_delim(d))
+        {
+            self.lex.eat_delim(op)?;
+            let rhs = self.unary()?;
+            expr = Expression::Arith(
+                Box::new(expr),
+                ArithOp::from_symbol(op).unwrap(),
+                Box::new(rhs),
+            );
+        }
+        Ok(expr)
+    }
+
+    /// A negated integer is read as a negative constant.
+    fn unary(&mut self) -> Result<Expression, SQLException> {
+        if !self.lex.match_delim("-") {
+            return self.primary();
+        }
+        self.lex.eat_delim("-")?;
+        Ok(match self.unary()? {
+            Expression::Constant(Constant::Int(i)) => Expression::Constant(Constant::Int(-i)),
+            expr => Expression::Negate(Box::new(expr)),
+        })
+    }
+
+    fn primary(&mut self) -> Result<Expression, SQLException> {
+        if let Some(kind) = self.match_aggregate() {
+            Ok(Expression::Field(self.aggregate(kind)?))
+        } else if self.lex.match_id() {
+            Ok(Expression::Field(self.field_ref()?))
+        } else if self.lex.match_delim("(") {
+            self.lex.eat_delim("(")?;
+            let expr = if self.lex.match_keyword("select") {
+                Expression::Subquery(Box::new(self.query()?))
+            } else {
+                self.expression()?
+            };
+            self.lex.eat_delim(")")?;
+            Ok(expr)
+        } else if self.lex.match_keyword("case") {
+            self.case()
+        } else {
+            Ok(Expression::Constant(self.constant()?))
+        }
+    }
+
+    fn case(&mut self) -> Result<Expression, SQLException> {
+        self.lex.eat_keyword("case")?;
+        let mut branches = Vec::new();
+        loop {
+            self.lex.eat_keyword("when")?;
+            let condition = self.predicate()?;
+            self.lex.eat_keyword("then")?;
+            branches.push((condition, self.expression()?));
+            if !self.lex.match_keyword("when") {
+                break;
+            }
+        }
+        let mut otherwise = None;
+        if self.lex.match_keyword("else") {
+            self.lex.eat_keyword("else")?;
+            otherwise = Some(Box::new(self.expression()?));
+        }
+        self.lex.eat_keyword("end")?;
+        Ok(Expression::Case {
+            branches,
+            otherwise,
+        })
+    }
+
+    /// A single condition: a comparison, `[NOT] BETWEEN`, `[NOT] IN` a list
+    /// or `[NOT] LIKE` a pattern.
+    pub(crate) fn term(&mut self) -> Result<Term, SQLException> {
+        if self.lex.match_keyword("exists") {
+            self.lex.eat_keyword("exists")?;
+            return Ok(Term::Exists(Box::new(self.subquery()?)));
+        }
+        let expr = self.expression()?;
+        if let Some(op) = ["=", "<>", "!=", "<=", ">=", "<", ">"]
+            .into_iter()
+            .find(|symbol| self.lex.match_delim(symbol))
+        {
+            self.lex.eat_delim(op)?;
+            let rhs = self.expression()?;
+            return Ok(Term::new(expr, Operator::from_symbol(op).unwrap(), rhs));
+        }
+        let negated = self.lex.match_keyword("not");
+        if negated {
+            self.lex.eat_keyword("not")?;
+        }
+        if self.lex.match_keyword("between") {
+            self.lex.eat_keyword("between")?;
+            let low = self.expression()?;
+            self.lex.eat_keyword("and")?;
+            let high = self.expression()?;
+            Ok(Term::Between {
+                expr,
+                low,
+                high,
+                negated,
+            })
+        } else if self.lex.match_keyword("in") {
+            self.lex.eat_keyword("in")?;
+            self.lex.eat_delim("(")?;
+            if self.lex.match_keyword("select") {
+                let query = Box::new(self.query()?);
+                self.lex.eat_delim(")")?;
+                return Ok(Term::InQuery {
+                    expr,
+                    query,
+                    negated,
+                });
+            }
+            let mut list = vec![self.expression()?];
+            while self.lex.match_delim(",") {
+                self.lex.eat_delim(",")?;
+                list.push(self.expression()?);
+            }
+            self.lex.eat_delim(")")?;
+            Ok(Term::In {
+                expr,
+                list,
+                negated,
+            })
+        } else if self.lex.match_keyword("like") {
+            self.lex.eat_keyword("like")?;
+            let pattern = self.expression()?;
+            Ok(Term::Like {
+                expr,
+                pattern,
+                negated,
+            })
+        } else if negated {
+            Err(self.lex.expected("BETWEEN, IN or LIKE"))
+        } else {
+            Err(self.lex.expected("a comparison"))
+        }
+    }
+
+    /// Conjunctions joined by OR, AND binding tighter.
+    pub(crate) fn predicate(&mut self) -> Result<Predicate, SQLException> {
+        let mut disjuncts = vec![self.conjunction()?];
+        while self.lex.match_keyword("or") {
+            self.lex.eat_keyword("or")?;
+            disjuncts.push(self.conjunction()?);
+        }
+        if disjuncts.len() == 1 {
+            return Ok(disjuncts.pop().unwrap());
+        }
+        Ok(Predicate::from_term(Term::Or(disjuncts)))
+    }
+
+    fn conjunction(&mut self) -> Result<Predicate, SQLException> {
+        let mut predicate = self.factor()?;
+        while self.lex.match_keyword("and") {
+            self.lex.eat_keyword("and")?;
+            predicate.conjoin_with(self.factor()?);
+        }
+        Ok(predicate)
+    }
+
+    /// A term, a negated factor or a parenthesized predicate. The terms of a
+    /// parenthesized conjunction join the one around it.
+    fn factor(&mut self) -> Result<Predicate, SQLException> {
+        if self.lex.match_keyword("not") {
+            self.lex.eat_keyword("not")?;
+            let negated = self.factor()?;
+            return Ok(Predicate::from_term(Term::Not(negated)));
+        }
+        if !self.lex.match_delim("(") {
+            return Ok(Predicate::from_term(self.term()?));
+        }
+        // The parenthesis opens either an expression or a predicate
+        let start = self.lex.position();
+        let term_error = match self.term() {
+            Ok(term) => return Ok(Predicate::from_term(term)),
+            Err(e) => e,
+        };
+        self.lex.reset(start);
+        self.lex.eat_delim("(")?;
+        let predicate = self.predicate().and_then(|predicate| {
+            self.lex.eat_delim(")")?;
+            Ok(predicate)
+        });
+        predicate.map_err(|e| further(term_error, e))
+    }
+
+    fn match_aggregate(&self) -> Option<AggregateKind> {
+        ["count", "sum", "avg", "min", "max"]
+            .into_iter()
+            .find(|name| self.lex.match_keyword(name))
+            .and_then(AggregateKind::from_name)
+    }
+
+    /// An aggregate call, which stands for the field holding its result.
+    fn aggregate(&mut self, kind: AggregateKind) -> Result<String, SQLException> {
+        if self.aggregates.is_none() {
+            return Err(self.lex.error(format!(
+                "aggregate {} is not allowed here",
+                kind.name().to_uppercase()
+            )));
+        }
+        self.lex.eat_keyword(kind.name())?;
+        self.lex.eat_delim("(")?;
+        let field = if kind == AggregateKind::Count && self.lex.match_delim("*") {
+            self.lex.eat_delim("*")?;
+            None
+        } else {
+            Some(self.field_ref()?)
+        };
+        self.lex.eat_delim(")")?;
+        let function = AggregateFn::new(kind, field.as_deref());
+        let fldname = function.field_name();
+        let aggregates = self.aggregates.as_mut().unwrap();
+        if !aggregates.contains(&function) {
+            aggregates.push(function);
+        }
+        Ok(fldname)
+    }
+
+    /// An expression, named by `[AS] alias`.
+    fn select_item(&mut self) -> Result<SelectItem, SQLException> {
+        let expr = self.expression()?;
+        if self.lex.match_keyword("as") {
+            self.lex.eat_keyword("as")?;
+            return Ok(SelectItem::new(expr, Some(&self.lex.eat_id()?)));
+        }
+        if self.lex.match_id() {
+            return Ok(SelectItem::new(expr, Some(&self.lex.eat_id()?)));
+        }
+        Ok(SelectItem::new(expr, None))
+    }
+
+    /// A field, an aggregate or the alias of a select item.
+    fn sort_field(&mut self) -> Result<String, SQLException> {
+        match self.match_aggregate() {
+            Some(kind) => self.aggregate(kind),
+            None => self.field_ref(),
+        }
+    }
+
+    /// SELECTs combined by set operators, left to right, with an ORDER BY
+    /// and a LIMIT for the whole. The sort keys of a compound query name fields of its
+    /// result.
+    pub(crate) fn query(&mut self) -> Result<QueryData, SQLException> {
+        let outer = self.aggregates.take();
+        let mut data = self.select()?;
+        if ["union", "intersect", "except"]
+            .iter()
+            .any(|k| self.lex.match_keyword(k))
+        {
+            self.check_grouping(&data)?;
+        }
+        while let Some(op) = self.set_op()? {
+            let part = self.select()?;
+            if part.items.len() != data.items.len() {
+                return Err(self.lex.error(format!(
+                    "{} of queries with {} and {} fields",
+                    op.keywords().to_uppercase(),
+                    data.items.len(),
+                    part.items.len()
+                )));
+            }
+            self.check_grouping(&part)?;
+            data.compound.push((op, part));
+        }
+        if data.compound.is_empty() {
+            self.aggregates = Some(std::mem::take(&mut data.aggregates));
+        }
+        if self.lex.match_keyword("order") {
+            self.lex.eat_keyword("order")?;
+            self.lex.eat_keyword("by")?;
+            loop {
+                let field = self.sort_field()?;
+                let mut descending = false;
+                if self.lex.match_keyword("desc") {
+                    self.lex.eat_keyword("desc")?;
+                    descending = true;
+                } else if self.lex.match_keyword("asc") {
+                    self.lex.eat_keyword("asc")?;
+                }
+                data.order_by.push(SortKey::new(&field, descending));
+                if !self.lex.match_delim(",") {
+                    break;
+                }
+                self.lex.eat_delim(",")?;
+            }
+        }
+        if self.lex.match_keyword("limit") {
+            self.lex.eat_keyword("limit")?;
+            data.limit = Some(self.lex.eat_int_constant()? as u64);
+            if self.lex.match_keyword("offset") {
+                self.lex.eat_keyword("offset")?;
+                data.offset = self.lex.eat_int_constant()? as u64;
+            }
+        }
+        if let Some(aggregates) = std::mem::replace(&mut self.aggregates, outer) {
+            data.aggregates = aggregates;
+        }
+        if data.compound.is_empty() {
+            self.check_grouping(&data)?;
+        }
+        Ok(data)
+    }
+
+    fn select(&mut self) -> Result<QueryData, SQLException> {
+        self.aggregates = Some(Vec::new());
+        self.lex.eat_keyword("select")?;
+        let distinct = self.lex.match_keyword("distinct");
+        if distinct {
+            self.lex.eat_keyword("distinct")?;
+        }
+        let mut items = vec![self.select_item()?];
+        while self.lex.match_delim(",") {
+            self.lex.eat_delim(",")?;
+            items.push(self.select_item()?);
+        }
+        let aggregates = self.aggregates.take();
+        self.lex.eat_keyword("from")?;
+        let mut from = vec![self.table_item()?];
+        while self.lex.match_delim(",") {
+            self.lex.eat_delim(",")?;
+            from.push(self.table_item()?);
+        }
+        let predicate = self.where_clause()?;
+        self.aggregates = aggregates;
+
+        let mut group_by = Vec::new();
+        if self.lex.match_keyword("group") {
+            self.lex.eat_keyword("group")?;
+            self.lex.eat_keyword("by")?;
+            group_by.push(self.field_ref()?);
+            while self.lex.match_delim(",") {
+                self.lex.eat_delim(",")?;
+                group_by.push(self.field_ref()?);
+            }
+        }
+        let mut having = Predicate::new();
+        if self.lex.match_keyword("having") {
+            self.lex.eat_keyword("having")?;
+            having = self.predicate()?;
+        }
+        Ok(QueryData {
+            distinct,
+            items,
+            from,
+            predicate,
+            group_by,
+            aggregates: self.aggregates.take().unwrap(),
+            having,
+            order_by: Vec::new(),
+            limit: None,
+            offset: 0,
+            compound: Vec::new(),
+        })
+    }
+
+    fn set_op(&mut self) -> Result<Option<SetOp>, SQLException> {
+        let op = if self.lex.match_keyword("union") {
+            self.lex.eat_keyword("union")?;
+            if self.lex.match_keyword("all") {
+                self.lex.eat_keyword("all")?;
+                SetOp::UnionAll
+            } else {
+                SetOp::Union
+            }
+        } else if self.lex.match_keyword("intersect") {
+            self.lex.eat_keyword("intersect")?;
+            SetOp::Intersect
+        } else if self.lex.match_keyword("except") {
+            self.lex.eat_keyword("except")?;
+            SetOp::Except
+        } else {
+            return Ok(None);
+        };
+        Ok(Some(op))
+    }
+
+    fn check_grouping(&self, data: &QueryData) -> Result<(), SQLException> {
+        if !data.is_grouped() {
+            return Ok(());
+        }
+        let aggregated = data
+            .aggregates
+            .iter()
+            .map(|a| a.field_name())
+            .collect::<Vec<_>>();
+        let aliases = data
+            .items
+            .iter()
+            .filter_map(|i| i.alias.clone())
+            .collect::<Vec<_>>();
+        let mut fields = Vec::new();
+        for item in &data.items {
+            item.expr.collect_fields(&mut fields);
+        }
+        fields.extend(data.having.fields());
+        let sort_fields = data.order_by.iter().map(|k| &k.field);
+        fields.extend(sort_fields.filter(|f| !aliases.contains(f)).cloned());
+        let used = fields
+            .into_iter()
+            .find(|f| !data.group_by.contains(f) && !aggregated.contains(f));
+        if let Some(fldname) = used {
+            return Err(self.lex.error(format!(
+                "field {} is neither grouped nor aggregated",
+                fldname
+            )));
+        }
+        Ok(())
+    }
+
+    /// A parenthesized query.
+    fn subquery(&mut self) -> Result<QueryData, SQLException> {
+        self.lex.eat_delim("(")?;
+        let query = self.query()?;
+        self.lex.eat_delim(")")?;
+        Ok(query)
+    }
+
+    /// A table, or a subquery with `[AS] alias` as a derived table.
+    fn table_ref(&mut self) -> Result<TableRef, SQLException> {
+        if self.lex.match_delim("(") {
+            let query = self.subquery()?;
+            if self.lex.match_keyword("as") {
+                self.lex.eat_keyword("as")?;
+            }
+            return Ok(TableRef::derived(query, &self.lex.eat_id()?));
+        }
+        let tblname = self.lex.eat_id()?;
+        if self.lex.match_keyword("as") {
+            self.lex.eat_keyword("as")?;
+            return Ok(TableRef::new(&tblname, Some(&self.lex.eat_id()?)));
+        }
+        if self.lex.match_id() {
+            return Ok(TableRef::new(&tblname, Some(&self.lex.eat_id()?)));
+        }
+        Ok(TableRef::new(&tblname, None))
+    }
+
+    fn table_item(&mut self) -> Result<FromItem, SQLException> {
+        let table = self.table_ref()?;
+        let mut joins = Vec::new();
+        while let Some(kind) = self.join_kind()? {
+            let table = self.table_ref()?;
+            self.lex.eat_keyword("on")?;
+            let on = self.predicate()?;
+            joins.push(Join { kind, table, on });
+        }
+        Ok(FromItem { table, joins })
+    }
+
+    /// Reads the words that start a join, up to and including JOIN.
+    fn join_kind(&mut self) -> Result<Option<JoinKind>, SQLException> {
+        let kind = if self.lex.match_keyword("join") {
+            JoinKind::Inner
+        } else if self.lex.match_keyword("inner") {
+            self.lex.eat_keyword("inner")?;
+            JoinKind::Inner
+        } else {
+            let Some((word, kind)) = [
+                ("left", JoinKind::Left),
+                ("right", JoinKind::Right),
+                ("full", JoinKind::Full),
+            ]
+            .into_iter()
+            .find(|(word, _)| self.lex.match_keyword(word)) else {
+                return Ok(None);
+            };
+            self.lex.eat_keyword(word)?;
+            if self.lex.match_keyword("outer") {
+                self.lex.eat_keyword("outer")?;
+            }
+            kind
+        };
+        self.lex.eat_keyword("join")?;
+        Ok(Some(kind))
+    }
+
+    fn field_list(&mut self) -> Result<Vec<String>, SQLException> {
+        let mut fields = vec![self.field()?];
+        while self.lex.match_delim(",") {
+            self.lex.eat_delim(",")?;
+            fields.push(self.field()?);
+        }
+        Ok(fields)
+    }
+
+    /// A parenthesized field list.
+    fn field_group(&mut self) -> Result<Vec<String>, SQLException> {
+        self.lex.eat_delim("(")?;
+        let fields = self.field_list()?;
+        self.lex.eat_delim(")")?;
+        Ok(fields)
+    }
+
+    fn where_clause(&mut self) -> Result<Predicate, SQLException> {
+        if !self.lex.match_keyword("where") {
+            return Ok(Predicate::new());
+        }
+        self.lex.eat_keyword("where")?;
+        self.predicate()
+    }
+
+    pub(crate) fn update_cmd(&mut self) -> Result<Command, SQLException> {
+        if self.lex.match_keyword("insert") {
+            Ok(Command::Insert(self.insert()?))
+        } else if self.lex.match_keyword("delete") {
+            Ok(Command::Delete(self.delete()?))
+        } else if self.lex.match_keyword("update") {
+            Ok(Command::Modify(self.modify()?))
+        } else if self.lex.match_keyword("create") {
+            self.create()
+        } else if self.lex.match_keyword("drop") {
+            self.drop()
+        } else if self.lex.match_keyword("alter") {
+            Ok(Command::AlterTable(self.alter_table()?))
+        } else if self.lex.match_keyword("rename") {
+            Ok(Command::AlterTable(self.rename_table()?))
+        } else if self.lex.match_keyword("analyze") {
+            Ok(Command::Analyze(self.analyze()?))
+        } else {
+            Err(self
+                .lex
+                .expected("INSERT, DELETE, UPDATE, CREATE, DROP, ALTER, RENAME or ANALYZE"))
+        }
+    }
+
+    fn insert(&mut self) -> Result<InsertData, SQLException> {
+        self.lex.eat_keyword("insert")?;
+        self.lex.eat_keyword("into")?;
+        let tblname = self.lex.eat_id()?;
+        let fields = self.field_group()?;
+        self.lex.eat_keyword("values")?;
+        self.lex.eat_delim("(")?;
+        let mut values = vec![self.constant()?];
+        while self.lex.match_delim(",") {
+            self.lex.eat_delim(",")?;
+            values.push(self.constant()?);
+        }
+        if values.len() != fields.len() {
+            return Err(self.lex.error(format!(
+                "{} values for {} fields",
+                values.len(),
+                fields.len()
+            )));
+        }
+        self.lex.eat_delim(")")?;
+        Ok(InsertData {
+            tblname,
+            fields,
+            values,
+        })
+    }
+
+    fn delete(&mut self) -> Result<DeleteData, SQLException> {
+        self.lex.eat_keyword("delete")?;
+        self.lex.eat_keyword("from")?;
+        let tblname = self.lex.eat_id()?;
+        let predicate = self.where_clause()?;
+        Ok(DeleteData { tblname, predicate })
+    }
+
+    fn modify(&mut self) -> Result<ModifyData, SQLException> {
+        self.lex.eat_keyword("update")?;
+        let tblname = self.lex.eat_id()?;
+        self.lex.eat_keyword("set")?;
+        let mut assignments = Vec::new();
+        loop {
+            let fldname = self.field()?;
+            self.lex.eat_delim("=")?;
+            assignments.push((fldname, self.expression()?));
+            if !self.lex.match_delim(",") {
+                break;
+            }
+            self.lex.eat_delim(",")?;
+        }
+        let predicate = self.where_clause()?;
+        Ok(ModifyData {
+            tblname,
+            assignments,
+            predicate,
+        })
+    }
+
+    fn create(&mut self) -> Result<Command, SQLException> {
+        self.lex.eat_keyword("create")?;
+        if self.lex.match_keyword("table") {
+            Ok(Command::CreateTable(self.create_table()?))
+        } else if self.lex.match_keyword("view") {
+            Ok(Command::CreateView(self.create_view()?))
+        } else if self.lex.match_keyword("index") {
+            Ok(Command::CreateIndex(self.create_index()?))
+        } else {
+            Err(self.lex.expected("TABLE, VIEW or INDEX"))
+        }
+    }
+
+    fn create_table(&mut self) -> Result<CreateTableData, SQLException> {
+        self.lex.eat_keyword("table")?;
+        let mut data = CreateTableData {
+            tblname: self.lex.eat_id()?,
+            schema: Schema::new(),
+            constraints: Vec::new(),
+            defaults: Vec::new(),
+        };
+        self.lex.eat_delim("(")?;
+        loop {
+            if self.lex.match_id() {
+                self.field_def(&mut data)?;
+            } else {
+                let constraint = self.table_constraint()?;
+                data.constraints.push(constraint);
+            }
+            if !self.lex.match_delim(",") {
+                break;
+            }
+            self.lex.eat_delim(",")?;
+        }
+        self.lex.eat_delim(")")?;
+        Ok(data)
+    }
+
+    fn field_def(&mut self, data: &mut CreateTableData) -> Result<(), SQLException> {
+        let fldname = self.field()?;
+        if data.schema.has_field(&fldname) {
+            return Err(self
+                .lex
+                .error(format!("field {} is declared twice", fldname)));
+        }
+        let (ctype, length) = self.field_type()?;
+        data.schema.add_field(&fldname, ctype, length);
+
+        loop {
+            if self.lex.match_keyword("default") {
+                self.lex.eat_keyword("default")?;
+                data.defaults.push((fldname.clone(), self.expression()?));
+                continue;
+            }
+            let name = self.constraint_name()?;
+            let kind = if self.lex.match_keyword("not") {
+                self.lex.eat_keyword("not")?;
+                self.lex.eat_keyword("null")?;
+                ConstraintKind::NotNull
+            } else if self.lex.match_keyword("primary") {
+                self.lex.eat_keyword("primary")?;
+                self.lex.eat_keyword("key")?;
+                ConstraintKind::PrimaryKey
+            } else if self.lex.match_keyword("unique") {
+                self.lex.eat_keyword("unique")?;
+                ConstraintKind::Unique
+            } else if self.lex.match_keyword("references") {
+                ConstraintKind::ForeignKey(self.reference()?)
+            } else if self.lex.match_keyword("check") {
+                let predicate = self.check()?;
+                data.constraints.push(Constraint::check(&name, &predicate));
+                continue;
+            } else if name.is_empty() {
+                return Ok(());
+            } else {
+                return Err(self.lex.expected("a constraint"));
+            };
+            data.constraints
+                .push(Constraint::new(&name, kind, &[fldname.as_str()]));
+        }
+    }
+
+    /// A field's type, with the length of a VARCHAR.
+    fn field_type(&mut self) -> Result<(ColumnType, u64), SQLException> {
+        if self.lex.match_keyword("int") {
+            self.lex.eat_keyword("int")?;
+            Ok((ColumnType::Integer, 0))
+        } else if self.lex.match_keyword("varchar") {
+            self.lex.eat_keyword("varchar")?;
+            self.lex.eat_delim("(")?;
+            let length = self.lex.eat_int_constant()?;
+            if length <= 0 {
+                return Err(self
+                    .lex
+                    .error("VARCHAR length must be positive".to_string()));
+            }
+            self.lex.eat_delim(")")?;
+            Ok((ColumnType::VarChar, length as u64))
+        } else if self.lex.match_keyword("text") {
+            self.lex.eat_keyword("text")?;
+            Ok((ColumnType::Text, 0))
+        } else if self.lex.match_keyword("blob") {
+            self.lex.eat_keyword("blob")?;
+            Ok((ColumnType::Blob, 0))
+        } else {
+            Err(self.lex.expected("a field type"))
+        }
+    }
+
+    fn constraint_name(&mut self) -> Result<String, SQLException> {
+        if !self.lex.match_keyword("constraint") {
+            return Ok(String::new());
+        }
+        self.lex.eat_keyword("constraint")?;
+        self.lex.eat_id()
+    }
+
+    fn table_constraint(&mut self) -> Result<Constraint, SQLException> {
+        let name = self.constraint_name()?;
+        let (kind, fields) = if self.lex.match_keyword("primary") {
+            self.lex.eat_keyword("primary")?;
+            self.lex.eat_keyword("key")?;
+            (ConstraintKind::PrimaryKey, self.field_group()?)
+        } else if self.lex.match_keyword("unique") {
+            self.lex.eat_keyword("unique")?;
+            (ConstraintKind::Unique, self.field_group()?)
+        } else if self.lex.match_keyword("foreign") {
+            self.lex.eat_keyword("foreign")?;
+            self.lex.eat_keyword("key")?;
+            let fields = self.field_group()?;
+            (ConstraintKind::ForeignKey(self.reference()?), fields)
+        } else if self.lex.match_keyword("check") {
+            return Ok(Constraint::check(&name, &self.check()?));
+        } else {
+            return Err(self.lex.expected("a field or constraint"));
+        };
+        Ok(Constraint { name, kind, fields })
+    }
+
+    fn reference(&mut self) -> Result<Reference, SQLException> {
+        self.lex.eat_keyword("references")?;
+        let table = self.lex.eat_id()?;
+        let fields = self.field_group()?;
+        let mut on_delete = OnDelete::Restrict;
+        if self.lex.match_keyword("on") {
+            self.lex.eat_keyword("on")?;
+            self.lex.eat_keyword("delete")?;
+            on_delete = if self.lex.match_keyword("cascade") {
+                self.lex.eat_keyword("cascade")?;
+                OnDelete::Cascade
+            } else if self.lex.match_keyword("restrict") {
+                self.lex.eat_keyword("restrict")?;
+                OnDelete::Restrict
+            } else if self.lex.match_keyword("set") {
+                self.lex.eat_keyword("set")?;
+                self.lex.eat_keyword("null")?;
+                OnDelete::SetNull
+            } else {
+                return Err(self.lex.expected("CASCADE, RESTRICT or SET NULL"));
+            };
+        }
+        Ok(Reference {
+            table,
+            fields,
+            on_delete,
+        })
+    }
+
+    fn check(&mut self) -> Result<Predicate, SQLException> {
+        self.lex.eat_keyword("check")?;
+        self.lex.eat_delim("(")?;
+        let predicate = self.predicate()?;
+        self.lex.eat_delim(")")?;
+        Ok(predicate)
+    }
+
+    fn create_view(&mut self) -> Result<CreateViewData, SQLException> {
+        self.lex.eat_keyword("view")?;
+        let viewname = self.lex.eat_id()?;
+        self.lex.eat_keyword("as")?;
+        let query = self.query()?;
+        Ok(CreateViewData { viewname, query })
+    }
+
+    fn create_index(&mut self) -> Result<CreateIndexData, SQLException> {
+        self.lex.eat_keyword("index")?;
+        let idxname = self.lex.eat_id()?;
+        self.lex.eat_keyword("on")?;
+        let tblname = self.lex.eat_id()?;
+        self.lex.eat_delim("(")?;
+        let fldname = self.field()?;
+        self.lex.eat_delim(")")?;
+        let mut index_type = IndexType::Hash;
+        if self.lex.match_keyword("using") {
+            self.lex.eat_keyword("using")?;
+            let name = self.lex.eat_id()?;
+            index_type = IndexType::from_name(&name.to_lowercase())
+                .ok_or_else(|| self.lex.error(format!("unknown index type {}", name)))?;
+        }
+        Ok(CreateIndexData {
+            idxname,
+            tblname,
+            fldname,
+            index_type,
+        })
+    }
+
+    fn drop(&mut self) -> Result<Command, SQLException> {
+        self.lex.eat_keyword("drop")?;
+        if self.lex.match_keyword("table") {
+            self.lex.eat_keyword("table")?;
+            let tblname = self.lex.eat_id()?;
+            Ok(Command::DropTable(DropTableData { tblname }))
+        } else if self.lex.match_keyword("view") {
+            self.lex.eat_keyword("view")?;
+            let viewname = self.lex.eat_id()?;
+            Ok(Command::DropView(DropViewData { viewname }))
+        } else if self.lex.match_keyword("index") {
+            self.lex.eat_keyword("index")?;
+            let idxname = self.lex.eat_id()?;
+            Ok(Command::DropIndex(DropIndexData { idxname }))
+        } else {
+            Err(self.lex.expected("TABLE, VIEW or INDEX"))
+        }
+    }
+
+    fn alter_table(&mut self) -> Result<AlterTableData, SQLException> {
+        self.lex.eat_keyword("alter")?;
+        self.lex.eat_keyword("table")?;
+        let tblname = self.lex.eat_id()?;
+        let action = if self.lex.match_keyword("add") {
+            self.lex.eat_keyword("add")?;
+            self.optional_column()?;
+            let fldname = self.field()?;
+            let (ctype, length) = self.field_type()?;
+            let mut default = None;
+            if self.lex.match_keyword("default") {
+                self.lex.eat_keyword("default")?;
+                default = Some(self.constant()?);
+            }
+            AlterAction::AddColumn {
+                fldname,
+                ctype,
+                length,
+                default,
+            }
+        } else if self.lex.match_keyword("drop") {
+            self.lex.eat_keyword("drop")?;
+            self.optional_column()?;
+            AlterAction::DropColumn(self.field()?)
+        } else if self.lex.match_keyword("rename") {
+            self.lex.eat_keyword("rename")?;
+            if self.lex.match_keyword("to") {
+                self.lex.eat_keyword("to")?;
+                AlterAction::RenameTable(self.lex.eat_id()?)
+            } else {
+                self.optional_column()?;
+                let old = self.field()?;
+                self.lex.eat_keyword("to")?;
+                AlterAction::RenameColumn {
+                    old,
+                    new: self.field()?,
+                }
+            }
+        } else {
+            return Err(self.lex.expected("ADD, DROP or RENAME"));
+        };
+        Ok(AlterTableData { tblname, action })
+    }
+
+    fn optional_column(&mut self) -> Result<(), SQLException> {
+        if self.lex.match_keyword("column") {
+            self.lex.eat_keyword("column")?;
+        }
+        Ok(())
+    }
+
+    fn rename_table(&mut self) -> Result<AlterTableData, SQLException> {
+        self.lex.eat_keyword("rename")?;
+        self.lex.eat_keyword("table")?;
+        let tblname = self.lex.eat_id()?;
+        self.lex.eat_keyword("to")?;
+        let action = AlterAction::RenameTable(self.lex.eat_id()?);
+        Ok(AlterTableData { tblname, action })
+    }
+
+    fn analyze(&mut self) -> Result<AnalyzeData, SQLException> {
+        self.lex.eat_keyword("analyze")?;
+        if self.lex.match_id() {
+            let tblname = Some(self.lex.eat_id()?);
+            return Ok(AnalyzeData { tblname });
+        }
+        Ok(AnalyzeData { tblname: None })
+    }
+
+    /// Fails unless every token was used, allowing for a final `;`.
+    fn end_statement(&mut self) -> Result<(), SQLException> {
+        if self.lex.match_delim(";") {
+            self.lex.eat_delim(";")?;
+        }
+        self.end()
+    }
+}
+
+/// Parses text that holds nothing but an expression.
+/// Of two ways a parse failed, the one that got further.
+fn further(e1: SQLException, e2: SQLException) -> SQLException {
+    match (&e1, &e2) {
+        (
+            SQLException::BadSyntax {
+                line: l1,
+                column: c1,
+                ..
+            },
+            SQLException::BadSyntax {
+                line: l2,
+                column: c2,
+                ..
+            },
+        ) if (l1, c1) > (l2, c2) => e1,
+        _ => e2,
+    }
+}
+
+pub(crate) fn parse_expression(text: &str) -> Result<Expression, SQLException> {
+    let mut parser = Parser::new(text)?;
+    let expression = parser.expression()?;
+    parser.end()?;
+    Ok(expression)
+}
+
+/// Parses the text of a SELECT statement.
+pub(crate) fn parse_query(text: &str) -> Result<QueryData, SQLException> {
+    let mut parser = Parser::new(text)?;
+    let query = parser.query()?;
+    parser.end_statement()?;
+    Ok(query)
+}
+
+/// Parses the text of any statement but a query.
+pub(crate) fn parse_command(text: &str) -> Result<Command, SQLException> {
+    let mut parser = Parser::new(text)?;
+    let command = parser.update_cmd()?;
+    parser.end_statement()?;
+    Ok(command)
+}
+
+/// Parses text that holds nothing but a predicate.
+pub(crate) fn parse_predicate(text: &str) -> Result<Predicate, SQLException> {
+    let mut parser = Parser::new(text)?;
+    let predicate = parser.predicate()?;
+    parser.end()?;
+    Ok(predicate)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{
+        aggregate::{AggregateFn, AggregateKind},
+        command::{AlterAction, Command, DropIndexData, DropTableData, DropViewData},
+        constant::Constant,
+        constraint::{Constraint, ConstraintKind, OnDelete, Reference},
+        expression::Expression,
+        index::IndexType,
+        query_data::{FromItem, Join, JoinKind, SetOp, TableRef},
+        record_comparator::SortKey,
+        resultset::ColumnType,
+        sql_exception::SQLException,
+    };
+
+    use super::{parse_command, parse_expression, parse_predicate, parse_query};
+
+    #[test]
+    fn test_parse_predicate() {
+        let predicate = parse_predicate("A >= -3 AND\n  B <> 'it''s' and 5 = C").unwrap();
+        assert_eq!(predicate.to_string(), "A >= -3 and B <> 'it''s' and 5 = C");
+        assert_eq!(parse_predicate(&predicate.to_string()).unwrap(), predicate);
+        assert_eq!(predicate.fields(), ["A", "B", "C"]);
+
+        let row = [
+            ("A".to_string(), Constant::Int(0)),
+            ("B".to_string(), Constant::Str("x".to_string())),
+            ("C".to_string(), Constant::Int(5)),
+        ]
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+        assert_eq!(predicate.evaluate(&mut &row).unwrap(), Some(true));
+        let mut row = row;
+        row.insert("C".to_string(), Constant::Null);
+        assert_eq!(predicate.evaluate(&mut &row).unwrap(), None);
+        row.insert("A".to_string(), Constant::Int(-4));
+        assert_eq!(predicate.evaluate(&mut &row).unwrap(), Some(false));
+
+        assert_eq!(
+            parse_expression("NULL").unwrap().to_string(),
+            "NULL".to_string()
+        );
+        assert_eq!(
+            parse_expression("x'00fF1a'").unwrap(),
+            Expression::Constant(Constant::Bytes(vec![0, 255, 26]))
+        );
+        assert_eq!(
+            parse_expression("X'00ff1a'").unwrap().to_string(),
+            "X'00ff1a'"
+        );
+        for (text, line, column) in [
+            ("A = 1 and\n  B", 2, 4),
+            ("A = 1 B = 2", 1, 7),
+            ("A = 'open", 1, 5),
+            ("A # 1", 1, 3),
+            ("A = X'abc'", 1, 5),
+        ] {
+            match parse_predicate(text).unwrap_err() {
+                SQLException::BadSyntax {
+                    line: l, column: c, ..
+                } => assert_eq!((l, c), (line, column), "{}", text),
+                e => panic!("unexpected error {}", e),
+            }
+        }
+        assert_eq!(
+            parse_predicate("A = ").unwrap_err().to_string(),
+            "syntax error at line 1, column 5: expected a constant, found end of input"
+        );
+    }
+
+    #[test]
+    fn test_parse_conditions() {
+        let predicate = parse_predicate(
+            "A between 1 and 5 and (B in (1, 2, NULL) or not (C like 'a_c%' and A <> 2))",
+        )
+        .unwrap();
+        assert_eq!(
+            predicate.to_string(),
+            "A between 1 and 5 and (B in (1, 2, NULL) or not (C like 'a_c%' and A <> 2))"
+        );
+        assert_eq!(parse_predicate(&predicate.to_string()).unwrap(), predicate);
+        assert_eq!(predicate.terms().len(), 2);
+        assert_eq!(predicate.fields(), ["A", "B", "C"]);
+        let spliced = parse_predicate("(A = 1 and (B = 2)) and C = 3").unwrap();
+        assert_eq!(spliced.terms().len(), 3);
+        assert_eq!(
+            parse_predicate("A = 1 or B = 2 and C = 3")
+                .unwrap()
+                .to_string(),
+            "(A = 1 or B = 2 and C = 3)"
+        );
+        assert_eq!(
+            parse_predicate("not A not in (1) or A not like 'x' and A not between 1 and 2")
+                .unwrap()
+                .to_string(),
+            "(not (A not in (1)) or A not like 'x' and A not between 1 and 2)"
+        );
+
+        let row = |a: Constant, b: Constant, c: &str| {
+            [
+                ("A".to_string(), a),
+                ("B".to_string(), b),
+                ("C".to_string(), Constant::Str(c.to_string())),
+            ]
+            .into_iter()
+            .collect::<HashMap<_, _>>()
+        };
+        for (a, b, c, expected) in [
+            (3, 2, "x", Some(true)),
+            (6, 2, "x", Some(false)),
+            (3, 7, "x", Some(true)),
+            (2, 7, "x", Some(true)),
+            (3, 7, "abc", None),
+            (3, 7, "abcdef", None),
+            (3, 7, "ab", Some(true)),
+            (2, 7, "abc", Some(true)),
+        ] {
+            let r = row(Constant::Int(a), Constant::Int(b), c);
+            assert_eq!(
+                predicate.evaluate(&mut &r).unwrap(),
+                expected,
+                "{} {} {}",
+                a,
+                b,
+                c
+            );
+        }
+        let r = row(Constant::Null, Constant::Int(1), "x");
+        assert_eq!(predicate.evaluate(&mut &r).unwrap(), None);
+
+        for (pattern, text, expected) in [
+            ("%", "", true),
+            ("a%b%c", "aXbYbZc", true),
+            ("a%b%c", "aXbYbZ", false),
+            ("_%_", "a", false),
+            ("%ab", "aab", true),
+        ] {
+            let term = format!("'{}' like '{}'", text, pattern);
+            let p = parse_predicate(&term).unwrap();
+            assert_eq!(
+                p.evaluate(&mut &HashMap::new()).unwrap(),
+                Some(expected),
+                "{}",
+                term
+            );
+        }
+        assert!(parse_predicate("1 like 'x'")
+            .unwrap()
+            .evaluate(&mut &HashMap::new())
+            .is_err());
+        for (text, line, column) in [("A not = 1", 1, 7), ("(A = 1", 1, 7), ("A in ()", 1, 7)] {
+            match parse_predicate(text).unwrap_err() {
+                SQLException::BadSyntax {
+                    line: l, column: c, ..
+                } => assert_eq!((l, c), (line, column), "{}", text),
+                e => panic!("unexpected error {}", e),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_expressions() {
+        let row = [("A".to_string(), Constant::Int(7))]
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        for (text, shown, value) in [
+            ("1 + 2 * 3", "1 + 2 * 3", Constant::Int(7)),
+            ("(1 + 2) * 3", "(1 + 2) * 3", Constant::Int(9)),
+            ("10 - (4 - 3)", "10 - (4 - 3)", Constant::Int(9)),
+            ("10 - 4 - 3", "10 - 4 - 3", Constant::Int(3)),
+            ("A % 4 * -(A)", "A % 4 * -A", Constant::Int(-21)),
+            ("- -A / 2", "-(-A) / 2", Constant::Int(3)),
+            ("-(A + 1)", "-(A + 1)", Constant::Int(-8)),
+            ("A - -3", "A - -3", Constant::Int(10)),
+            ("A + NULL", "A + NULL", Constant::Null),
+            (
+                "CASE WHEN A > 5 AND A < 7 THEN 'a' WHEN A in (7) THEN 'b' END",
+                "case when A > 5 and A < 7 then 'a' when A in (7) then 'b' end",
+                Constant::Str("b".to_string()),
+            ),
+            (
+                "case when A = 1 then 1 else A * 2 end + 1",
+                "case when A = 1 then 1 else A * 2 end + 1",
+                Constant::Int(15),
+            ),
+        ] {
+            let expr = parse_expression(text).unwrap();
+            assert_eq!(expr.to_string(), shown);
+            assert_eq!(parse_expression(shown).unwrap(), expr, "{}", shown);
+            assert_eq!(expr.evaluate(&mut &row).unwrap(), value, "{}", text);
+        }
+        for text in [
+            "A / 0",
+            "A % (A - 7)",
+            "'a' + 1",
+            "-'a'",
+            "9223372036854775807 + A",
+        ] {
+            let expr = parse_expression(text).unwrap();
+            assert!(expr.evaluate(&mut &row).is_err(), "{}", text);
+        }
+
+        // A parenthesis may open an expression or a predicate
+        for (text, shown, value) in [
+            ("(A + 1) * 2 > 15", "(A + 1) * 2 > 15", Some(true)),
+            (
+                "(A) = 7 and (A = 1 or A = 7)",
+                "A = 7 and (A = 1 or A = 7)",
+                Some(true),
+            ),
+            (
+                "((A = 1)) or not (A - 7) = 0",
+                "(A = 1 or not (A - 7 = 0))",
+                Some(false),
+            ),
+        ] {
+            let predicate = parse_predicate(text).unwrap();
+            assert_eq!(predicate.to_string(), shown);
+            assert_eq!(predicate.evaluate(&mut &row).unwrap(), value, "{}", text);
+        }
+        for (text, column) in [("(A + 1 = 2", 11), ("(A + ) = 2", 6), ("case A end", 6)] {
+            match parse_predicate(text).unwrap_err() {
+                SQLException::BadSyntax { column: c, .. } => assert_eq!(c, column, "{}", text),
+                e => panic!("unexpected error {}", e),
+            }
+        }
+
+        let query = parse_query(
+            "select A + 1 as B, A * 2 C, sum(S) * 2, D from T group by A, D order by C desc, D",
+        )
+        .unwrap();
+        assert_eq!(query.field_names(), ["B", "C", "sum(S) * 2", "D"]);
+        assert_eq!(
+            query.to_string(),
+            "select A + 1 as B, A * 2 as C, sum(S) * 2, D from T group by A, D order by C desc, D"
+        );
+        assert_eq!(parse_query(&query.to_string()).unwrap(), query);
+        assert_eq!(
+            parse_query("select A + S from T group by A")
+                .unwrap_err()
+                .to_string(),
+            "syntax error at line 1, column 31: field S is neither grouped nor aggregated"
+        );
+    }
+
+    #[test]
+    fn test_parse_subqueries() {
+        let text = "select N, (select max(B) from DEPT d where d.ID = e.DID) as M \
+                    from EMP e, (select ID from DEPT where B > 5) AS big \
+                    where exists (select ID from DEPT where ID = e.DID) \
+                    and not exists (select ID from DEPT) \
+                    and e.DID not in (select ID from DEPT) \
+                    and (e.S > (select 1 from DEPT) + 1 or e.ID in (1, 2))";
+        let query = parse_query(text).unwrap();
+        assert_eq!(
+            query.to_string(),
+            "select N, (select max(B) from DEPT d where d.ID = e.DID) as M \
+             from EMP e, (select ID from DEPT where B > 5) big \
+             where exists (select ID from DEPT where ID = e.DID) \
+             and not (exists (select ID from DEPT)) \
+             and e.DID not in (select ID from DEPT) \
+             and (e.S > (select 1 from DEPT) + 1 or e.ID in (1, 2))"
+        );
+        assert_eq!(parse_query(&query.to_string()).unwrap(), query);
+        assert!(query.predicate.has_subquery());
+        assert_eq!(query.aggregates, []);
+        let derived = &query.from[1].table;
+        assert_eq!(derived.range_name(), "big");
+        assert_eq!(
+            derived.subquery.as_ref().unwrap().to_string(),
+            "select ID from DEPT where B > 5"
+        );
+
+        // Aggregates of a subquery are its own
+        let query = parse_query(
+            "select D from EMP group by D having count(*) > (select count(*) from DEPT)",
+        )
+        .unwrap();
+        assert_eq!(query.aggregates.len(), 1);
+        for (text, column) in [
+            ("select A from (select A from T)", 32),
+            ("select A from T where exists T", 30),
+            ("select A from T where A in (select B from U", 44),
+        ] {
+            match parse_query(text).unwrap_err() {
+                SQLException::BadSyntax { column: c, .. } => assert_eq!(c, column, "{}", text),
+                e => panic!("unexpected error {}", e),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_set_operations() {
+        let text = "select distinct A, B from T where A > 1 \
+                    UNION select C, D from U \
+                    union all select C, count(*) from U group by C \
+                    INTERSECT select A, B from T \
+                    except select distinct E, F from V order by B desc, A";
+        let query = parse_query(text).unwrap();
+        assert_eq!(
+            query.to_string(),
+            "select distinct A, B from T where A > 1 \
+             union select C, D from U \
+             union all select C, count(*) from U group by C \
+             intersect select A, B from T \
+             except select distinct E, F from V order by B desc, A"
+        );
+        assert_eq!(parse_query(&query.to_string()).unwrap(), query);
+        assert!(query.distinct);
+        let ops = query.compound.iter().map(|(op, _)| *op).collect::<Vec<_>>();
+        assert_eq!(
+            ops,
+            [
+                SetOp::Union,
+                SetOp::UnionAll,
+                SetOp::Intersect,
+                SetOp::Except
+            ]
+        );
+        // The sort keys are the whole query's, the aggregates each part's
+        assert!(query
+            .compound
+            .iter()
+            .all(|(_, part)| part.order_by.is_empty()));
+        assert_eq!(query.aggregates, []);
+        assert_eq!(query.compound[1].1.aggregates.len(), 1);
+
+        // LIMIT and OFFSET apply to the whole query too
+        let query =
+            parse_query("select A from T union select B from U order by A limit 10 offset 20")
+                .unwrap();
+        assert_eq!((query.limit, query.offset), (Some(10), 20));
+        assert_eq!(query.compound[0].1.limit, None);
+        assert_eq!(parse_query(&query.to_string()).unwrap(), query);
+        let query = parse_query("select A from T limit 0").unwrap();
+        assert_eq!(query.to_string(), "select A from T limit 0");
+
+        for (text, message) in [
+            (
+                "select A from T limit -1",
+                "column 23: expected an integer, found '-'",
+            ),
+            (
+                "select A from T offset 1",
+                "column 17: expected end of input, found OFFSET",
+            ),
+            (
+                "select A from T union select A, B from U",
+                "column 41: UNION of queries with 1 and 2 fields",
+            ),
+            (
+                "select A, count(*) from T union select A, B from U",
+                "column 27: field A is neither grouped nor aggregated",
+            ),
+            (
+                "select A from T union select A from U order by count(*)",
+                "aggregate COUNT is not allowed here",
+            ),
+            (
+                "select A from T order by A union select A from U",
+                "column 28: expected end of input, found UNION",
+            ),
+        ] {
+            let error = parse_query(text).unwrap_err().to_string();
+            assert!(error.ends_with(message), "{}: {}", text, error);
+        }
+    }
+
+    #[test]
+    fn test_parse_statements() {
+        let query = parse_query("SELECT A, B\nFROM T1, T2 WHERE A = C and B = 'x';").unwrap();
+        assert_eq!(query.field_names(), ["A", "B"]);
+        assert_eq!(
+            query.from,
+            [
+                FromItem {
+                    table: TableRef::new("T1", None),
+                    joins: Vec::new()
+                },
+                FromItem {
+                    table: TableRef::new("T2", None),
+                    joins: Vec::new()
+                }
+            ]
+        );
+        assert_eq!(query.predicate.to_string(), "A = C and B = 'x'");
+        assert_eq!(
+            query.to_string(),
+            "select A, B from T1, T2 where A = C and B = 'x'"
+        );
+        assert_eq!(parse_query(&query.to_string()).unwrap(), query);
+        assert!(parse_query("select A from T")
+            .unwrap()
+            .predicate
+            .terms()
+            .is_empty());
+
+        let Command::Insert(insert) =
+            parse_command("insert into T (A, B, C) values (1, 'x', NULL)").unwrap()
+        else {
+            panic!("not an insert");
+        };
+        assert_eq!(insert.tblname, "T");
+        assert_eq!(insert.fields, ["A", "B", "C"]);
+        assert_eq!(
+            insert.values,
+            [
+                Constant::Int(1),
+                Constant::Str("x".to_string()),
+                Constant::Null
+            ]
+        );
+
+        let Command::Delete(delete) = parse_command("delete from T where A > 3").unwrap() else {
+            panic!("not a delete");
+        };
+        assert_eq!(delete.tblname, "T");
+        assert_eq!(delete.predicate.to_string(), "A > 3");
+
+        let Command::Modify(modify) =
+            parse_command("update T set A = 4, B = C where A = 3").unwrap()
+        else {
+            panic!("not an update");
+        };
+        assert_eq!(
+            modify.assignments,
+            [
+                ("A".to_string(), Expression::Constant(Constant::Int(4))),
+                ("B".to_string(), Expression::Field("C".to_string()))
+            ]
+        );
+        assert_eq!(modify.predicate.to_string(), "A = 3");
+
+        let Command::CreateTable(create) = parse_command(
+            "create table T (ID int primary key, P int not null references P(ID) on delete \
+             cascade, B varchar(10) default 'b' unique, C text, D blob, \
+             constraint T_ck check (ID > 0 and ID < P), unique (B, C))",
+        )
+        .unwrap() else {
+            panic!("not a create table");
+        };
+        assert_eq!(create.tblname, "T");
+        assert_eq!(create.schema.fields(), ["ID", "P", "B", "C", "D"]);
+        assert_eq!(create.schema.ctype("B"), ColumnType::VarChar);
+        assert_eq!(create.schema.length("B"), 10);
+        assert_eq!(create.schema.ctype("C"), ColumnType::Text);
+        assert_eq!(create.schema.ctype("D"), ColumnType::Blob);
+        assert_eq!(
+            create.defaults,
+            [(
+                "B".to_string(),
+                Expression::Constant(Constant::Str("b".to_string()))
+            )]
+        );
+        assert_eq!(
+            create.constraints,
+            [
+                Constraint::new("", ConstraintKind::PrimaryKey, &["ID"]),
+                Constraint::new("", ConstraintKind::NotNull, &["P"]),
+                Constraint::new(
+                    "",
+                    ConstraintKind::ForeignKey(Reference::new("P", &["ID"], OnDelete::Cascade)),
+                    &["P"]
+                ),
+                Constraint::new("", ConstraintKind::Unique, &["B"]),
+                Constraint::check("T_ck", &parse_predicate("ID > 0 and ID < P").unwrap()),
+                Constraint::new("", ConstraintKind::Unique, &["B", "C"]),
+            ]
+        );
+
+        let Command::CreateView(view) =
+            parse_command("create view V as select A from T where A = 1").unwrap()
+        else {
+            panic!("not a create view");
+        };
+        assert_eq!(view.viewname, "V");
+        assert_eq!(view.view_def(), "select A from T where A = 1");
+
+        let Command::CreateIndex(index) =
+            parse_command("create index I on T (A) using HASH").unwrap()
+        else {
+            panic!("not a create index");
+        };
+        assert_eq!(
+            (
+                index.idxname.as_str(),
+                index.tblname.as_str(),
+                index.fldname.as_str()
+            ),
+            ("I", "T", "A")
+        );
+        assert_eq!(index.index_type, IndexType::Hash);
+
+        let tblname = "T".to_string();
+        let viewname = "V".to_string();
+        let idxname = "I".to_string();
+        for (text, command) in [
+            (
+                "drop table T",
+                Command::DropTable(DropTableData { tblname }),
+            ),
+            ("drop view V;", Command::DropView(DropViewData { viewname })),
+            (
+                "DROP INDEX I",
+                Command::DropIndex(DropIndexData { idxname }),
+            ),
+        ] {
+            assert_eq!(parse_command(text).unwrap(), command);
+        }
+
+        for (text, action) in [
+            (
+                "alter table T add column C varchar(5) default 'c'",
+                AlterAction::AddColumn {
+                    fldname: "C".to_string(),
+                    ctype: ColumnType::VarChar,
+                    length: 5,
+                    default: Some(Constant::Str("c".to_string())),
+                },
+            ),
+            (
+                "alter table T add D int",
+                AlterAction::AddColumn {
+                    fldname: "D".to_string(),
+                    ctype: ColumnType::Integer,
+                    length: 0,
+                    default: None,
+                },
+            ),
+            (
+                "alter table T drop column C",
+                AlterAction::DropColumn("C".to_string()),
+            ),
+            (
+                "alter table T rename C to E",
+                AlterAction::RenameColumn {
+                    old: "C".to_string(),
+                    new: "E".to_string(),
+                },
+            ),
+            (
+                "alter table T rename to U",
+                AlterAction::RenameTable("U".to_string()),
+            ),
+            (
+                "rename table T to U",
+                AlterAction::RenameTable("U".to_string()),
+            ),
+        ] {
+            let Command::AlterTable(alter) = parse_command(text).unwrap() else {
+                panic!("not an alter table");
+            };
+            assert_eq!(alter.tblname, "T");
+            assert_eq!(alter.action, action, "{}", text);
+        }
+
+        let Command::Analyze(analyze) = parse_command("analyze T").unwrap() else {
+            panic!("not an analyze");
+        };
+        assert_eq!(analyze.tblname.as_deref(), Some("T"));
+        let Command::Analyze(analyze) = parse_command("ANALYZE;").unwrap() else {
+            panic!("not an analyze");
+        };
+        assert_eq!(analyze.tblname, None);
+
+        for (text, message) in [
+            (
+                "select A from T where",
+                "syntax error at line 1, column 22: expected a constant, found end of input",
+            ),
+            (
+                "select A\nfrom where",
+                "syntax error at line 2, column 6: expected a name, found WHERE",
+            ),
+            (
+                "insert into T (A, B) values (1)",
+                "syntax error at line 1, column 31: 1 values for 2 fields",
+            ),
+            (
+                "create table T (A int, A int)",
+                "syntax error at line 1, column 26: field A is declared twice",
+            ),
+            (
+                "create index I on T (A) using btree",
+                "syntax error at line 1, column 36: unknown index type btree",
+            ),
+            (
+                "grant select on T",
+                "syntax error at line 1, column 1: expected INSERT, DELETE, UPDATE, CREATE, \
+                 DROP, ALTER, RENAME or ANALYZE, found grant",
+            ),
+            (
+                "drop T",
+                "syntax error at line 1, column 6: expected TABLE, VIEW or INDEX, found T",
+            ),
+            (
+                "alter table T modify A int",
+                "syntax error at line 1, column 15: expected ADD, DROP or RENAME, found modify",
+            ),
+            (
+                "select A from T; select",
+                "syntax error at line 1, column 18: expected end of input, found SELECT",
+            ),
+        ] {
+            assert_eq!(parse_command_or_query(text), message, "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_parse_grouping() {
+        let query = parse_query(
+            "select D, count(*), SUM(S) from T where S > 0 group by D \
+             having count(*) > 1 and max(S) < 100 order by sum(S) desc, D asc",
+        )
+        .unwrap();
+        assert_eq!(query.field_names(), ["D", "count(*)", "sum(S)"]);
+        assert_eq!(query.group_by, ["D"]);
+        assert_eq!(
+            query.aggregates,
+            [
+                AggregateFn::new(AggregateKind::Count, None),
+                AggregateFn::new(AggregateKind::Sum, Some("S")),
+                AggregateFn::new(AggregateKind::Max, Some("S")),
+            ]
+        );
+        assert_eq!(query.having.to_string(), "count(*) > 1 and max(S) < 100");
+        assert_eq!(
+            query.order_by,
+            [SortKey::new("sum(S)", true), SortKey::new("D", false)]
+        );
+        assert_eq!(
+            query.to_string(),
+            "select D, count(*), sum(S) from T where S > 0 group by D \
+             having count(*) > 1 and max(S) < 100 order by sum(S) desc, D"
+        );
+        assert_eq!(parse_query(&query.to_string()).unwrap(), query);
+        assert!(query.is_grouped());
+        let ordered = parse_query("select A, B from T order by B desc").unwrap();
+        assert!(!ordered.is_grouped());
+
+        for (text, message) in [
+            (
+                "select A from T where count(A) > 1",
+                "syntax error at line 1, column 23: aggregate COUNT is not allowed here",
+            ),
+            (
+                "select A, count(B) from T",
+                "syntax error at line 1, column 26: field A is neither grouped nor \
+                 aggregated",
+            ),
+            (
+                "select A from T group by A order by B",
+                "syntax error at line 1, column 38: field B is neither grouped nor \
+                 aggregated",
+            ),
+            (
+                "select sum(*) from T",
+                "syntax error at line 1, column 12: expected a name, found '*'",
+            ),
+        ] {
+            assert_eq!(parse_command_or_query(text), message, "{}", text);
+        }
+        assert!(parse_predicate("count(A) = 1").is_err());
+    }
+
+    #[test]
+    fn test_parse_joins() {
+        let query = parse_query(
+            "select e.N, d.N, T.A from EMP as e join DEPT d on e.D = d.ID, T \
+             left outer join U on T.A = U.A and U.B > 1 right join V on V.C = T.C \
+             full join W w on w.D = T.D inner join X on X.E = T.E where e.S > 10",
+        )
+        .unwrap();
+        assert_eq!(query.field_names(), ["e.N", "d.N", "T.A"]);
+        assert_eq!(query.from.len(), 2);
+        assert_eq!(query.from[0].table, TableRef::new("EMP", Some("e")));
+        assert_eq!(
+            query.from[0].joins[0],
+            Join {
+                kind: JoinKind::Inner,
+                table: TableRef::new("DEPT", Some("d")),
+                on: parse_predicate("e.D = d.ID").unwrap(),
+            }
+        );
+        assert_eq!(
+            query.from[1]
+                .joins
+                .iter()
+                .map(|j| (j.kind, j.table.range_name()))
+                .collect::<Vec<_>>(),
+            [
+                (JoinKind::Left, "U"),
+                (JoinKind::Right, "V"),
+                (JoinKind::Full, "w"),
+                (JoinKind::Inner, "X")
+            ]
+        );
+        assert_eq!(
+            query
+                .tables()
+                .iter()
+                .map(|t| t.tblname.as_str())
+                .collect::<Vec<_>>(),
+            ["EMP", "DEPT", "T", "U", "V", "W", "X"]
+        );
+        assert_eq!(
+            query.to_string(),
+            "select e.N, d.N, T.A from EMP e join DEPT d on e.D = d.ID, T \
+             left join U on T.A = U.A and U.B > 1 right join V on V.C = T.C \
+             full join W w on w.D = T.D join X on X.E = T.E where e.S > 10"
+        );
+        assert_eq!(parse_query(&query.to_string()).unwrap(), query);
+
+        assert_eq!(
+            parse_command_or_query("select A from T left U on T.A = U.A"),
+            "syntax error at line 1, column 22: expected JOIN, found U"
+        );
+        assert_eq!(
+            parse_command_or_query("select A from T join U where T.A = U.A"),
+            "syntax error at line 1, column 24: expected ON, found WHERE"
+        );
+        assert_eq!(
+            parse_command_or_query("select count(T.A) from T join U on count(A) > 1"),
+            "syntax error at line 1, column 36: aggregate COUNT is not allowed here"
+        );
+    }
+
+    fn parse_command_or_query(text: &str) -> String {
+        if text.starts_with("select") {
+            parse_query(text).unwrap_err().to_string()
+        } else {
+            parse_command(text).unwrap_err().to_string()
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use std::rc::Rc;
+
+use crate::{constant::Constant, scan::Scan, schema::Schema, transaction::Transaction};
+
+/// A node of a query plan, which knows what its scan would cost and
+/// produce without opening it.
+pub(crate) trait Plan {
+    fn open(&self, tx: &mut Transaction) -> Result<Box<dyn Scan>, std::io::Error>;
+    fn blocks_accessed(&self) -> u64;
+    fn records_output(&self) -> u64;
+    fn distinct_values(&self, fldname: &str) -> u64;
+    fn schema(&self) -> &Schema;
+
+    /// Estimated fraction of the output where the field equals `value`.
+    fn eq_selectivity(&self, fldname: &str, _value: &Constant) -> f64 {
+        1.0 / self.distinct_values(fldname).max(1) as f64
+    }
+
+    /// Estimated fraction of the output where the field lies between the
+    /// bounds, a missing bound leaving that side open.
+    fn range_selectivity(
+        &self,
+        _fldname: &str,
+        _low: Option<&Constant>,
+        _high: Option<&Constant>,
+    ) -> f64 {
+        1.0 / 3.0
+    }
+}
+
+impl<P: Plan + ?Sized> Plan for Rc<P> {
+    fn open(&self, tx: &mut Transaction) -> Result<Box<dyn Scan>, std::io::Error> {
+        (**self).open(tx)
+    }
+
+    fn blocks_accessed(&self) -> u64 {
+        (**self).blocks_accessed()
+    }
+
+    fn records_output(&self) -> u64 {
+        (**self).records_output()
+    }
+
+    fn distinct_values(&self, fldname: &str) -> u64 {
+        (**self).distinct_values(fldname)
+    }
+
+    fn schema(&self) -> &Schema {
+        (**self).schema()
+    }
+
+    fn eq_selectivity(&self, fldname: &str, value: &Constant) -> f64 {
+        (**self).eq_selectivity(fldname, value)
+    }
+
+    fn range_selectivity(
+        &self,
+        fldname: &str,
+        low: Option<&Constant>,
+        high: Option<&Constant>,
+    ) -> f64 {
+        (**self).range_selectivity(fldname, low, high)
+    }
+}
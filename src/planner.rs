@@ -0,0 +1,666 @@
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{
+    alias_plan::AliasPlan,
+    command::{
+        AlterTableData, AnalyzeData, Command, CreateIndexData, CreateTableData, CreateViewData,
+        DeleteData, DropIndexData, DropTableData, DropViewData, InsertData, ModifyData,
+    },
+    constant::Constant,
+    decorrelate::{decorrelate, outer_fields},
+    distinct_plan::DistinctPlan,
+    expression::Expression,
+    extend_plan::ExtendPlan,
+    group_by_plan::GroupByPlan,
+    index_manager::IndexInfo,
+    layout::Layout,
+    limit_plan::LimitPlan,
+    metadata_manager::MetadataManager,
+    parser::parse_query,
+    plan::Plan,
+    predicate::Predicate,
+    project_plan::ProjectPlan,
+    query_data::{FromItem, QueryData, SetOp, TableRef},
+    record_comparator::SortKey,
+    schema::Schema,
+    select_plan::SelectPlan,
+    set_op_plan::SetOpPlan,
+    sort_plan::SortPlan,
+    sql_exception::SQLException,
+    stat_manager::StatInfo,
+    subquery_plan::SubqueryPlan,
+    subquery_scan::SubqueryRunner,
+    table_plan::TablePlan,
+    top_n_plan::TopNPlan,
+    transaction::Transaction,
+    union_plan::UnionPlan,
+};
+
+/// Chooses how to get the records of a FROM clause, which is where planners
+/// differ. The rest of a query is planned the same way by [`plan_query`].
+pub(crate) trait QueryPlanner {
+    /// A plan for the records of the FROM items, joined, that satisfy the
+    /// predicate.
+    fn plan_from(
+        &self,
+        from: &[FromItem],
+        predicate: &Predicate,
+        ctx: &PlanContext,
+    ) -> Result<Box<dyn Plan>, std::io::Error>;
+}
+
+/// Runs the statements other than queries.
+pub(crate) trait UpdatePlanner {
+    fn execute_insert(
+        &self,
+        data: &InsertData,
+        ctx: &PlanContext,
+        tx: &mut Transaction,
+        mdm: &mut MetadataManager,
+    ) -> Result<u64, SQLException>;
+
+    fn execute_delete(
+        &self,
+        data: &DeleteData,
+        ctx: &PlanContext,
+        tx: &mut Transaction,
+        mdm: &mut MetadataManager,
+    ) -> Result<u64, SQLException>;
+
+    fn execute_modify(
+        &self,
+        data: &ModifyData,
+        ctx: &PlanContext,
+        tx: &mut Transaction,
+        mdm: &mut MetadataManager,
+    ) -> Result<u64, SQLException>;
+
+    fn execute_create_table(
+        &self,
+        data: &CreateTableData,
+        ctx: &PlanContext,
+        tx: &mut Transaction,
+        mdm: &mut MetadataManager,
+    ) -> Result<u64, SQLException>;
+
+    fn execute_create_view(
+        &self,
+        data: &CreateViewData,
+        ctx: &PlanContext,
+        tx: &mut Transaction,
+        mdm: &mut MetadataManager,
+    ) -> Result<u64, SQLException>;
+
+    fn execute_create_index(
+        &self,
+        data: &CreateIndexData,
+        ctx: &PlanContext,
+        tx: &mut Transaction,
+        mdm: &mut MetadataManager,
+    ) -> Result<u64, SQLException>;
+    fn execute_drop_table(
+        &self,
+        data: &DropTableData,
+        ctx: &PlanContext,
+        tx: &mut Transaction,
+        mdm: &mut MetadataManager,
+    ) -> Result<u64, SQLException>;
+
+    fn execute_drop_view(
+        &self,
+        data: &DropViewData,
+        ctx: &PlanContext,
+        tx: &mut Transaction,
+        mdm: &mut MetadataManager,
+    ) -> Result<u64, SQLException>;
+
+    fn execute_drop_index(
+        &self,
+        data: &DropIndexData,
+        ctx: &PlanContext,
+        tx: &mut Transaction,
+        mdm: &mut MetadataManager,
+    ) -> Result<u64, SQLException>;
+
+    fn execute_alter_table(
+        &self,
+        data: &AlterTableData,
+        ctx: &PlanContext,
+        tx: &mut Transaction,
+        mdm: &mut MetadataManager,
+    ) -> Result<u64, SQLException>;
+
+    fn execute_analyze(
+        &self,
+        data: &AnalyzeData,
+        ctx: &PlanContext,
+        tx: &mut Transaction,
+        mdm: &mut MetadataManager,
+    ) -> Result<u64, SQLException>;
+}
+
+/// Plans queries and runs statements with the given planners.
+pub(crate) struct Planner {
+    query_planner: Rc<dyn QueryPlanner>,
+    update_planner: Box<dyn UpdatePlanner>,
+}
+
+impl Planner {
+    pub(crate) fn new(
+        query_planner: Rc<dyn QueryPlanner>,
+        update_planner: Box<dyn UpdatePlanner>,
+    ) -> Self {
+        Self {
+            query_planner,
+            update_planner,
+        }
+    }
+
+    pub(crate) fn set_query_planner(&mut self, query_planner: Rc<dyn QueryPlanner>) {
+        self.query_planner = query_planner;
+    }
+
+    /// A plan whose records have the fields of the select items, named
+    /// after them.
+    pub(crate) fn create_query_plan(
+        &self,
+        query: &QueryData,
+        tx: &mut Transaction,
+        mdm: &mut MetadataManager,
+    ) -> Result<Box<dyn Plan>, SQLException> {
+        let ctx = self.context(&[query], tx, mdm)?;
+        Ok(plan_query(query, &ctx)?)
+    }
+
+    /// Runs the command, giving the number of records it changed.
+    pub(crate) fn execute_update(
+        &self,
+        command: &Command,
+        tx: &mut Transaction,
+        mdm: &mut MetadataManager,
+    ) -> Result<u64, SQLException> {
+        let planner = self.update_planner.as_ref();
+        match command {
+            Command::Insert(data) => {
+                let ctx = self.context(&[], tx, mdm)?;
+                planner.execute_insert(data, &ctx, tx, mdm)
+            }
+            Command::Delete(data) => {
+                let ctx = self.context(&data.predicate.subqueries(), tx, mdm)?;
+                planner.execute_delete(data, &ctx, tx, mdm)
+            }
+            Command::Modify(data) => {
+                let mut queries = data.predicate.subqueries();
+                for (_, expr) in &data.assignments {
+                    expr.collect_subqueries(&mut queries);
+                }
+                let ctx = self.context(&queries, tx, mdm)?;
+                planner.execute_modify(data, &ctx, tx, mdm)
+            }
+            Command::CreateTable(data) => {
+                let ctx = self.context(&[], tx, mdm)?;
+                planner.execute_create_table(data, &ctx, tx, mdm)
+            }
+            Command::CreateView(data) => {
+                let ctx = self.context(&[&data.query], tx, mdm)?;
+                planner.execute_create_view(data, &ctx, tx, mdm)
+            }
+            Command::CreateIndex(data) => {
+                let ctx = self.context(&[], tx, mdm)?;
+                planner.execute_create_index(data, &ctx, tx, mdm)
+            }
+            Command::DropTable(data) => {
+                let ctx = self.context(&[], tx, mdm)?;
+                planner.execute_drop_table(data, &ctx, tx, mdm)
+            }
+            Command::DropView(data) => {
+                let ctx = self.context(&[], tx, mdm)?;
+                planner.execute_drop_view(data, &ctx, tx, mdm)
+            }
+            Command::DropIndex(data) => {
+                let ctx = self.context(&[], tx, mdm)?;
+                planner.execute_drop_index(data, &ctx, tx, mdm)
+            }
+            Command::AlterTable(data) => {
+                let ctx = self.context(&[], tx, mdm)?;
+                planner.execute_alter_table(data, &ctx, tx, mdm)
+            }
+            Command::Analyze(data) => {
+                let ctx = self.context(&[], tx, mdm)?;
+                planner.execute_analyze(data, &ctx, tx, mdm)
+            }
+        }
+    }
+
+    fn context(
+        &self,
+        queries: &[&QueryData],
+        tx: &mut Transaction,
+        mdm: &mut MetadataManager,
+    ) -> Result<PlanContext, SQLException> {
+        Ok(PlanContext {
+            catalog: Rc::new(Catalog::load(queries, tx, mdm)?),
+            planner: self.query_planner.clone(),
+        })
+    }
+}
+
+/// What the planner knows of a table.
+pub(crate) struct TableInfo {
+    pub(crate) layout: Layout,
+    pub(crate) stat_info: StatInfo,
+    /// The indexes on the table, keyed by the field they index.
+    pub(crate) indexes: HashMap<String, IndexInfo>,
+}
+
+/// The tables and views of a statement's queries as the metadata had them
+/// when planning started. Subqueries are planned again each time they run,
+/// from the same catalog.
+pub(crate) struct Catalog {
+    tables: HashMap<String, TableInfo>,
+    views: HashMap<String, QueryData>,
+    pub(crate) block_size: u64,
+    pub(crate) available_buffers: u64,
+}
+
+impl Catalog {
+    fn load(
+        queries: &[&QueryData],
+        tx: &mut Transaction,
+        mdm: &mut MetadataManager,
+    ) -> Result<Self, SQLException> {
+        let mut catalog = Self {
+            tables: HashMap::new(),
+            views: HashMap::new(),
+            block_size: tx.block_size(),
+            available_buffers: tx.available_buffers(),
+        };
+        for query in queries {
+            catalog.add_query(query, tx, mdm)?;
+        }
+        Ok(catalog)
+    }
+
+    fn add_query(
+        &mut self,
+        query: &QueryData,
+        tx: &mut Transaction,
+        mdm: &mut MetadataManager,
+    ) -> Result<(), SQLException> {
+        for table in query.tables() {
+            let name = &table.tblname;
+            if table.subquery.is_some()
+                || self.tables.contains_key(name)
+                || self.views.contains_key(name)
+            {
+                continue;
+            }
+            if let Some(def) = mdm.get_view_def(name, tx)? {
+                let view = parse_query(&def)?;
+                self.add_query(&view, tx, mdm)?;
+                self.views.insert(name.clone(), view);
+            } else if let Some(layout) = mdm.get_layout(name, tx)? {
+                let stat_info = mdm.get_stat_info(name, &layout, tx)?;
+                let indexes = mdm.get_index_info(name, tx)?;
+                let info = TableInfo {
+                    layout,
+                    stat_info,
+                    indexes,
+                };
+                self.tables.insert(name.clone(), info);
+            } else {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("table {} does not exist", name),
+                )
+                .into());
+            }
+        }
+        for subquery in query.subqueries() {
+            self.add_query(subquery, tx, mdm)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn table(&self, tblname: &str) -> Option<&TableInfo> {
+        self.tables.get(tblname)
+    }
+}
+
+/// What planning a query needs: the catalog, and the planner for FROM
+/// clauses, which subqueries are planned with too.
+#[derive(Clone)]
+pub(crate) struct PlanContext {
+    pub(crate) catalog: Rc<Catalog>,
+    pub(crate) planner: Rc<dyn QueryPlanner>,
+}
+
+impl PlanContext {
+    pub(crate) fn block_size(&self) -> u64 {
+        self.catalog.block_size
+    }
+
+    /// The fields of the table, not qualified by its name.
+    fn schema_of(&self, table: &TableRef) -> Result<Schema, std::io::Error> {
+        if let Some(info) = self.catalog.table(&table.tblname) {
+            if table.subquery.is_none() {
+                return Ok(info.layout.schema().clone());
+            }
+        }
+        Ok(self.plan_unaliased(table)?.schema().clone())
+    }
+
+    fn plan_unaliased(&self, table: &TableRef) -> Result<Box<dyn Plan>, std::io::Error> {
+        if let Some(query) = &table.subquery {
+            return plan_query(query, self);
+        }
+        if let Some(view) = self.catalog.views.get(&table.tblname) {
+            return plan_query(view, self);
+        }
+        match self.catalog.table(&table.tblname) {
+            Some(info) => Ok(Box::new(TablePlan::new(
+                &table.tblname,
+                info.layout.clone(),
+                info.stat_info.clone(),
+            ))),
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("table {} does not exist", table.tblname),
+            )),
+        }
+    }
+
+    /// A plan for the records of a table, view or derived table of the FROM
+    /// clause, whose fields can be qualified by its range name.
+    pub(crate) fn plan_table(&self, table: &TableRef) -> Result<Box<dyn Plan>, std::io::Error> {
+        let plan = self.plan_unaliased(table)?;
+        Ok(Box::new(AliasPlan::new(plan, table.range_name())))
+    }
+
+    /// The plan with its records kept where the predicate holds. The
+    /// subqueries of the predicate run for each record.
+    pub(crate) fn select(
+        &self,
+        plan: Box<dyn Plan>,
+        predicate: &Predicate,
+    ) -> Result<Box<dyn Plan>, std::io::Error> {
+        if predicate.terms().is_empty() {
+            return Ok(plan);
+        }
+        check_fields(&predicate.fields(), plan.schema())?;
+        let plan = self.with_subqueries(plan, &predicate.subqueries())?;
+        Ok(Box::new(SelectPlan::new(plan, predicate.clone())))
+    }
+
+    /// The plan, ready to run the subqueries of the expressions above it.
+    fn with_subqueries(
+        &self,
+        plan: Box<dyn Plan>,
+        subqueries: &[&QueryData],
+    ) -> Result<Box<dyn Plan>, std::io::Error> {
+        if subqueries.is_empty() {
+            return Ok(plan);
+        }
+        for subquery in subqueries {
+            check_fields(&self.outer_fields(subquery)?, plan.schema())?;
+        }
+        Ok(Box::new(SubqueryPlan::new(plan, Rc::new(self.clone()))))
+    }
+}
+
+impl SubqueryRunner for PlanContext {
+    fn outer_fields(&self, query: &QueryData) -> Result<Vec<String>, std::io::Error> {
+        outer_fields(query, &|table| self.schema_of(table))
+    }
+
+    fn run(
+        &self,
+        query: &QueryData,
+        tx: &mut Transaction,
+    ) -> Result<Vec<Constant>, std::io::Error> {
+        let plan = plan_query(query, self)?;
+        let fldname = plan.schema().fields()[0].clone();
+        let mut scan = plan.open(tx)?;
+        let mut values = Vec::new();
+        while scan.next(tx)? {
+            values.push(scan.get_val(tx, &fldname)?);
+        }
+        scan.close(tx);
+        Ok(values)
+    }
+}
+
+/// A plan for the query, whose records have the fields of its select
+/// items. Subqueries that can be are turned into joins first, the FROM
+/// clause is left to the context's planner, and the rest follows the
+/// clauses: grouping, HAVING, the select items, DISTINCT, ORDER BY and
+/// LIMIT.
+pub(crate) fn plan_query(
+    query: &QueryData,
+    ctx: &PlanContext,
+) -> Result<Box<dyn Plan>, std::io::Error> {
+    if !query.compound.is_empty() {
+        return plan_compound(query, ctx);
+    }
+    let query = decorrelate(query, &|table| ctx.schema_of(table))?;
+    let mut plan = ctx.planner.plan_from(&query.from, &query.predicate, ctx)?;
+    if query.is_grouped() {
+        plan = Box::new(GroupByPlan::new(
+            plan,
+            query.group_by.clone(),
+            query.aggregates.clone(),
+            ctx.block_size(),
+        )?);
+        plan = ctx.select(plan, &query.having)?;
+    }
+    plan = extend(plan, &query, ctx)?;
+    let names = query.field_names();
+    if query.distinct {
+        plan = Box::new(ProjectPlan::new(plan, &names)?);
+        plan = Box::new(DistinctPlan::new(plan, ctx.block_size()));
+        plan = order(plan, &query.order_by, query.limit, query.offset, ctx)?;
+    } else {
+        plan = order(plan, &query.order_by, query.limit, query.offset, ctx)?;
+        plan = Box::new(ProjectPlan::new(plan, &names)?);
+    }
+    Ok(limit(plan, query.limit, query.offset))
+}
+
+/// The parts of a compound query combined left to right, then ordered and
+/// limited as a whole.
+fn plan_compound(query: &QueryData, ctx: &PlanContext) -> Result<Box<dyn Plan>, std::io::Error> {
+    let head = QueryData {
+        order_by: Vec::new(),
+        limit: None,
+        offset: 0,
+        compound: Vec::new(),
+        ..query.clone()
+    };
+    let mut plan = plan_query(&head, ctx)?;
+    for (op, part) in &query.compound {
+        let other = plan_query(part, ctx)?;
+        plan = match op {
+            SetOp::UnionAll => Box::new(UnionPlan::new(plan, other)?),
+            SetOp::Union => Box::new(DistinctPlan::new(
+                Box::new(UnionPlan::new(plan, other)?),
+                ctx.block_size(),
+            )),
+            SetOp::Intersect | SetOp::Except => {
+                Box::new(SetOpPlan::new(*op, plan, other, ctx.block_size())?)
+            }
+        };
+    }
+    plan = order(plan, &query.order_by, query.limit, query.offset, ctx)?;
+    Ok(limit(plan, query.limit, query.offset))
+}
+
+/// The plan with a field for each select item that is not just a field of
+/// it.
+fn extend(
+    plan: Box<dyn Plan>,
+    query: &QueryData,
+    ctx: &PlanContext,
+) -> Result<Box<dyn Plan>, std::io::Error> {
+    let mut computed: Vec<(String, Expression)> = Vec::new();
+    for item in &query.items {
+        let name = item.name();
+        if item.expr.as_field() == Some(name.as_str()) || computed.iter().any(|(n, _)| *n == name) {
+            continue;
+        }
+        computed.push((name, item.expr.clone()));
+    }
+    if computed.is_empty() {
+        return Ok(plan);
+    }
+    let mut fields = Vec::new();
+    let mut subqueries = Vec::new();
+    for (_, expr) in &computed {
+        expr.collect_fields(&mut fields);
+        expr.collect_subqueries(&mut subqueries);
+    }
+    check_fields(&fields, plan.schema())?;
+
+    // A subquery's values are typed by planning it, with the outer fields
+    // it refers to standing for NULL
+    let mut subquery_types = Schema::new();
+    for subquery in &subqueries {
+        let outer = ctx.outer_fields(subquery)?;
+        let bound = subquery.map_fields(&|f| {
+            outer
+                .contains(&f.to_string())
+                .then_some(Expression::Constant(Constant::Null))
+        });
+        let sub_plan = plan_query(&bound, ctx)?;
+        let schema = sub_plan.schema();
+        let fldname = &schema.fields()[0];
+        subquery_types.add_field(
+            &format!("({})", subquery),
+            schema.ctype(fldname),
+            schema.length(fldname),
+        );
+    }
+    let plan = ctx.with_subqueries(plan, &subqueries)?;
+    Ok(Box::new(ExtendPlan::new(plan, computed, &subquery_types)?))
+}
+
+/// The plan with its records in the order of the sort keys. Only the first
+/// `limit` after `offset` being wanted, they are kept in memory rather than
+/// sorted in temporary tables when they fit in the available buffers.
+fn order(
+    plan: Box<dyn Plan>,
+    keys: &[SortKey],
+    limit: Option<u64>,
+    offset: u64,
+    ctx: &PlanContext,
+) -> Result<Box<dyn Plan>, std::io::Error> {
+    if keys.is_empty() {
+        return Ok(plan);
+    }
+    let fields = keys.iter().map(|k| k.field.clone()).collect::<Vec<_>>();
+    check_fields(&fields, plan.schema())?;
+    if let Some(limit) = limit {
+        let n = limit.saturating_add(offset);
+        let slot_size = Layout::new(plan.schema().clone()).slot_size();
+        let memory = ctx.catalog.available_buffers * ctx.block_size();
+        if n.saturating_mul(slot_size) <= memory {
+            return Ok(Box::new(TopNPlan::new(plan, keys.to_vec(), n)));
+        }
+    }
+    Ok(Box::new(SortPlan::new(
+        plan,
+        keys.to_vec(),
+        ctx.block_size(),
+    )))
+}
+
+fn limit(plan: Box<dyn Plan>, limit: Option<u64>, offset: u64) -> Box<dyn Plan> {
+    if limit.is_none() && offset == 0 {
+        return plan;
+    }
+    Box::new(LimitPlan::new(plan, limit, offset))
+}
+
+pub(crate) fn check_fields(fields: &[String], schema: &Schema) -> Result<(), std::io::Error> {
+    match fields.iter().find(|f| !schema.has_field(f)) {
+        Some(fldname) => Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("field {} not found", fldname),
+        )),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        parser::parse_query, simpledb::SimpleDB, statement::Statement, transaction::Transaction,
+    };
+
+    #[test]
+    fn test_planner() {
+        let _ = std::fs::remove_dir_all("plannertest");
+        let mut db = SimpleDB::new("plannertest", 400, 8).unwrap();
+        let mut stmt = Statement::new(&mut db);
+        stmt.execute_update("create table T1 (A int, B varchar(9))")
+            .unwrap();
+        stmt.execute_update("create table T2 (C int, D varchar(9))")
+            .unwrap();
+        for i in 0..50 {
+            let cmd = format!("insert into T1 (A, B) values ({}, 'b{}')", i, i % 5);
+            stmt.execute_update(&cmd).unwrap();
+        }
+        for i in 0..10 {
+            let cmd = format!("insert into T2 (C, D) values ({}, 'd{}')", i, i);
+            stmt.execute_update(&cmd).unwrap();
+        }
+
+        let mut tx = Transaction::new(
+            &db.file_manager,
+            &mut db.log_manager,
+            &mut db.buffer_manager,
+        );
+        let mdm = &mut db.metadata_manager;
+        let mut plan = |text: &str| {
+            db.planner
+                .create_query_plan(&parse_query(text).unwrap(), &mut tx, mdm)
+                .unwrap()
+        };
+        let t1 = plan("select A, B from T1");
+        let t2 = plan("select C from T2");
+        assert_eq!(t1.records_output(), 50);
+        assert_eq!(t2.records_output(), 10);
+
+        // The product reads the second table once per record of the first
+        let product = plan("select A, C from T1, T2");
+        assert_eq!(product.schema().fields(), ["A", "C"]);
+        assert_eq!(product.records_output(), 500);
+        assert_eq!(
+            product.blocks_accessed(),
+            t1.blocks_accessed() + 50 * t2.blocks_accessed()
+        );
+        let join = plan("select A, D from T1, T2 where A = C");
+        assert_eq!(join.records_output(), 10);
+        assert_eq!(join.distinct_values("A"), 10);
+        assert_eq!(
+            plan("select B from T1 where B = 'b1'").distinct_values("B"),
+            1
+        );
+
+        let grouped = plan("select B, count(*) from T1 group by B");
+        assert_eq!(grouped.records_output(), 5);
+        assert_eq!(grouped.schema().fields(), ["B", "count(*)"]);
+        assert_eq!(plan("select count(*) from T1").records_output(), 1);
+        let limited = plan("select A from T1 order by A desc limit 5 offset 3");
+        assert_eq!(limited.records_output(), 5);
+        assert_eq!(plan("select distinct B from T1").records_output(), 5);
+        assert_eq!(
+            plan("select A from T1 union all select C from T2").records_output(),
+            60
+        );
+        assert_eq!(
+            plan("select A from T1 intersect select C from T2").records_output(),
+            10
+        );
+        tx.commit().unwrap();
+    }
+}
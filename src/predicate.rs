@@ -0,0 +1,270 @@
+use crate::{
+    constant::Constant,
+    expression::{Expression, Row},
+    plan::Plan,
+    query_data::QueryData,
+    scan::{Scan, ScanRow},
+    schema::Schema,
+    term::Term,
+    transaction::Transaction,
+};
+
+/// A conjunction of terms. The empty predicate holds for every record.
+/// Planning works on the terms, so a condition split over several of them
+/// can be pushed down piecewise.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Predicate {
+    terms: Vec<Term>,
+}
+
+impl Predicate {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn from_term(term: Term) -> Self {
+        Self { terms: vec![term] }
+    }
+
+    pub(crate) fn from_terms(terms: Vec<Term>) -> Self {
+        Self { terms }
+    }
+
+    pub(crate) fn conjoin_with(&mut self, other: Predicate) {
+        self.terms.extend(other.terms);
+    }
+
+    /// The outcome under SQL's three valued logic: false when some term is
+    /// false, otherwise unknown when some term is unknown.
+    pub(crate) fn evaluate(&self, row: &mut dyn Row) -> Result<Option<bool>, std::io::Error> {
+        let mut result = Some(true);
+        for term in &self.terms {
+            match term.evaluate(row)? {
+                Some(false) => return Ok(Some(false)),
+                None => result = None,
+                Some(true) => {}
+            }
+        }
+        Ok(result)
+    }
+
+    /// Whether a query keeps the current record of the scan, which takes
+    /// the predicate to be true rather than unknown.
+    pub(crate) fn is_satisfied(
+        &self,
+        scan: &mut dyn Scan,
+        tx: &mut Transaction,
+    ) -> Result<bool, std::io::Error> {
+        Ok(self.evaluate(&mut ScanRow { scan, tx })? == Some(true))
+    }
+
+    /// Estimated fraction of the plan's records that satisfy the
+    /// predicate, its terms being taken to be independent.
+    pub(crate) fn selectivity(&self, plan: &dyn Plan) -> f64 {
+        self.terms.iter().map(|t| t.selectivity(plan)).product()
+    }
+
+    /// How many times fewer records satisfy the predicate than the plan
+    /// outputs, infinite when none are expected to.
+    pub(crate) fn reduction_factor(&self, plan: &dyn Plan) -> f64 {
+        1.0 / self.selectivity(plan)
+    }
+
+    /// The terms that only name fields of the schema, if there are any.
+    pub(crate) fn select_sub_pred(&self, schema: &Schema) -> Option<Predicate> {
+        let terms = self
+            .terms
+            .iter()
+            .filter(|t| t.applies_to(schema))
+            .cloned()
+            .collect::<Vec<_>>();
+        (!terms.is_empty()).then_some(Predicate { terms })
+    }
+
+    /// The terms that join the two schemas: they need fields of both, and
+    /// of no other schema.
+    pub(crate) fn join_sub_pred(&self, schema1: &Schema, schema2: &Schema) -> Option<Predicate> {
+        let mut union = Schema::new();
+        union.add_all(schema1);
+        union.add_all(schema2);
+        let terms = self
+            .terms
+            .iter()
+            .filter(|t| !t.applies_to(schema1) && !t.applies_to(schema2) && t.applies_to(&union))
+            .cloned()
+            .collect::<Vec<_>>();
+        (!terms.is_empty()).then_some(Predicate { terms })
+    }
+
+    pub(crate) fn equates_with_constant(&self, fldname: &str) -> Option<&Constant> {
+        self.terms
+            .iter()
+            .find_map(|t| t.equates_with_constant(fldname))
+    }
+
+    pub(crate) fn equates_with_field(&self, fldname: &str) -> Option<&str> {
+        self.terms
+            .iter()
+            .find_map(|t| t.equates_with_field(fldname))
+    }
+
+    pub(crate) fn has_subquery(&self) -> bool {
+        self.terms.iter().any(|t| t.has_subquery())
+    }
+
+    /// The subqueries of the terms, but not those nested in them.
+    pub(crate) fn subqueries(&self) -> Vec<&QueryData> {
+        let mut subqueries = Vec::new();
+        for term in &self.terms {
+            term.collect_subqueries(&mut subqueries);
+        }
+        subqueries
+    }
+
+    /// A copy with each field the function gives an expression for replaced
+    /// by it.
+    pub(crate) fn map_fields(&self, f: &dyn Fn(&str) -> Option<Expression>) -> Predicate {
+        Self {
+            terms: self.terms.iter().map(|t| t.map_fields(f)).collect(),
+        }
+    }
+
+    pub(crate) fn terms(&self) -> &[Term] {
+        &self.terms
+    }
+
+    pub(crate) fn fields(&self) -> Vec<String> {
+        let mut fields = Vec::new();
+        for term in &self.terms {
+            term.collect_fields(&mut fields);
+        }
+        fields
+    }
+}
+
+impl std::fmt::Display for Predicate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let terms = self.terms.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+        write!(f, "{}", terms.join(" and "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{
+        constant::Constant, parser::parse_predicate, plan::Plan, scan::Scan, schema::Schema,
+        transaction::Transaction,
+    };
+
+    struct FakePlan {
+        schema: Schema,
+        distinct: HashMap<&'static str, u64>,
+    }
+
+    impl Plan for FakePlan {
+        fn open(&self, _tx: &mut Transaction) -> Result<Box<dyn Scan>, std::io::Error> {
+            Err(std::io::Error::other("not a real plan"))
+        }
+
+        fn blocks_accessed(&self) -> u64 {
+            10
+        }
+
+        fn records_output(&self) -> u64 {
+            1000
+        }
+
+        fn distinct_values(&self, fldname: &str) -> u64 {
+            self.distinct[fldname]
+        }
+
+        fn schema(&self) -> &Schema {
+            &self.schema
+        }
+    }
+
+    #[test]
+    fn test_predicate_planning() {
+        let mut s1 = Schema::new();
+        s1.add_int_field("A");
+        s1.add_int_field("B");
+        let mut s2 = Schema::new();
+        s2.add_int_field("C");
+        let mut both = Schema::new();
+        both.add_all(&s1);
+        both.add_all(&s2);
+        let plan = FakePlan {
+            schema: both,
+            distinct: [("A", 10), ("B", 100), ("C", 50)].into_iter().collect(),
+        };
+
+        let predicate = parse_predicate("A = 3 and 7 = B and B = C and A < 5 and 1 = 1").unwrap();
+        assert_eq!(
+            predicate.equates_with_constant("A"),
+            Some(&Constant::Int(3))
+        );
+        assert_eq!(
+            predicate.equates_with_constant("B"),
+            Some(&Constant::Int(7))
+        );
+        assert_eq!(predicate.equates_with_constant("C"), None);
+        assert_eq!(predicate.equates_with_field("C"), Some("B"));
+        assert_eq!(predicate.equates_with_field("A"), None);
+
+        assert_eq!(
+            predicate.select_sub_pred(&s1).unwrap().to_string(),
+            "A = 3 and 7 = B and A < 5 and 1 = 1"
+        );
+        assert_eq!(predicate.select_sub_pred(&s2).unwrap().to_string(), "1 = 1");
+        assert_eq!(
+            predicate.join_sub_pred(&s1, &s2).unwrap().to_string(),
+            "B = C"
+        );
+        assert!(parse_predicate("A = B")
+            .unwrap()
+            .join_sub_pred(&s1, &s2)
+            .is_none());
+
+        // 10 * 100 * 100 * 3 * 1
+        let factor = predicate.reduction_factor(&plan);
+        assert!((factor - 300_000.0).abs() < 1e-6, "{}", factor);
+        let never = parse_predicate("A = NULL").unwrap().reduction_factor(&plan);
+        assert!(never.is_infinite());
+        assert!(parse_predicate("1 = 2")
+            .unwrap()
+            .reduction_factor(&plan)
+            .is_infinite());
+
+        let selectivity = |text: &str| parse_predicate(text).unwrap().selectivity(&plan);
+        for (text, expected) in [
+            ("A <> 3", 0.9),
+            ("A between 1 and 5", 1.0 / 3.0),
+            ("A not between 1 and 5", 2.0 / 3.0),
+            ("A between NULL and 5", 0.0),
+            ("A in (1, 2, 2, NULL)", 0.2),
+            ("A not in (1, 2)", 0.8),
+            ("A in (1, B)", 1.0 / 3.0),
+            ("B in (1, 2) and C in (3)", 0.02 * 0.02),
+            ("C like 'abc'", 0.02),
+            ("C like 'ab%'", 1.0 / 3.0),
+            ("C like '%b'", 0.1),
+            ("C not like '_b'", 0.9),
+            ("not A = 3", 0.9),
+            ("A = 1 or A = 2", 1.0 - 0.9 * 0.9),
+            ("A = 1 or 1 = 1", 1.0),
+            ("A = B", 0.01),
+            ("1 in (2, 1)", 1.0),
+        ] {
+            let s = selectivity(text);
+            assert!((s - expected).abs() < 1e-9, "{}: {}", text, s);
+        }
+        let split = parse_predicate("(A = 1 or C = 2) and B = C").unwrap();
+        assert_eq!(split.select_sub_pred(&s1), None);
+        assert_eq!(
+            split.join_sub_pred(&s1, &s2).unwrap().to_string(),
+            "(A = 1 or C = 2) and B = C"
+        );
+    }
+}
@@ -0,0 +1,80 @@
+use crate::{
+    constant::Constant, plan::Plan, product_scan::ProductScan, scan::Scan, schema::Schema,
+    transaction::Transaction,
+};
+
+/// Every pairing of the records of two plans. The second plan is read once
+/// per record of the first, which is what makes the order matter.
+pub(crate) struct ProductPlan {
+    p1: Box<dyn Plan>,
+    p2: Box<dyn Plan>,
+    schema: Schema,
+}
+
+impl ProductPlan {
+    pub(crate) fn new(p1: Box<dyn Plan>, p2: Box<dyn Plan>) -> Self {
+        let schema = combined_schema(p1.schema(), p2.schema());
+        Self { p1, p2, schema }
+    }
+}
+
+impl Plan for ProductPlan {
+    fn open(&self, tx: &mut Transaction) -> Result<Box<dyn Scan>, std::io::Error> {
+        let s1 = self.p1.open(tx)?;
+        let s2 = self.p2.open(tx)?;
+        Ok(Box::new(ProductScan::new(s1, s2, tx)?))
+    }
+
+    fn blocks_accessed(&self) -> u64 {
+        self.p1.blocks_accessed() + self.p1.records_output() * self.p2.blocks_accessed()
+    }
+
+    fn records_output(&self) -> u64 {
+        self.p1.records_output() * self.p2.records_output()
+    }
+
+    fn distinct_values(&self, fldname: &str) -> u64 {
+        side(self.p1.as_ref(), self.p2.as_ref(), fldname).distinct_values(fldname)
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn eq_selectivity(&self, fldname: &str, value: &Constant) -> f64 {
+        side(self.p1.as_ref(), self.p2.as_ref(), fldname).eq_selectivity(fldname, value)
+    }
+
+    fn range_selectivity(
+        &self,
+        fldname: &str,
+        low: Option<&Constant>,
+        high: Option<&Constant>,
+    ) -> f64 {
+        side(self.p1.as_ref(), self.p2.as_ref(), fldname).range_selectivity(fldname, low, high)
+    }
+}
+
+/// The fields of two combined plans. A field both have is left out, as
+/// their scans refuse it for being ambiguous; qualified names tell the two
+/// apart.
+pub(crate) fn combined_schema(s1: &Schema, s2: &Schema) -> Schema {
+    let mut schema = Schema::new();
+    for (this, other) in [(s1, s2), (s2, s1)] {
+        for fldname in this.fields() {
+            if !other.has_field(fldname) {
+                schema.add(fldname, this);
+            }
+        }
+    }
+    schema
+}
+
+/// Which of two combined plans the field comes from.
+pub(crate) fn side<'p>(p1: &'p dyn Plan, p2: &'p dyn Plan, fldname: &str) -> &'p dyn Plan {
+    if p1.schema().has_field(fldname) {
+        p1
+    } else {
+        p2
+    }
+}
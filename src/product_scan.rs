@@ -0,0 +1,108 @@
+use crate::{constant::Constant, overflow::LargeValue, scan::Scan, transaction::Transaction};
+
+/// Every pairing of a record of the first scan with a record of the second,
+/// the second scan being rescanned once per record of the first. A field
+/// that both scans have has to be referred to by a qualified name.
+pub(crate) struct ProductScan<S1: Scan, S2: Scan> {
+    s1: S1,
+    s2: S2,
+    /// Whether `s1` is positioned on a record.
+    on_record: bool,
+}
+
+impl<S1: Scan, S2: Scan> ProductScan<S1, S2> {
+    pub(crate) fn new(s1: S1, s2: S2, tx: &mut Transaction) -> Result<Self, std::io::Error> {
+        let mut scan = Self {
+            s1,
+            s2,
+            on_record: false,
+        };
+        Scan::before_first(&mut scan, tx)?;
+        Ok(scan)
+    }
+}
+
+impl<S1: Scan, S2: Scan> Scan for ProductScan<S1, S2> {
+    fn before_first(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        self.s1.before_first(tx)?;
+        self.on_record = self.s1.next(tx)?;
+        self.s2.before_first(tx)
+    }
+
+    fn next(&mut self, tx: &mut Transaction) -> Result<bool, std::io::Error> {
+        while self.on_record {
+            if self.s2.next(tx)? {
+                return Ok(true);
+            }
+            self.s2.before_first(tx)?;
+            self.on_record = self.s1.next(tx)?;
+        }
+        Ok(false)
+    }
+
+    fn get_int(&mut self, tx: &mut Transaction, fldname: &str) -> Result<i64, std::io::Error> {
+        if in_first(&self.s1, &self.s2, fldname)? {
+            self.s1.get_int(tx, fldname)
+        } else {
+            self.s2.get_int(tx, fldname)
+        }
+    }
+
+    fn get_string(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+    ) -> Result<String, std::io::Error> {
+        if in_first(&self.s1, &self.s2, fldname)? {
+            self.s1.get_string(tx, fldname)
+        } else {
+            self.s2.get_string(tx, fldname)
+        }
+    }
+
+    fn get_val(&mut self, tx: &mut Transaction, fldname: &str) -> Result<Constant, std::io::Error> {
+        if in_first(&self.s1, &self.s2, fldname)? {
+            self.s1.get_val(tx, fldname)
+        } else {
+            self.s2.get_val(tx, fldname)
+        }
+    }
+
+    fn has_field(&self, fldname: &str) -> bool {
+        self.s1.has_field(fldname) || self.s2.has_field(fldname)
+    }
+
+    fn get_large(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+    ) -> Result<Option<LargeValue>, std::io::Error> {
+        if in_first(&self.s1, &self.s2, fldname)? {
+            self.s1.get_large(tx, fldname)
+        } else {
+            self.s2.get_large(tx, fldname)
+        }
+    }
+
+    fn close(&mut self, tx: &mut Transaction) {
+        self.s1.close(tx);
+        self.s2.close(tx);
+    }
+}
+
+/// Whether the field belongs to the first of two combined scans. It is an
+/// error for both to have it.
+pub(crate) fn in_first(
+    s1: &dyn Scan,
+    s2: &dyn Scan,
+    fldname: &str,
+) -> Result<bool, std::io::Error> {
+    let in_first = s1.has_field(fldname);
+    if in_first && s2.has_field(fldname) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("field {} is ambiguous", fldname),
+        ));
+    }
+    Ok(in_first)
+}
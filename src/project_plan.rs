@@ -0,0 +1,63 @@
+use crate::{
+    constant::Constant, plan::Plan, project_scan::ProjectScan, scan::Scan, schema::Schema,
+    transaction::Transaction,
+};
+
+/// The records of the underlying plan with only the listed fields.
+pub(crate) struct ProjectPlan {
+    plan: Box<dyn Plan>,
+    schema: Schema,
+}
+
+impl ProjectPlan {
+    pub(crate) fn new(plan: Box<dyn Plan>, fields: &[String]) -> Result<Self, std::io::Error> {
+        let mut schema = Schema::new();
+        for fldname in fields {
+            if !plan.schema().has_field(fldname) {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("field {} not found", fldname),
+                ));
+            }
+            schema.add(fldname, plan.schema());
+        }
+        Ok(Self { plan, schema })
+    }
+}
+
+impl Plan for ProjectPlan {
+    fn open(&self, tx: &mut Transaction) -> Result<Box<dyn Scan>, std::io::Error> {
+        let scan = self.plan.open(tx)?;
+        let fields = self.schema.fields().to_vec();
+        Ok(Box::new(ProjectScan::new(scan, fields)))
+    }
+
+    fn blocks_accessed(&self) -> u64 {
+        self.plan.blocks_accessed()
+    }
+
+    fn records_output(&self) -> u64 {
+        self.plan.records_output()
+    }
+
+    fn distinct_values(&self, fldname: &str) -> u64 {
+        self.plan.distinct_values(fldname)
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn eq_selectivity(&self, fldname: &str, value: &Constant) -> f64 {
+        self.plan.eq_selectivity(fldname, value)
+    }
+
+    fn range_selectivity(
+        &self,
+        fldname: &str,
+        low: Option<&Constant>,
+        high: Option<&Constant>,
+    ) -> f64 {
+        self.plan.range_selectivity(fldname, low, high)
+    }
+}
@@ -0,0 +1,81 @@
+use crate::{
+    constant::Constant, overflow::LargeValue, query_data::QueryData, scan::Scan,
+    transaction::Transaction,
+};
+
+/// The records of the underlying scan, with only the listed fields visible.
+pub(crate) struct ProjectScan<S: Scan> {
+    scan: S,
+    fields: Vec<String>,
+}
+
+impl<S: Scan> ProjectScan<S> {
+    pub(crate) fn new(scan: S, fields: Vec<String>) -> Self {
+        Self { scan, fields }
+    }
+
+    fn check_field(&self, fldname: &str) -> Result<(), std::io::Error> {
+        if self.has_field(fldname) {
+            Ok(())
+        } else {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("field {} is not in the projection", fldname),
+            ))
+        }
+    }
+}
+
+impl<S: Scan> Scan for ProjectScan<S> {
+    fn before_first(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        self.scan.before_first(tx)
+    }
+
+    fn next(&mut self, tx: &mut Transaction) -> Result<bool, std::io::Error> {
+        self.scan.next(tx)
+    }
+
+    fn get_int(&mut self, tx: &mut Transaction, fldname: &str) -> Result<i64, std::io::Error> {
+        self.check_field(fldname)?;
+        self.scan.get_int(tx, fldname)
+    }
+
+    fn get_string(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+    ) -> Result<String, std::io::Error> {
+        self.check_field(fldname)?;
+        self.scan.get_string(tx, fldname)
+    }
+
+    fn get_val(&mut self, tx: &mut Transaction, fldname: &str) -> Result<Constant, std::io::Error> {
+        self.check_field(fldname)?;
+        self.scan.get_val(tx, fldname)
+    }
+
+    fn has_field(&self, fldname: &str) -> bool {
+        self.fields.iter().any(|f| f == fldname)
+    }
+
+    fn get_large(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+    ) -> Result<Option<LargeValue>, std::io::Error> {
+        self.check_field(fldname)?;
+        self.scan.get_large(tx, fldname)
+    }
+
+    fn close(&mut self, tx: &mut Transaction) {
+        self.scan.close(tx)
+    }
+
+    fn subquery_values(
+        &mut self,
+        tx: &mut Transaction,
+        query: &QueryData,
+    ) -> Result<Vec<Constant>, std::io::Error> {
+        self.scan.subquery_values(tx, query)
+    }
+}
@@ -0,0 +1,312 @@
+use crate::{
+    aggregate::AggregateFn, expression::Expression, predicate::Predicate,
+    record_comparator::SortKey,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum JoinKind {
+    Inner,
+    /// Records of the left side without a match are kept, padded with
+    /// NULLs for the fields of the right side.
+    Left,
+    Right,
+    Full,
+    /// Records of the left side with a match, each once and with only the
+    /// fields of the left side. Semi and anti joins have no syntax of their
+    /// own, they come from decorrelating subqueries.
+    Semi,
+    /// Records of the left side without a match.
+    Anti,
+}
+
+impl JoinKind {
+    pub(crate) fn keywords(&self) -> &'static str {
+        match self {
+            JoinKind::Inner => "join",
+            JoinKind::Left => "left join",
+            JoinKind::Right => "right join",
+            JoinKind::Full => "full join",
+            JoinKind::Semi => "semi join",
+            JoinKind::Anti => "anti join",
+        }
+    }
+}
+
+/// How the records of the queries of a compound query combine. All but
+/// `UNION ALL` give each record once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SetOp {
+    Union,
+    UnionAll,
+    Intersect,
+    Except,
+}
+
+impl SetOp {
+    pub(crate) fn keywords(&self) -> &'static str {
+        match self {
+            SetOp::Union => "union",
+            SetOp::UnionAll => "union all",
+            SetOp::Intersect => "intersect",
+            SetOp::Except => "except",
+        }
+    }
+}
+
+/// An expression of the SELECT list, with the name of the field holding
+/// its value in the result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SelectItem {
+    pub(crate) expr: Expression,
+    pub(crate) alias: Option<String>,
+}
+
+impl SelectItem {
+    pub(crate) fn new(expr: Expression, alias: Option<&str>) -> Self {
+        Self {
+            expr,
+            alias: alias.map(|a| a.to_string()),
+        }
+    }
+
+    /// The alias, else the text of the expression.
+    pub(crate) fn name(&self) -> String {
+        match &self.alias {
+            Some(alias) => alias.clone(),
+            None => self.expr.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for SelectItem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.expr)?;
+        if let Some(alias) = &self.alias {
+            write!(f, " as {}", alias)?;
+        }
+        Ok(())
+    }
+}
+
+/// A table of the FROM clause. Its fields can be referred to as
+/// `alias.field`, or `table.field` when it has no alias. A derived table is
+/// the result of a query and always has an alias, which is also its name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TableRef {
+    pub(crate) tblname: String,
+    pub(crate) alias: Option<String>,
+    pub(crate) subquery: Option<Box<QueryData>>,
+}
+
+impl TableRef {
+    pub(crate) fn new(tblname: &str, alias: Option<&str>) -> Self {
+        Self {
+            tblname: tblname.to_string(),
+            alias: alias.map(|a| a.to_string()),
+            subquery: None,
+        }
+    }
+
+    pub(crate) fn derived(query: QueryData, alias: &str) -> Self {
+        Self {
+            tblname: alias.to_string(),
+            alias: Some(alias.to_string()),
+            subquery: Some(Box::new(query)),
+        }
+    }
+
+    /// The name that qualifies the table's fields in the query.
+    pub(crate) fn range_name(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.tblname)
+    }
+}
+
+impl std::fmt::Display for TableRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.subquery {
+            Some(query) => write!(f, "({})", query)?,
+            None => write!(f, "{}", self.tblname)?,
+        }
+        if let Some(alias) = &self.alias {
+            write!(f, " {}", alias)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Join {
+    pub(crate) kind: JoinKind,
+    pub(crate) table: TableRef,
+    pub(crate) on: Predicate,
+}
+
+/// An item of the FROM clause: a table and the tables joined to it, left to
+/// right. The items themselves are combined by cartesian product.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FromItem {
+    pub(crate) table: TableRef,
+    pub(crate) joins: Vec<Join>,
+}
+
+impl std::fmt::Display for FromItem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.table)?;
+        for join in &self.joins {
+            write!(f, " {} {} on {}", join.kind.keywords(), join.table, join.on)?;
+        }
+        Ok(())
+    }
+}
+
+/// A parsed SELECT statement. Aggregates appear in the select items, in the
+/// HAVING predicate and in the sort keys as fields named by
+/// [`AggregateFn::field_name`], and each of them is listed once in
+/// `aggregates`.
+///
+/// A compound query is the first of its queries, with the others and the
+/// operators combining them in `compound`; the parts after the first have
+/// no ORDER BY or LIMIT, those of the first apply to the whole. The result
+/// skips `offset` records, then stops after `limit` more.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct QueryData {
+    pub(crate) distinct: bool,
+    pub(crate) items: Vec<SelectItem>,
+    pub(crate) from: Vec<FromItem>,
+    pub(crate) predicate: Predicate,
+    pub(crate) group_by: Vec<String>,
+    pub(crate) aggregates: Vec<AggregateFn>,
+    pub(crate) having: Predicate,
+    pub(crate) order_by: Vec<SortKey>,
+    pub(crate) limit: Option<u64>,
+    pub(crate) offset: u64,
+    pub(crate) compound: Vec<(SetOp, QueryData)>,
+}
+
+impl QueryData {
+    /// The names of the fields of the result, in order.
+    pub(crate) fn field_names(&self) -> Vec<String> {
+        self.items.iter().map(|i| i.name()).collect()
+    }
+
+    /// Every table of the FROM clause, joined ones included.
+    pub(crate) fn tables(&self) -> Vec<&TableRef> {
+        self.from
+            .iter()
+            .flat_map(|item| {
+                std::iter::once(&item.table).chain(item.joins.iter().map(|j| &j.table))
+            })
+            .collect()
+    }
+
+    /// A copy with each field the function gives an expression for replaced
+    /// by it, wherever an expression may stand: in the select items, the
+    /// conditions and the subqueries, derived tables included.
+    pub(crate) fn map_fields(&self, f: &dyn Fn(&str) -> Option<Expression>) -> QueryData {
+        let map_table = |table: &TableRef| TableRef {
+            subquery: table.subquery.as_ref().map(|q| Box::new(q.map_fields(f))),
+            ..table.clone()
+        };
+        QueryData {
+            items: self
+                .items
+                .iter()
+                .map(|i| SelectItem {
+                    expr: i.expr.map_fields(f),
+                    alias: i.alias.clone(),
+                })
+                .collect(),
+            from: self
+                .from
+                .iter()
+                .map(|item| FromItem {
+                    table: map_table(&item.table),
+                    joins: item
+                        .joins
+                        .iter()
+                        .map(|j| Join {
+                            kind: j.kind,
+                            table: map_table(&j.table),
+                            on: j.on.map_fields(f),
+                        })
+                        .collect(),
+                })
+                .collect(),
+            predicate: self.predicate.map_fields(f),
+            having: self.having.map_fields(f),
+            compound: self
+                .compound
+                .iter()
+                .map(|(op, part)| (*op, part.map_fields(f)))
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// The queries nested in this one, but not those nested in them: derived
+    /// tables, the subqueries of its expressions and conditions, and the
+    /// other parts of a compound query.
+    pub(crate) fn subqueries(&self) -> Vec<&QueryData> {
+        let mut subqueries = Vec::new();
+        for item in &self.items {
+            item.expr.collect_subqueries(&mut subqueries);
+        }
+        for item in &self.from {
+            let tables = std::iter::once(&item.table).chain(item.joins.iter().map(|j| &j.table));
+            subqueries.extend(tables.filter_map(|t| t.subquery.as_deref()));
+            for join in &item.joins {
+                subqueries.extend(join.on.subqueries());
+            }
+        }
+        subqueries.extend(self.predicate.subqueries());
+        subqueries.extend(self.having.subqueries());
+        subqueries.extend(self.compound.iter().map(|(_, part)| part));
+        subqueries
+    }
+
+    /// Whether the records are grouped, which aggregates or a HAVING clause
+    /// imply even without GROUP BY.
+    pub(crate) fn is_grouped(&self) -> bool {
+        !self.group_by.is_empty() || !self.aggregates.is_empty() || !self.having.terms().is_empty()
+    }
+}
+
+// The text parses back into the same query, which is how views are stored
+impl std::fmt::Display for QueryData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let items = self.items.iter().map(|i| i.to_string()).collect::<Vec<_>>();
+        let from = self.from.iter().map(|i| i.to_string()).collect::<Vec<_>>();
+        write!(f, "select ")?;
+        if self.distinct {
+            write!(f, "distinct ")?;
+        }
+        write!(f, "{} from {}", items.join(", "), from.join(", "))?;
+        if !self.predicate.terms().is_empty() {
+            write!(f, " where {}", self.predicate)?;
+        }
+        if !self.group_by.is_empty() {
+            write!(f, " group by {}", self.group_by.join(", "))?;
+        }
+        if !self.having.terms().is_empty() {
+            write!(f, " having {}", self.having)?;
+        }
+        for (op, part) in &self.compound {
+            write!(f, " {} {}", op.keywords(), part)?;
+        }
+        if !self.order_by.is_empty() {
+            let keys = self
+                .order_by
+                .iter()
+                .map(|k| k.to_string())
+                .collect::<Vec<_>>();
+            write!(f, " order by {}", keys.join(", "))?;
+        }
+        if let Some(limit) = self.limit {
+            write!(f, " limit {}", limit)?;
+            if self.offset > 0 {
+                write!(f, " offset {}", self.offset)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,67 @@
+use std::cmp::Ordering;
+
+use crate::{scan::Scan, transaction::Transaction};
+
+/// A field to sort on, and which way.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SortKey {
+    pub(crate) field: String,
+    pub(crate) descending: bool,
+}
+
+impl SortKey {
+    pub(crate) fn new(field: &str, descending: bool) -> Self {
+        Self {
+            field: field.to_string(),
+            descending,
+        }
+    }
+}
+
+impl std::fmt::Display for SortKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.field)?;
+        if self.descending {
+            write!(f, " desc")?;
+        }
+        Ok(())
+    }
+}
+
+/// Orders the current records of two scans by the keys in turn. NULL comes
+/// before every other value, so last in descending order.
+#[derive(Debug, Clone)]
+pub(crate) struct RecordComparator {
+    keys: Vec<SortKey>,
+}
+
+impl RecordComparator {
+    pub(crate) fn new(keys: Vec<SortKey>) -> Self {
+        Self { keys }
+    }
+
+    pub(crate) fn keys(&self) -> &[SortKey] {
+        &self.keys
+    }
+
+    pub(crate) fn compare(
+        &self,
+        s1: &mut dyn Scan,
+        s2: &mut dyn Scan,
+        tx: &mut Transaction,
+    ) -> Result<Ordering, std::io::Error> {
+        for key in &self.keys {
+            let ordering = s1
+                .get_val(tx, &key.field)?
+                .cmp(&s2.get_val(tx, &key.field)?);
+            if ordering.is_ne() {
+                return Ok(if key.descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                });
+            }
+        }
+        Ok(Ordering::Equal)
+    }
+}
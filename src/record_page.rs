@@ -0,0 +1,191 @@
+use crate::{
+    file_manager::BlockId, layout::Layout, overflow::OverflowPointer, transaction::Transaction,
+};
+
+pub(crate) const EMPTY: u64 = 0;
+pub(crate) const USED: u64 = 1;
+// The remaining bits of the flag are the null bits of the fields
+pub(crate) const MAX_FIELDS: usize = 63;
+
+/// A block of fixed size slots, one record per slot, formatted after `layout`.
+/// Each slot starts with a flag telling whether it is in use, which also has
+/// a bit per field that is set while the field is NULL.
+pub(crate) struct RecordPage {
+    block: BlockId,
+    layout: Layout,
+    block_size: u64,
+}
+
+impl RecordPage {
+    pub(crate) fn new(
+        tx: &mut Transaction,
+        block: BlockId,
+        layout: Layout,
+    ) -> Result<Self, std::io::Error> {
+        tx.pin(&block)?;
+        Ok(Self {
+            block,
+            layout,
+            block_size: tx.block_size(),
+        })
+    }
+
+    pub(crate) fn block(&self) -> &BlockId {
+        &self.block
+    }
+
+    pub(crate) fn close(&self, tx: &mut Transaction) {
+        tx.unpin(&self.block);
+    }
+
+    pub(crate) fn get_int(&self, tx: &mut Transaction, slot: usize, field: &str) -> i64 {
+        tx.get_int(&self.block, self.field_offset(slot, field)) as i64
+    }
+
+    pub(crate) fn get_string(&self, tx: &mut Transaction, slot: usize, field: &str) -> String {
+        tx.get_string(&self.block, self.field_offset(slot, field))
+    }
+
+    pub(crate) fn get_overflow_pointer(
+        &self,
+        tx: &mut Transaction,
+        slot: usize,
+        field: &str,
+    ) -> OverflowPointer {
+        let offset = self.field_offset(slot, field);
+        OverflowPointer {
+            first_block: tx.get_int(&self.block, offset),
+            length: tx.get_int(&self.block, offset + 8),
+        }
+    }
+
+    pub(crate) fn is_null(&self, tx: &mut Transaction, slot: usize, field: &str) -> bool {
+        tx.get_int(&self.block, self.offset(slot)) & self.null_bit(field) != 0
+    }
+
+    /// Sets or clears the field's null bit, writing the flag only when the
+    /// bit changes.
+    pub(crate) fn set_null(
+        &self,
+        tx: &mut Transaction,
+        slot: usize,
+        field: &str,
+        null: bool,
+    ) -> Result<(), std::io::Error> {
+        let flag = tx.get_int(&self.block, self.offset(slot));
+        let new_flag = if null {
+            flag | self.null_bit(field)
+        } else {
+            flag & !self.null_bit(field)
+        };
+        if new_flag != flag {
+            self.set_flag(tx, slot, new_flag)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn set_int(
+        &self,
+        tx: &mut Transaction,
+        slot: usize,
+        field: &str,
+        value: i64,
+    ) -> Result<(), std::io::Error> {
+        tx.set_int(&self.block, self.field_offset(slot, field), value as u64)
+    }
+
+    pub(crate) fn set_string(
+        &self,
+        tx: &mut Transaction,
+        slot: usize,
+        field: &str,
+        value: &str,
+    ) -> Result<(), std::io::Error> {
+        let length = self.layout.schema().length(field);
+        if value.len() as u64 > length {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "a string of {} bytes does not fit field {} of length {}",
+                    value.len(),
+                    field,
+                    length
+                ),
+            ));
+        }
+        tx.set_string(&self.block, self.field_offset(slot, field), value)
+    }
+
+    pub(crate) fn set_overflow_pointer(
+        &self,
+        tx: &mut Transaction,
+        slot: usize,
+        field: &str,
+        pointer: OverflowPointer,
+    ) -> Result<(), std::io::Error> {
+        let offset = self.field_offset(slot, field);
+        tx.set_int(&self.block, offset, pointer.first_block)?;
+        tx.set_int(&self.block, offset + 8, pointer.length)
+    }
+
+    pub(crate) fn delete(&self, tx: &mut Transaction, slot: usize) -> Result<(), std::io::Error> {
+        self.set_flag(tx, slot, EMPTY)
+    }
+
+    /// Marks every slot of a freshly appended block as empty.
+    pub(crate) fn format(&self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        for slot in 0..self.slots() {
+            tx.set_int(&self.block, self.offset(slot), EMPTY)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn next_after(&self, tx: &mut Transaction, slot: Option<usize>) -> Option<usize> {
+        self.search_after(tx, slot, true)
+    }
+
+    pub(crate) fn insert_after(
+        &self,
+        tx: &mut Transaction,
+        slot: Option<usize>,
+    ) -> Result<Option<usize>, std::io::Error> {
+        let Some(slot) = self.search_after(tx, slot, false) else {
+            return Ok(None);
+        };
+        self.set_flag(tx, slot, USED)?;
+        Ok(Some(slot))
+    }
+
+    pub(crate) fn slots(&self) -> usize {
+        (self.block_size / self.layout.slot_size()) as usize
+    }
+
+    fn search_after(&self, tx: &mut Transaction, slot: Option<usize>, used: bool) -> Option<usize> {
+        let start = slot.map_or(0, |s| s + 1);
+        (start..self.slots())
+            .find(|&s| (tx.get_int(&self.block, self.offset(s)) & USED != 0) == used)
+    }
+
+    fn null_bit(&self, field: &str) -> u64 {
+        let index = self
+            .layout
+            .schema()
+            .fields()
+            .iter()
+            .position(|f| f == field)
+            .unwrap_or_else(|| panic!("no field {} in the record", field));
+        1 << (index + 1)
+    }
+
+    fn set_flag(&self, tx: &mut Transaction, slot: usize, flag: u64) -> Result<(), std::io::Error> {
+        tx.set_int(&self.block, self.offset(slot), flag)
+    }
+
+    fn field_offset(&self, slot: usize, field: &str) -> u64 {
+        self.offset(slot) + self.layout.offset(field)
+    }
+
+    fn offset(&self, slot: usize) -> u64 {
+        slot as u64 * self.layout.slot_size()
+    }
+}
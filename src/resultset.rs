@@ -1,17 +1,90 @@
+use std::collections::HashMap;
 
+use crate::{
+    constant::Constant,
+    file_manager::FileManager,
+    overflow::{OverflowPointer, OverflowReader},
+    query_data::SelectItem,
+    schema::Schema,
+};
 
+pub(crate) enum Value {
+    Null,
+    Int(i64),
+    String(String),
+    Bytes(Vec<u8>),
+    // TEXT and BLOB values stay in their overflow blocks until they are read
+    Large {
+        filename: String,
+        pointer: OverflowPointer,
+    },
+}
 
-pub struct Record {
+impl From<Constant> for Value {
+    fn from(value: Constant) -> Self {
+        match value {
+            Constant::Null => Value::Null,
+            Constant::Int(value) => Value::Int(value),
+            Constant::Str(value) => Value::String(value),
+            Constant::Bytes(value) => Value::Bytes(value),
+        }
+    }
+}
 
+pub struct Record {
+    values: HashMap<String, Value>,
 }
 
 impl Record {
+    pub(crate) fn new() -> Self {
+        Self {
+            values: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn set(&mut self, s: &str, value: Value) {
+        self.values.insert(s.to_string(), value);
+    }
+
+    pub(crate) fn is_null(&self, s: &str) -> bool {
+        matches!(self.values[s], Value::Null)
+    }
+
     pub(crate) fn get_string(&self, s: &str) -> String {
-        unimplemented!()
+        match &self.values[s] {
+            Value::String(value) => value.clone(),
+            _ => panic!("field {} is not a string", s),
+        }
     }
 
     pub(crate) fn get_int(&self, s: &str) -> i64 {
-        unimplemented!()
+        match &self.values[s] {
+            Value::Int(value) => *value,
+            _ => panic!("field {} is not an integer", s),
+        }
+    }
+
+    pub(crate) fn get_bytes(&self, s: &str) -> Vec<u8> {
+        match &self.values[s] {
+            Value::Bytes(value) => value.clone(),
+            _ => panic!("field {} is not a blob", s),
+        }
+    }
+
+    /// Streams a TEXT or BLOB value that was left in its overflow blocks.
+    /// Values a query computed or copied are held in the record instead, and
+    /// have no reader.
+    pub(crate) fn get_reader<'a>(
+        &self,
+        s: &str,
+        file_manager: &'a FileManager,
+    ) -> Option<OverflowReader<'a>> {
+        match &self.values[s] {
+            Value::Large { filename, pointer } => {
+                Some(OverflowReader::new(file_manager, filename, *pointer))
+            }
+            _ => None,
+        }
     }
 
     pub(crate) fn get_float(&self, s: &str) -> f32 {
@@ -31,15 +104,28 @@ impl Record {
     }
 }
 pub(crate) struct ResultSet {
-    records: Vec<Record>,
+    records: std::vec::IntoIter<Record>,
     metadata: ResultSetMetadata
 }
 
+impl ResultSet {
+    pub(crate) fn new(records: Vec<Record>, metadata: ResultSetMetadata) -> Self {
+        Self {
+            records: records.into_iter(),
+            metadata,
+        }
+    }
+
+    pub(crate) fn metadata(&self) -> &ResultSetMetadata {
+        &self.metadata
+    }
+}
+
 impl Iterator for ResultSet {
     type Item = Record;
 
     fn next(&mut self) -> Option<Self::Item> {
-        todo!()
+        self.records.next()
     }
 }
 
@@ -47,13 +133,95 @@ pub(crate) struct ResultSetMetadata {
     columns: Vec<Column>,
 }
 
+impl ResultSetMetadata {
+    /// The columns the select items give over records of the schema, typed
+    /// by what their expressions compute. A column that can only be NULL is
+    /// taken to be a VARCHAR.
+    pub(crate) fn new(items: &[SelectItem], schema: &Schema) -> Result<Self, std::io::Error> {
+        let mut columns = Vec::new();
+        for item in items {
+            let name = item.name();
+            let (ctype, display_size) = match item.expr.column_type(schema)? {
+                Some((ColumnType::Integer, _)) => (ColumnType::Integer, 6),
+                Some((ColumnType::VarChar, length)) => (ColumnType::VarChar, length),
+                Some((ctype, _)) => (ctype, LARGE_DISPLAY_SIZE),
+                None => (ColumnType::VarChar, "NULL".len() as u64),
+            };
+            let display_size = display_size.max(name.chars().count() as u64);
+            columns.push(Column {
+                name,
+                display_size,
+                ctype,
+            });
+        }
+        Ok(Self { columns })
+    }
+
+    pub(crate) fn column_count(&self) -> usize {
+        self.columns.len()
+    }
+
+    // Columns are numbered from 1, as in JDBC
+    pub(crate) fn column_name(&self, column: usize) -> &str {
+        &self.columns[column - 1].name
+    }
+
+    pub(crate) fn column_type(&self, column: usize) -> ColumnType {
+        self.columns[column - 1].ctype
+    }
+
+    pub(crate) fn column_display_size(&self, column: usize) -> u64 {
+        self.columns[column - 1].display_size
+    }
+}
+
+// TEXT and BLOB values are cut to this many characters when shown
+const LARGE_DISPLAY_SIZE: u64 = 20;
+
 pub(crate) struct Column {
     name: String,
     display_size: u64,
     ctype: ColumnType
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) enum ColumnType {
     Integer,
-    VarChar
+    VarChar,
+    Text,
+    Blob,
+}
+
+impl ColumnType {
+    // The java.sql.Types codes, which is how the catalog stores types
+    pub(crate) fn code(&self) -> i64 {
+        match self {
+            ColumnType::Integer => 4,
+            ColumnType::VarChar => 12,
+            ColumnType::Text => 2005,
+            ColumnType::Blob => 2004,
+        }
+    }
+
+    pub(crate) fn from_code(code: i64) -> Option<Self> {
+        match code {
+            4 => Some(ColumnType::Integer),
+            12 => Some(ColumnType::VarChar),
+            2005 => Some(ColumnType::Text),
+            2004 => Some(ColumnType::Blob),
+            _ => None,
+        }
+    }
+
+    /// Whether a field of this type can hold the value. Strings also go
+    /// into TEXT and BLOB fields, bytes only into BLOB fields, NULL goes
+    /// anywhere.
+    pub(crate) fn accepts(&self, value: &Constant) -> bool {
+        match value {
+            Constant::Null => true,
+            Constant::Int(_) => *self == ColumnType::Integer,
+            Constant::Str(_) => *self != ColumnType::Integer,
+            Constant::Bytes(_) => *self == ColumnType::Blob,
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,17 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct Rid {
+    pub(crate) block_number: u64,
+    pub(crate) slot: usize,
+}
+
+impl Rid {
+    pub(crate) fn new(block_number: u64, slot: usize) -> Self {
+        Self { block_number, slot }
+    }
+}
+
+impl std::fmt::Display for Rid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}, {}]", self.block_number, self.slot)
+    }
+}
@@ -0,0 +1,283 @@
+use crate::{
+    constant::Constant, expression::Row, overflow::LargeValue, query_data::QueryData, rid::Rid,
+    transaction::Transaction,
+};
+
+/// A cursor over the records a relational operator produces. A scan starts
+/// out before its first record.
+pub(crate) trait Scan {
+    fn before_first(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error>;
+    fn next(&mut self, tx: &mut Transaction) -> Result<bool, std::io::Error>;
+    fn get_int(&mut self, tx: &mut Transaction, fldname: &str) -> Result<i64, std::io::Error>;
+    fn get_string(&mut self, tx: &mut Transaction, fldname: &str)
+        -> Result<String, std::io::Error>;
+    fn get_val(&mut self, tx: &mut Transaction, fldname: &str) -> Result<Constant, std::io::Error>;
+    fn has_field(&self, fldname: &str) -> bool;
+    fn close(&mut self, tx: &mut Transaction);
+
+    /// Where a TEXT or BLOB field of the current record is kept, when the
+    /// record comes straight from a table, so that the value can be streamed
+    /// later instead of read now. Scans that compute or copy their records
+    /// know of no such place and leave it to `get_val`.
+    fn get_large(
+        &mut self,
+        _tx: &mut Transaction,
+        _fldname: &str,
+    ) -> Result<Option<LargeValue>, std::io::Error> {
+        Ok(None)
+    }
+
+    /// The values of the single field of the query's records, with the
+    /// fields of the current record it refers to taking their values. Only
+    /// a [`SubqueryScan`](crate::subquery_scan::SubqueryScan) runs queries,
+    /// scans that just pass its records on ask it.
+    fn subquery_values(
+        &mut self,
+        _tx: &mut Transaction,
+        query: &QueryData,
+    ) -> Result<Vec<Constant>, std::io::Error> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            format!("subquery ({}) cannot run here", query),
+        ))
+    }
+}
+
+/// A scan whose records can be changed, because each of them is a record
+/// of a single table.
+pub(crate) trait UpdateScan: Scan {
+    fn set_int(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+        value: i64,
+    ) -> Result<(), std::io::Error>;
+    fn set_string(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+        value: &str,
+    ) -> Result<(), std::io::Error>;
+    fn set_val(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+        value: &Constant,
+    ) -> Result<(), std::io::Error>;
+    fn insert(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error>;
+    fn delete(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error>;
+    fn get_rid(&self) -> Rid;
+    fn move_to_rid(&mut self, tx: &mut Transaction, rid: Rid) -> Result<(), std::io::Error>;
+}
+
+impl<S: Scan + ?Sized> Scan for Box<S> {
+    fn before_first(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        (**self).before_first(tx)
+    }
+
+    fn next(&mut self, tx: &mut Transaction) -> Result<bool, std::io::Error> {
+        (**self).next(tx)
+    }
+
+    fn get_int(&mut self, tx: &mut Transaction, fldname: &str) -> Result<i64, std::io::Error> {
+        (**self).get_int(tx, fldname)
+    }
+
+    fn get_string(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+    ) -> Result<String, std::io::Error> {
+        (**self).get_string(tx, fldname)
+    }
+
+    fn get_val(&mut self, tx: &mut Transaction, fldname: &str) -> Result<Constant, std::io::Error> {
+        (**self).get_val(tx, fldname)
+    }
+
+    fn has_field(&self, fldname: &str) -> bool {
+        (**self).has_field(fldname)
+    }
+
+    fn close(&mut self, tx: &mut Transaction) {
+        (**self).close(tx)
+    }
+
+    fn get_large(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+    ) -> Result<Option<LargeValue>, std::io::Error> {
+        (**self).get_large(tx, fldname)
+    }
+
+    fn subquery_values(
+        &mut self,
+        tx: &mut Transaction,
+        query: &QueryData,
+    ) -> Result<Vec<Constant>, std::io::Error> {
+        (**self).subquery_values(tx, query)
+    }
+}
+
+impl<S: UpdateScan + ?Sized> UpdateScan for Box<S> {
+    fn set_int(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+        value: i64,
+    ) -> Result<(), std::io::Error> {
+        (**self).set_int(tx, fldname, value)
+    }
+
+    fn set_string(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+        value: &str,
+    ) -> Result<(), std::io::Error> {
+        (**self).set_string(tx, fldname, value)
+    }
+
+    fn set_val(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+        value: &Constant,
+    ) -> Result<(), std::io::Error> {
+        (**self).set_val(tx, fldname, value)
+    }
+
+    fn insert(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        (**self).insert(tx)
+    }
+
+    fn delete(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        (**self).delete(tx)
+    }
+
+    fn get_rid(&self) -> Rid {
+        (**self).get_rid()
+    }
+
+    fn move_to_rid(&mut self, tx: &mut Transaction, rid: Rid) -> Result<(), std::io::Error> {
+        (**self).move_to_rid(tx, rid)
+    }
+}
+
+/// The current record of a scan, as expressions see it.
+pub(crate) struct ScanRow<'s, 't, 'a, S: Scan + ?Sized> {
+    pub(crate) scan: &'s mut S,
+    pub(crate) tx: &'t mut Transaction<'a>,
+}
+
+impl<S: Scan + ?Sized> Row for ScanRow<'_, '_, '_, S> {
+    fn get_val(&mut self, fldname: &str) -> Result<Constant, std::io::Error> {
+        self.scan.get_val(self.tx, fldname)
+    }
+
+    fn subquery_values(&mut self, query: &QueryData) -> Result<Vec<Constant>, std::io::Error> {
+        self.scan.subquery_values(self.tx, query)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        layout::Layout, parser::parse_predicate, product_scan::ProductScan,
+        project_scan::ProjectScan, schema::Schema, select_scan::SelectScan, simpledb::SimpleDB,
+        table_scan::TableScan, transaction::Transaction,
+    };
+
+    use super::{Scan, UpdateScan};
+
+    fn contents(scan: &mut dyn Scan, tx: &mut Transaction, fields: &[&str]) -> Vec<String> {
+        let mut rows = Vec::new();
+        scan.before_first(tx).unwrap();
+        while scan.next(tx).unwrap() {
+            let values = fields
+                .iter()
+                .map(|f| scan.get_val(tx, f).unwrap().to_string())
+                .collect::<Vec<_>>();
+            rows.push(values.join(" "));
+        }
+        rows
+    }
+
+    #[test]
+    fn test_scans() {
+        let _ = std::fs::remove_dir_all("scantest");
+        let mut db = SimpleDB::new("scantest", 400, 8).unwrap();
+        let mut tx = Transaction::new(
+            &db.file_manager,
+            &mut db.log_manager,
+            &mut db.buffer_manager,
+        );
+
+        let mut schema = Schema::new();
+        schema.add_int_field("A");
+        schema.add_string_field("B", 9);
+        let mut ts = TableScan::new(&mut tx, "T1", Layout::new(schema)).unwrap();
+        for i in 0..20 {
+            ts.insert(&mut tx).unwrap();
+            ts.set_int(&mut tx, "A", i).unwrap();
+            ts.set_string(&mut tx, "B", &format!("b{}", i)).unwrap();
+        }
+        ts.close(&mut tx);
+        let mut schema = Schema::new();
+        schema.add_int_field("C");
+        schema.add_string_field("D", 9);
+        let mut ts = TableScan::new(&mut tx, "T2", Layout::new(schema.clone())).unwrap();
+        for i in 0..3 {
+            ts.insert(&mut tx).unwrap();
+            ts.set_int(&mut tx, "C", i * 10).unwrap();
+            ts.set_string(&mut tx, "D", &format!("d{}", i)).unwrap();
+        }
+        ts.close(&mut tx);
+
+        // Delete through a selection, the table sees it
+        let mut schema1 = Schema::new();
+        schema1.add_int_field("A");
+        schema1.add_string_field("B", 9);
+        let t1 = TableScan::new(&mut tx, "T1", Layout::new(schema1.clone())).unwrap();
+        let mut select = SelectScan::new(t1, parse_predicate("A >= 5").unwrap());
+        select.before_first(&mut tx).unwrap();
+        while select.next(&mut tx).unwrap() {
+            if select.get_int(&mut tx, "A").unwrap() % 2 == 1 {
+                select.delete(&mut tx).unwrap();
+            } else {
+                select.set_string(&mut tx, "B", "even").unwrap();
+            }
+        }
+        select.close(&mut tx);
+
+        // A join of the two tables as a selection over their product
+        let t1 = TableScan::new(&mut tx, "T1", Layout::new(schema1.clone())).unwrap();
+        let t2 = TableScan::new(&mut tx, "T2", Layout::new(schema.clone())).unwrap();
+        let product = ProductScan::new(t1, t2, &mut tx).unwrap();
+        let select = SelectScan::new(product, parse_predicate("A = C").unwrap());
+        let mut project = ProjectScan::new(select, vec!["B".to_string(), "D".to_string()]);
+        assert!(project.has_field("D"));
+        assert!(!project.has_field("A"));
+        assert_eq!(
+            contents(&mut project, &mut tx, &["B", "D"]),
+            ["'b0' 'd0'", "'even' 'd1'"]
+        );
+        assert!(project.get_int(&mut tx, "A").is_err());
+        project.close(&mut tx);
+
+        // An empty side gives an empty product
+        let t1 = TableScan::new(&mut tx, "T1", Layout::new(schema1.clone())).unwrap();
+        let t2 = TableScan::new(&mut tx, "T2", Layout::new(schema)).unwrap();
+        let empty = SelectScan::new(t2, parse_predicate("C = 5").unwrap());
+        let mut product: Box<dyn Scan> = Box::new(ProductScan::new(empty, t1, &mut tx).unwrap());
+        assert!(contents(&mut product, &mut tx, &["A"]).is_empty());
+        product.close(&mut tx);
+
+        let mut t1: Box<dyn UpdateScan> =
+            Box::new(TableScan::new(&mut tx, "T1", Layout::new(schema1)).unwrap());
+        assert_eq!(contents(&mut t1, &mut tx, &["A"]).len(), 12);
+        t1.close(&mut tx);
+        tx.commit().unwrap();
+    }
+}
@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+
+use crate::resultset::ColumnType;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct FieldInfo {
+    pub(crate) ctype: ColumnType,
+    pub(crate) length: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Schema {
+    fields: Vec<String>,
+    info: HashMap<String, FieldInfo>,
+}
+
+impl Schema {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn add_field(&mut self, name: &str, ctype: ColumnType, length: u64) {
+        if !self.info.contains_key(name) {
+            self.fields.push(name.to_string());
+        }
+        self.info
+            .insert(name.to_string(), FieldInfo { ctype, length });
+    }
+
+    pub(crate) fn add_int_field(&mut self, name: &str) {
+        self.add_field(name, ColumnType::Integer, 0);
+    }
+
+    pub(crate) fn add_string_field(&mut self, name: &str, length: u64) {
+        self.add_field(name, ColumnType::VarChar, length);
+    }
+
+    pub(crate) fn add_text_field(&mut self, name: &str) {
+        self.add_field(name, ColumnType::Text, 0);
+    }
+
+    pub(crate) fn add_blob_field(&mut self, name: &str) {
+        self.add_field(name, ColumnType::Blob, 0);
+    }
+
+    pub(crate) fn add(&mut self, name: &str, schema: &Schema) {
+        let info = schema.info[name];
+        self.add_field(name, info.ctype, info.length);
+    }
+
+    pub(crate) fn add_all(&mut self, schema: &Schema) {
+        for name in &schema.fields {
+            self.add(name, schema);
+        }
+    }
+
+    pub(crate) fn fields(&self) -> &[String] {
+        &self.fields
+    }
+
+    pub(crate) fn has_field(&self, name: &str) -> bool {
+        self.info.contains_key(name)
+    }
+
+    pub(crate) fn ctype(&self, name: &str) -> ColumnType {
+        self.info[name].ctype
+    }
+
+    pub(crate) fn length(&self, name: &str) -> u64 {
+        self.info[name].length
+    }
+}
@@ -0,0 +1,70 @@
+use crate::{
+    constant::Constant, plan::Plan, predicate::Predicate, scan::Scan, schema::Schema,
+    select_scan::SelectScan, transaction::Transaction,
+};
+
+/// The records of the underlying plan that satisfy the predicate, as many
+/// as its selectivity over that plan leaves.
+pub(crate) struct SelectPlan {
+    plan: Box<dyn Plan>,
+    predicate: Predicate,
+    records: u64,
+}
+
+impl SelectPlan {
+    pub(crate) fn new(plan: Box<dyn Plan>, predicate: Predicate) -> Self {
+        let selectivity = predicate.selectivity(plan.as_ref());
+        let records = (plan.records_output() as f64 * selectivity).ceil() as u64;
+        Self {
+            plan,
+            predicate,
+            records,
+        }
+    }
+}
+
+impl Plan for SelectPlan {
+    fn open(&self, tx: &mut Transaction) -> Result<Box<dyn Scan>, std::io::Error> {
+        let scan = self.plan.open(tx)?;
+        Ok(Box::new(SelectScan::new(scan, self.predicate.clone())))
+    }
+
+    fn blocks_accessed(&self) -> u64 {
+        self.plan.blocks_accessed()
+    }
+
+    fn records_output(&self) -> u64 {
+        self.records
+    }
+
+    fn distinct_values(&self, fldname: &str) -> u64 {
+        if self.predicate.equates_with_constant(fldname).is_some() {
+            return 1;
+        }
+        let distinct = match self.predicate.equates_with_field(fldname) {
+            Some(other) => self
+                .plan
+                .distinct_values(fldname)
+                .min(self.plan.distinct_values(other)),
+            None => self.plan.distinct_values(fldname),
+        };
+        distinct.min(self.records)
+    }
+
+    fn schema(&self) -> &Schema {
+        self.plan.schema()
+    }
+
+    fn eq_selectivity(&self, fldname: &str, value: &Constant) -> f64 {
+        self.plan.eq_selectivity(fldname, value)
+    }
+
+    fn range_selectivity(
+        &self,
+        fldname: &str,
+        low: Option<&Constant>,
+        high: Option<&Constant>,
+    ) -> f64 {
+        self.plan.range_selectivity(fldname, low, high)
+    }
+}
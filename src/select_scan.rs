@@ -0,0 +1,123 @@
+use crate::{
+    constant::Constant,
+    overflow::LargeValue,
+    predicate::Predicate,
+    query_data::QueryData,
+    rid::Rid,
+    scan::{Scan, UpdateScan},
+    transaction::Transaction,
+};
+
+/// The records of the underlying scan that satisfy the predicate. Changes go
+/// straight to the underlying scan, so a selection over a table can be
+/// updated.
+pub(crate) struct SelectScan<S: Scan> {
+    scan: S,
+    predicate: Predicate,
+}
+
+impl<S: Scan> SelectScan<S> {
+    pub(crate) fn new(scan: S, predicate: Predicate) -> Self {
+        Self { scan, predicate }
+    }
+}
+
+impl<S: Scan> Scan for SelectScan<S> {
+    fn before_first(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        self.scan.before_first(tx)
+    }
+
+    fn next(&mut self, tx: &mut Transaction) -> Result<bool, std::io::Error> {
+        while self.scan.next(tx)? {
+            if self.predicate.is_satisfied(&mut self.scan, tx)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn get_int(&mut self, tx: &mut Transaction, fldname: &str) -> Result<i64, std::io::Error> {
+        self.scan.get_int(tx, fldname)
+    }
+
+    fn get_string(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+    ) -> Result<String, std::io::Error> {
+        self.scan.get_string(tx, fldname)
+    }
+
+    fn get_val(&mut self, tx: &mut Transaction, fldname: &str) -> Result<Constant, std::io::Error> {
+        self.scan.get_val(tx, fldname)
+    }
+
+    fn has_field(&self, fldname: &str) -> bool {
+        self.scan.has_field(fldname)
+    }
+
+    fn get_large(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+    ) -> Result<Option<LargeValue>, std::io::Error> {
+        self.scan.get_large(tx, fldname)
+    }
+
+    fn close(&mut self, tx: &mut Transaction) {
+        self.scan.close(tx)
+    }
+
+    fn subquery_values(
+        &mut self,
+        tx: &mut Transaction,
+        query: &QueryData,
+    ) -> Result<Vec<Constant>, std::io::Error> {
+        self.scan.subquery_values(tx, query)
+    }
+}
+
+impl<S: UpdateScan> UpdateScan for SelectScan<S> {
+    fn set_int(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+        value: i64,
+    ) -> Result<(), std::io::Error> {
+        self.scan.set_int(tx, fldname, value)
+    }
+
+    fn set_string(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+        value: &str,
+    ) -> Result<(), std::io::Error> {
+        self.scan.set_string(tx, fldname, value)
+    }
+
+    fn set_val(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+        value: &Constant,
+    ) -> Result<(), std::io::Error> {
+        self.scan.set_val(tx, fldname, value)
+    }
+
+    fn insert(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        self.scan.insert(tx)
+    }
+
+    fn delete(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        self.scan.delete(tx)
+    }
+
+    fn get_rid(&self) -> Rid {
+        self.scan.get_rid()
+    }
+
+    fn move_to_rid(&mut self, tx: &mut Transaction, rid: Rid) -> Result<(), std::io::Error> {
+        self.scan.move_to_rid(tx, rid)
+    }
+}
@@ -1,10 +1,4 @@
 
-mod connection;
-mod driver;
-mod resultset;
-mod sql_exception;
-mod statement;
-
 fn main() {
     println!("Hello, world!");
 }
@@ -0,0 +1,65 @@
+use crate::{
+    distinct_plan::DistinctPlan, plan::Plan, query_data::SetOp, scan::Scan, schema::Schema,
+    set_op_scan::SetOpScan, transaction::Transaction, union_plan::compound_schema,
+};
+
+/// The distinct records of one plan that are, for `INTERSECT`, or are not,
+/// for `EXCEPT`, among those of the other. Both sides are sorted and rid of
+/// duplicates first, and the result comes in that order.
+pub(crate) struct SetOpPlan {
+    op: SetOp,
+    p1: DistinctPlan,
+    p2: DistinctPlan,
+    schema: Schema,
+}
+
+impl SetOpPlan {
+    pub(crate) fn new(
+        op: SetOp,
+        p1: Box<dyn Plan>,
+        p2: Box<dyn Plan>,
+        block_size: u64,
+    ) -> Result<Self, std::io::Error> {
+        let schema = compound_schema(p1.schema(), p2.schema())?;
+        Ok(Self {
+            op,
+            p1: DistinctPlan::new(p1, block_size),
+            p2: DistinctPlan::new(p2, block_size),
+            schema,
+        })
+    }
+}
+
+impl Plan for SetOpPlan {
+    fn open(&self, tx: &mut Transaction) -> Result<Box<dyn Scan>, std::io::Error> {
+        let s1 = self.p1.open(tx)?;
+        let s2 = self.p2.open(tx)?;
+        Ok(Box::new(SetOpScan::new(
+            self.op,
+            s1,
+            s2,
+            self.p1.schema().fields().to_vec(),
+            self.p2.schema().fields().to_vec(),
+            tx,
+        )?))
+    }
+
+    fn blocks_accessed(&self) -> u64 {
+        self.p1.blocks_accessed() + self.p2.blocks_accessed()
+    }
+
+    fn records_output(&self) -> u64 {
+        match self.op {
+            SetOp::Intersect => self.p1.records_output().min(self.p2.records_output()),
+            _ => self.p1.records_output(),
+        }
+    }
+
+    fn distinct_values(&self, fldname: &str) -> u64 {
+        self.p1.distinct_values(fldname).min(self.records_output())
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+}
@@ -0,0 +1,185 @@
+use std::cmp::Ordering;
+
+use crate::{constant::Constant, query_data::SetOp, scan::Scan, transaction::Transaction};
+
+/// The records of the first scan that are in the second, for `INTERSECT`,
+/// or that are not, for `EXCEPT`. Both scans have to be sorted on all their
+/// fields in ascending order and be free of duplicates, so that the two can
+/// be merged in a single pass. Records are compared field by field in
+/// position, and NULLs count as equal to each other.
+pub(crate) struct SetOpScan<S1: Scan, S2: Scan> {
+    op: SetOp,
+    s1: S1,
+    s2: S2,
+    fields1: Vec<String>,
+    fields2: Vec<String>,
+    /// Whether `s2` is positioned on a record.
+    s2_on_record: bool,
+}
+
+impl<S1: Scan, S2: Scan> SetOpScan<S1, S2> {
+    pub(crate) fn new(
+        op: SetOp,
+        s1: S1,
+        s2: S2,
+        fields1: Vec<String>,
+        fields2: Vec<String>,
+        tx: &mut Transaction,
+    ) -> Result<Self, std::io::Error> {
+        assert!(matches!(op, SetOp::Intersect | SetOp::Except));
+        assert_eq!(fields1.len(), fields2.len());
+        let mut scan = Self {
+            op,
+            s1,
+            s2,
+            fields1,
+            fields2,
+            s2_on_record: false,
+        };
+        Scan::before_first(&mut scan, tx)?;
+        Ok(scan)
+    }
+
+    fn compare(&mut self, tx: &mut Transaction) -> Result<Ordering, std::io::Error> {
+        for (f1, f2) in self.fields1.iter().zip(&self.fields2) {
+            let ordering = self.s1.get_val(tx, f1)?.cmp(&self.s2.get_val(tx, f2)?);
+            if ordering.is_ne() {
+                return Ok(ordering);
+            }
+        }
+        Ok(Ordering::Equal)
+    }
+}
+
+impl<S1: Scan, S2: Scan> Scan for SetOpScan<S1, S2> {
+    fn before_first(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        self.s1.before_first(tx)?;
+        self.s2.before_first(tx)?;
+        self.s2_on_record = self.s2.next(tx)?;
+        Ok(())
+    }
+
+    fn next(&mut self, tx: &mut Transaction) -> Result<bool, std::io::Error> {
+        while self.s1.next(tx)? {
+            let mut ordering = Ordering::Less;
+            while self.s2_on_record {
+                ordering = self.compare(tx)?;
+                if ordering.is_le() {
+                    break;
+                }
+                self.s2_on_record = self.s2.next(tx)?;
+            }
+            let found = self.s2_on_record && ordering.is_eq();
+            if found == (self.op == SetOp::Intersect) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn get_int(&mut self, tx: &mut Transaction, fldname: &str) -> Result<i64, std::io::Error> {
+        self.s1.get_int(tx, fldname)
+    }
+
+    fn get_string(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+    ) -> Result<String, std::io::Error> {
+        self.s1.get_string(tx, fldname)
+    }
+
+    fn get_val(&mut self, tx: &mut Transaction, fldname: &str) -> Result<Constant, std::io::Error> {
+        self.s1.get_val(tx, fldname)
+    }
+
+    fn has_field(&self, fldname: &str) -> bool {
+        self.s1.has_field(fldname)
+    }
+
+    fn close(&mut self, tx: &mut Transaction) {
+        self.s1.close(tx);
+        self.s2.close(tx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        distinct_scan::DistinctScan,
+        layout::Layout,
+        query_data::SetOp,
+        record_comparator::{RecordComparator, SortKey},
+        scan::Scan,
+        schema::Schema,
+        simpledb::SimpleDB,
+        sort_scan::SortScan,
+        table_scan::TableScan,
+        transaction::Transaction,
+    };
+
+    use super::SetOpScan;
+
+    #[test]
+    fn test_set_op_scan() {
+        let _ = std::fs::remove_dir_all("setoptest");
+        let mut db = SimpleDB::new("setoptest", 400, 8).unwrap();
+        let mut tx = Transaction::new(
+            &db.file_manager,
+            &mut db.log_manager,
+            &mut db.buffer_manager,
+        );
+
+        let tables = [
+            (
+                "T",
+                "A",
+                vec![Some(5), Some(1), None, Some(3), Some(1), Some(7)],
+            ),
+            (
+                "U",
+                "C",
+                vec![Some(3), Some(8), None, Some(1), Some(3), Some(0)],
+            ),
+        ];
+        let mut schemas = Vec::new();
+        for (tblname, fldname, values) in tables {
+            let mut schema = Schema::new();
+            schema.add_int_field(fldname);
+            let mut ts = TableScan::new(&mut tx, tblname, Layout::new(schema.clone())).unwrap();
+            for value in values {
+                ts.insert(&mut tx).unwrap();
+                match value {
+                    Some(value) => ts.set_int(&mut tx, fldname, value).unwrap(),
+                    None => ts.set_null(&mut tx, fldname).unwrap(),
+                }
+            }
+            ts.close(&mut tx);
+            schemas.push((tblname, fldname, schema));
+        }
+
+        for (op, expected) in [
+            (SetOp::Intersect, vec!["NULL", "1", "3"]),
+            (SetOp::Except, vec!["5", "7"]),
+        ] {
+            let mut inputs = Vec::new();
+            for (tblname, fldname, schema) in &schemas {
+                let ts = TableScan::new(&mut tx, tblname, Layout::new(schema.clone())).unwrap();
+                let comparator = RecordComparator::new(vec![SortKey::new(fldname, false)]);
+                let sorted = SortScan::new(ts, schema, comparator, &mut tx).unwrap();
+                inputs.push(DistinctScan::new(sorted, vec![fldname.to_string()]));
+            }
+            let u = inputs.pop().unwrap();
+            let t = inputs.pop().unwrap();
+            let fields = (vec!["A".to_string()], vec!["C".to_string()]);
+            let mut scan = SetOpScan::new(op, t, u, fields.0, fields.1, &mut tx).unwrap();
+            let mut values = Vec::new();
+            while scan.next(&mut tx).unwrap() {
+                values.push(scan.get_val(&mut tx, "A").unwrap().to_string());
+            }
+            assert_eq!(values, expected);
+            scan.close(&mut tx);
+        }
+        tx.commit().unwrap();
+    }
+}
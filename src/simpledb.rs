@@ -1,9 +1,22 @@
-use crate::{buffer_manager::BufferManager, file_manager::FileManager, log_manager::LogManager};
+use std::rc::Rc;
+
+use crate::{
+    basic_query_planner::BasicQueryPlanner,
+    basic_update_planner::BasicUpdatePlanner,
+    buffer_manager::BufferManager,
+    file_manager::FileManager,
+    log_manager::LogManager,
+    metadata_manager::MetadataManager,
+    planner::{Planner, QueryPlanner},
+    transaction::Transaction,
+};
 
 pub(crate) struct SimpleDB {
     pub(crate) file_manager: FileManager,
     pub(crate) log_manager: LogManager,
     pub(crate) buffer_manager: BufferManager,
+    pub(crate) metadata_manager: MetadataManager,
+    pub(crate) planner: Planner,
 }
 
 impl SimpleDB {
@@ -14,14 +27,30 @@ impl SimpleDB {
     ) -> Result<Self, std::io::Error> {
         let file_manager = FileManager::new(dirname, blocksize)?;
         // lm = new LogMgr(fm, LOG_FILE);
-        let log_manager = LogManager::new(&file_manager, "logfile");
+        let mut log_manager = LogManager::new(&file_manager, "logfile");
         // bm = new BufferMgr(fm, lm, buffsize);
-        let buffer_manager = BufferManager::new(&file_manager, buffersize);
+        let mut buffer_manager = BufferManager::new(&file_manager, buffersize);
+
+        // The catalog tables are created along with the database directory
+        let mut tx = Transaction::new(&file_manager, &mut log_manager, &mut buffer_manager);
+        if !file_manager.is_new {
+            tx.recover()?;
+        }
+        let metadata_manager = MetadataManager::new(file_manager.is_new, &mut tx)?;
+        tx.commit()?;
 
         Ok(Self {
             file_manager,
             log_manager,
             buffer_manager,
+            metadata_manager,
+            planner: Planner::new(Rc::new(BasicQueryPlanner), Box::new(BasicUpdatePlanner)),
         })
     }
-}
\ No newline at end of file
+
+    /// Plans queries with the given planner from now on, such as
+    /// `HeuristicQueryPlanner` in place of the default `BasicQueryPlanner`.
+    pub(crate) fn set_query_planner(&mut self, query_planner: Rc<dyn QueryPlanner>) {
+        self.planner.set_query_planner(query_planner);
+    }
+}
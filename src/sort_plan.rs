@@ -0,0 +1,78 @@
+use crate::{
+    constant::Constant,
+    layout::Layout,
+    plan::Plan,
+    record_comparator::{RecordComparator, SortKey},
+    scan::Scan,
+    schema::Schema,
+    sort_scan::SortScan,
+    transaction::Transaction,
+};
+
+/// The records of the underlying plan in the order of the sort keys. The
+/// sort happens when the plan is opened; what reading the result costs is
+/// that of the temporary table holding it.
+pub(crate) struct SortPlan {
+    plan: Box<dyn Plan>,
+    keys: Vec<SortKey>,
+    block_size: u64,
+}
+
+impl SortPlan {
+    pub(crate) fn new(plan: Box<dyn Plan>, keys: Vec<SortKey>, block_size: u64) -> Self {
+        Self {
+            plan,
+            keys,
+            block_size,
+        }
+    }
+}
+
+impl Plan for SortPlan {
+    fn open(&self, tx: &mut Transaction) -> Result<Box<dyn Scan>, std::io::Error> {
+        let src = self.plan.open(tx)?;
+        let comparator = RecordComparator::new(self.keys.clone());
+        Ok(Box::new(SortScan::new(
+            src,
+            self.plan.schema(),
+            comparator,
+            tx,
+        )?))
+    }
+
+    fn blocks_accessed(&self) -> u64 {
+        temp_blocks(self.schema(), self.records_output(), self.block_size)
+    }
+
+    fn records_output(&self) -> u64 {
+        self.plan.records_output()
+    }
+
+    fn distinct_values(&self, fldname: &str) -> u64 {
+        self.plan.distinct_values(fldname)
+    }
+
+    fn schema(&self) -> &Schema {
+        self.plan.schema()
+    }
+
+    fn eq_selectivity(&self, fldname: &str, value: &Constant) -> f64 {
+        self.plan.eq_selectivity(fldname, value)
+    }
+
+    fn range_selectivity(
+        &self,
+        fldname: &str,
+        low: Option<&Constant>,
+        high: Option<&Constant>,
+    ) -> f64 {
+        self.plan.range_selectivity(fldname, low, high)
+    }
+}
+
+/// The blocks a temporary table of the records would take.
+pub(crate) fn temp_blocks(schema: &Schema, records: u64, block_size: u64) -> u64 {
+    let slot_size = Layout::new(schema.clone()).slot_size();
+    let records_per_block = (block_size / slot_size).max(1);
+    records.div_ceil(records_per_block)
+}
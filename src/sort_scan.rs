@@ -0,0 +1,295 @@
+use crate::{
+    constant::Constant, record_comparator::RecordComparator, scan::Scan, schema::Schema,
+    table_scan::TableScan, temp_table::TempTable, transaction::Transaction,
+};
+
+/// The records of a scan in the comparator's order. They are copied into
+/// temporary tables, each a run of records already in order, and runs are
+/// merged pairwise until at most two are left. Those two are merged as the
+/// scan goes.
+pub(crate) struct SortScan {
+    runs: Vec<TempTable>,
+    scans: Vec<TableScan>,
+    has_more: Vec<bool>,
+    current: Option<usize>,
+    comparator: RecordComparator,
+    schema: Schema,
+}
+
+impl SortScan {
+    /// Sorts the records of `src`, which has the fields of the schema, and
+    /// closes it.
+    pub(crate) fn new<S: Scan>(
+        mut src: S,
+        schema: &Schema,
+        comparator: RecordComparator,
+        tx: &mut Transaction,
+    ) -> Result<Self, std::io::Error> {
+        let mut runs = split_into_runs(&mut src, schema, &comparator, tx)?;
+        src.close(tx);
+        while runs.len() > 2 {
+            runs = merge_all(runs, schema, &comparator, tx)?;
+        }
+        let mut scans = Vec::new();
+        for run in &runs {
+            scans.push(run.open(tx)?);
+        }
+        let mut scan = Self {
+            runs,
+            has_more: vec![false; scans.len()],
+            scans,
+            current: None,
+            comparator,
+            schema: schema.clone(),
+        };
+        Scan::before_first(&mut scan, tx)?;
+        Ok(scan)
+    }
+}
+
+impl Scan for SortScan {
+    fn before_first(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        self.current = None;
+        for (scan, has_more) in self.scans.iter_mut().zip(&mut self.has_more) {
+            scan.before_first(tx)?;
+            *has_more = scan.next(tx)?;
+        }
+        Ok(())
+    }
+
+    fn next(&mut self, tx: &mut Transaction) -> Result<bool, std::io::Error> {
+        if let Some(i) = self.current {
+            self.has_more[i] = self.scans[i].next(tx)?;
+        }
+        self.current = match self.has_more[..] {
+            [true, true] => {
+                let (s1, s2) = self.scans.split_at_mut(1);
+                if self.comparator.compare(&mut s1[0], &mut s2[0], tx)?.is_le() {
+                    Some(0)
+                } else {
+                    Some(1)
+                }
+            }
+            [true, ..] => Some(0),
+            [_, true] => Some(1),
+            _ => None,
+        };
+        Ok(self.current.is_some())
+    }
+
+    fn get_int(&mut self, tx: &mut Transaction, fldname: &str) -> Result<i64, std::io::Error> {
+        self.current_scan().get_int(tx, fldname)
+    }
+
+    fn get_string(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+    ) -> Result<String, std::io::Error> {
+        self.current_scan().get_string(tx, fldname)
+    }
+
+    fn get_val(&mut self, tx: &mut Transaction, fldname: &str) -> Result<Constant, std::io::Error> {
+        self.current_scan().get_val(tx, fldname)
+    }
+
+    fn has_field(&self, fldname: &str) -> bool {
+        self.schema.has_field(fldname)
+    }
+
+    fn close(&mut self, tx: &mut Transaction) {
+        for scan in &mut self.scans {
+            scan.close(tx);
+        }
+        self.scans.clear();
+        self.has_more.clear();
+        self.current = None;
+        for run in self.runs.drain(..) {
+            // What cannot be dropped now goes when the database next starts
+            let _ = run.remove(tx);
+        }
+    }
+}
+
+impl SortScan {
+    fn current_scan(&mut self) -> &mut TableScan {
+        &mut self.scans[self.current.expect("sort scan is not on a record")]
+    }
+}
+
+fn split_into_runs(
+    src: &mut dyn Scan,
+    schema: &Schema,
+    comparator: &RecordComparator,
+    tx: &mut Transaction,
+) -> Result<Vec<TempTable>, std::io::Error> {
+    let mut runs = Vec::new();
+    src.before_first(tx)?;
+    if !src.next(tx)? {
+        return Ok(runs);
+    }
+    let mut run = TempTable::new(schema.clone());
+    let mut dest = run.open(tx)?;
+    copy(src, &mut dest, schema, tx)?;
+    while src.next(tx)? {
+        if comparator.compare(src, &mut dest, tx)?.is_lt() {
+            dest.close(tx);
+            runs.push(run);
+            run = TempTable::new(schema.clone());
+            dest = run.open(tx)?;
+        }
+        copy(src, &mut dest, schema, tx)?;
+    }
+    dest.close(tx);
+    runs.push(run);
+    Ok(runs)
+}
+
+fn merge_all(
+    runs: Vec<TempTable>,
+    schema: &Schema,
+    comparator: &RecordComparator,
+    tx: &mut Transaction,
+) -> Result<Vec<TempTable>, std::io::Error> {
+    let mut merged = Vec::new();
+    let mut runs = runs.into_iter();
+    while let Some(r1) = runs.next() {
+        match runs.next() {
+            Some(r2) => merged.push(merge_two(r1, r2, schema, comparator, tx)?),
+            None => merged.push(r1),
+        }
+    }
+    Ok(merged)
+}
+
+fn merge_two(
+    r1: TempTable,
+    r2: TempTable,
+    schema: &Schema,
+    comparator: &RecordComparator,
+    tx: &mut Transaction,
+) -> Result<TempTable, std::io::Error> {
+    let mut src1 = r1.open(tx)?;
+    let mut src2 = r2.open(tx)?;
+    let result = TempTable::new(schema.clone());
+    let mut dest = result.open(tx)?;
+    let mut has_more1 = src1.next(tx)?;
+    let mut has_more2 = src2.next(tx)?;
+    while has_more1 && has_more2 {
+        if comparator.compare(&mut src1, &mut src2, tx)?.is_le() {
+            copy(&mut src1, &mut dest, schema, tx)?;
+            has_more1 = src1.next(tx)?;
+        } else {
+            copy(&mut src2, &mut dest, schema, tx)?;
+            has_more2 = src2.next(tx)?;
+        }
+    }
+    while has_more1 {
+        copy(&mut src1, &mut dest, schema, tx)?;
+        has_more1 = src1.next(tx)?;
+    }
+    while has_more2 {
+        copy(&mut src2, &mut dest, schema, tx)?;
+        has_more2 = src2.next(tx)?;
+    }
+    src1.close(tx);
+    src2.close(tx);
+    dest.close(tx);
+    r1.remove(tx)?;
+    r2.remove(tx)?;
+    Ok(result)
+}
+
+fn copy(
+    src: &mut dyn Scan,
+    dest: &mut TableScan,
+    schema: &Schema,
+    tx: &mut Transaction,
+) -> Result<(), std::io::Error> {
+    dest.insert(tx)?;
+    for fldname in schema.fields() {
+        let value = src.get_val(tx, fldname)?;
+        dest.set_val(tx, fldname, &value)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        layout::Layout,
+        record_comparator::{RecordComparator, SortKey},
+        scan::Scan,
+        schema::Schema,
+        simpledb::SimpleDB,
+        table_scan::TableScan,
+        transaction::Transaction,
+    };
+
+    use super::SortScan;
+
+    #[test]
+    fn test_sort_scan() {
+        let _ = std::fs::remove_dir_all("sortscantest");
+        let mut db = SimpleDB::new("sortscantest", 400, 8).unwrap();
+        let mut tx = Transaction::new(
+            &db.file_manager,
+            &mut db.log_manager,
+            &mut db.buffer_manager,
+        );
+
+        let mut schema = Schema::new();
+        schema.add_int_field("A");
+        schema.add_string_field("B", 9);
+        let layout = Layout::new(schema.clone());
+        let mut ts = TableScan::new(&mut tx, "T", layout.clone()).unwrap();
+        // Scrambled enough to start many runs
+        for i in 0..200 {
+            ts.insert(&mut tx).unwrap();
+            ts.set_int(&mut tx, "A", (i * 37) % 10).unwrap();
+            if i % 7 == 0 {
+                ts.set_null(&mut tx, "B").unwrap();
+            } else {
+                ts.set_string(&mut tx, "B", &format!("b{:03}", (i * 53) % 200))
+                    .unwrap();
+            }
+        }
+        ts.close(&mut tx);
+
+        let comparator =
+            RecordComparator::new(vec![SortKey::new("A", true), SortKey::new("B", false)]);
+        let ts = TableScan::new(&mut tx, "T", layout.clone()).unwrap();
+        let mut sorted = SortScan::new(ts, &schema, comparator, &mut tx).unwrap();
+        assert!(sorted.has_field("B"));
+        let mut records = Vec::new();
+        while sorted.next(&mut tx).unwrap() {
+            records.push((
+                sorted.get_val(&mut tx, "A").unwrap(),
+                sorted.get_val(&mut tx, "B").unwrap(),
+            ));
+        }
+        sorted.close(&mut tx);
+        assert_eq!(records.len(), 200);
+        let mut expected = records.clone();
+        expected.sort_by(|r1, r2| r2.0.cmp(&r1.0).then(r1.1.cmp(&r2.1)));
+        assert_eq!(records, expected);
+        assert!(records[0].1.is_null());
+
+        // Sorting nothing gives nothing
+        let ts = TableScan::new(&mut tx, "E", layout).unwrap();
+        let comparator = RecordComparator::new(vec![SortKey::new("A", false)]);
+        let mut sorted = SortScan::new(ts, &schema, comparator, &mut tx).unwrap();
+        assert!(!sorted.next(&mut tx).unwrap());
+        sorted.close(&mut tx);
+        tx.commit().unwrap();
+
+        let temp_files = std::fs::read_dir("sortscantest")
+            .unwrap()
+            .filter(|e| {
+                let name = e.as_ref().unwrap().file_name();
+                name.to_string_lossy().starts_with("temp")
+            })
+            .count();
+        assert_eq!(temp_files, 0);
+    }
+}
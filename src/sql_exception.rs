@@ -1,4 +1,78 @@
+use std::fmt::Display;
 
+#[derive(Debug)]
 pub(crate) enum SQLException {
+    Io(std::io::Error),
+    /// The statement text could not be parsed, the position is that of the
+    /// offending token, counting from 1.
+    BadSyntax {
+        message: String,
+        line: usize,
+        column: usize,
+    },
+    /// A write would break the named constraint of the table.
+    ConstraintViolation {
+        constraint: String,
+        table: String,
+        detail: String,
+    },
+    /// A write would break the named foreign key from `table` to
+    /// `referenced`.
+    ForeignKeyViolation {
+        constraint: String,
+        table: String,
+        referenced: String,
+        detail: String,
+    },
+}
 
-}
\ No newline at end of file
+impl Display for SQLException {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SQLException::Io(e) => write!(f, "{}", e),
+            SQLException::BadSyntax {
+                message,
+                line,
+                column,
+            } => write!(
+                f,
+                "syntax error at line {}, column {}: {}",
+                line, column, message
+            ),
+            SQLException::ConstraintViolation {
+                constraint,
+                table,
+                detail,
+            } => write!(
+                f,
+                "constraint {} on {} violated: {}",
+                constraint, table, detail
+            ),
+            SQLException::ForeignKeyViolation {
+                constraint,
+                table,
+                referenced,
+                detail,
+            } => write!(
+                f,
+                "foreign key {} from {} to {} violated: {}",
+                constraint, table, referenced, detail
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SQLException {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SQLException::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for SQLException {
+    fn from(e: std::io::Error) -> Self {
+        SQLException::Io(e)
+    }
+}
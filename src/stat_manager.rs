@@ -0,0 +1,582 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    constant::Constant,
+    histogram::{decode_constants, encode_constants, ColumnStats},
+    layout::Layout,
+    resultset::ColumnType,
+    schema::Schema,
+    table_manager::{TableManager, MAX_NAME},
+    table_scan::TableScan,
+    transaction::Transaction,
+};
+
+// How many updates may happen before the cached statistics are recomputed
+pub(crate) const DEFAULT_REFRESH_THRESHOLD: u64 = 100;
+// How many blocks ANALYZE reads from each table
+pub(crate) const DEFAULT_SAMPLE_BLOCKS: u64 = 100;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct StatInfo {
+    num_blocks: u64,
+    num_records: u64,
+    distinct_values: HashMap<String, u64>,
+    columns: HashMap<String, ColumnStats>,
+}
+
+impl StatInfo {
+    pub(crate) fn blocks_accessed(&self) -> u64 {
+        self.num_blocks
+    }
+
+    pub(crate) fn records_output(&self) -> u64 {
+        self.num_records
+    }
+
+    pub(crate) fn distinct_values(&self, field: &str) -> u64 {
+        self.distinct_values
+            .get(field)
+            .copied()
+            .unwrap_or(self.num_records)
+    }
+
+    /// The histogram and most common values of an analyzed field.
+    pub(crate) fn column_stats(&self, field: &str) -> Option<&ColumnStats> {
+        self.columns.get(field)
+    }
+
+    /// Estimated fraction of records where `field` equals `value`.
+    pub(crate) fn eq_selectivity(&self, field: &str, value: &Constant) -> f64 {
+        match self.columns.get(field) {
+            Some(stats) => stats.eq_selectivity(value),
+            None => 1.0 / self.distinct_values(field).max(1) as f64,
+        }
+    }
+
+    /// Estimated fraction of records where `field` lies between the bounds.
+    pub(crate) fn range_selectivity(
+        &self,
+        field: &str,
+        low: Option<&Constant>,
+        high: Option<&Constant>,
+    ) -> f64 {
+        match self.columns.get(field) {
+            Some(stats) => stats.range_selectivity(low, high),
+            // The traditional guess for a range without a histogram
+            None => 1.0 / 3.0,
+        }
+    }
+}
+
+/// Computes block, record and distinct value counts per table by scanning
+/// its heap file. The results are cached until enough updates have been
+/// reported to make them stale.
+///
+/// Tables that went through `analyze` are only sampled instead, and their
+/// statistics are kept in the `histcat` catalog table along with a histogram
+/// per field. Once the statistics have been refreshed, the next time such a
+/// table is looked up it is sampled again, as `analyze` would.
+pub(crate) struct StatManager {
+    table_stats: HashMap<String, StatInfo>,
+    num_updates: u64,
+    refresh_threshold: u64,
+    sample_blocks: u64,
+    hcat_layout: Layout,
+    // The analyzed tables sampled again since the last refresh. None until
+    // the first one, when what histcat holds is as fresh as anything.
+    resampled: Option<HashSet<String>>,
+}
+
+impl StatManager {
+    pub(crate) fn new(
+        is_new: bool,
+        table_manager: &TableManager,
+        refresh_threshold: u64,
+        tx: &mut Transaction,
+    ) -> Result<Self, std::io::Error> {
+        let mut schema = Schema::new();
+        schema.add_string_field("tblname", MAX_NAME);
+        schema.add_string_field("fldname", MAX_NAME);
+        schema.add_int_field("numblocks");
+        schema.add_int_field("numrecs");
+        schema.add_int_field("distinct");
+        schema.add_text_field("mcvvals");
+        schema.add_text_field("mcvfreqs");
+        schema.add_text_field("bounds");
+        if is_new {
+            table_manager.create_table("histcat", &schema, tx)?;
+        }
+
+        Ok(Self {
+            table_stats: HashMap::new(),
+            num_updates: 0,
+            refresh_threshold,
+            sample_blocks: DEFAULT_SAMPLE_BLOCKS,
+            hcat_layout: Layout::new(schema),
+            resampled: None,
+        })
+    }
+
+    pub(crate) fn set_sample_blocks(&mut self, sample_blocks: u64) {
+        self.sample_blocks = sample_blocks.max(1);
+    }
+
+    pub(crate) fn set_refresh_threshold(&mut self, refresh_threshold: u64) {
+        self.refresh_threshold = refresh_threshold;
+    }
+
+    /// Called by whatever modifies table contents, `count` being the number
+    /// of records inserted, deleted or modified.
+    pub(crate) fn record_updates(&mut self, count: u64) {
+        self.num_updates += count;
+        if self.num_updates >= self.refresh_threshold {
+            self.table_stats.clear();
+            self.num_updates = 0;
+            self.resampled = Some(HashSet::new());
+        }
+    }
+
+    pub(crate) fn get_stat_info(
+        &mut self,
+        tblname: &str,
+        layout: &Layout,
+        tx: &mut Transaction,
+    ) -> Result<StatInfo, std::io::Error> {
+        if let Some(stat_info) = self.table_stats.get(tblname) {
+            return Ok(stat_info.clone());
+        }
+        let stat_info = self.table_stats(tblname, layout, tx)?;
+        self.table_stats
+            .insert(tblname.to_string(), stat_info.clone());
+        Ok(stat_info)
+    }
+
+    /// Recomputes the statistics of every table in the catalog right away.
+    pub(crate) fn refresh_statistics(
+        &mut self,
+        table_manager: &TableManager,
+        tx: &mut Transaction,
+    ) -> Result<(), std::io::Error> {
+        self.table_stats.clear();
+        self.num_updates = 0;
+        self.resampled = Some(HashSet::new());
+        for tblname in table_manager.table_names(tx)? {
+            if let Some(layout) = table_manager.get_layout(&tblname, tx)? {
+                let stat_info = self.table_stats(&tblname, &layout, tx)?;
+                self.table_stats.insert(tblname, stat_info);
+            }
+        }
+        Ok(())
+    }
+
+    /// Samples the table and stores an equi-depth histogram and the most
+    /// common values of each field in `histcat`.
+    pub(crate) fn analyze(
+        &mut self,
+        tblname: &str,
+        layout: &Layout,
+        tx: &mut Transaction,
+    ) -> Result<StatInfo, std::io::Error> {
+        let fields = comparable_fields(layout);
+        let num_blocks = tx.size(&format!("{}.tbl", tblname))?;
+        let sampled_blocks = if num_blocks <= self.sample_blocks {
+            (0..num_blocks).collect::<Vec<_>>()
+        } else {
+            // Evenly spread over the file
+            (0..self.sample_blocks)
+                .map(|i| i * num_blocks / self.sample_blocks)
+                .collect()
+        };
+
+        let mut samples: HashMap<&str, Vec<Constant>> = HashMap::new();
+        let mut sampled_records = 0;
+        let mut ts = TableScan::new(tx, tblname, layout.clone())?;
+        for &block_number in &sampled_blocks {
+            ts.move_to_block(tx, block_number)?;
+            while ts.next(tx)? && ts.get_rid().block_number == block_number {
+                sampled_records += 1;
+                for field in &fields {
+                    // NULLs are neither equal nor in any range
+                    let value = ts.get_val(tx, field)?;
+                    if !value.is_null() {
+                        samples.entry(field).or_default().push(value);
+                    }
+                }
+            }
+        }
+        ts.close(tx);
+
+        let num_records = if sampled_blocks.is_empty() {
+            0
+        } else {
+            (sampled_records as f64 * num_blocks as f64 / sampled_blocks.len() as f64).round()
+                as u64
+        };
+        let mut stat_info = StatInfo {
+            num_blocks,
+            num_records,
+            distinct_values: HashMap::new(),
+            columns: HashMap::new(),
+        };
+        for field in &fields {
+            let sample = samples.remove(field.as_str()).unwrap_or_default();
+            let stats = ColumnStats::from_sample(sample, num_records);
+            stat_info
+                .distinct_values
+                .insert(field.clone(), stats.distinct_values);
+            stat_info.columns.insert(field.clone(), stats);
+        }
+
+        self.store_analyzed_stats(tblname, &stat_info, tx)?;
+        if let Some(resampled) = &mut self.resampled {
+            resampled.insert(tblname.to_string());
+        }
+        self.table_stats
+            .insert(tblname.to_string(), stat_info.clone());
+        Ok(stat_info)
+    }
+
+    // The statistics of a table that is not cached. An analyzed table is
+    // sampled again the first time it is needed after a refresh, and its
+    // histcat rows are trusted until the next one.
+    fn table_stats(
+        &mut self,
+        tblname: &str,
+        layout: &Layout,
+        tx: &mut Transaction,
+    ) -> Result<StatInfo, std::io::Error> {
+        let Some(stat_info) = self.load_analyzed_stats(tblname, tx)? else {
+            return Self::calc_table_stats(tblname, layout, tx);
+        };
+        if self
+            .resampled
+            .as_ref()
+            .is_some_and(|resampled| !resampled.contains(tblname))
+        {
+            return self.analyze(tblname, layout, tx);
+        }
+        Ok(stat_info)
+    }
+
+    fn store_analyzed_stats(
+        &self,
+        tblname: &str,
+        stat_info: &StatInfo,
+        tx: &mut Transaction,
+    ) -> Result<(), std::io::Error> {
+        self.delete_analyzed_stats(tblname, tx)?;
+        let mut hcat = TableScan::new(tx, "histcat", self.hcat_layout.clone())?;
+        for (field, stats) in &stat_info.columns {
+            let (mcvs, freqs): (Vec<_>, Vec<_>) = stats.mcvs.iter().cloned().unzip();
+            hcat.insert(tx)?;
+            hcat.set_string(tx, "tblname", tblname)?;
+            hcat.set_string(tx, "fldname", field)?;
+            hcat.set_int(tx, "numblocks", stat_info.num_blocks as i64)?;
+            hcat.set_int(tx, "numrecs", stat_info.num_records as i64)?;
+            hcat.set_int(tx, "distinct", stats.distinct_values as i64)?;
+            hcat.set_string(tx, "mcvvals", &encode_constants(&mcvs))?;
+            let freqs = freqs.iter().map(|f| f.to_string()).collect::<Vec<_>>();
+            hcat.set_string(tx, "mcvfreqs", &freqs.join(" "))?;
+            hcat.set_string(tx, "bounds", &encode_constants(&stats.bounds))?;
+        }
+        hcat.close(tx);
+        Ok(())
+    }
+
+    fn load_analyzed_stats(
+        &self,
+        tblname: &str,
+        tx: &mut Transaction,
+    ) -> Result<Option<StatInfo>, std::io::Error> {
+        let mut stat_info: Option<StatInfo> = None;
+        let mut hcat = TableScan::new(tx, "histcat", self.hcat_layout.clone())?;
+        while hcat.next(tx)? {
+            if hcat.get_string(tx, "tblname")? != tblname {
+                continue;
+            }
+            let info = stat_info.get_or_insert(StatInfo {
+                num_blocks: hcat.get_int(tx, "numblocks")? as u64,
+                num_records: hcat.get_int(tx, "numrecs")? as u64,
+                distinct_values: HashMap::new(),
+                columns: HashMap::new(),
+            });
+            let field = hcat.get_string(tx, "fldname")?;
+            let mcvs = decode_constants(&hcat.get_string(tx, "mcvvals")?)?;
+            let freqs = hcat
+                .get_string(tx, "mcvfreqs")?
+                .split_whitespace()
+                .map(|f| {
+                    f.parse::<f64>().map_err(|_| {
+                        std::io::Error::new(
+                            std::io::ErrorKind::InvalidData,
+                            format!("malformed frequency {} for {}.{}", f, tblname, field),
+                        )
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let stats = ColumnStats {
+                distinct_values: hcat.get_int(tx, "distinct")? as u64,
+                mcvs: mcvs.into_iter().zip(freqs).collect(),
+                bounds: decode_constants(&hcat.get_string(tx, "bounds")?)?,
+            };
+            info.distinct_values
+                .insert(field.clone(), stats.distinct_values);
+            info.columns.insert(field, stats);
+        }
+        hcat.close(tx);
+        Ok(stat_info)
+    }
+
+    /// Drops everything known about the table, for when its definition or
+    /// name changes.
+    pub(crate) fn forget(
+        &mut self,
+        tblname: &str,
+        tx: &mut Transaction,
+    ) -> Result<(), std::io::Error> {
+        self.table_stats.remove(tblname);
+        self.delete_analyzed_stats(tblname, tx)
+    }
+
+    fn delete_analyzed_stats(
+        &self,
+        tblname: &str,
+        tx: &mut Transaction,
+    ) -> Result<(), std::io::Error> {
+        let mut hcat = TableScan::new(tx, "histcat", self.hcat_layout.clone())?;
+        while hcat.next(tx)? {
+            if hcat.get_string(tx, "tblname")? == tblname {
+                hcat.delete(tx)?;
+            }
+        }
+        hcat.close(tx);
+        Ok(())
+    }
+
+    fn calc_table_stats(
+        tblname: &str,
+        layout: &Layout,
+        tx: &mut Transaction,
+    ) -> Result<StatInfo, std::io::Error> {
+        let fields = comparable_fields(layout);
+        let mut values: HashMap<&str, HashSet<Constant>> = HashMap::new();
+        let mut num_records = 0;
+
+        let mut ts = TableScan::new(tx, tblname, layout.clone())?;
+        while ts.next(tx)? {
+            num_records += 1;
+            for field in &fields {
+                let value = ts.get_val(tx, field)?;
+                values.entry(field).or_default().insert(value);
+            }
+        }
+        ts.close(tx);
+
+        let distinct_values = fields
+            .iter()
+            .map(|f| {
+                (
+                    f.clone(),
+                    values.get(f.as_str()).map_or(0, |v| v.len() as u64),
+                )
+            })
+            .collect();
+
+        Ok(StatInfo {
+            num_blocks: tx.size(&format!("{}.tbl", tblname))?,
+            num_records,
+            distinct_values,
+            columns: HashMap::new(),
+        })
+    }
+}
+
+// Large values would have to be read in full to be compared, they are
+// assumed to be distinct instead
+fn comparable_fields(layout: &Layout) -> Vec<String> {
+    layout
+        .schema()
+        .fields()
+        .iter()
+        .filter(|f| {
+            matches!(
+                layout.schema().ctype(f),
+                ColumnType::Integer | ColumnType::VarChar
+            )
+        })
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        constant::Constant, schema::Schema, simpledb::SimpleDB, table_scan::TableScan,
+        transaction::Transaction,
+    };
+
+    #[test]
+    fn test_stat_manager() {
+        let _ = std::fs::remove_dir_all("statmgrtest");
+        let mut db = SimpleDB::new("statmgrtest", 400, 8).unwrap();
+        let mdm = &mut db.metadata_manager;
+        let mut tx = Transaction::new(
+            &db.file_manager,
+            &mut db.log_manager,
+            &mut db.buffer_manager,
+        );
+
+        let mut schema = Schema::new();
+        schema.add_int_field("A");
+        schema.add_string_field("B", 24);
+        schema.add_text_field("C");
+        mdm.create_table("T", &schema, &mut tx).unwrap();
+        let layout = mdm.get_layout("T", &mut tx).unwrap().unwrap();
+
+        let insert = |tx: &mut Transaction, range: std::ops::Range<i64>| {
+            let mut ts = TableScan::new(tx, "T", layout.clone()).unwrap();
+            for i in range {
+                ts.insert(tx).unwrap();
+                ts.set_int(tx, "A", i).unwrap();
+                ts.set_string(tx, "B", &format!("b{}", i % 7)).unwrap();
+                ts.set_string(tx, "C", "c").unwrap();
+            }
+            ts.close(tx);
+        };
+        insert(&mut tx, 0..50);
+
+        let stats = mdm.get_stat_info("T", &layout, &mut tx).unwrap();
+        assert_eq!(stats.records_output(), 50);
+        // 64 byte slots, 6 to a block
+        assert_eq!(stats.blocks_accessed(), 9);
+        assert_eq!(stats.distinct_values("A"), 50);
+        assert_eq!(stats.distinct_values("B"), 7);
+        assert_eq!(stats.distinct_values("C"), 50);
+
+        // The cached numbers are kept until enough updates are reported
+        mdm.set_stat_refresh_threshold(20);
+        insert(&mut tx, 50..60);
+        mdm.record_updates(10);
+        assert_eq!(mdm.get_stat_info("T", &layout, &mut tx).unwrap(), stats);
+        insert(&mut tx, 60..70);
+        mdm.record_updates(10);
+        let stats = mdm.get_stat_info("T", &layout, &mut tx).unwrap();
+        assert_eq!(stats.records_output(), 70);
+        assert_eq!(stats.distinct_values("A"), 70);
+
+        mdm.refresh_statistics(&mut tx).unwrap();
+        let tblcat = mdm.get_layout("tblcat", &mut tx).unwrap().unwrap();
+        assert_eq!(
+            mdm.get_stat_info("tblcat", &tblcat, &mut tx)
+                .unwrap()
+                .records_output(),
+            8
+        );
+
+        // A damaged histcat row is an error rather than a panic
+        mdm.analyze(Some("T"), &mut tx).unwrap();
+        let histcat = mdm.get_layout("histcat", &mut tx).unwrap().unwrap();
+        let mut hcat = TableScan::new(&mut tx, "histcat", histcat).unwrap();
+        while hcat.next(&mut tx).unwrap() {
+            hcat.set_string(&mut tx, "mcvfreqs", "often").unwrap();
+        }
+        hcat.close(&mut tx);
+        let err = mdm.refresh_statistics(&mut tx).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        tx.commit().unwrap();
+    }
+
+    #[test]
+    fn test_analyze() {
+        let _ = std::fs::remove_dir_all("analyzetest");
+        let mut schema = Schema::new();
+        schema.add_int_field("A");
+        schema.add_string_field("B", 16);
+
+        {
+            let mut db = SimpleDB::new("analyzetest", 400, 8).unwrap();
+            let mdm = &mut db.metadata_manager;
+            let mut tx = Transaction::new(
+                &db.file_manager,
+                &mut db.log_manager,
+                &mut db.buffer_manager,
+            );
+            mdm.create_table("T", &schema, &mut tx).unwrap();
+            let layout = mdm.get_layout("T", &mut tx).unwrap().unwrap();
+
+            // 40 byte slots, 10 to a block; B is "hot" in a fifth of the records
+            let mut ts = TableScan::new(&mut tx, "T", layout).unwrap();
+            for i in 0..2000 {
+                ts.insert(&mut tx).unwrap();
+                ts.set_int(&mut tx, "A", i).unwrap();
+                let b = if i % 5 == 0 {
+                    "hot".to_string()
+                } else {
+                    format!("b{}", i % 97)
+                };
+                ts.set_string(&mut tx, "B", &b).unwrap();
+            }
+            ts.close(&mut tx);
+
+            mdm.set_stat_sample_blocks(50);
+            mdm.analyze(Some("T"), &mut tx).unwrap();
+            assert!(mdm.analyze(Some("Missing"), &mut tx).is_err());
+            tx.commit().unwrap();
+        }
+
+        // The sampled statistics come back from histcat after a restart
+        let mut db = SimpleDB::new("analyzetest", 400, 8).unwrap();
+        let mdm = &mut db.metadata_manager;
+        let mut tx = Transaction::new(
+            &db.file_manager,
+            &mut db.log_manager,
+            &mut db.buffer_manager,
+        );
+        let layout = mdm.get_layout("T", &mut tx).unwrap().unwrap();
+        let stats = mdm.get_stat_info("T", &layout, &mut tx).unwrap();
+        assert_eq!(stats.blocks_accessed(), 200);
+        assert_eq!(stats.records_output(), 2000);
+        assert!(stats.column_stats("A").is_some());
+
+        let hot = stats.eq_selectivity("B", &Constant::Str("hot".to_string()));
+        assert!((hot - 0.2).abs() < 0.01, "{}", hot);
+        let b3 = stats.eq_selectivity("B", &Constant::Str("b3".to_string()));
+        assert!((b3 - 0.8 / 96.0).abs() < 0.005, "{}", b3);
+        let low = stats.range_selectivity("A", None, Some(&Constant::Int(499)));
+        assert!((low - 0.25).abs() < 0.02, "{}", low);
+        let mid =
+            stats.range_selectivity("A", Some(&Constant::Int(500)), Some(&Constant::Int(1499)));
+        assert!((mid - 0.5).abs() < 0.02, "{}", mid);
+
+        // After a refresh the table is sampled again, once
+        let mut ts = TableScan::new(&mut tx, "T", layout.clone()).unwrap();
+        while ts.next(&mut tx).unwrap() {
+            if ts.get_int(&mut tx, "A").unwrap() >= 1000 {
+                ts.delete(&mut tx).unwrap();
+            }
+        }
+        ts.close(&mut tx);
+        mdm.set_stat_refresh_threshold(1000);
+        mdm.record_updates(1000);
+        let stats = mdm.get_stat_info("T", &layout, &mut tx).unwrap();
+        assert_eq!(stats.records_output(), 1000);
+        assert!(stats.column_stats("A").is_some());
+        let low = stats.range_selectivity("A", None, Some(&Constant::Int(499)));
+        assert!((low - 0.5).abs() < 0.02, "{}", low);
+
+        // The new sample went to histcat, later lookups read it from there
+        // until the next refresh rather than look at the table
+        let mut ts = TableScan::new(&mut tx, "T", layout.clone()).unwrap();
+        while ts.next(&mut tx).unwrap() {
+            ts.delete(&mut tx).unwrap();
+        }
+        ts.close(&mut tx);
+        mdm.stat_manager.table_stats.clear();
+        let stats = mdm.get_stat_info("T", &layout, &mut tx).unwrap();
+        assert_eq!(stats.records_output(), 1000);
+        mdm.record_updates(1000);
+        let stats = mdm.get_stat_info("T", &layout, &mut tx).unwrap();
+        assert_eq!(stats.records_output(), 0);
+        tx.commit().unwrap();
+    }
+}
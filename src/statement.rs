@@ -1,16 +1,379 @@
-use crate::{resultset::ResultSet, sql_exception::SQLException};
+use crate::{
+    expression::Expression,
+    file_manager::FileManager,
+    overflow::LargeValue,
+    parser::{parse_command, parse_query},
+    query_data::SelectItem,
+    resultset::{Record, ResultSet, ResultSetMetadata, Value},
+    simpledb::SimpleDB,
+    sql_exception::SQLException,
+    transaction::Transaction,
+};
 
+/// Runs SQL statements against the database, each in a transaction of its
+/// own that is committed when it succeeds and rolled back when it fails.
+pub(crate) struct Statement<'a> {
+    db: &'a mut SimpleDB,
+}
+
+impl<'a> Statement<'a> {
+    pub(crate) fn new(db: &'a mut SimpleDB) -> Self {
+        Self { db }
+    }
+
+    /// For streaming the large values of query results.
+    pub(crate) fn file_manager(&self) -> &FileManager {
+        &self.db.file_manager
+    }
+
+    /// Runs a command, giving the number of records it changed.
+    pub(crate) fn execute_update(&mut self, cmd: &str) -> Result<u64, SQLException> {
+        let command = parse_command(cmd)?;
+        let db = &mut *self.db;
+        let mut tx = Transaction::new(
+            &db.file_manager,
+            &mut db.log_manager,
+            &mut db.buffer_manager,
+        );
+        let result = db
+            .planner
+            .execute_update(&command, &mut tx, &mut db.metadata_manager);
+        end(tx, result)
+    }
 
-pub(crate) struct Statement {
-    
+    /// Runs a query, reading all its records. TEXT and BLOB fields taken
+    /// straight from a table are left in their overflow blocks, to be
+    /// streamed with `Record::get_reader`.
+    pub(crate) fn execute_query(&mut self, qry: &str) -> Result<ResultSet, SQLException> {
+        let query = parse_query(qry)?;
+        let db = &mut *self.db;
+        let mut tx = Transaction::new(
+            &db.file_manager,
+            &mut db.log_manager,
+            &mut db.buffer_manager,
+        );
+        let result = (|| {
+            let plan = db
+                .planner
+                .create_query_plan(&query, &mut tx, &mut db.metadata_manager)?;
+            let fields = plan.schema().fields().to_vec();
+            let mut scan = plan.open(&mut tx)?;
+            let mut records = Vec::new();
+            let mut read = || {
+                while scan.next(&mut tx)? {
+                    let mut record = Record::new();
+                    for fldname in &fields {
+                        // Large values of table records are only read when
+                        // the caller streams them
+                        let value = match scan.get_large(&mut tx, fldname)? {
+                            Some(LargeValue { filename, pointer }) => {
+                                Value::Large { filename, pointer }
+                            }
+                            None => scan.get_val(&mut tx, fldname)?.into(),
+                        };
+                        record.set(fldname, value);
+                    }
+                    records.push(record);
+                }
+                Ok::<_, std::io::Error>(())
+            };
+            let read = read();
+            scan.close(&mut tx);
+            read?;
+            // The fields of the plan are named after the select items
+            let items = fields
+                .iter()
+                .map(|f| SelectItem::new(Expression::Field(f.clone()), None))
+                .collect::<Vec<_>>();
+            let metadata = ResultSetMetadata::new(&items, plan.schema())?;
+            Ok(ResultSet::new(records, metadata))
+        })();
+        end(tx, result)
+    }
+}
+
+/// Commits the transaction of a statement that succeeded, and rolls back
+/// that of one that failed.
+fn end<T>(mut tx: Transaction, result: Result<T, SQLException>) -> Result<T, SQLException> {
+    match result {
+        Ok(value) => {
+            tx.commit()?;
+            Ok(value)
+        }
+        Err(e) => {
+            tx.rollback()?;
+            Err(e)
+        }
+    }
 }
 
-impl Statement {
-    pub(crate) fn execute_update(cmd: &str) -> Result<u64, SQLException> {
-        unimplemented!()
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use crate::{
+        resultset::{ColumnType, ResultSet},
+        simpledb::SimpleDB,
+        sql_exception::SQLException,
+        transaction::Transaction,
+    };
+
+    use super::Statement;
+
+    fn rows(result: Result<ResultSet, SQLException>) -> Vec<String> {
+        let result = result.unwrap();
+        let columns = (1..=result.metadata().column_count())
+            .map(|i| {
+                let metadata = result.metadata();
+                (metadata.column_name(i).to_string(), metadata.column_type(i))
+            })
+            .collect::<Vec<_>>();
+        result
+            .map(|record| {
+                let values = columns
+                    .iter()
+                    .map(|(name, ctype)| match ctype {
+                        _ if record.is_null(name) => "NULL".to_string(),
+                        ColumnType::Integer => record.get_int(name).to_string(),
+                        _ => record.get_string(name),
+                    })
+                    .collect::<Vec<_>>();
+                values.join(" ")
+            })
+            .collect()
     }
 
-    pub(crate) fn execute_query(qry: &str) -> Result<ResultSet, SQLException> {
-        unimplemented!()
+    #[test]
+    fn test_statement() {
+        let _ = std::fs::remove_dir_all("statementtest");
+        let mut db = SimpleDB::new("statementtest", 400, 8).unwrap();
+        let mut stmt = Statement::new(&mut db);
+        for cmd in [
+            "create table DEPT (DID int primary key, DNAME varchar(10))",
+            "create table EMP (EID int primary key, ENAME varchar(10) not null, \
+             DID int references DEPT(DID), SALARY int default 100)",
+            "insert into DEPT (DID, DNAME) values (1, 'sales')",
+            "insert into DEPT (DID, DNAME) values (2, 'research')",
+            "insert into DEPT (DID, DNAME) values (3, 'empty')",
+        ] {
+            stmt.execute_update(cmd).unwrap();
+        }
+        for (eid, name, did) in [(1, "ann", 1), (2, "bob", 2), (3, "cid", 2), (4, "dee", 1)] {
+            let cmd = format!(
+                "insert into EMP (EID, ENAME, DID, SALARY) values ({}, '{}', {}, {})",
+                eid,
+                name,
+                did,
+                eid * 10
+            );
+            assert_eq!(stmt.execute_update(&cmd).unwrap(), 1);
+        }
+        stmt.execute_update("insert into EMP (EID, ENAME, DID) values (5, 'eve', 3)")
+            .unwrap();
+
+        assert_eq!(
+            rows(stmt.execute_query(
+                "select ENAME, DNAME from EMP, DEPT where EMP.DID = DEPT.DID and SALARY > 10 \
+                 order by ENAME desc"
+            )),
+            ["eve empty", "dee sales", "cid research", "bob research"]
+        );
+        assert_eq!(
+            rows(stmt.execute_query(
+                "select DNAME, count(*), sum(SALARY) from DEPT d join EMP e on d.DID = e.DID \
+                 group by DNAME having count(*) > 1 order by DNAME"
+            )),
+            ["research 2 50", "sales 2 50"]
+        );
+        assert_eq!(
+            rows(stmt.execute_query(
+                "select ENAME, SALARY * 2 as DOUBLE from EMP order by SALARY limit 2 offset 1"
+            )),
+            ["bob 40", "cid 60"]
+        );
+        assert_eq!(
+            rows(stmt.execute_query(
+                "select DNAME, (select count(*) from EMP e where e.DID = d.DID) as N from DEPT d \
+                 where exists (select EID from EMP e where e.DID = d.DID and SALARY >= 40)"
+            )),
+            ["sales 2", "empty 1"]
+        );
+        assert_eq!(
+            rows(stmt.execute_query(
+                "select DID from DEPT except select DID from EMP where SALARY < 100 \
+                 union select 7 from DEPT"
+            )),
+            ["3", "7"]
+        );
+
+        stmt.execute_update("create view RICH as select ENAME, DID from EMP where SALARY >= 40")
+            .unwrap();
+        assert_eq!(
+            rows(stmt.execute_query("select distinct DID from RICH order by DID")),
+            ["1", "3"]
+        );
+
+        assert_eq!(
+            stmt.execute_update("update EMP set SALARY = SALARY + 1 where DID = 2")
+                .unwrap(),
+            2
+        );
+        // A failed statement changes nothing
+        assert!(matches!(
+            stmt.execute_update("update EMP set DID = 9 where EID > 3"),
+            Err(SQLException::ForeignKeyViolation { .. })
+        ));
+        assert_eq!(
+            stmt.execute_update(
+                "delete from EMP where DID in (select DID from DEPT where DNAME = 'sales')"
+            )
+            .unwrap(),
+            2
+        );
+        assert_eq!(
+            rows(stmt.execute_query("select EID, DID, SALARY from EMP order by EID")),
+            ["2 2 21", "3 2 31", "5 3 100"]
+        );
+
+        let errors = [
+            ("select X from EMP", "field X not found"),
+            ("select DID from EMP, DEPT", "field DID not found"),
+            ("select A from NOPE", "table NOPE does not exist"),
+        ];
+        for (qry, message) in errors {
+            let Err(e) = stmt.execute_query(qry) else {
+                panic!("{} should fail", qry);
+            };
+            assert_eq!(e.to_string(), message);
+        }
+        assert_eq!(
+            stmt.execute_update("create table DEPT (A int)")
+                .unwrap_err()
+                .to_string(),
+            "table DEPT already exists"
+        );
+    }
+
+    #[test]
+    fn test_schema_statements() {
+        let _ = std::fs::remove_dir_all("schemastmttest");
+        let mut db = SimpleDB::new("schemastmttest", 400, 8).unwrap();
+        let mut stmt = Statement::new(&mut db);
+        stmt.execute_update("create table T (A int, B varchar(10))")
+            .unwrap();
+        for i in 0..20 {
+            let cmd = format!("insert into T (A, B) values ({}, 'b{}')", i % 4, i);
+            stmt.execute_update(&cmd).unwrap();
+        }
+
+        stmt.execute_update("analyze T").unwrap();
+        stmt.execute_update("analyze").unwrap();
+        assert_eq!(
+            stmt.execute_update("analyze NOPE").unwrap_err().to_string(),
+            "table NOPE does not exist"
+        );
+
+        for cmd in [
+            "alter table T add column C int default 7",
+            "alter table T rename column B to BB",
+            "alter table T drop A",
+            "rename table T to U",
+        ] {
+            stmt.execute_update(cmd).unwrap();
+        }
+        assert_eq!(
+            rows(stmt.execute_query("select BB, C from U where BB = 'b3'")),
+            ["b3 7"]
+        );
+        let errors = [
+            ("alter table U drop column A", "field U.A does not exist"),
+            ("alter table U add C int", "field U.C already exists"),
+            ("alter table U rename BB to C", "field U.C already exists"),
+            ("alter table T rename to V", "table T does not exist"),
+        ];
+        for (cmd, message) in errors {
+            let Err(e) = stmt.execute_update(cmd) else {
+                panic!("{} should fail", cmd);
+            };
+            assert_eq!(e.to_string(), message);
+        }
+        stmt.execute_update("create view W as select BB from U")
+            .unwrap();
+        for (cmd, message) in [
+            ("alter table U rename to W", "table W already exists"),
+            ("rename table U to T", "U is used by views W"),
+        ] {
+            assert_eq!(stmt.execute_update(cmd).unwrap_err().to_string(), message);
+        }
+        stmt.execute_update("analyze U").unwrap();
+
+        let mut tx = Transaction::new(
+            &db.file_manager,
+            &mut db.log_manager,
+            &mut db.buffer_manager,
+        );
+        let mdm = &mut db.metadata_manager;
+        let layout = mdm.get_layout("U", &mut tx).unwrap().unwrap();
+        let stat_info = mdm.get_stat_info("U", &layout, &mut tx).unwrap();
+        assert!(stat_info.column_stats("BB").is_some());
+        assert_eq!(stat_info.distinct_values("C"), 1);
+        tx.commit().unwrap();
+
+        let mut stmt = Statement::new(&mut db);
+        stmt.execute_update("create index UC on U (C)").unwrap();
+        for (cmd, message) in [
+            ("drop table U", Some("U is used by views W")),
+            ("drop view W", None),
+            ("drop view W", Some("view W does not exist")),
+            ("drop index UC", None),
+            ("drop index UC", Some("index UC does not exist")),
+            ("drop table U", None),
+            ("drop table U", Some("table U does not exist")),
+        ] {
+            match message {
+                Some(message) => {
+                    assert_eq!(stmt.execute_update(cmd).unwrap_err().to_string(), message)
+                }
+                None => assert_eq!(stmt.execute_update(cmd).unwrap(), 0),
+            }
+        }
+        assert!(stmt.execute_query("select C from U").is_err());
+        assert!(!std::path::Path::new("schemastmttest/U.tbl").exists());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_large_values() {
+        let _ = std::fs::remove_dir_all("largestmttest");
+        let mut db = SimpleDB::new("largestmttest", 400, 8).unwrap();
+        let mut stmt = Statement::new(&mut db);
+        stmt.execute_update("create table T (A int, C text)")
+            .unwrap();
+        let text = |a: usize| format!("{}", a).repeat(300);
+        for a in 1..4 {
+            let cmd = format!("insert into T (A, C) values ({}, '{}')", a, text(a));
+            stmt.execute_update(&cmd).unwrap();
+        }
+        stmt.execute_update("insert into T (A) values (4)").unwrap();
+
+        // Values straight from the table are streamed from their overflow
+        // blocks, a NULL or a sorted copy has no reader
+        let result = stmt
+            .execute_query("select A, C from T where A > 1")
+            .unwrap();
+        for record in result {
+            let a = record.get_int("A") as usize;
+            let reader = record.get_reader("C", stmt.file_manager());
+            if a == 4 {
+                assert!(reader.is_none());
+                continue;
+            }
+            let mut read = String::new();
+            reader.unwrap().read_to_string(&mut read).unwrap();
+            assert_eq!(read, text(a));
+        }
+        let result = stmt.execute_query("select C from T order by C").unwrap();
+        for record in result {
+            assert!(record.get_reader("C", stmt.file_manager()).is_none());
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use std::rc::Rc;
+
+use crate::{
+    constant::Constant,
+    plan::Plan,
+    scan::Scan,
+    schema::Schema,
+    subquery_scan::{SubqueryRunner, SubqueryScan},
+    transaction::Transaction,
+};
+
+/// The records of the underlying plan, for which the runner can run the
+/// subqueries of the conditions and expressions above. What the subqueries
+/// cost is not known until they run, so the estimates are the plan's.
+pub(crate) struct SubqueryPlan {
+    plan: Box<dyn Plan>,
+    runner: Rc<dyn SubqueryRunner>,
+}
+
+impl SubqueryPlan {
+    pub(crate) fn new(plan: Box<dyn Plan>, runner: Rc<dyn SubqueryRunner>) -> Self {
+        Self { plan, runner }
+    }
+}
+
+impl Plan for SubqueryPlan {
+    fn open(&self, tx: &mut Transaction) -> Result<Box<dyn Scan>, std::io::Error> {
+        let scan = self.plan.open(tx)?;
+        Ok(Box::new(SubqueryScan::new(scan, self.runner.clone())))
+    }
+
+    fn blocks_accessed(&self) -> u64 {
+        self.plan.blocks_accessed()
+    }
+
+    fn records_output(&self) -> u64 {
+        self.plan.records_output()
+    }
+
+    fn distinct_values(&self, fldname: &str) -> u64 {
+        self.plan.distinct_values(fldname)
+    }
+
+    fn schema(&self) -> &Schema {
+        self.plan.schema()
+    }
+
+    fn eq_selectivity(&self, fldname: &str, value: &Constant) -> f64 {
+        self.plan.eq_selectivity(fldname, value)
+    }
+
+    fn range_selectivity(
+        &self,
+        fldname: &str,
+        low: Option<&Constant>,
+        high: Option<&Constant>,
+    ) -> f64 {
+        self.plan.range_selectivity(fldname, low, high)
+    }
+}
@@ -0,0 +1,288 @@
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{
+    constant::Constant,
+    expression::Expression,
+    overflow::LargeValue,
+    query_data::QueryData,
+    rid::Rid,
+    scan::{Scan, UpdateScan},
+    transaction::Transaction,
+};
+
+/// Plans and runs the subqueries of a query, which only the planner knows
+/// how to do.
+pub(crate) trait SubqueryRunner {
+    /// The fields the query refers to that are not its own, which come from
+    /// the records of the query around it.
+    fn outer_fields(&self, query: &QueryData) -> Result<Vec<String>, std::io::Error>;
+
+    /// The values of the single field of the query's records. The query
+    /// refers to no outer fields.
+    fn run(&self, query: &QueryData, tx: &mut Transaction)
+        -> Result<Vec<Constant>, std::io::Error>;
+}
+
+/// The records of the underlying scan, for which subqueries can be run. A
+/// subquery is run with the outer fields it refers to replaced by their
+/// values in the current record, and its values are kept for when the same
+/// values come up again; an uncorrelated subquery thus runs only once.
+pub(crate) struct SubqueryScan<S: Scan> {
+    scan: S,
+    runner: Rc<dyn SubqueryRunner>,
+    outer_fields: HashMap<String, Vec<String>>,
+    // The values of each subquery run so far, by its text once bound
+    results: HashMap<String, Vec<Constant>>,
+}
+
+impl<S: Scan> SubqueryScan<S> {
+    pub(crate) fn new(scan: S, runner: Rc<dyn SubqueryRunner>) -> Self {
+        Self {
+            scan,
+            runner,
+            outer_fields: HashMap::new(),
+            results: HashMap::new(),
+        }
+    }
+}
+
+impl<S: Scan> Scan for SubqueryScan<S> {
+    fn before_first(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        self.scan.before_first(tx)
+    }
+
+    fn next(&mut self, tx: &mut Transaction) -> Result<bool, std::io::Error> {
+        self.scan.next(tx)
+    }
+
+    fn get_int(&mut self, tx: &mut Transaction, fldname: &str) -> Result<i64, std::io::Error> {
+        self.scan.get_int(tx, fldname)
+    }
+
+    fn get_string(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+    ) -> Result<String, std::io::Error> {
+        self.scan.get_string(tx, fldname)
+    }
+
+    fn get_val(&mut self, tx: &mut Transaction, fldname: &str) -> Result<Constant, std::io::Error> {
+        self.scan.get_val(tx, fldname)
+    }
+
+    fn has_field(&self, fldname: &str) -> bool {
+        self.scan.has_field(fldname)
+    }
+
+    fn get_large(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+    ) -> Result<Option<LargeValue>, std::io::Error> {
+        self.scan.get_large(tx, fldname)
+    }
+
+    fn close(&mut self, tx: &mut Transaction) {
+        self.scan.close(tx)
+    }
+
+    fn subquery_values(
+        &mut self,
+        tx: &mut Transaction,
+        query: &QueryData,
+    ) -> Result<Vec<Constant>, std::io::Error> {
+        let text = query.to_string();
+        let outer_fields = match self.outer_fields.get(&text) {
+            Some(fields) => fields.clone(),
+            None => {
+                let fields = self.runner.outer_fields(query)?;
+                self.outer_fields.insert(text, fields.clone());
+                fields
+            }
+        };
+        let mut values = HashMap::new();
+        for fldname in outer_fields {
+            let value = self.scan.get_val(tx, &fldname)?;
+            values.insert(fldname, value);
+        }
+        let bound = query.map_fields(&|f| values.get(f).cloned().map(Expression::Constant));
+        let key = bound.to_string();
+        if let Some(result) = self.results.get(&key) {
+            return Ok(result.clone());
+        }
+        let result = self.runner.run(&bound, tx)?;
+        self.results.insert(key, result.clone());
+        Ok(result)
+    }
+}
+
+impl<S: UpdateScan> UpdateScan for SubqueryScan<S> {
+    fn set_int(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+        value: i64,
+    ) -> Result<(), std::io::Error> {
+        self.scan.set_int(tx, fldname, value)
+    }
+
+    fn set_string(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+        value: &str,
+    ) -> Result<(), std::io::Error> {
+        self.scan.set_string(tx, fldname, value)
+    }
+
+    fn set_val(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+        value: &Constant,
+    ) -> Result<(), std::io::Error> {
+        self.scan.set_val(tx, fldname, value)
+    }
+
+    fn insert(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        self.scan.insert(tx)
+    }
+
+    fn delete(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        self.scan.delete(tx)
+    }
+
+    fn get_rid(&self) -> Rid {
+        self.scan.get_rid()
+    }
+
+    fn move_to_rid(&mut self, tx: &mut Transaction, rid: Rid) -> Result<(), std::io::Error> {
+        self.scan.move_to_rid(tx, rid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, collections::HashMap, rc::Rc};
+
+    use crate::{
+        constant::Constant,
+        extend_scan::ExtendScan,
+        layout::Layout,
+        parser::parse_query,
+        query_data::QueryData,
+        scan::Scan,
+        schema::Schema,
+        select_scan::SelectScan,
+        simpledb::SimpleDB,
+        table_scan::TableScan,
+        transaction::Transaction,
+    };
+
+    use super::{SubqueryRunner, SubqueryScan};
+
+    /// Runs single table queries over rows kept in memory.
+    struct FakeRunner {
+        rows: Vec<HashMap<String, Constant>>,
+        runs: Cell<usize>,
+    }
+
+    impl SubqueryRunner for FakeRunner {
+        fn outer_fields(&self, query: &QueryData) -> Result<Vec<String>, std::io::Error> {
+            let mut fields = query.predicate.fields();
+            for item in &query.items {
+                item.expr.collect_fields(&mut fields);
+            }
+            fields.retain(|f| !self.rows[0].contains_key(f));
+            Ok(fields)
+        }
+
+        fn run(
+            &self,
+            query: &QueryData,
+            _tx: &mut Transaction,
+        ) -> Result<Vec<Constant>, std::io::Error> {
+            self.runs.set(self.runs.get() + 1);
+            let mut values = Vec::new();
+            for row in &self.rows {
+                if query.predicate.evaluate(&mut &*row)? == Some(true) {
+                    values.push(query.items[0].expr.evaluate(&mut &*row)?);
+                }
+            }
+            Ok(values)
+        }
+    }
+
+    #[test]
+    fn test_subquery_scan() {
+        let _ = std::fs::remove_dir_all("subquerytest");
+        let mut db = SimpleDB::new("subquerytest", 400, 8).unwrap();
+        let mut tx = Transaction::new(
+            &db.file_manager,
+            &mut db.log_manager,
+            &mut db.buffer_manager,
+        );
+
+        let mut schema = Schema::new();
+        schema.add_int_field("A");
+        let layout = Layout::new(schema);
+        let mut ts = TableScan::new(&mut tx, "T", layout.clone()).unwrap();
+        for a in [1, 2, 3, 2] {
+            ts.insert(&mut tx).unwrap();
+            ts.set_int(&mut tx, "A", a).unwrap();
+        }
+        ts.close(&mut tx);
+        let runner = Rc::new(FakeRunner {
+            rows: [(1, 10), (2, 20), (2, 21)]
+                .into_iter()
+                .map(|(k, v)| {
+                    [
+                        ("K".to_string(), Constant::Int(k)),
+                        ("V".to_string(), Constant::Int(v)),
+                    ]
+                    .into_iter()
+                    .collect()
+                })
+                .collect(),
+            runs: Cell::new(0),
+        });
+
+        // A correlated EXISTS and scalar subquery, and uncorrelated ones
+        let query = parse_query(
+            "select A, (select V from S where K = A) M, (select K from S where V = 10) C \
+             from T where exists (select K from S where K = A) and A + 18 in (select V from S)",
+        )
+        .unwrap();
+        let ts = TableScan::new(&mut tx, "T", layout.clone()).unwrap();
+        let select = SelectScan::new(SubqueryScan::new(ts, runner.clone()), query.predicate);
+        let computed = vec![
+            ("C".to_string(), query.items[2].expr.clone()),
+            ("M".to_string(), query.items[1].expr.clone()),
+        ];
+        let mut scan = ExtendScan::new(select, computed);
+        let mut rows = Vec::new();
+        scan.before_first(&mut tx).unwrap();
+        while scan.next(&mut tx).unwrap() {
+            let a = scan.get_val(&mut tx, "A").unwrap();
+            let c = scan.get_val(&mut tx, "C").unwrap();
+            rows.push(format!("{} {}", a, c));
+            // More than one record for a scalar subquery
+            assert!(scan.get_val(&mut tx, "M").is_err());
+        }
+        assert_eq!(rows, ["2 1", "2 1"]);
+        // Each subquery ran once for each distinct binding of its outer
+        // fields: EXISTS for the three values of A, the others once
+        assert_eq!(runner.runs.get(), 6);
+        scan.close(&mut tx);
+
+        // Subqueries need a scan that can run them
+        let ts = TableScan::new(&mut tx, "T", layout).unwrap();
+        let query = parse_query("select A from T where exists (select K from S)").unwrap();
+        let mut select = SelectScan::new(ts, query.predicate);
+        select.before_first(&mut tx).unwrap();
+        assert!(select.next(&mut tx).is_err());
+        select.close(&mut tx);
+        tx.commit().unwrap();
+    }
+}
@@ -0,0 +1,276 @@
+use std::collections::HashMap;
+
+use crate::{
+    layout::Layout, record_page::MAX_FIELDS, resultset::ColumnType, schema::Schema,
+    table_scan::TableScan, transaction::Transaction,
+};
+
+// The longest table or field name the catalog can hold
+pub(crate) const MAX_NAME: u64 = 16;
+
+/// Keeps table definitions in two catalog tables: `tblcat` has a row per
+/// table with its slot size and `fldcat` a row per field with its type,
+/// length and offset.
+pub(crate) struct TableManager {
+    tcat_layout: Layout,
+    fcat_layout: Layout,
+}
+
+impl TableManager {
+    pub(crate) fn new(is_new: bool, tx: &mut Transaction) -> Result<Self, std::io::Error> {
+        let mut tcat_schema = Schema::new();
+        tcat_schema.add_string_field("tblname", MAX_NAME);
+        tcat_schema.add_int_field("slotsize");
+        let tcat_layout = Layout::new(tcat_schema.clone());
+
+        let mut fcat_schema = Schema::new();
+        fcat_schema.add_string_field("tblname", MAX_NAME);
+        fcat_schema.add_string_field("fldname", MAX_NAME);
+        fcat_schema.add_int_field("type");
+        fcat_schema.add_int_field("length");
+        fcat_schema.add_int_field("offset");
+        let fcat_layout = Layout::new(fcat_schema.clone());
+
+        let table_manager = Self {
+            tcat_layout,
+            fcat_layout,
+        };
+        if is_new {
+            table_manager.create_table("tblcat", &tcat_schema, tx)?;
+            table_manager.create_table("fldcat", &fcat_schema, tx)?;
+        }
+
+        Ok(table_manager)
+    }
+
+    pub(crate) fn create_table(
+        &self,
+        tblname: &str,
+        schema: &Schema,
+        tx: &mut Transaction,
+    ) -> Result<(), std::io::Error> {
+        check_schema(tblname, schema, tx)?;
+        if tx.is_dropping(&format!("{}.tbl", tblname)) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("table {} was dropped by this transaction", tblname),
+            ));
+        }
+        let layout = Layout::new(schema.clone());
+
+        let mut tcat = TableScan::new(tx, "tblcat", self.tcat_layout.clone())?;
+        tcat.insert(tx)?;
+        tcat.set_string(tx, "tblname", tblname)?;
+        tcat.set_int(tx, "slotsize", layout.slot_size() as i64)?;
+        tcat.close(tx);
+
+        let mut fcat = TableScan::new(tx, "fldcat", self.fcat_layout.clone())?;
+        for field in schema.fields() {
+            fcat.insert(tx)?;
+            fcat.set_string(tx, "tblname", tblname)?;
+            fcat.set_string(tx, "fldname", field)?;
+            fcat.set_int(tx, "type", schema.ctype(field).code())?;
+            fcat.set_int(tx, "length", schema.length(field) as i64)?;
+            fcat.set_int(tx, "offset", layout.offset(field) as i64)?;
+        }
+        fcat.close(tx);
+
+        Ok(())
+    }
+
+    /// Deletes the table's rows from `tblcat` and `fldcat`. Its files are
+    /// left alone.
+    pub(crate) fn remove_table(
+        &self,
+        tblname: &str,
+        tx: &mut Transaction,
+    ) -> Result<(), std::io::Error> {
+        for (catalog, layout) in [("tblcat", &self.tcat_layout), ("fldcat", &self.fcat_layout)] {
+            let mut ts = TableScan::new(tx, catalog, layout.clone())?;
+            while ts.next(tx)? {
+                if ts.get_string(tx, "tblname")? == tblname {
+                    ts.delete(tx)?;
+                }
+            }
+            ts.close(tx);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn table_names(&self, tx: &mut Transaction) -> Result<Vec<String>, std::io::Error> {
+        let mut names = Vec::new();
+        let mut tcat = TableScan::new(tx, "tblcat", self.tcat_layout.clone())?;
+        while tcat.next(tx)? {
+            names.push(tcat.get_string(tx, "tblname")?);
+        }
+        tcat.close(tx);
+        Ok(names)
+    }
+
+    pub(crate) fn get_layout(
+        &self,
+        tblname: &str,
+        tx: &mut Transaction,
+    ) -> Result<Option<Layout>, std::io::Error> {
+        let mut slot_size = None;
+        let mut tcat = TableScan::new(tx, "tblcat", self.tcat_layout.clone())?;
+        while tcat.next(tx)? {
+            if tcat.get_string(tx, "tblname")? == tblname {
+                slot_size = Some(tcat.get_int(tx, "slotsize")? as u64);
+                break;
+            }
+        }
+        tcat.close(tx);
+        let Some(slot_size) = slot_size else {
+            return Ok(None);
+        };
+
+        let mut schema = Schema::new();
+        let mut offsets = HashMap::new();
+        let mut fcat = TableScan::new(tx, "fldcat", self.fcat_layout.clone())?;
+        while fcat.next(tx)? {
+            if fcat.get_string(tx, "tblname")? == tblname {
+                let fldname = fcat.get_string(tx, "fldname")?;
+                let code = fcat.get_int(tx, "type")?;
+                let ctype = ColumnType::from_code(code).ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("unknown type {} for field {}.{}", code, tblname, fldname),
+                    )
+                })?;
+                let length = fcat.get_int(tx, "length")? as u64;
+                let offset = fcat.get_int(tx, "offset")? as u64;
+                schema.add_field(&fldname, ctype, length);
+                offsets.insert(fldname, offset);
+            }
+        }
+        fcat.close(tx);
+
+        Ok(Some(Layout::from_parts(schema, offsets, slot_size)))
+    }
+}
+
+/// Checks that the names fit the catalog and that a record of the table
+/// fits a block, as a record page holds whole records only.
+pub(crate) fn check_schema(
+    tblname: &str,
+    schema: &Schema,
+    tx: &Transaction,
+) -> Result<(), std::io::Error> {
+    check_name(tblname)?;
+    for field in schema.fields() {
+        check_name(field)?;
+    }
+    if schema.fields().len() > MAX_FIELDS {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("table {} has more than {} fields", tblname, MAX_FIELDS),
+        ));
+    }
+    let slot_size = Layout::new(schema.clone()).slot_size();
+    if slot_size > tx.block_size() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!(
+                "records of {} would take {} bytes, more than the {} of a block",
+                tblname,
+                slot_size,
+                tx.block_size()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+pub(crate) fn check_name(name: &str) -> Result<(), std::io::Error> {
+    if name.len() as u64 > MAX_NAME {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("name {} is longer than {} bytes", name, MAX_NAME),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{schema::Schema, simpledb::SimpleDB, transaction::Transaction};
+
+    #[test]
+    fn test_table_manager() {
+        let _ = std::fs::remove_dir_all("tblmgrtest");
+        let mut schema = Schema::new();
+        schema.add_int_field("A");
+        schema.add_string_field("B", 9);
+        schema.add_blob_field("C");
+
+        {
+            let mut db = SimpleDB::new("tblmgrtest", 400, 8).unwrap();
+            let mut tx = Transaction::new(
+                &db.file_manager,
+                &mut db.log_manager,
+                &mut db.buffer_manager,
+            );
+            db.metadata_manager
+                .create_table("MyTable", &schema, &mut tx)
+                .unwrap();
+            assert!(db
+                .metadata_manager
+                .create_table("AVeryLongTableName", &schema, &mut tx)
+                .is_err());
+            let mut wide = Schema::new();
+            wide.add_string_field("A", 500);
+            let err = db
+                .metadata_manager
+                .create_table("Wide", &wide, &mut tx)
+                .unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "records of Wide would take 516 bytes, more than the 400 of a block"
+            );
+            assert!(db
+                .metadata_manager
+                .get_layout("Wide", &mut tx)
+                .unwrap()
+                .is_none());
+            tx.commit().unwrap();
+        }
+
+        // The definition survives a restart
+        let mut db = SimpleDB::new("tblmgrtest", 400, 8).unwrap();
+        let mut tx = Transaction::new(
+            &db.file_manager,
+            &mut db.log_manager,
+            &mut db.buffer_manager,
+        );
+        let layout = db
+            .metadata_manager
+            .get_layout("MyTable", &mut tx)
+            .unwrap()
+            .unwrap();
+        assert_eq!(layout.slot_size(), 49);
+        assert_eq!(layout.schema(), &schema);
+        assert_eq!(
+            schema
+                .fields()
+                .iter()
+                .map(|f| layout.offset(f))
+                .collect::<Vec<_>>(),
+            vec![8, 16, 33]
+        );
+        assert!(db
+            .metadata_manager
+            .get_layout("Missing", &mut tx)
+            .unwrap()
+            .is_none());
+
+        // The catalog describes itself
+        let tblcat = db
+            .metadata_manager
+            .get_layout("tblcat", &mut tx)
+            .unwrap()
+            .unwrap();
+        assert_eq!(tblcat.schema().fields(), ["tblname", "slotsize"]);
+        tx.commit().unwrap();
+    }
+}
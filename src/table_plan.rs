@@ -0,0 +1,62 @@
+use crate::{
+    constant::Constant, layout::Layout, plan::Plan, scan::Scan, schema::Schema,
+    stat_manager::StatInfo, table_scan::TableScan, transaction::Transaction,
+};
+
+/// The records of a stored table, estimated from its statistics.
+pub(crate) struct TablePlan {
+    tblname: String,
+    layout: Layout,
+    stat_info: StatInfo,
+}
+
+impl TablePlan {
+    pub(crate) fn new(tblname: &str, layout: Layout, stat_info: StatInfo) -> Self {
+        Self {
+            tblname: tblname.to_string(),
+            layout,
+            stat_info,
+        }
+    }
+
+    /// The scan of the table itself, for plans that move it to the records
+    /// an index finds.
+    pub(crate) fn open_table(&self, tx: &mut Transaction) -> Result<TableScan, std::io::Error> {
+        TableScan::new(tx, &self.tblname, self.layout.clone())
+    }
+}
+
+impl Plan for TablePlan {
+    fn open(&self, tx: &mut Transaction) -> Result<Box<dyn Scan>, std::io::Error> {
+        Ok(Box::new(self.open_table(tx)?))
+    }
+
+    fn blocks_accessed(&self) -> u64 {
+        self.stat_info.blocks_accessed()
+    }
+
+    fn records_output(&self) -> u64 {
+        self.stat_info.records_output()
+    }
+
+    fn distinct_values(&self, fldname: &str) -> u64 {
+        self.stat_info.distinct_values(fldname)
+    }
+
+    fn schema(&self) -> &Schema {
+        self.layout.schema()
+    }
+
+    fn eq_selectivity(&self, fldname: &str, value: &Constant) -> f64 {
+        self.stat_info.eq_selectivity(fldname, value)
+    }
+
+    fn range_selectivity(
+        &self,
+        fldname: &str,
+        low: Option<&Constant>,
+        high: Option<&Constant>,
+    ) -> f64 {
+        self.stat_info.range_selectivity(fldname, low, high)
+    }
+}
@@ -0,0 +1,619 @@
+use std::io::Read;
+
+use crate::{
+    constant::Constant,
+    file_manager::BlockId,
+    free_space_map::FreeSpaceMap,
+    layout::Layout,
+    overflow::{
+        free_all_overflow, free_overflow, overflow_file, write_overflow, LargeValue,
+        OverflowPointer, OverflowReader,
+    },
+    record_page::RecordPage,
+    resultset::{ColumnType, Record, Value},
+    rid::Rid,
+    scan::{Scan, UpdateScan},
+    transaction::Transaction,
+};
+
+pub(crate) struct TableScan {
+    tblname: String,
+    filename: String,
+    layout: Layout,
+    rp: RecordPage,
+    current_slot: Option<usize>,
+    free_space_map: FreeSpaceMap,
+}
+
+impl TableScan {
+    pub(crate) fn new(
+        tx: &mut Transaction,
+        tblname: &str,
+        layout: Layout,
+    ) -> Result<Self, std::io::Error> {
+        let filename = format!("{}.tbl", tblname);
+        let mut free_space_map = FreeSpaceMap::new(tblname);
+        let rp = if tx.size(&filename)? == 0 {
+            Self::new_block(tx, &filename, &layout, &mut free_space_map)?
+        } else {
+            RecordPage::new(tx, BlockId::new(filename.clone(), 0), layout.clone())?
+        };
+
+        Ok(Self {
+            tblname: tblname.to_string(),
+            filename,
+            layout,
+            rp,
+            current_slot: None,
+            free_space_map,
+        })
+    }
+
+    /// The heap, free-space map and overflow files kept for a table.
+    pub(crate) fn files(tblname: &str) -> [String; 3] {
+        [
+            format!("{}.tbl", tblname),
+            FreeSpaceMap::new(tblname).filename().to_string(),
+            overflow_file(tblname),
+        ]
+    }
+
+    pub(crate) fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    pub(crate) fn before_first(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        self.move_to_block(tx, 0)
+    }
+
+    pub(crate) fn next(&mut self, tx: &mut Transaction) -> Result<bool, std::io::Error> {
+        self.current_slot = self.rp.next_after(tx, self.current_slot);
+        while self.current_slot.is_none() {
+            if self.at_last_block(tx)? {
+                return Ok(false);
+            }
+            self.move_to_block(tx, self.rp.block().number() + 1)?;
+            self.current_slot = self.rp.next_after(tx, self.current_slot);
+        }
+        Ok(true)
+    }
+
+    pub(crate) fn get_int(
+        &mut self,
+        tx: &mut Transaction,
+        field: &str,
+    ) -> Result<i64, std::io::Error> {
+        Ok(self.rp.get_int(tx, self.slot(), field))
+    }
+
+    pub(crate) fn get_string(
+        &mut self,
+        tx: &mut Transaction,
+        field: &str,
+    ) -> Result<String, std::io::Error> {
+        if self.layout.schema().ctype(field) == ColumnType::VarChar {
+            return Ok(self.rp.get_string(tx, self.slot(), field));
+        }
+        let mut value = String::new();
+        self.get_reader(tx, field).read_to_string(&mut value)?;
+        Ok(value)
+    }
+
+    pub(crate) fn get_val(
+        &mut self,
+        tx: &mut Transaction,
+        field: &str,
+    ) -> Result<Constant, std::io::Error> {
+        if self.is_null(tx, field) {
+            return Ok(Constant::Null);
+        }
+        match self.layout.schema().ctype(field) {
+            ColumnType::Integer => Ok(Constant::Int(self.get_int(tx, field)?)),
+            ColumnType::VarChar | ColumnType::Text => {
+                Ok(Constant::Str(self.get_string(tx, field)?))
+            }
+            ColumnType::Blob => {
+                let mut value = Vec::new();
+                self.get_reader(tx, field).read_to_end(&mut value)?;
+                Ok(Constant::Bytes(value))
+            }
+        }
+    }
+
+    /// Streams a TEXT or BLOB field of the current record without loading it
+    /// into memory.
+    pub(crate) fn get_reader<'a>(
+        &mut self,
+        tx: &mut Transaction<'a>,
+        field: &str,
+    ) -> OverflowReader<'a> {
+        let pointer = self.rp.get_overflow_pointer(tx, self.slot(), field);
+        OverflowReader::new(tx.file_manager, &overflow_file(&self.tblname), pointer)
+    }
+
+    /// Where the TEXT or BLOB field of the current record is kept, or None
+    /// when it is NULL or of another type.
+    pub(crate) fn get_large(&mut self, tx: &mut Transaction, field: &str) -> Option<LargeValue> {
+        let ctype = self.layout.schema().ctype(field);
+        if !matches!(ctype, ColumnType::Text | ColumnType::Blob) || self.is_null(tx, field) {
+            return None;
+        }
+        Some(LargeValue {
+            filename: overflow_file(&self.tblname),
+            pointer: self.rp.get_overflow_pointer(tx, self.slot(), field),
+        })
+    }
+
+    pub(crate) fn is_null(&mut self, tx: &mut Transaction, field: &str) -> bool {
+        self.rp.is_null(tx, self.slot(), field)
+    }
+
+    pub(crate) fn set_null(
+        &mut self,
+        tx: &mut Transaction,
+        field: &str,
+    ) -> Result<(), std::io::Error> {
+        self.free_large_value(tx, field)?;
+        self.rp.set_null(tx, self.slot(), field, true)
+    }
+
+    pub(crate) fn has_field(&self, field: &str) -> bool {
+        self.layout.schema().has_field(field)
+    }
+
+    pub(crate) fn close(&mut self, tx: &mut Transaction) {
+        self.rp.close(tx);
+    }
+
+    pub(crate) fn set_int(
+        &mut self,
+        tx: &mut Transaction,
+        field: &str,
+        value: i64,
+    ) -> Result<(), std::io::Error> {
+        self.rp.set_null(tx, self.slot(), field, false)?;
+        self.rp.set_int(tx, self.slot(), field, value)
+    }
+
+    pub(crate) fn set_string(
+        &mut self,
+        tx: &mut Transaction,
+        field: &str,
+        value: &str,
+    ) -> Result<(), std::io::Error> {
+        if self.layout.schema().ctype(field) == ColumnType::VarChar {
+            self.rp.set_string(tx, self.slot(), field, value)?;
+            return self.rp.set_null(tx, self.slot(), field, false);
+        }
+        self.set_from_reader(tx, field, &mut value.as_bytes())
+    }
+
+    pub(crate) fn set_val(
+        &mut self,
+        tx: &mut Transaction,
+        field: &str,
+        value: &Constant,
+    ) -> Result<(), std::io::Error> {
+        match value {
+            Constant::Null => self.set_null(tx, field),
+            Constant::Int(i) => self.set_int(tx, field, *i),
+            Constant::Str(s) => self.set_string(tx, field, s),
+            Constant::Bytes(b) => self.set_from_reader(tx, field, &mut b.as_slice()),
+        }
+    }
+
+    /// Stores a TEXT or BLOB field by streaming `data` into a new overflow
+    /// chain, the record itself only keeps the pointer. The chain of the old
+    /// value is freed.
+    pub(crate) fn set_from_reader(
+        &mut self,
+        tx: &mut Transaction,
+        field: &str,
+        data: &mut dyn Read,
+    ) -> Result<(), std::io::Error> {
+        self.free_large_value(tx, field)?;
+        let pointer = write_overflow(tx, &overflow_file(&self.tblname), data)?;
+        self.rp.set_null(tx, self.slot(), field, false)?;
+        self.rp
+            .set_overflow_pointer(tx, self.slot(), field, pointer)
+    }
+
+    pub(crate) fn insert(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        // No block would ever have room, however many were appended
+        if self.rp.slots() == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "records of {} are {} bytes, more than a block of {}",
+                    self.tblname,
+                    self.layout.slot_size(),
+                    tx.block_size()
+                ),
+            ));
+        }
+        loop {
+            let size = tx.size(&self.filename)?;
+            match self.free_space_map.find_block_with_room(tx, size)? {
+                Some(block_number) => {
+                    if block_number != self.rp.block().number() {
+                        self.move_to_block(tx, block_number)?;
+                    }
+                }
+                None => {
+                    self.rp.close(tx);
+                    self.rp = Self::new_block(
+                        tx,
+                        &self.filename,
+                        &self.layout,
+                        &mut self.free_space_map,
+                    )?;
+                    self.current_slot = None;
+                }
+            }
+            let block_number = self.rp.block().number();
+            self.current_slot = self.rp.insert_after(tx, None)?;
+            match self.current_slot {
+                Some(_) => {
+                    let free = self.free_space_map.get_free(tx, block_number)?;
+                    self.free_space_map.set_free(tx, block_number, free - 1)?;
+                    break;
+                }
+                // The map was stale, correct it and look again
+                None => self.free_space_map.set_free(tx, block_number, 0)?,
+            }
+        }
+        // Large fields start out as empty values rather than dangling pointers
+        for field in self.layout.schema().fields() {
+            if matches!(
+                self.layout.schema().ctype(field),
+                ColumnType::Text | ColumnType::Blob
+            ) {
+                self.rp
+                    .set_overflow_pointer(tx, self.slot(), field, OverflowPointer::empty())?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn delete(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        for field in self.layout.schema().fields() {
+            self.free_large_value(tx, field)?;
+        }
+        self.rp.delete(tx, self.slot())?;
+        let block_number = self.rp.block().number();
+        let free = self.free_space_map.get_free(tx, block_number)?;
+        self.free_space_map.set_free(tx, block_number, free + 1)
+    }
+
+    /// Empties every block of the heap file, formatting them after this
+    /// scan's layout whatever layout they had before. The blocks of the
+    /// overflow file all become free.
+    pub(crate) fn clear(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        self.rp.close(tx);
+        free_all_overflow(tx, &overflow_file(&self.tblname))?;
+        let size = tx.size(&self.filename)?;
+        self.free_space_map = FreeSpaceMap::new(&self.tblname);
+        for block_number in 0..size {
+            let block = BlockId::new(self.filename.clone(), block_number);
+            let rp = RecordPage::new(tx, block, self.layout.clone())?;
+            rp.format(tx)?;
+            self.free_space_map
+                .set_free(tx, block_number, rp.slots() as u64)?;
+            rp.close(tx);
+        }
+        let block = BlockId::new(self.filename.clone(), 0);
+        self.rp = RecordPage::new(tx, block, self.layout.clone())?;
+        self.current_slot = None;
+        Ok(())
+    }
+
+    pub(crate) fn get_rid(&self) -> Rid {
+        Rid::new(self.rp.block().number(), self.slot())
+    }
+
+    pub(crate) fn move_to_rid(
+        &mut self,
+        tx: &mut Transaction,
+        rid: Rid,
+    ) -> Result<(), std::io::Error> {
+        self.move_to_block(tx, rid.block_number)?;
+        self.current_slot = Some(rid.slot);
+        Ok(())
+    }
+
+    /// Copies the current record out of the page. Large values are kept as
+    /// pointers and can be streamed later with `Record::get_reader`.
+    pub(crate) fn record(&mut self, tx: &mut Transaction) -> Result<Record, std::io::Error> {
+        let mut record = Record::new();
+        for field in self.layout.schema().fields() {
+            if self.rp.is_null(tx, self.slot(), field) {
+                record.set(field, Value::Null);
+                continue;
+            }
+            let value = match self.layout.schema().ctype(field) {
+                ColumnType::Integer => Value::Int(self.rp.get_int(tx, self.slot(), field)),
+                ColumnType::VarChar => Value::String(self.rp.get_string(tx, self.slot(), field)),
+                ColumnType::Text | ColumnType::Blob => Value::Large {
+                    filename: overflow_file(&self.tblname),
+                    pointer: self.rp.get_overflow_pointer(tx, self.slot(), field),
+                },
+            };
+            record.set(field, value);
+        }
+        Ok(record)
+    }
+
+    // Frees the overflow chain of a TEXT or BLOB field and leaves an empty
+    // value behind, so the chain is never freed twice
+    fn free_large_value(&self, tx: &mut Transaction, field: &str) -> Result<(), std::io::Error> {
+        if !matches!(
+            self.layout.schema().ctype(field),
+            ColumnType::Text | ColumnType::Blob
+        ) {
+            return Ok(());
+        }
+        let pointer = self.rp.get_overflow_pointer(tx, self.slot(), field);
+        if pointer == OverflowPointer::empty() {
+            return Ok(());
+        }
+        free_overflow(tx, &overflow_file(&self.tblname), pointer)?;
+        self.rp
+            .set_overflow_pointer(tx, self.slot(), field, OverflowPointer::empty())
+    }
+
+    fn slot(&self) -> usize {
+        self.current_slot
+            .expect("table scan is not positioned on a record")
+    }
+
+    pub(crate) fn move_to_block(
+        &mut self,
+        tx: &mut Transaction,
+        block_number: u64,
+    ) -> Result<(), std::io::Error> {
+        self.rp.close(tx);
+        let block = BlockId::new(self.filename.clone(), block_number);
+        self.rp = RecordPage::new(tx, block, self.layout.clone())?;
+        self.current_slot = None;
+        Ok(())
+    }
+
+    fn new_block(
+        tx: &mut Transaction,
+        filename: &str,
+        layout: &Layout,
+        free_space_map: &mut FreeSpaceMap,
+    ) -> Result<RecordPage, std::io::Error> {
+        let block = tx.append(filename)?;
+        let rp = RecordPage::new(tx, block, layout.clone())?;
+        rp.format(tx)?;
+        free_space_map.set_free(tx, rp.block().number(), rp.slots() as u64)?;
+        Ok(rp)
+    }
+
+    fn at_last_block(&self, tx: &mut Transaction) -> Result<bool, std::io::Error> {
+        Ok(self.rp.block().number() == tx.size(&self.filename)? - 1)
+    }
+}
+
+impl Scan for TableScan {
+    fn before_first(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        TableScan::before_first(self, tx)
+    }
+
+    fn next(&mut self, tx: &mut Transaction) -> Result<bool, std::io::Error> {
+        TableScan::next(self, tx)
+    }
+
+    fn get_int(&mut self, tx: &mut Transaction, fldname: &str) -> Result<i64, std::io::Error> {
+        TableScan::get_int(self, tx, fldname)
+    }
+
+    fn get_string(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+    ) -> Result<String, std::io::Error> {
+        TableScan::get_string(self, tx, fldname)
+    }
+
+    fn get_val(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+    ) -> Result<Constant, std::io::Error> {
+        TableScan::get_val(self, tx, fldname)
+    }
+
+    fn get_large(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+    ) -> Result<Option<LargeValue>, std::io::Error> {
+        Ok(TableScan::get_large(self, tx, fldname))
+    }
+
+    fn has_field(&self, fldname: &str) -> bool {
+        TableScan::has_field(self, fldname)
+    }
+
+    fn close(&mut self, tx: &mut Transaction) {
+        TableScan::close(self, tx)
+    }
+}
+
+impl UpdateScan for TableScan {
+    fn set_int(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+        value: i64,
+    ) -> Result<(), std::io::Error> {
+        TableScan::set_int(self, tx, fldname, value)
+    }
+
+    fn set_string(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+        value: &str,
+    ) -> Result<(), std::io::Error> {
+        TableScan::set_string(self, tx, fldname, value)
+    }
+
+    fn set_val(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+        value: &Constant,
+    ) -> Result<(), std::io::Error> {
+        TableScan::set_val(self, tx, fldname, value)
+    }
+
+    fn insert(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        TableScan::insert(self, tx)
+    }
+
+    fn delete(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        TableScan::delete(self, tx)
+    }
+
+    fn get_rid(&self) -> Rid {
+        TableScan::get_rid(self)
+    }
+
+    fn move_to_rid(&mut self, tx: &mut Transaction, rid: Rid) -> Result<(), std::io::Error> {
+        TableScan::move_to_rid(self, tx, rid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use crate::{layout::Layout, schema::Schema, simpledb::SimpleDB, transaction::Transaction};
+
+    use super::TableScan;
+
+    #[test]
+    fn test_table_scan() {
+        let _ = std::fs::remove_dir_all("tablescantest");
+        let mut db = SimpleDB::new("tablescantest", 400, 8).unwrap();
+        let mut tx = Transaction::new(
+            &db.file_manager,
+            &mut db.log_manager,
+            &mut db.buffer_manager,
+        );
+
+        let mut schema = Schema::new();
+        schema.add_int_field("A");
+        schema.add_string_field("B", 9);
+        schema.add_text_field("C");
+        schema.add_blob_field("D");
+        let layout = Layout::new(schema);
+        assert_eq!(layout.offset("A"), 8);
+        assert_eq!(layout.offset("B"), 16);
+        assert_eq!(layout.offset("C"), 33);
+        assert_eq!(layout.offset("D"), 49);
+        assert_eq!(layout.slot_size(), 65);
+
+        let blob = (0..100_000u32).map(|i| (i % 256) as u8).collect::<Vec<_>>();
+        let mut ts = TableScan::new(&mut tx, "T", layout.clone()).unwrap();
+        for i in 0..50 {
+            ts.insert(&mut tx).unwrap();
+            ts.set_int(&mut tx, "A", i).unwrap();
+            ts.set_string(&mut tx, "B", &format!("rec{}", i)).unwrap();
+            ts.set_string(&mut tx, "C", &"text".repeat(i as usize * 10))
+                .unwrap();
+            if i % 10 == 0 {
+                ts.set_from_reader(&mut tx, "D", &mut blob.as_slice())
+                    .unwrap();
+            }
+        }
+        // A string longer than its field is rejected before anything is written
+        assert!(ts.set_string(&mut tx, "B", "rec49 and more").is_err());
+        assert_eq!(ts.get_string(&mut tx, "B").unwrap(), "rec49");
+
+        // Delete the odd records
+        ts.before_first(&mut tx).unwrap();
+        while ts.next(&mut tx).unwrap() {
+            if ts.get_int(&mut tx, "A").unwrap() % 2 == 1 {
+                ts.delete(&mut tx).unwrap();
+            }
+        }
+
+        ts.before_first(&mut tx).unwrap();
+        let mut records = Vec::new();
+        while ts.next(&mut tx).unwrap() {
+            records.push(ts.record(&mut tx).unwrap());
+        }
+        ts.close(&mut tx);
+        tx.commit().unwrap();
+
+        assert_eq!(records.len(), 25);
+        for (i, record) in records.iter().enumerate() {
+            let a = i as i64 * 2;
+            assert_eq!(record.get_int("A"), a);
+            assert_eq!(record.get_string("B"), format!("rec{}", a));
+
+            let mut text = String::new();
+            record
+                .get_reader("C", &db.file_manager)
+                .unwrap()
+                .read_to_string(&mut text)
+                .unwrap();
+            assert_eq!(text, "text".repeat(a as usize * 10));
+
+            // Read the blob back in small pieces
+            let mut reader = record.get_reader("D", &db.file_manager).unwrap();
+            let mut chunk = [0; 1000];
+            let mut read = Vec::new();
+            loop {
+                let n = reader.read(&mut chunk).unwrap();
+                if n == 0 {
+                    break;
+                }
+                read.extend_from_slice(&chunk[..n]);
+            }
+            if a % 10 == 0 {
+                assert_eq!(read, blob);
+            } else {
+                assert!(read.is_empty());
+            }
+        }
+
+        // Replaced and deleted values give their overflow blocks back
+        let size = db.file_manager.size("T.ovf").unwrap();
+        let mut ts = TableScan::new(&mut tx, "T", layout).unwrap();
+        while ts.next(&mut tx).unwrap() {
+            if ts.get_int(&mut tx, "A").unwrap() % 10 == 0 {
+                ts.set_from_reader(&mut tx, "D", &mut blob.as_slice())
+                    .unwrap();
+            }
+            ts.set_null(&mut tx, "C").unwrap();
+        }
+        ts.before_first(&mut tx).unwrap();
+        while ts.next(&mut tx).unwrap() {
+            ts.delete(&mut tx).unwrap();
+        }
+        for i in 0..5 {
+            ts.insert(&mut tx).unwrap();
+            ts.set_int(&mut tx, "A", i).unwrap();
+            ts.set_from_reader(&mut tx, "D", &mut blob.as_slice())
+                .unwrap();
+        }
+        ts.close(&mut tx);
+        tx.commit().unwrap();
+        assert_eq!(db.file_manager.size("T.ovf").unwrap(), size);
+
+        // A record larger than a block is refused rather than looked for a
+        // place in ever more new blocks
+        let mut schema = Schema::new();
+        schema.add_string_field("A", 500);
+        let mut ts = TableScan::new(&mut tx, "W", Layout::new(schema)).unwrap();
+        assert!(ts.insert(&mut tx).is_err());
+        ts.close(&mut tx);
+        tx.commit().unwrap();
+        assert!(db.file_manager.size("W.tbl").unwrap() <= 1);
+    }
+}
@@ -0,0 +1,1075 @@
+use std::collections::{hash_map::Entry, HashMap};
+
+use crate::{
+    constant::Constant,
+    constraint::{Constraint, OnDelete},
+    index_manager::IndexInfo,
+    layout::Layout,
+    metadata_manager::MetadataManager,
+    parser::parse_predicate,
+    predicate::Predicate,
+    resultset::ColumnType,
+    rid::Rid,
+    sql_exception::SQLException,
+    table_scan::TableScan,
+    transaction::Transaction,
+};
+
+/// Changes the records of a table the way one statement does. Every record
+/// is checked against NOT NULL and UNIQUE constraints before it is written,
+/// and the indexes are kept up to date. Foreign keys are checked by `finish`
+/// at the end of the statement, so a record may refer to one the statement
+/// inserts later. A statement that fails leaves its changes to be undone by
+/// rolling back the transaction.
+pub(crate) struct TableWriter {
+    tblname: String,
+    // The statement's table and those linked to it by foreign keys
+    tables: HashMap<String, Table>,
+    pending: Vec<Pending>,
+}
+
+struct Table {
+    tblname: String,
+    layout: Layout,
+    // Opened on first use
+    ts: Option<TableScan>,
+    indexes: Vec<IndexInfo>,
+    constraints: Vec<Constraint>,
+    // The predicates of the CHECK constraints by name
+    checks: HashMap<String, Predicate>,
+    defaults: HashMap<String, Constant>,
+    // Foreign keys of other tables, or this one, that refer to this table
+    referencing: Vec<(String, Constraint)>,
+}
+
+// Foreign key checks left for the end of the statement
+enum Pending {
+    // A record of `table` was given `key` for the constraint
+    Match {
+        table: String,
+        constraint: Constraint,
+        key: Vec<Constant>,
+    },
+    // The referenced table lost `key`, which records of `table` may use
+    Unused {
+        table: String,
+        constraint: Constraint,
+        key: Vec<Constant>,
+    },
+}
+
+impl TableWriter {
+    pub(crate) fn new(
+        tblname: &str,
+        mdm: &mut MetadataManager,
+        tx: &mut Transaction,
+    ) -> Result<Self, SQLException> {
+        let mut tables = HashMap::new();
+        let mut queue = vec![tblname.to_string()];
+        while let Some(name) = queue.pop() {
+            if tables.contains_key(&name) {
+                continue;
+            }
+            let layout = mdm.get_layout(&name, tx)?.ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("table {} does not exist", name),
+                )
+            })?;
+            let constraints = mdm.get_constraints(&name, tx)?;
+            let referencing = mdm.get_referencing(&name, tx)?;
+            queue.extend(
+                constraints
+                    .iter()
+                    .filter_map(|c| c.reference().map(|r| r.table.clone())),
+            );
+            queue.extend(referencing.iter().map(|(t, _)| t.clone()));
+            let mut checks = HashMap::new();
+            for constraint in &constraints {
+                if let Some(predicate) = constraint.predicate() {
+                    checks.insert(constraint.name.clone(), parse_predicate(predicate)?);
+                }
+            }
+            let table = Table {
+                tblname: name.clone(),
+                layout,
+                ts: None,
+                indexes: mdm.get_index_info(&name, tx)?.into_values().collect(),
+                constraints,
+                checks,
+                defaults: mdm.get_defaults(&name, tx)?,
+                referencing,
+            };
+            tables.insert(name, table);
+        }
+        Ok(Self {
+            tblname: tblname.to_string(),
+            tables,
+            pending: Vec::new(),
+        })
+    }
+
+    pub(crate) fn constraints(&self) -> &[Constraint] {
+        &self.tables[&self.tblname].constraints
+    }
+
+    /// Inserts a record, fields without a value take their DEFAULT or are
+    /// NULL.
+    pub(crate) fn insert(
+        &mut self,
+        tx: &mut Transaction,
+        values: &HashMap<String, Constant>,
+    ) -> Result<Rid, SQLException> {
+        let tblname = self.tblname.clone();
+        let table = self.table(&tblname);
+        for (fldname, value) in values {
+            table.check_type(fldname, value)?;
+        }
+        let row = table
+            .layout
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| {
+                let value = values.get(f).or_else(|| table.defaults.get(f));
+                (f.clone(), value.cloned().unwrap_or(Constant::Null))
+            })
+            .collect::<HashMap<_, _>>();
+        let rid = table.insert(tx, &row)?;
+        self.expect_matches(&tblname, &row, None);
+        Ok(rid)
+    }
+
+    /// Sets the given fields of the record, the others keep their values.
+    pub(crate) fn update(
+        &mut self,
+        tx: &mut Transaction,
+        rid: Rid,
+        changes: &HashMap<String, Constant>,
+    ) -> Result<(), SQLException> {
+        let tblname = self.tblname.clone();
+        self.update_in(tx, &tblname, rid, changes)
+    }
+
+    /// Deletes the record and applies the delete actions of the foreign keys
+    /// that refer to it.
+    pub(crate) fn delete(&mut self, tx: &mut Transaction, rid: Rid) -> Result<(), SQLException> {
+        let tblname = self.tblname.clone();
+        self.delete_from(tx, &tblname, rid)
+    }
+
+    /// Ends the statement by checking its foreign keys.
+    pub(crate) fn finish(&mut self, tx: &mut Transaction) -> Result<(), SQLException> {
+        let result = self.check_pending(tx);
+        self.close(tx);
+        result
+    }
+
+    /// Closes the tables without any further checks, for a statement that
+    /// already failed.
+    pub(crate) fn close(&mut self, tx: &mut Transaction) {
+        for table in self.tables.values_mut() {
+            if let Some(mut ts) = table.ts.take() {
+                ts.close(tx);
+            }
+        }
+        self.pending.clear();
+    }
+
+    fn table(&mut self, tblname: &str) -> &mut Table {
+        self.tables
+            .get_mut(tblname)
+            .unwrap_or_else(|| panic!("table {} is not part of the statement", tblname))
+    }
+
+    fn update_in(
+        &mut self,
+        tx: &mut Transaction,
+        tblname: &str,
+        rid: Rid,
+        changes: &HashMap<String, Constant>,
+    ) -> Result<(), SQLException> {
+        let table = self.table(tblname);
+        for (fldname, value) in changes {
+            table.check_type(fldname, value)?;
+        }
+        let old = table.update(tx, rid, changes)?;
+        let mut row = old.clone();
+        row.extend(changes.iter().map(|(f, v)| (f.clone(), v.clone())));
+        self.expect_matches(tblname, &row, Some(changes));
+
+        // Keys that records may refer to behave like a restricted delete
+        // when they change
+        for (child, constraint) in self.tables[tblname].referencing.clone() {
+            let fields = &constraint.reference().unwrap().fields;
+            if !fields.iter().any(|f| changes.contains_key(f)) {
+                continue;
+            }
+            let key = fields.iter().map(|f| old[f].clone()).collect::<Vec<_>>();
+            if !key.iter().any(|v| v.is_null()) {
+                self.pending.push(Pending::Unused {
+                    table: child,
+                    constraint,
+                    key,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn delete_from(
+        &mut self,
+        tx: &mut Transaction,
+        tblname: &str,
+        rid: Rid,
+    ) -> Result<(), SQLException> {
+        let old = self.table(tblname).delete(tx, rid)?;
+        for (child, constraint) in self.tables[tblname].referencing.clone() {
+            let reference = constraint.reference().unwrap();
+            let key = reference
+                .fields
+                .iter()
+                .map(|f| old[f].clone())
+                .collect::<Vec<_>>();
+            if key.iter().any(|v| v.is_null()) {
+                continue;
+            }
+            match reference.on_delete {
+                OnDelete::Restrict => self.pending.push(Pending::Unused {
+                    table: child,
+                    constraint,
+                    key,
+                }),
+                OnDelete::Cascade => {
+                    for rid in self.table(&child).find(tx, &constraint.fields, &key)? {
+                        self.delete_from(tx, &child, rid)?;
+                    }
+                }
+                OnDelete::SetNull => {
+                    let changes = constraint
+                        .fields
+                        .iter()
+                        .map(|f| (f.clone(), Constant::Null))
+                        .collect::<HashMap<_, _>>();
+                    for rid in self.table(&child).find(tx, &constraint.fields, &key)? {
+                        self.update_in(tx, &child, rid, &changes)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Remembers the keys the record's foreign keys need to find, for those
+    // whose fields were set
+    fn expect_matches(
+        &mut self,
+        tblname: &str,
+        row: &HashMap<String, Constant>,
+        changes: Option<&HashMap<String, Constant>>,
+    ) {
+        for constraint in &self.tables[tblname].constraints {
+            if constraint.reference().is_none()
+                || changes.is_some_and(|c| !constraint.fields.iter().any(|f| c.contains_key(f)))
+            {
+                continue;
+            }
+            let key = constraint
+                .fields
+                .iter()
+                .map(|f| row[f].clone())
+                .collect::<Vec<_>>();
+            if !key.iter().any(|v| v.is_null()) {
+                self.pending.push(Pending::Match {
+                    table: tblname.to_string(),
+                    constraint: constraint.clone(),
+                    key,
+                });
+            }
+        }
+    }
+
+    fn check_pending(&mut self, tx: &mut Transaction) -> Result<(), SQLException> {
+        for pending in std::mem::take(&mut self.pending) {
+            match pending {
+                Pending::Match {
+                    table,
+                    constraint,
+                    key,
+                } => {
+                    let reference = constraint.reference().unwrap();
+                    let parent = self.table(&reference.table);
+                    if parent.find(tx, &reference.fields, &key)?.is_empty() {
+                        let detail = format!("has no match in {}", reference.table);
+                        return Err(constraint.reference_violation(&table, &key, &detail));
+                    }
+                }
+                Pending::Unused {
+                    table,
+                    constraint,
+                    key,
+                } => {
+                    // The key may have come back in the same statement
+                    let reference = constraint.reference().unwrap();
+                    let parent = self.table(&reference.table);
+                    if !parent.find(tx, &reference.fields, &key)?.is_empty() {
+                        continue;
+                    }
+                    if !self
+                        .table(&table)
+                        .find(tx, &constraint.fields, &key)?
+                        .is_empty()
+                    {
+                        let detail = format!("is still used by {}", table);
+                        return Err(constraint.reference_violation(&table, &key, &detail));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Table {
+    fn scan(&mut self, tx: &mut Transaction) -> Result<&mut TableScan, std::io::Error> {
+        if self.ts.is_none() {
+            self.ts = Some(TableScan::new(tx, &self.tblname, self.layout.clone())?);
+        }
+        Ok(self.ts.as_mut().unwrap())
+    }
+
+    fn insert(
+        &mut self,
+        tx: &mut Transaction,
+        row: &HashMap<String, Constant>,
+    ) -> Result<Rid, SQLException> {
+        self.check(tx, row, None)?;
+        let ts = self.scan(tx)?;
+        ts.insert(tx)?;
+        for (fldname, value) in row {
+            ts.set_val(tx, fldname, value)?;
+        }
+        let rid = ts.get_rid();
+        for info in &self.indexes {
+            let mut index = info.open();
+            index.insert(tx, &row[info.field_name()], rid)?;
+            index.close(tx);
+        }
+        Ok(rid)
+    }
+
+    // Returns the old values of the fields that keys and indexes use, and of
+    // the changed fields
+    fn update(
+        &mut self,
+        tx: &mut Transaction,
+        rid: Rid,
+        changes: &HashMap<String, Constant>,
+    ) -> Result<HashMap<String, Constant>, SQLException> {
+        let keyed = self
+            .constraints
+            .iter()
+            .filter(|c| !c.rejects_null() || c.is_unique())
+            .flat_map(|c| c.fields.iter().cloned())
+            .chain(self.indexes.iter().map(|i| i.field_name().to_string()))
+            .chain(
+                self.referencing
+                    .iter()
+                    .flat_map(|(_, c)| c.reference().unwrap().fields.iter().cloned()),
+            )
+            .chain(changes.keys().cloned())
+            .collect::<Vec<_>>();
+        let old = self.values(tx, rid, keyed)?;
+        let mut row = old.clone();
+        row.extend(changes.iter().map(|(f, v)| (f.clone(), v.clone())));
+        self.check(tx, &row, Some(rid))?;
+
+        let ts = self.scan(tx)?;
+        ts.move_to_rid(tx, rid)?;
+        for (fldname, value) in changes {
+            ts.set_val(tx, fldname, value)?;
+        }
+        for info in &self.indexes {
+            let Some(value) = changes.get(info.field_name()) else {
+                continue;
+            };
+            let mut index = info.open();
+            index.delete(tx, &old[info.field_name()], rid)?;
+            index.insert(tx, value, rid)?;
+            index.close(tx);
+        }
+        Ok(old)
+    }
+
+    // Returns the old values of the fields that indexes and foreign keys use
+    fn delete(
+        &mut self,
+        tx: &mut Transaction,
+        rid: Rid,
+    ) -> Result<HashMap<String, Constant>, SQLException> {
+        let keyed = self
+            .indexes
+            .iter()
+            .map(|i| i.field_name().to_string())
+            .chain(
+                self.referencing
+                    .iter()
+                    .flat_map(|(_, c)| c.reference().unwrap().fields.iter().cloned()),
+            )
+            .collect::<Vec<_>>();
+        let old = self.values(tx, rid, keyed)?;
+        for info in &self.indexes {
+            let mut index = info.open();
+            index.delete(tx, &old[info.field_name()], rid)?;
+            index.close(tx);
+        }
+        let ts = self.scan(tx)?;
+        ts.move_to_rid(tx, rid)?;
+        ts.delete(tx)?;
+        Ok(old)
+    }
+
+    fn values(
+        &mut self,
+        tx: &mut Transaction,
+        rid: Rid,
+        fields: Vec<String>,
+    ) -> Result<HashMap<String, Constant>, std::io::Error> {
+        let ts = self.scan(tx)?;
+        ts.move_to_rid(tx, rid)?;
+        let mut values = HashMap::new();
+        for fldname in fields {
+            if let Entry::Vacant(entry) = values.entry(fldname) {
+                let value = match ts.layout().schema().ctype(entry.key()) {
+                    // Blobs take part in no key, only whether they are set
+                    // matters
+                    ColumnType::Blob if !ts.is_null(tx, entry.key()) => {
+                        Constant::Str(String::new())
+                    }
+                    _ => ts.get_val(tx, entry.key())?,
+                };
+                entry.insert(value);
+            }
+        }
+        Ok(values)
+    }
+
+    fn check_type(&self, fldname: &str, value: &Constant) -> Result<(), std::io::Error> {
+        let schema = self.layout.schema();
+        if !schema.has_field(fldname) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("field {}.{} does not exist", self.tblname, fldname),
+            ));
+        }
+        let ctype = schema.ctype(fldname);
+        if !ctype.accepts(value) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "{} does not fit field {}.{} of type {:?}",
+                    value, self.tblname, fldname, ctype
+                ),
+            ));
+        }
+        if let (ColumnType::VarChar, Constant::Str(s)) = (ctype, value) {
+            let length = schema.length(fldname);
+            if s.len() as u64 > length {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!(
+                        "{} is longer than the {} bytes of field {}.{}",
+                        value, length, self.tblname, fldname
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // Checks the values in `row` against the NOT NULL, UNIQUE and CHECK
+    // constraints, a record being updated is not its own duplicate
+    fn check(
+        &mut self,
+        tx: &mut Transaction,
+        row: &HashMap<String, Constant>,
+        rid: Option<Rid>,
+    ) -> Result<(), SQLException> {
+        for i in 0..self.constraints.len() {
+            let constraint = &self.constraints[i];
+            if constraint.rejects_null() {
+                let null = constraint
+                    .fields
+                    .iter()
+                    .find(|f| row.get(*f).is_some_and(|v| v.is_null()));
+                if let Some(fldname) = null {
+                    return Err(constraint.null_violation(&self.tblname, fldname));
+                }
+            }
+            if let Some(predicate) = self.checks.get(&constraint.name) {
+                if predicate.evaluate(&mut &*row)? == Some(false) {
+                    return Err(constraint.check_violation(&self.tblname));
+                }
+            }
+            if !constraint.is_unique() {
+                continue;
+            }
+            let key = constraint
+                .fields
+                .iter()
+                .map(|f| row[f].clone())
+                .collect::<Vec<_>>();
+            if key.iter().any(|v| v.is_null()) {
+                continue;
+            }
+            let fields = constraint.fields.clone();
+            if self
+                .find(tx, &fields, &key)?
+                .iter()
+                .any(|r| Some(*r) != rid)
+            {
+                let constraint = &self.constraints[i];
+                return Err(constraint.duplicate_violation(&self.tblname, &key));
+            }
+        }
+        Ok(())
+    }
+
+    // The records holding the key, looked up through an index on the first
+    // field when there is one and by scanning the table otherwise
+    fn find(
+        &mut self,
+        tx: &mut Transaction,
+        fields: &[String],
+        key: &[Constant],
+    ) -> Result<Vec<Rid>, std::io::Error> {
+        let mut found = Vec::new();
+        let info = self.indexes.iter().find(|i| i.field_name() == fields[0]);
+        let Some(info) = info else {
+            let ts = self.scan(tx)?;
+            ts.before_first(tx)?;
+            while ts.next(tx)? {
+                if matches(ts, tx, fields, key)? {
+                    found.push(ts.get_rid());
+                }
+            }
+            return Ok(found);
+        };
+        let mut candidates = Vec::new();
+        let mut index = info.open();
+        index.before_first(tx, &key[0])?;
+        while index.next(tx)? {
+            candidates.push(index.get_data_rid(tx)?);
+        }
+        index.close(tx);
+        let ts = self.scan(tx)?;
+        for candidate in candidates {
+            ts.move_to_rid(tx, candidate)?;
+            if matches(ts, tx, fields, key)? {
+                found.push(candidate);
+            }
+        }
+        Ok(found)
+    }
+}
+
+fn matches(
+    ts: &mut TableScan,
+    tx: &mut Transaction,
+    fields: &[String],
+    key: &[Constant],
+) -> Result<bool, std::io::Error> {
+    for (fldname, value) in fields.iter().zip(key) {
+        if ts.get_val(tx, fldname)? != *value {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{
+        constant::Constant,
+        constraint::{Constraint, ConstraintKind, OnDelete, Reference},
+        metadata_manager::MetadataManager,
+        parser::parse_predicate,
+        resultset::ColumnType,
+        schema::Schema,
+        simpledb::SimpleDB,
+        sql_exception::SQLException,
+        table_scan::TableScan,
+        transaction::Transaction,
+    };
+
+    use super::TableWriter;
+
+    fn row(a: Option<i64>, b: Option<&str>, c: Option<i64>) -> HashMap<String, Constant> {
+        let mut row = HashMap::new();
+        if let Some(a) = a {
+            row.insert("A".to_string(), Constant::Int(a));
+        }
+        if let Some(b) = b {
+            row.insert("B".to_string(), Constant::Str(b.to_string()));
+        }
+        if let Some(c) = c {
+            row.insert("C".to_string(), Constant::Int(c));
+        }
+        row
+    }
+
+    fn violated(result: Result<impl std::fmt::Debug, SQLException>) -> String {
+        match result.unwrap_err() {
+            SQLException::ConstraintViolation {
+                constraint, table, ..
+            } => {
+                assert_eq!(table, "T");
+                constraint
+            }
+            e => panic!("unexpected error {}", e),
+        }
+    }
+
+    #[test]
+    fn test_constraints() {
+        let _ = std::fs::remove_dir_all("constrainttest");
+        let mut db = SimpleDB::new("constrainttest", 400, 8).unwrap();
+        let mdm = &mut db.metadata_manager;
+        let mut tx = Transaction::new(
+            &db.file_manager,
+            &mut db.log_manager,
+            &mut db.buffer_manager,
+        );
+
+        let mut schema = Schema::new();
+        schema.add_int_field("A");
+        schema.add_string_field("B", 10);
+        schema.add_int_field("C");
+        mdm.create_table("T", &schema, &mut tx).unwrap();
+        for constraint in [
+            Constraint::new("T_pk", ConstraintKind::PrimaryKey, &["A"]),
+            Constraint::new("T_b_key", ConstraintKind::Unique, &["B"]),
+            Constraint::new("T_c_nn", ConstraintKind::NotNull, &["C"]),
+            Constraint::new("T_bc_key", ConstraintKind::Unique, &["C", "B"]),
+        ] {
+            mdm.add_constraint("T", &constraint, &mut tx).unwrap();
+        }
+        let pk = Constraint::new("T_pk2", ConstraintKind::PrimaryKey, &["C"]);
+        assert!(mdm.add_constraint("T", &pk, &mut tx).is_err());
+        // Unique constraints are backed by an index on their first field
+        let mut indexed = mdm
+            .get_index_info("T", &mut tx)
+            .unwrap()
+            .into_values()
+            .map(|i| i.index_name().to_string())
+            .collect::<Vec<_>>();
+        indexed.sort();
+        assert_eq!(indexed, ["T_b_key", "T_bc_key", "T_pk"]);
+
+        let mut writer = TableWriter::new("T", mdm, &mut tx).unwrap();
+        let first = writer
+            .insert(&mut tx, &row(Some(1), Some("one"), Some(1)))
+            .unwrap();
+        writer
+            .insert(&mut tx, &row(Some(2), None, Some(2)))
+            .unwrap();
+        // NULLs are never duplicates of each other
+        writer
+            .insert(&mut tx, &row(Some(3), None, Some(3)))
+            .unwrap();
+        assert_eq!(
+            violated(writer.insert(&mut tx, &row(Some(1), Some("x"), Some(4)))),
+            "T_pk"
+        );
+        assert_eq!(
+            violated(writer.insert(&mut tx, &row(None, Some("x"), Some(4)))),
+            "T_pk"
+        );
+        assert_eq!(
+            violated(writer.insert(&mut tx, &row(Some(4), Some("one"), Some(4)))),
+            "T_b_key"
+        );
+        assert_eq!(
+            violated(writer.insert(&mut tx, &row(Some(4), Some("x"), None))),
+            "T_c_nn"
+        );
+        let err = writer
+            .insert(&mut tx, &row(Some(4), Some("one"), Some(4)))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "constraint T_b_key on T violated: duplicate key ('one')"
+        );
+        assert!(matches!(
+            writer.insert(&mut tx, &row(Some(4), Some("x"), None)),
+            Err(SQLException::ConstraintViolation { .. })
+        ));
+        let second = writer
+            .insert(&mut tx, &row(Some(4), Some("four"), Some(4)))
+            .unwrap();
+
+        // Strings longer than their field are rejected before anything is
+        // written
+        let long = "x".repeat(26);
+        for result in [
+            writer.insert(&mut tx, &row(Some(7), Some(&long), Some(7))),
+            writer
+                .update(&mut tx, second, &row(None, Some(&long), None))
+                .map(|_| first),
+        ] {
+            assert!(matches!(
+                result,
+                Err(SQLException::Io(e)) if e.kind() == std::io::ErrorKind::InvalidInput
+            ));
+        }
+        assert_eq!(
+            contents(mdm, "T", &mut tx),
+            ["1 'one' 1", "2 NULL 2", "3 NULL 3", "4 'four' 4"]
+        );
+
+        // A record may keep its own key, but not take another's
+        writer
+            .update(&mut tx, first, &row(Some(1), Some("one"), None))
+            .unwrap();
+        assert_eq!(
+            violated(writer.update(&mut tx, second, &row(None, Some("one"), None))),
+            "T_b_key"
+        );
+        assert_eq!(
+            violated(writer.update(&mut tx, second, &row(Some(1), None, None))),
+            "T_pk"
+        );
+        writer
+            .update(&mut tx, second, &row(Some(5), None, None))
+            .unwrap();
+        // Deleting a record frees its key, also in the index
+        writer.delete(&mut tx, first).unwrap();
+        writer
+            .update(&mut tx, second, &row(Some(1), Some("one"), None))
+            .unwrap();
+        writer
+            .insert(&mut tx, &row(Some(5), Some("five"), Some(5)))
+            .unwrap();
+        writer.finish(&mut tx).unwrap();
+
+        // Constraints are checked against the records already there
+        let unique = Constraint::new("T_c_key", ConstraintKind::Unique, &["C"]);
+        mdm.add_constraint("T", &unique, &mut tx).unwrap();
+        let mut writer = TableWriter::new("T", mdm, &mut tx).unwrap();
+        writer
+            .insert(&mut tx, &row(Some(6), Some("six"), Some(6)))
+            .unwrap();
+        writer.finish(&mut tx).unwrap();
+        let unique = Constraint::new("T_b_c_key", ConstraintKind::Unique, &["B", "C"]);
+        mdm.add_constraint("T", &unique, &mut tx).unwrap();
+        let not_null = Constraint::new("T_b_nn", ConstraintKind::NotNull, &["B"]);
+        assert_eq!(
+            violated(mdm.add_constraint("T", &not_null, &mut tx)),
+            "T_b_nn"
+        );
+
+        // Constrained fields stay, renames follow them
+        assert!(mdm.drop_column("T", "C", &mut tx).is_err());
+        mdm.rename_column("T", "C", "CC", &mut tx).unwrap();
+        mdm.rename_table("T", "U", &mut tx).unwrap();
+        let constraints = mdm.get_constraints("U", &mut tx).unwrap();
+        assert_eq!(constraints.len(), 6);
+        assert_eq!(constraints[2].fields, ["CC"]);
+        mdm.drop_table("U", &mut tx).unwrap();
+        assert!(mdm.get_constraints("U", &mut tx).unwrap().is_empty());
+        tx.commit().unwrap();
+    }
+
+    fn ints(values: &[(&str, Option<i64>)]) -> HashMap<String, Constant> {
+        values
+            .iter()
+            .map(|(f, v)| (f.to_string(), v.map_or(Constant::Null, Constant::Int)))
+            .collect()
+    }
+
+    fn contents(mdm: &mut MetadataManager, tblname: &str, tx: &mut Transaction) -> Vec<String> {
+        let layout = mdm.get_layout(tblname, tx).unwrap().unwrap();
+        let mut ts = TableScan::new(tx, tblname, layout.clone()).unwrap();
+        let mut records = Vec::new();
+        while ts.next(tx).unwrap() {
+            let values = layout
+                .schema()
+                .fields()
+                .iter()
+                .map(|f| ts.get_val(tx, f).unwrap().to_string())
+                .collect::<Vec<_>>();
+            records.push(values.join(" "));
+        }
+        ts.close(tx);
+        records.sort();
+        records
+    }
+
+    fn referencing(tblname: &str, on_delete: OnDelete) -> Constraint {
+        let reference = Reference::new("P", &["ID"], on_delete);
+        Constraint::new(
+            &format!("{}_fk", tblname),
+            ConstraintKind::ForeignKey(reference),
+            &["PID"],
+        )
+    }
+
+    #[test]
+    fn test_foreign_keys() {
+        let _ = std::fs::remove_dir_all("foreignkeytest");
+        let mut db = SimpleDB::new("foreignkeytest", 400, 8).unwrap();
+        let mdm = &mut db.metadata_manager;
+        let mut tx = Transaction::new(
+            &db.file_manager,
+            &mut db.log_manager,
+            &mut db.buffer_manager,
+        );
+
+        let mut schema = Schema::new();
+        schema.add_int_field("ID");
+        schema.add_int_field("X");
+        mdm.create_table("P", &schema, &mut tx).unwrap();
+        let pk = Constraint::new("P_pk", ConstraintKind::PrimaryKey, &["ID"]);
+        mdm.add_constraint("P", &pk, &mut tx).unwrap();
+        let mut schema = Schema::new();
+        schema.add_int_field("ID");
+        schema.add_int_field("PID");
+        for (tblname, on_delete) in [
+            ("C", OnDelete::Cascade),
+            ("R", OnDelete::Restrict),
+            ("N", OnDelete::SetNull),
+        ] {
+            mdm.create_table(tblname, &schema, &mut tx).unwrap();
+            mdm.add_constraint(tblname, &referencing(tblname, on_delete), &mut tx)
+                .unwrap();
+        }
+        // Only keys can be referred to
+        let by_x = Constraint::new(
+            "C_x_fk",
+            ConstraintKind::ForeignKey(Reference::new("P", &["X"], OnDelete::Restrict)),
+            &["ID"],
+        );
+        assert!(mdm.add_constraint("C", &by_x, &mut tx).is_err());
+        tx.commit().unwrap();
+
+        // A reference to a missing key fails when the statement ends
+        let mut writer = TableWriter::new("C", mdm, &mut tx).unwrap();
+        writer
+            .insert(&mut tx, &ints(&[("ID", Some(1)), ("PID", Some(1))]))
+            .unwrap();
+        match writer.finish(&mut tx).unwrap_err() {
+            SQLException::ForeignKeyViolation {
+                constraint,
+                table,
+                referenced,
+                ..
+            } => assert_eq!((&*constraint, &*table, &*referenced), ("C_fk", "C", "P")),
+            e => panic!("unexpected error {}", e),
+        }
+        tx.rollback().unwrap();
+
+        let mut writer = TableWriter::new("P", mdm, &mut tx).unwrap();
+        let mut parents = HashMap::new();
+        for id in 1..=4 {
+            let rid = writer
+                .insert(&mut tx, &ints(&[("ID", Some(id)), ("X", Some(id))]))
+                .unwrap();
+            parents.insert(id, rid);
+        }
+        writer.finish(&mut tx).unwrap();
+        for (tblname, records) in [
+            ("C", [(1, Some(1)), (2, Some(1)), (3, Some(4))]),
+            ("R", [(1, Some(2)), (2, Some(3)), (3, None)]),
+            ("N", [(1, Some(1)), (2, Some(3)), (3, Some(4))]),
+        ] {
+            let mut writer = TableWriter::new(tblname, mdm, &mut tx).unwrap();
+            for (id, pid) in records {
+                writer
+                    .insert(&mut tx, &ints(&[("ID", Some(id)), ("PID", pid)]))
+                    .unwrap();
+            }
+            writer.finish(&mut tx).unwrap();
+        }
+        tx.commit().unwrap();
+        assert!(mdm.drop_table("P", &mut tx).is_err());
+
+        // Deleting a key still in use by a restricting table fails
+        let mut writer = TableWriter::new("P", mdm, &mut tx).unwrap();
+        writer.delete(&mut tx, parents[&2]).unwrap();
+        let err = writer.finish(&mut tx).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "foreign key R_fk from R to P violated: key (2) is still used by R"
+        );
+        tx.rollback().unwrap();
+
+        // Unless the statement puts it back
+        let mut writer = TableWriter::new("P", mdm, &mut tx).unwrap();
+        writer.delete(&mut tx, parents[&2]).unwrap();
+        writer
+            .insert(&mut tx, &ints(&[("ID", Some(2)), ("X", Some(20))]))
+            .unwrap();
+        writer.finish(&mut tx).unwrap();
+
+        // Other tables follow the delete of their key
+        let mut writer = TableWriter::new("P", mdm, &mut tx).unwrap();
+        writer.delete(&mut tx, parents[&1]).unwrap();
+        writer.finish(&mut tx).unwrap();
+        assert_eq!(contents(mdm, "C", &mut tx), ["3 4"]);
+        assert_eq!(contents(mdm, "N", &mut tx), ["1 NULL", "2 3", "3 4"]);
+        tx.commit().unwrap();
+
+        // Changing a key that is in use is no different
+        let mut writer = TableWriter::new("P", mdm, &mut tx).unwrap();
+        writer
+            .update(&mut tx, parents[&3], &ints(&[("ID", Some(30))]))
+            .unwrap();
+        assert!(matches!(
+            writer.finish(&mut tx),
+            Err(SQLException::ForeignKeyViolation { .. })
+        ));
+        tx.rollback().unwrap();
+        let mut writer = TableWriter::new("P", mdm, &mut tx).unwrap();
+        writer
+            .update(&mut tx, parents[&3], &ints(&[("X", Some(30))]))
+            .unwrap();
+        writer.finish(&mut tx).unwrap();
+        tx.commit().unwrap();
+
+        // A table may refer to itself, in any order within a statement
+        let mut schema = Schema::new();
+        schema.add_int_field("ID");
+        schema.add_int_field("PID");
+        mdm.create_table("T", &schema, &mut tx).unwrap();
+        let pk = Constraint::new("T_pk", ConstraintKind::PrimaryKey, &["ID"]);
+        mdm.add_constraint("T", &pk, &mut tx).unwrap();
+        let reference = Reference::new("T", &["ID"], OnDelete::Cascade);
+        let fk = Constraint::new("T_fk", ConstraintKind::ForeignKey(reference), &["PID"]);
+        mdm.add_constraint("T", &fk, &mut tx).unwrap();
+        let mut writer = TableWriter::new("T", mdm, &mut tx).unwrap();
+        writer
+            .insert(&mut tx, &ints(&[("ID", Some(2)), ("PID", Some(1))]))
+            .unwrap();
+        writer
+            .insert(&mut tx, &ints(&[("ID", Some(3)), ("PID", Some(2))]))
+            .unwrap();
+        let root = writer
+            .insert(&mut tx, &ints(&[("ID", Some(1)), ("PID", None)]))
+            .unwrap();
+        writer.finish(&mut tx).unwrap();
+        let mut writer = TableWriter::new("T", mdm, &mut tx).unwrap();
+        writer.delete(&mut tx, root).unwrap();
+        writer.finish(&mut tx).unwrap();
+        assert!(contents(mdm, "T", &mut tx).is_empty());
+
+        // Records already there are checked when the key is added
+        let mut writer = TableWriter::new("R", mdm, &mut tx).unwrap();
+        writer
+            .insert(&mut tx, &ints(&[("ID", Some(9)), ("PID", None)]))
+            .unwrap();
+        writer.finish(&mut tx).unwrap();
+        let fk = Constraint::new(
+            "R_fk2",
+            ConstraintKind::ForeignKey(Reference::new("P", &["ID"], OnDelete::Cascade)),
+            &["ID"],
+        );
+        assert!(matches!(
+            mdm.add_constraint("R", &fk, &mut tx),
+            Err(SQLException::ForeignKeyViolation { .. })
+        ));
+        tx.commit().unwrap();
+    }
+
+    #[test]
+    fn test_checks_and_defaults() {
+        let _ = std::fs::remove_dir_all("checktest");
+        let mut db = SimpleDB::new("checktest", 400, 8).unwrap();
+        let mdm = &mut db.metadata_manager;
+        let mut tx = Transaction::new(
+            &db.file_manager,
+            &mut db.log_manager,
+            &mut db.buffer_manager,
+        );
+
+        let mut schema = Schema::new();
+        schema.add_int_field("A");
+        schema.add_string_field("B", 10);
+        schema.add_int_field("C");
+        mdm.create_table("T", &schema, &mut tx).unwrap();
+        for (name, text) in [("T_chk", "A > 0 and C <= 100"), ("T_b_chk", "B <> 'bad'")] {
+            let check = Constraint::check(name, &parse_predicate(text).unwrap());
+            mdm.add_constraint("T", &check, &mut tx).unwrap();
+        }
+        mdm.set_default("T", "B", Some("'none'"), &mut tx).unwrap();
+        mdm.set_default("T", "C", Some("7"), &mut tx).unwrap();
+        assert!(mdm.set_default("T", "A", Some("'x'"), &mut tx).is_err());
+        assert!(mdm.set_default("T", "A", Some("C"), &mut tx).is_err());
+        assert!(matches!(
+            mdm.set_default("T", "A", Some("7 7"), &mut tx),
+            Err(SQLException::BadSyntax { .. })
+        ));
+
+        let mut writer = TableWriter::new("T", mdm, &mut tx).unwrap();
+        let rid = writer.insert(&mut tx, &ints(&[("A", Some(1))])).unwrap();
+        // An explicit NULL is no default, and an unknown check passes
+        writer
+            .insert(&mut tx, &ints(&[("A", Some(2)), ("C", None)]))
+            .unwrap();
+        let err = writer
+            .insert(&mut tx, &ints(&[("A", Some(0))]))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "constraint T_chk on T violated: CHECK (A > 0 and C <= 100) is false"
+        );
+        let mut bad = ints(&[("A", Some(3))]);
+        bad.insert("B".to_string(), Constant::Str("bad".to_string()));
+        assert_eq!(violated(writer.insert(&mut tx, &bad)), "T_b_chk");
+        assert_eq!(
+            violated(writer.update(&mut tx, rid, &ints(&[("C", Some(200))]))),
+            "T_chk"
+        );
+        writer
+            .update(&mut tx, rid, &ints(&[("C", Some(100))]))
+            .unwrap();
+        writer.finish(&mut tx).unwrap();
+        assert_eq!(
+            contents(mdm, "T", &mut tx),
+            ["1 'none' 100", "2 'none' NULL"]
+        );
+
+        // Checks hold for the records already there, and keep their fields
+        let check = Constraint::check("T_a_chk", &parse_predicate("A < 2").unwrap());
+        assert_eq!(
+            violated(mdm.add_constraint("T", &check, &mut tx)),
+            "T_a_chk"
+        );
+        assert!(mdm.rename_column("T", "A", "AA", &mut tx).is_err());
+        assert!(mdm.drop_column("T", "C", &mut tx).is_err());
+
+        // A column added with a default keeps it for later inserts
+        mdm.add_column(
+            "T",
+            "D",
+            ColumnType::Integer,
+            0,
+            Some(Constant::Int(9)),
+            &mut tx,
+        )
+        .unwrap();
+        let mut writer = TableWriter::new("T", mdm, &mut tx).unwrap();
+        writer.insert(&mut tx, &ints(&[("A", Some(3))])).unwrap();
+        writer.finish(&mut tx).unwrap();
+        assert_eq!(
+            contents(mdm, "T", &mut tx),
+            ["1 'none' 100 9", "2 'none' NULL 9", "3 'none' 7 9"]
+        );
+        mdm.drop_column("T", "D", &mut tx).unwrap();
+        assert_eq!(mdm.get_defaults("T", &mut tx).unwrap().len(), 2);
+        mdm.set_default("T", "C", None, &mut tx).unwrap();
+        assert_eq!(mdm.get_defaults("T", &mut tx).unwrap().len(), 1);
+        tx.commit().unwrap();
+    }
+}
@@ -0,0 +1,38 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{layout::Layout, schema::Schema, table_scan::TableScan, transaction::Transaction};
+
+static NEXT_TABLE_NUM: AtomicU64 = AtomicU64::new(1);
+
+/// A table that holds intermediate results, such as the runs of a sort.
+/// It is not in the catalog, and its files are deleted when the transaction
+/// that removes it commits, or else when the database next starts.
+pub(crate) struct TempTable {
+    tblname: String,
+    layout: Layout,
+}
+
+impl TempTable {
+    pub(crate) fn new(schema: Schema) -> Self {
+        let tblname = format!("temp{}", NEXT_TABLE_NUM.fetch_add(1, Ordering::SeqCst));
+        Self {
+            tblname,
+            layout: Layout::new(schema),
+        }
+    }
+
+    pub(crate) fn open(&self, tx: &mut Transaction) -> Result<TableScan, std::io::Error> {
+        TableScan::new(tx, &self.tblname, self.layout.clone())
+    }
+
+    pub(crate) fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    pub(crate) fn remove(self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        for filename in TableScan::files(&self.tblname) {
+            tx.drop_file(&filename)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,585 @@
+use std::{cmp::Ordering, collections::HashMap};
+
+use crate::{
+    constant::Constant,
+    expression::{Expression, Row},
+    plan::Plan,
+    predicate::Predicate,
+    query_data::QueryData,
+    schema::Schema,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Operator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Operator {
+    pub(crate) fn symbol(&self) -> &'static str {
+        match self {
+            Operator::Eq => "=",
+            Operator::Ne => "<>",
+            Operator::Lt => "<",
+            Operator::Le => "<=",
+            Operator::Gt => ">",
+            Operator::Ge => ">=",
+        }
+    }
+
+    pub(crate) fn from_symbol(symbol: &str) -> Option<Self> {
+        match symbol {
+            "=" => Some(Operator::Eq),
+            "<>" | "!=" => Some(Operator::Ne),
+            "<" => Some(Operator::Lt),
+            "<=" => Some(Operator::Le),
+            ">" => Some(Operator::Gt),
+            ">=" => Some(Operator::Ge),
+            _ => None,
+        }
+    }
+
+    /// The operator that gives the same outcome with the sides swapped.
+    pub(crate) fn reversed(&self) -> Self {
+        match self {
+            Operator::Lt => Operator::Gt,
+            Operator::Le => Operator::Ge,
+            Operator::Gt => Operator::Lt,
+            Operator::Ge => Operator::Le,
+            op => *op,
+        }
+    }
+
+    fn holds(&self, ordering: Ordering) -> bool {
+        match self {
+            Operator::Eq => ordering.is_eq(),
+            Operator::Ne => ordering.is_ne(),
+            Operator::Lt => ordering.is_lt(),
+            Operator::Le => ordering.is_le(),
+            Operator::Gt => ordering.is_gt(),
+            Operator::Ge => ordering.is_ge(),
+        }
+    }
+}
+
+/// A condition on a record, the conjuncts of a [`Predicate`]. Disjunctions
+/// and negations hold whole predicates, so conditions nest to any depth.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Term {
+    Compare(Expression, Operator, Expression),
+    /// `expr BETWEEN low AND high`, bounds included.
+    Between {
+        expr: Expression,
+        low: Expression,
+        high: Expression,
+        negated: bool,
+    },
+    In {
+        expr: Expression,
+        list: Vec<Expression>,
+        negated: bool,
+    },
+    /// `expr LIKE pattern`, where `%` in the pattern matches any text and
+    /// `_` any one character.
+    Like {
+        expr: Expression,
+        pattern: Expression,
+        negated: bool,
+    },
+    /// `expr IN` the values of the single field of the query's records.
+    InQuery {
+        expr: Expression,
+        query: Box<QueryData>,
+        negated: bool,
+    },
+    Exists(Box<QueryData>),
+    Not(Predicate),
+    Or(Vec<Predicate>),
+}
+
+impl Term {
+    pub(crate) fn new(lhs: Expression, op: Operator, rhs: Expression) -> Self {
+        Term::Compare(lhs, op, rhs)
+    }
+
+    /// The outcome under SQL's three valued logic, where comparing with
+    /// NULL is unknown.
+    pub(crate) fn evaluate(&self, row: &mut dyn Row) -> Result<Option<bool>, std::io::Error> {
+        match self {
+            Term::Compare(lhs, op, rhs) => {
+                let lhs = lhs.evaluate(row)?;
+                let rhs = rhs.evaluate(row)?;
+                Ok(compare(&lhs, *op, &rhs))
+            }
+            Term::Between {
+                expr,
+                low,
+                high,
+                negated,
+            } => {
+                let value = expr.evaluate(row)?;
+                let above = compare(&value, Operator::Ge, &low.evaluate(row)?);
+                let below = compare(&value, Operator::Le, &high.evaluate(row)?);
+                let result = match (above, below) {
+                    (Some(false), _) | (_, Some(false)) => Some(false),
+                    (Some(true), Some(true)) => Some(true),
+                    _ => None,
+                };
+                Ok(negate(result, *negated))
+            }
+            Term::In {
+                expr,
+                list,
+                negated,
+            } => {
+                let value = expr.evaluate(row)?;
+                let mut items = Vec::new();
+                for item in list {
+                    items.push(item.evaluate(row)?);
+                }
+                Ok(negate(is_in(&value, &items), *negated))
+            }
+            Term::InQuery {
+                expr,
+                query,
+                negated,
+            } => {
+                let value = expr.evaluate(row)?;
+                let items = row.subquery_values(query)?;
+                Ok(negate(is_in(&value, &items), *negated))
+            }
+            Term::Exists(query) => Ok(Some(!row.subquery_values(query)?.is_empty())),
+            Term::Like {
+                expr,
+                pattern,
+                negated,
+            } => {
+                let (value, pattern) = (expr.evaluate(row)?, pattern.evaluate(row)?);
+                let result = match (&value, &pattern) {
+                    (Constant::Null, _) | (_, Constant::Null) => None,
+                    (Constant::Str(s), Constant::Str(p)) => Some(like(s, p)),
+                    _ => {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::InvalidInput,
+                            format!("{} LIKE {} needs strings", value, pattern),
+                        ))
+                    }
+                };
+                Ok(negate(result, *negated))
+            }
+            Term::Not(predicate) => Ok(predicate.evaluate(row)?.map(|b| !b)),
+            Term::Or(predicates) => {
+                let mut result = Some(false);
+                for predicate in predicates {
+                    match predicate.evaluate(row)? {
+                        Some(true) => return Ok(Some(true)),
+                        None => result = None,
+                        Some(false) => {}
+                    }
+                }
+                Ok(result)
+            }
+        }
+    }
+
+    pub(crate) fn collect_fields(&self, fields: &mut Vec<String>) {
+        match self {
+            Term::Compare(lhs, _, rhs) => {
+                lhs.collect_fields(fields);
+                rhs.collect_fields(fields);
+            }
+            Term::Between {
+                expr, low, high, ..
+            } => {
+                for e in [expr, low, high] {
+                    e.collect_fields(fields);
+                }
+            }
+            Term::In { expr, list, .. } => {
+                for e in std::iter::once(expr).chain(list) {
+                    e.collect_fields(fields);
+                }
+            }
+            Term::Like { expr, pattern, .. } => {
+                expr.collect_fields(fields);
+                pattern.collect_fields(fields);
+            }
+            Term::InQuery { expr, .. } => expr.collect_fields(fields),
+            Term::Exists(_) => {}
+            Term::Not(predicate) => {
+                for fldname in predicate.fields() {
+                    if !fields.contains(&fldname) {
+                        fields.push(fldname);
+                    }
+                }
+            }
+            Term::Or(predicates) => {
+                for fldname in predicates.iter().flat_map(|p| p.fields()) {
+                    if !fields.contains(&fldname) {
+                        fields.push(fldname);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Whether every field the term names is in the schema. Never so for a
+    /// term with a subquery, which may name others.
+    pub(crate) fn applies_to(&self, schema: &Schema) -> bool {
+        if self.has_subquery() {
+            return false;
+        }
+        let mut fields = Vec::new();
+        self.collect_fields(&mut fields);
+        fields.iter().all(|f| schema.has_field(f))
+    }
+
+    /// Adds the subqueries of the term to `subqueries`, but not those nested
+    /// in them.
+    pub(crate) fn collect_subqueries<'q>(&'q self, subqueries: &mut Vec<&'q QueryData>) {
+        match self {
+            Term::Compare(lhs, _, rhs) => {
+                lhs.collect_subqueries(subqueries);
+                rhs.collect_subqueries(subqueries);
+            }
+            Term::Between {
+                expr, low, high, ..
+            } => {
+                for e in [expr, low, high] {
+                    e.collect_subqueries(subqueries);
+                }
+            }
+            Term::In { expr, list, .. } => {
+                for e in std::iter::once(expr).chain(list) {
+                    e.collect_subqueries(subqueries);
+                }
+            }
+            Term::Like { expr, pattern, .. } => {
+                expr.collect_subqueries(subqueries);
+                pattern.collect_subqueries(subqueries);
+            }
+            Term::InQuery { expr, query, .. } => {
+                expr.collect_subqueries(subqueries);
+                subqueries.push(query);
+            }
+            Term::Exists(query) => subqueries.push(query),
+            Term::Not(predicate) => subqueries.extend(predicate.subqueries()),
+            Term::Or(predicates) => {
+                for predicate in predicates {
+                    subqueries.extend(predicate.subqueries());
+                }
+            }
+        }
+    }
+
+    pub(crate) fn has_subquery(&self) -> bool {
+        match self {
+            Term::Compare(lhs, _, rhs) => lhs.has_subquery() || rhs.has_subquery(),
+            Term::Between {
+                expr, low, high, ..
+            } => [expr, low, high].iter().any(|e| e.has_subquery()),
+            Term::In { expr, list, .. } => {
+                expr.has_subquery() || list.iter().any(|e| e.has_subquery())
+            }
+            Term::Like { expr, pattern, .. } => expr.has_subquery() || pattern.has_subquery(),
+            Term::InQuery { .. } | Term::Exists(_) => true,
+            Term::Not(predicate) => predicate.has_subquery(),
+            Term::Or(predicates) => predicates.iter().any(|p| p.has_subquery()),
+        }
+    }
+
+    pub(crate) fn map_fields(&self, f: &dyn Fn(&str) -> Option<Expression>) -> Term {
+        match self {
+            Term::Compare(lhs, op, rhs) => Term::Compare(lhs.map_fields(f), *op, rhs.map_fields(f)),
+            Term::Between {
+                expr,
+                low,
+                high,
+                negated,
+            } => Term::Between {
+                expr: expr.map_fields(f),
+                low: low.map_fields(f),
+                high: high.map_fields(f),
+                negated: *negated,
+            },
+            Term::In {
+                expr,
+                list,
+                negated,
+            } => Term::In {
+                expr: expr.map_fields(f),
+                list: list.iter().map(|e| e.map_fields(f)).collect(),
+                negated: *negated,
+            },
+            Term::Like {
+                expr,
+                pattern,
+                negated,
+            } => Term::Like {
+                expr: expr.map_fields(f),
+                pattern: pattern.map_fields(f),
+                negated: *negated,
+            },
+            Term::InQuery {
+                expr,
+                query,
+                negated,
+            } => Term::InQuery {
+                expr: expr.map_fields(f),
+                query: Box::new(query.map_fields(f)),
+                negated: *negated,
+            },
+            Term::Exists(query) => Term::Exists(Box::new(query.map_fields(f))),
+            Term::Not(predicate) => Term::Not(predicate.map_fields(f)),
+            Term::Or(predicates) => Term::Or(predicates.iter().map(|p| p.map_fields(f)).collect()),
+        }
+    }
+
+    /// The constant the field is equated with, when the term has the form
+    /// `F = c` or `c = F`.
+    pub(crate) fn equates_with_constant(&self, fldname: &str) -> Option<&Constant> {
+        match self {
+            Term::Compare(Expression::Field(f), Operator::Eq, Expression::Constant(c))
+            | Term::Compare(Expression::Constant(c), Operator::Eq, Expression::Field(f))
+                if f == fldname =>
+            {
+                Some(c)
+            }
+            _ => None,
+        }
+    }
+
+    /// The other field the field is equated with, when the term has the form
+    /// `F = G` or `G = F`.
+    pub(crate) fn equates_with_field(&self, fldname: &str) -> Option<&str> {
+        let Term::Compare(lhs, Operator::Eq, rhs) = self else {
+            return None;
+        };
+        match (lhs.as_field(), rhs.as_field()) {
+            (Some(l), Some(r)) if l == fldname => Some(r),
+            (Some(l), Some(r)) if r == fldname => Some(l),
+            _ => None,
+        }
+    }
+
+    /// Estimated fraction of the plan's records that satisfy the term.
+    pub(crate) fn selectivity(&self, plan: &dyn Plan) -> f64 {
+        let mut fields = Vec::new();
+        self.collect_fields(&mut fields);
+        if fields.is_empty() && !self.has_subquery() {
+            return match self.evaluate(&mut &HashMap::new()) {
+                Ok(Some(true)) => 1.0,
+                _ => 0.0,
+            };
+        }
+        match self {
+            Term::Compare(lhs, op, rhs) => match (lhs, rhs) {
+                (Expression::Field(l), Expression::Field(r)) => {
+                    let distinct = plan.distinct_values(l).max(plan.distinct_values(r));
+                    let eq = 1.0 / distinct.max(1) as f64;
+                    match op {
+                        Operator::Eq => eq,
+                        Operator::Ne => 1.0 - eq,
+                        _ => 1.0 / 3.0,
+                    }
+                }
+                (Expression::Field(f), Expression::Constant(c)) => {
+                    constant_selectivity(plan, f, *op, c)
+                }
+                (Expression::Constant(c), Expression::Field(f)) => {
+                    constant_selectivity(plan, f, op.reversed(), c)
+                }
+                // Equal to a subquery's value, as though to some constant
+                (Expression::Field(f), Expression::Subquery(_))
+                | (Expression::Subquery(_), Expression::Field(f))
+                    if *op == Operator::Eq =>
+                {
+                    1.0 / plan.distinct_values(f).max(1) as f64
+                }
+                _ => 1.0 / 3.0,
+            },
+            Term::Between {
+                expr: Expression::Field(f),
+                low: Expression::Constant(low),
+                high: Expression::Constant(high),
+                negated,
+            } => {
+                let s = if low.is_null() || high.is_null() {
+                    0.0
+                } else {
+                    plan.range_selectivity(f, Some(low), Some(high))
+                };
+                negate_selectivity(s, *negated)
+            }
+            Term::In {
+                expr: Expression::Field(f),
+                list,
+                negated,
+            } if list.iter().all(|e| e.as_constant().is_some()) => {
+                let mut values = list
+                    .iter()
+                    .filter_map(|e| e.as_constant())
+                    .collect::<Vec<_>>();
+                values.sort();
+                values.dedup();
+                let s = values
+                    .iter()
+                    .filter(|v| !v.is_null())
+                    .map(|v| plan.eq_selectivity(f, v))
+                    .sum::<f64>();
+                negate_selectivity(s.min(1.0), *negated)
+            }
+            Term::Like {
+                expr: Expression::Field(f),
+                pattern: Expression::Constant(Constant::Str(pattern)),
+                negated,
+            } => negate_selectivity(like_selectivity(plan, f, pattern), *negated),
+            Term::Not(predicate) => 1.0 - predicate.selectivity(plan),
+            // The disjuncts are taken to be independent
+            Term::Or(predicates) => {
+                1.0 - predicates
+                    .iter()
+                    .map(|p| 1.0 - p.selectivity(plan))
+                    .product::<f64>()
+            }
+            // The traditional guess when nothing better is known
+            _ => 1.0 / 3.0,
+        }
+    }
+
+    /// How many times fewer records satisfy the term than the plan outputs,
+    /// infinite when none are expected to.
+    pub(crate) fn reduction_factor(&self, plan: &dyn Plan) -> f64 {
+        1.0 / self.selectivity(plan)
+    }
+}
+
+fn compare(lhs: &Constant, op: Operator, rhs: &Constant) -> Option<bool> {
+    if lhs.is_null() || rhs.is_null() {
+        return None;
+    }
+    Some(op.holds(lhs.cmp(rhs)))
+}
+
+fn is_in(value: &Constant, items: &[Constant]) -> Option<bool> {
+    let mut result = Some(false);
+    for item in items {
+        match compare(value, Operator::Eq, item) {
+            Some(true) => return Some(true),
+            None => result = None,
+            Some(false) => {}
+        }
+    }
+    result
+}
+
+fn negate(result: Option<bool>, negated: bool) -> Option<bool> {
+    if negated {
+        result.map(|b| !b)
+    } else {
+        result
+    }
+}
+
+fn negate_selectivity(selectivity: f64, negated: bool) -> f64 {
+    if negated {
+        1.0 - selectivity
+    } else {
+        selectivity
+    }
+}
+
+fn constant_selectivity(plan: &dyn Plan, fldname: &str, op: Operator, value: &Constant) -> f64 {
+    if value.is_null() {
+        return 0.0;
+    }
+    match op {
+        Operator::Eq => plan.eq_selectivity(fldname, value),
+        Operator::Ne => 1.0 - plan.eq_selectivity(fldname, value),
+        Operator::Lt | Operator::Le => plan.range_selectivity(fldname, None, Some(value)),
+        Operator::Gt | Operator::Ge => plan.range_selectivity(fldname, Some(value), None),
+    }
+}
+
+/// A pattern without wildcards is an equality, one with a fixed prefix a
+/// range of the strings starting with it.
+fn like_selectivity(plan: &dyn Plan, fldname: &str, pattern: &str) -> f64 {
+    let prefix = pattern.split(['%', '_']).next().unwrap_or_default();
+    if prefix.len() == pattern.len() {
+        return plan.eq_selectivity(fldname, &Constant::Str(pattern.to_string()));
+    }
+    if prefix.is_empty() {
+        return 0.1;
+    }
+    let low = Constant::Str(prefix.to_string());
+    let high = Constant::Str(format!("{}{}", prefix, char::MAX));
+    plan.range_selectivity(fldname, Some(&low), Some(&high))
+}
+
+/// Matches the text against a LIKE pattern, going back to the last `%` seen
+/// when the rest fails to match.
+fn like(text: &str, pattern: &str) -> bool {
+    let text = text.chars().collect::<Vec<_>>();
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let (mut t, mut p) = (0, 0);
+    let mut retry = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '_' || pattern[p] == text[t]) {
+            t += 1;
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == '%' {
+            retry = Some((t, p));
+            p += 1;
+        } else if let Some((rt, rp)) = retry {
+            retry = Some((rt + 1, rp));
+            t = rt + 1;
+            p = rp + 1;
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '%')
+}
+
+impl std::fmt::Display for Term {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let not = |negated: &bool| if *negated { "not " } else { "" };
+        match self {
+            Term::Compare(lhs, op, rhs) => write!(f, "{} {} {}", lhs, op.symbol(), rhs),
+            Term::Between {
+                expr,
+                low,
+                high,
+                negated,
+            } => write!(f, "{} {}between {} and {}", expr, not(negated), low, high),
+            Term::In {
+                expr,
+                list,
+                negated,
+            } => {
+                let list = list.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+                write!(f, "{} {}in ({})", expr, not(negated), list.join(", "))
+            }
+            Term::Like {
+                expr,
+                pattern,
+                negated,
+            } => write!(f, "{} {}like {}", expr, not(negated), pattern),
+            Term::InQuery {
+                expr,
+                query,
+                negated,
+            } => write!(f, "{} {}in ({})", expr, not(negated), query),
+            Term::Exists(query) => write!(f, "exists ({})", query),
+            Term::Not(predicate) => write!(f, "not ({})", predicate),
+            Term::Or(predicates) => {
+                let disjuncts = predicates.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+                write!(f, "({})", disjuncts.join(" or "))
+            }
+        }
+    }
+}
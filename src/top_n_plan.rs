@@ -0,0 +1,55 @@
+use crate::{
+    plan::Plan,
+    record_comparator::{RecordComparator, SortKey},
+    scan::Scan,
+    schema::Schema,
+    top_n_scan::TopNScan,
+    transaction::Transaction,
+};
+
+/// The first `n` records of the underlying plan in the order of the sort
+/// keys. They are kept in memory, so reading the result costs nothing
+/// beyond reading the underlying plan once.
+pub(crate) struct TopNPlan {
+    plan: Box<dyn Plan>,
+    keys: Vec<SortKey>,
+    n: u64,
+}
+
+impl TopNPlan {
+    pub(crate) fn new(plan: Box<dyn Plan>, keys: Vec<SortKey>, n: u64) -> Self {
+        Self { plan, keys, n }
+    }
+}
+
+impl Plan for TopNPlan {
+    fn open(&self, tx: &mut Transaction) -> Result<Box<dyn Scan>, std::io::Error> {
+        let src = self.plan.open(tx)?;
+        let comparator = RecordComparator::new(self.keys.clone());
+        Ok(Box::new(TopNScan::new(
+            src,
+            self.plan.schema(),
+            comparator,
+            self.n,
+            tx,
+        )?))
+    }
+
+    fn blocks_accessed(&self) -> u64 {
+        self.plan.blocks_accessed()
+    }
+
+    fn records_output(&self) -> u64 {
+        self.plan.records_output().min(self.n)
+    }
+
+    fn distinct_values(&self, fldname: &str) -> u64 {
+        self.plan
+            .distinct_values(fldname)
+            .min(self.records_output())
+    }
+
+    fn schema(&self) -> &Schema {
+        self.plan.schema()
+    }
+}
@@ -0,0 +1,259 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use crate::{
+    constant::Constant, record_comparator::RecordComparator, scan::Scan, schema::Schema,
+    transaction::Transaction,
+};
+
+/// The first `n` records of a scan in the comparator's order, for an ORDER
+/// BY with a LIMIT. Rather than sorting every record, the scan keeps the
+/// best `n` seen so far in a heap whose top is the worst of them, so it
+/// holds no more than `n` records at a time. Records that compare equal
+/// stay in the order the underlying scan gives them.
+pub(crate) struct TopNScan {
+    fields: Vec<String>,
+    rows: Vec<Vec<Constant>>,
+    current: Option<usize>,
+}
+
+/// A record in the heap, ordered by its sort keys and then by when it came.
+struct Entry {
+    // The value of each sort key, and whether it is descending
+    key: Vec<(Constant, bool)>,
+    seq: usize,
+    values: Vec<Constant>,
+}
+
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        for ((v1, descending), (v2, _)) in self.key.iter().zip(&other.key) {
+            let ordering = v1.cmp(v2);
+            if ordering.is_ne() {
+                return if *descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                };
+            }
+        }
+        self.seq.cmp(&other.seq)
+    }
+}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Entry {}
+
+impl TopNScan {
+    /// Reads the records of `src`, which has the fields of the schema, and
+    /// closes it.
+    pub(crate) fn new<S: Scan>(
+        mut src: S,
+        schema: &Schema,
+        comparator: RecordComparator,
+        n: u64,
+        tx: &mut Transaction,
+    ) -> Result<Self, std::io::Error> {
+        let fields = schema.fields().to_vec();
+        let mut heap = BinaryHeap::new();
+        if n > 0 {
+            src.before_first(tx)?;
+            let mut seq = 0;
+            while src.next(tx)? {
+                let mut key = Vec::new();
+                for k in comparator.keys() {
+                    key.push((src.get_val(tx, &k.field)?, k.descending));
+                }
+                let mut entry = Entry {
+                    key,
+                    seq,
+                    values: Vec::new(),
+                };
+                seq += 1;
+                if heap.len() as u64 == n {
+                    if heap.peek().is_some_and(|worst| entry >= *worst) {
+                        continue;
+                    }
+                    heap.pop();
+                }
+                for fldname in &fields {
+                    entry.values.push(src.get_val(tx, fldname)?);
+                }
+                heap.push(entry);
+            }
+        }
+        src.close(tx);
+        let rows = heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|e| e.values)
+            .collect();
+        Ok(Self {
+            fields,
+            rows,
+            current: None,
+        })
+    }
+
+    fn value(&self, fldname: &str) -> Result<&Constant, std::io::Error> {
+        let row = &self.rows[self.current.expect("top-n scan is not on a record")];
+        match self.fields.iter().position(|f| f == fldname) {
+            Some(i) => Ok(&row[i]),
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("field {} not found", fldname),
+            )),
+        }
+    }
+
+    fn mismatch(fldname: &str, value: &Constant, wanted: &str) -> std::io::Error {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("field {} is {}, not {}", fldname, value, wanted),
+        )
+    }
+}
+
+impl Scan for TopNScan {
+    fn before_first(&mut self, _tx: &mut Transaction) -> Result<(), std::io::Error> {
+        self.current = None;
+        Ok(())
+    }
+
+    fn next(&mut self, _tx: &mut Transaction) -> Result<bool, std::io::Error> {
+        let next = self.current.map_or(0, |i| i + 1);
+        if next < self.rows.len() {
+            self.current = Some(next);
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn get_int(&mut self, _tx: &mut Transaction, fldname: &str) -> Result<i64, std::io::Error> {
+        match self.value(fldname)? {
+            Constant::Int(i) => Ok(*i),
+            value => Err(Self::mismatch(fldname, value, "an integer")),
+        }
+    }
+
+    fn get_string(
+        &mut self,
+        _tx: &mut Transaction,
+        fldname: &str,
+    ) -> Result<String, std::io::Error> {
+        match self.value(fldname)? {
+            Constant::Str(s) => Ok(s.clone()),
+            value => Err(Self::mismatch(fldname, value, "a string")),
+        }
+    }
+
+    fn get_val(
+        &mut self,
+        _tx: &mut Transaction,
+        fldname: &str,
+    ) -> Result<Constant, std::io::Error> {
+        self.value(fldname).cloned()
+    }
+
+    fn has_field(&self, fldname: &str) -> bool {
+        self.fields.iter().any(|f| f == fldname)
+    }
+
+    fn close(&mut self, _tx: &mut Transaction) {
+        self.rows.clear();
+        self.current = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        layout::Layout,
+        limit_scan::LimitScan,
+        record_comparator::{RecordComparator, SortKey},
+        scan::Scan,
+        schema::Schema,
+        simpledb::SimpleDB,
+        sort_scan::SortScan,
+        table_scan::TableScan,
+        transaction::Transaction,
+    };
+
+    use super::TopNScan;
+
+    #[test]
+    fn test_top_n_scan() {
+        let _ = std::fs::remove_dir_all("topntest");
+        let mut db = SimpleDB::new("topntest", 400, 8).unwrap();
+        let mut tx = Transaction::new(
+            &db.file_manager,
+            &mut db.log_manager,
+            &mut db.buffer_manager,
+        );
+
+        let mut schema = Schema::new();
+        schema.add_int_field("A");
+        schema.add_int_field("B");
+        let layout = Layout::new(schema.clone());
+        let mut ts = TableScan::new(&mut tx, "T", layout.clone()).unwrap();
+        for i in 0..200 {
+            ts.insert(&mut tx).unwrap();
+            match (i * 37) % 11 {
+                0 => ts.set_null(&mut tx, "A").unwrap(),
+                a => ts.set_int(&mut tx, "A", a).unwrap(),
+            }
+            ts.set_int(&mut tx, "B", i).unwrap();
+        }
+        ts.close(&mut tx);
+
+        // Page through the records as a full sort would give them
+        let keys = vec![SortKey::new("A", true), SortKey::new("B", false)];
+        let read = |scan: &mut dyn Scan, tx: &mut Transaction| {
+            let mut rows = Vec::new();
+            scan.before_first(tx).unwrap();
+            while scan.next(tx).unwrap() {
+                let a = scan.get_val(tx, "A").unwrap();
+                let b = scan.get_val(tx, "B").unwrap();
+                rows.push(format!("{} {}", a, b));
+            }
+            rows
+        };
+        let ts = TableScan::new(&mut tx, "T", layout.clone()).unwrap();
+        let comparator = RecordComparator::new(keys.clone());
+        let mut sorted = SortScan::new(ts, &schema, comparator, &mut tx).unwrap();
+        let all = read(&mut sorted, &mut tx);
+        sorted.close(&mut tx);
+        for (limit, offset) in [(5, 0), (10, 15), (30, 190), (0, 3)] {
+            let ts = TableScan::new(&mut tx, "T", layout.clone()).unwrap();
+            let comparator = RecordComparator::new(keys.clone());
+            let top = TopNScan::new(ts, &schema, comparator, limit + offset, &mut tx).unwrap();
+            let mut scan = LimitScan::new(top, Some(limit), offset);
+            let rows = read(&mut scan, &mut tx);
+            let end = all.len().min((limit + offset) as usize);
+            let start = end.min(offset as usize);
+            assert_eq!(rows, all[start..end], "{} {}", limit, offset);
+            scan.close(&mut tx);
+        }
+
+        // Ties keep the order of the underlying scan
+        let ts = TableScan::new(&mut tx, "T", layout).unwrap();
+        let comparator = RecordComparator::new(vec![SortKey::new("A", false)]);
+        let mut scan = TopNScan::new(ts, &schema, comparator, 3, &mut tx).unwrap();
+        assert_eq!(read(&mut scan, &mut tx), ["NULL 0", "NULL 11", "NULL 22"]);
+        assert!(scan.get_string(&mut tx, "A").is_err());
+        assert!(scan.get_val(&mut tx, "C").is_err());
+        scan.close(&mut tx);
+        tx.commit().unwrap();
+    }
+}
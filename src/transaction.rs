@@ -0,0 +1,474 @@
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::{
+    buffer_manager::BufferManager,
+    file_manager::{BlockId, FileManager},
+    log_manager::LogManager,
+    log_record::LogRecord,
+    page::Page,
+};
+
+static NEXT_TX_NUM: AtomicU64 = AtomicU64::new(1);
+
+pub(crate) struct Transaction<'a> {
+    pub(crate) file_manager: &'a FileManager,
+    pub(crate) log_manager: &'a mut LogManager,
+    pub(crate) buffer_manager: &'a mut BufferManager,
+    pub(crate) txnum: u64,
+    buffers: HashMap<BlockId, usize>,
+    pins: Vec<BlockId>,
+    // The start record is only written along with the first update
+    started: bool,
+    // Files dropped by this transaction, deleted once it commits
+    pending_deletes: Vec<String>,
+}
+
+impl<'a> Transaction<'a> {
+    pub(crate) fn new(
+        file_manager: &'a FileManager,
+        log_manager: &'a mut LogManager,
+        buffer_manager: &'a mut BufferManager,
+    ) -> Self {
+        Self {
+            file_manager,
+            log_manager,
+            buffer_manager,
+            txnum: NEXT_TX_NUM.fetch_add(1, Ordering::SeqCst),
+            buffers: HashMap::new(),
+            pins: Vec::new(),
+            started: false,
+            pending_deletes: Vec::new(),
+        }
+    }
+
+    /// Writes the transaction's blocks to disk before the commit record, so
+    /// recovery never has to redo anything but the deletion of dropped files.
+    pub(crate) fn commit(&mut self) -> Result<(), std::io::Error> {
+        self.buffer_manager
+            .flush_all(self.file_manager, self.log_manager, self.txnum)?;
+        if self.started {
+            let lsn = self.log(&LogRecord::Commit(self.txnum))?;
+            self.log_manager.flush_with_lsn(self.file_manager, lsn)?;
+            self.started = false;
+        }
+        self.unpin_all();
+        let deletes = std::mem::take(&mut self.pending_deletes);
+        for filename in &deletes {
+            self.delete_file(filename)?;
+        }
+        // Recovery must not delete them again, by then a file of the same
+        // name may be a new one
+        if !deletes.is_empty() {
+            let lsn = self.log(&LogRecord::FilesDeleted(self.txnum))?;
+            self.log_manager.flush_with_lsn(self.file_manager, lsn)?;
+        }
+        Ok(())
+    }
+
+    /// Schedules the file for deletion when the transaction commits. Until
+    /// then it stays on disk, so a rollback or a crash loses nothing.
+    pub(crate) fn drop_file(&mut self, filename: &str) -> Result<(), std::io::Error> {
+        if self.size(filename)? == 0 || self.is_dropping(filename) {
+            return Ok(());
+        }
+        self.start()?;
+        let lsn = self.log(&LogRecord::DropFile {
+            txnum: self.txnum,
+            filename: filename.to_string(),
+        })?;
+        self.log_manager.flush_with_lsn(self.file_manager, lsn)?;
+        self.pending_deletes.push(filename.to_string());
+        Ok(())
+    }
+
+    pub(crate) fn is_dropping(&self, filename: &str) -> bool {
+        self.pending_deletes.iter().any(|f| f == filename)
+    }
+
+    fn delete_file(&mut self, filename: &str) -> Result<(), std::io::Error> {
+        self.buffer_manager.discard_file(filename);
+        self.file_manager.delete(filename)
+    }
+
+    /// Undoes every update the transaction made, newest first.
+    pub(crate) fn rollback(&mut self) -> Result<(), std::io::Error> {
+        if self.started {
+            let mut undo = Vec::new();
+            for bytes in self.log_manager.iterator(self.file_manager)? {
+                let record = LogRecord::from_bytes(bytes?)?;
+                if record.txnum() != Some(self.txnum) {
+                    continue;
+                }
+                match record {
+                    LogRecord::Start(_) => break,
+                    LogRecord::SetBytes {
+                        block, offset, old, ..
+                    } => undo.push((block, offset, old)),
+                    _ => {}
+                }
+            }
+            for (block, offset, old) in undo {
+                self.undo(&block, offset, &old)?;
+            }
+            self.buffer_manager
+                .flush_all(self.file_manager, self.log_manager, self.txnum)?;
+            let lsn = self.log(&LogRecord::Rollback(self.txnum))?;
+            self.log_manager.flush_with_lsn(self.file_manager, lsn)?;
+            self.started = false;
+        }
+        self.pending_deletes.clear();
+        self.unpin_all();
+        Ok(())
+    }
+
+    /// Undoes the updates of every transaction that neither committed nor
+    /// rolled back before the last shutdown and finishes the file deletions
+    /// of those that committed but did not get to log that they were done.
+    /// The log is then marked with a checkpoint so the next recovery can stop
+    /// there.
+    pub(crate) fn recover(&mut self) -> Result<(), std::io::Error> {
+        let mut committed = Vec::new();
+        let mut rolled_back = Vec::new();
+        let mut undo = Vec::new();
+        let mut deletes = Vec::new();
+        let mut deleted = Vec::new();
+        for bytes in self.log_manager.iterator(self.file_manager)? {
+            match LogRecord::from_bytes(bytes?)? {
+                LogRecord::Checkpoint => break,
+                LogRecord::Commit(txnum) => committed.push(txnum),
+                LogRecord::Rollback(txnum) => rolled_back.push(txnum),
+                LogRecord::FilesDeleted(txnum) => deleted.push(txnum),
+                LogRecord::SetBytes {
+                    txnum,
+                    block,
+                    offset,
+                    old,
+                } if !committed.contains(&txnum) && !rolled_back.contains(&txnum) => {
+                    undo.push((block, offset, old))
+                }
+                LogRecord::DropFile { txnum, filename }
+                    if committed.contains(&txnum) && !deleted.contains(&txnum) =>
+                {
+                    deletes.push(filename)
+                }
+                _ => {}
+            }
+        }
+        for (block, offset, old) in undo {
+            self.undo(&block, offset, &old)?;
+        }
+        for filename in deletes {
+            self.delete_file(&filename)?;
+        }
+        self.buffer_manager
+            .flush_all(self.file_manager, self.log_manager, self.txnum)?;
+        let lsn = self.log(&LogRecord::Checkpoint)?;
+        self.log_manager.flush_with_lsn(self.file_manager, lsn)
+    }
+
+    fn undo(&mut self, block: &BlockId, offset: u64, old: &[u8]) -> Result<(), std::io::Error> {
+        self.pin(block)?;
+        self.page(block).bb.set_bytes_with_offset(offset, old);
+        let index = self.buffers[block];
+        self.buffer_manager.bufferpool[index].set_modified(None, self.txnum);
+        self.unpin(block);
+        Ok(())
+    }
+
+    fn log(&mut self, record: &LogRecord) -> Result<u64, std::io::Error> {
+        self.log_manager
+            .append(self.file_manager, &record.to_bytes())
+    }
+
+    pub(crate) fn pin(&mut self, block: &BlockId) -> Result<(), std::io::Error> {
+        let index = self
+            .buffer_manager
+            .pin(block, self.file_manager, self.log_manager)?;
+        self.buffers.insert(block.clone(), index);
+        self.pins.push(block.clone());
+        Ok(())
+    }
+
+    pub(crate) fn unpin(&mut self, block: &BlockId) {
+        let index = self.buffers[block];
+        self.buffer_manager.unpin(index);
+        let pos = self.pins.iter().position(|b| b == block).unwrap();
+        self.pins.remove(pos);
+        if !self.pins.contains(block) {
+            self.buffers.remove(block);
+        }
+    }
+
+    fn unpin_all(&mut self) {
+        for block in self.pins.drain(..) {
+            self.buffer_manager.unpin(self.buffers[&block]);
+        }
+        self.buffers.clear();
+    }
+
+    fn page(&mut self, block: &BlockId) -> &mut Page {
+        let index = *self
+            .buffers
+            .get(block)
+            .unwrap_or_else(|| panic!("block {} is not pinned", block));
+        &mut self.buffer_manager.bufferpool[index].contents
+    }
+
+    pub(crate) fn get_int(&mut self, block: &BlockId, offset: u64) -> u64 {
+        self.page(block).get_int(offset)
+    }
+
+    pub(crate) fn get_string(&mut self, block: &BlockId, offset: u64) -> String {
+        self.page(block).get_string(offset).to_string()
+    }
+
+    pub(crate) fn set_int(
+        &mut self,
+        block: &BlockId,
+        offset: u64,
+        value: u64,
+    ) -> Result<(), std::io::Error> {
+        self.log_update(block, offset, 8)?;
+        self.page(block).set_int(offset, value);
+        Ok(())
+    }
+
+    pub(crate) fn set_string(
+        &mut self,
+        block: &BlockId,
+        offset: u64,
+        value: &str,
+    ) -> Result<(), std::io::Error> {
+        self.log_update(block, offset, Page::max_length(value.len() as u64))?;
+        self.page(block).set_string(offset, value);
+        Ok(())
+    }
+
+    /// Writes `value` as is, without a length in front. The old bytes are
+    /// logged in pieces of at most a quarter block, so that each record fits
+    /// in a page of the log.
+    pub(crate) fn set_raw_bytes(
+        &mut self,
+        block: &BlockId,
+        offset: u64,
+        value: &[u8],
+    ) -> Result<(), std::io::Error> {
+        let piece = (self.block_size() / 4) as usize;
+        for (i, chunk) in value.chunks(piece).enumerate() {
+            let offset = offset + (i * piece) as u64;
+            self.log_update(block, offset, chunk.len() as u64)?;
+            self.page(block).bb.set_bytes_with_offset(offset, chunk);
+        }
+        Ok(())
+    }
+
+    /// Writes the block to disk ahead of the commit, for readers that go to
+    /// the file rather than through the buffer pool.
+    pub(crate) fn flush(&mut self, block: &BlockId) -> Result<(), std::io::Error> {
+        let index = self.buffers[block];
+        self.buffer_manager.bufferpool[index].flush(self.file_manager, self.log_manager)
+    }
+
+    fn start(&mut self) -> Result<(), std::io::Error> {
+        if !self.started {
+            self.log(&LogRecord::Start(self.txnum))?;
+            self.started = true;
+        }
+        Ok(())
+    }
+
+    // Logs the bytes about to be overwritten so they can be put back
+    fn log_update(&mut self, block: &BlockId, offset: u64, len: u64) -> Result<(), std::io::Error> {
+        self.start()?;
+        let old = self
+            .page(block)
+            .bb
+            .get_bytes_with_offset(offset, len)
+            .to_vec();
+        let lsn = self.log(&LogRecord::SetBytes {
+            txnum: self.txnum,
+            block: block.clone(),
+            offset,
+            old,
+        })?;
+        let index = self.buffers[block];
+        self.buffer_manager.bufferpool[index].set_modified(Some(lsn), self.txnum);
+        Ok(())
+    }
+
+    pub(crate) fn size(&self, filename: &str) -> Result<u64, std::io::Error> {
+        self.file_manager.size(filename)
+    }
+
+    pub(crate) fn append(&mut self, filename: &str) -> Result<BlockId, std::io::Error> {
+        self.file_manager.append(filename)
+    }
+
+    pub(crate) fn block_size(&self) -> u64 {
+        self.file_manager.blocksize
+    }
+
+    pub(crate) fn available_buffers(&self) -> u64 {
+        self.buffer_manager.available
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        layout::Layout, log_record::LogRecord, schema::Schema, simpledb::SimpleDB,
+        table_scan::TableScan,
+    };
+
+    use super::Transaction;
+
+    fn values(tx: &mut Transaction, layout: &Layout) -> Vec<(i64, String)> {
+        let mut ts = TableScan::new(tx, "T", layout.clone()).unwrap();
+        let mut values = Vec::new();
+        while ts.next(tx).unwrap() {
+            values.push((
+                ts.get_int(tx, "A").unwrap(),
+                ts.get_string(tx, "B").unwrap(),
+            ));
+        }
+        ts.close(tx);
+        values
+    }
+
+    fn update(tx: &mut Transaction, layout: &Layout) {
+        let mut ts = TableScan::new(tx, "T", layout.clone()).unwrap();
+        while ts.next(tx).unwrap() {
+            let a = ts.get_int(tx, "A").unwrap();
+            if a % 2 == 0 {
+                ts.delete(tx).unwrap();
+            } else {
+                ts.set_int(tx, "A", -a).unwrap();
+                ts.set_string(tx, "B", "a much longer value").unwrap();
+            }
+        }
+        ts.insert(tx).unwrap();
+        ts.set_int(tx, "A", 1000).unwrap();
+        ts.close(tx);
+    }
+
+    #[test]
+    fn test_rollback_and_recovery() {
+        let _ = std::fs::remove_dir_all("recoverytest");
+        let mut schema = Schema::new();
+        schema.add_int_field("A");
+        schema.add_string_field("B", 20);
+        let layout = Layout::new(schema);
+
+        let expected = {
+            let mut db = SimpleDB::new("recoverytest", 400, 8).unwrap();
+            let mut tx = Transaction::new(
+                &db.file_manager,
+                &mut db.log_manager,
+                &mut db.buffer_manager,
+            );
+            let mut ts = TableScan::new(&mut tx, "T", layout.clone()).unwrap();
+            for i in 0..40 {
+                ts.insert(&mut tx).unwrap();
+                ts.set_int(&mut tx, "A", i).unwrap();
+                ts.set_string(&mut tx, "B", &format!("rec{}", i)).unwrap();
+            }
+            ts.close(&mut tx);
+            tx.commit().unwrap();
+            let expected = values(&mut tx, &layout);
+            assert_eq!(expected.len(), 40);
+
+            update(&mut tx, &layout);
+            assert_ne!(values(&mut tx, &layout), expected);
+            tx.rollback().unwrap();
+            assert_eq!(values(&mut tx, &layout), expected);
+
+            // A crash after the changes reached the disk but before the commit
+            update(&mut tx, &layout);
+            let txnum = tx.txnum;
+            tx.buffer_manager
+                .flush_all(tx.file_manager, tx.log_manager, txnum)
+                .unwrap();
+            expected
+        };
+
+        let mut db = SimpleDB::new("recoverytest", 400, 8).unwrap();
+        let mut tx = Transaction::new(
+            &db.file_manager,
+            &mut db.log_manager,
+            &mut db.buffer_manager,
+        );
+        assert_eq!(values(&mut tx, &layout), expected);
+        tx.commit().unwrap();
+    }
+
+    #[test]
+    fn test_drop_file_recovery() {
+        let _ = std::fs::remove_dir_all("dropfiletest");
+        let exists = |f: &str| std::path::Path::new("dropfiletest").join(f).exists();
+        let mut schema = Schema::new();
+        schema.add_int_field("A");
+        schema.add_string_field("B", 20);
+        let layout = Layout::new(schema);
+
+        {
+            let mut db = SimpleDB::new("dropfiletest", 400, 8).unwrap();
+            let mut tx = Transaction::new(
+                &db.file_manager,
+                &mut db.log_manager,
+                &mut db.buffer_manager,
+            );
+            for tblname in ["T", "U"] {
+                let mut ts = TableScan::new(&mut tx, tblname, layout.clone()).unwrap();
+                ts.insert(&mut tx).unwrap();
+                ts.close(&mut tx);
+            }
+            tx.commit().unwrap();
+
+            // A crash after the commit record but before the deletion
+            tx.drop_file("T.tbl").unwrap();
+            tx.buffer_manager
+                .flush_all(tx.file_manager, tx.log_manager, tx.txnum)
+                .unwrap();
+            let lsn = tx.log(&LogRecord::Commit(tx.txnum)).unwrap();
+            tx.log_manager.flush_with_lsn(tx.file_manager, lsn).unwrap();
+            tx.pending_deletes.clear();
+        }
+        assert!(exists("T.tbl"));
+
+        {
+            let mut db = SimpleDB::new("dropfiletest", 400, 8).unwrap();
+            let mut tx = Transaction::new(
+                &db.file_manager,
+                &mut db.log_manager,
+                &mut db.buffer_manager,
+            );
+            // A crash before the commit keeps the file
+            tx.drop_file("U.tbl").unwrap();
+        }
+        assert!(!exists("T.tbl"));
+        assert!(exists("U.tbl"));
+
+        let mut db = SimpleDB::new("dropfiletest", 400, 8).unwrap();
+        let mut tx = Transaction::new(
+            &db.file_manager,
+            &mut db.log_manager,
+            &mut db.buffer_manager,
+        );
+        assert!(exists("U.tbl"));
+        let mut ts = TableScan::new(&mut tx, "U", layout).unwrap();
+        assert!(ts.next(&mut tx).unwrap());
+        ts.close(&mut tx);
+        tx.commit().unwrap();
+
+        // A rolled back drop is forgotten by the next commit
+        tx.drop_file("U.tbl").unwrap();
+        assert!(tx.is_dropping("U.tbl"));
+        tx.rollback().unwrap();
+        assert!(!tx.is_dropping("U.tbl"));
+        tx.commit().unwrap();
+        assert!(exists("U.tbl"));
+    }
+}
@@ -0,0 +1,90 @@
+use crate::{
+    plan::Plan, resultset::ColumnType, scan::Scan, schema::Schema, transaction::Transaction,
+    union_scan::UnionScan,
+};
+
+/// The records of one plan followed by those of the other, which gives its
+/// records under the field names of the first.
+pub(crate) struct UnionPlan {
+    p1: Box<dyn Plan>,
+    p2: Box<dyn Plan>,
+    schema: Schema,
+}
+
+impl UnionPlan {
+    pub(crate) fn new(p1: Box<dyn Plan>, p2: Box<dyn Plan>) -> Result<Self, std::io::Error> {
+        let schema = compound_schema(p1.schema(), p2.schema())?;
+        Ok(Self { p1, p2, schema })
+    }
+}
+
+impl Plan for UnionPlan {
+    fn open(&self, tx: &mut Transaction) -> Result<Box<dyn Scan>, std::io::Error> {
+        let s1 = self.p1.open(tx)?;
+        let s2 = self.p2.open(tx)?;
+        Ok(Box::new(UnionScan::new(
+            s1,
+            s2,
+            self.p1.schema().fields().to_vec(),
+            self.p2.schema().fields().to_vec(),
+        )))
+    }
+
+    fn blocks_accessed(&self) -> u64 {
+        self.p1.blocks_accessed() + self.p2.blocks_accessed()
+    }
+
+    fn records_output(&self) -> u64 {
+        self.p1.records_output() + self.p2.records_output()
+    }
+
+    fn distinct_values(&self, fldname: &str) -> u64 {
+        let other = self.p1.schema().fields().iter().position(|f| f == fldname);
+        let d2 = other.map_or(0, |i| {
+            self.p2.distinct_values(&self.p2.schema().fields()[i])
+        });
+        self.p1.distinct_values(fldname) + d2
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+}
+
+/// The fields of the result of two queries combined by a set operator:
+/// those of the first, with types wide enough for the values of both. The
+/// fields in the same position have to be of compatible types, a VARCHAR
+/// and a TEXT giving TEXT.
+pub(crate) fn compound_schema(s1: &Schema, s2: &Schema) -> Result<Schema, std::io::Error> {
+    if s1.fields().len() != s2.fields().len() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!(
+                "queries with {} and {} distinct fields cannot be combined",
+                s1.fields().len(),
+                s2.fields().len()
+            ),
+        ));
+    }
+    let mut schema = Schema::new();
+    for (f1, f2) in s1.fields().iter().zip(s2.fields()) {
+        let (t1, t2) = (s1.ctype(f1), s2.ctype(f2));
+        let (ctype, length) = match (t1, t2) {
+            _ if t1 == t2 => (t1, s1.length(f1).max(s2.length(f2))),
+            (ColumnType::VarChar, ColumnType::Text) | (ColumnType::Text, ColumnType::VarChar) => {
+                (ColumnType::Text, 0)
+            }
+            _ => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!(
+                        "field {} of type {:?} cannot be combined with field {} of type {:?}",
+                        f1, t1, f2, t2
+                    ),
+                ))
+            }
+        };
+        schema.add_field(f1, ctype, length);
+    }
+    Ok(schema)
+}
@@ -0,0 +1,152 @@
+use crate::{constant::Constant, scan::Scan, transaction::Transaction};
+
+/// The records of the first scan followed by those of the second, duplicates
+/// and all. The fields are the first scan's; a field of the second is known
+/// by the name of the field in the same position of the first.
+pub(crate) struct UnionScan<S1: Scan, S2: Scan> {
+    s1: S1,
+    s2: S2,
+    fields1: Vec<String>,
+    fields2: Vec<String>,
+    /// Whether the scan has gone on to `s2`.
+    in_second: bool,
+}
+
+impl<S1: Scan, S2: Scan> UnionScan<S1, S2> {
+    pub(crate) fn new(s1: S1, s2: S2, fields1: Vec<String>, fields2: Vec<String>) -> Self {
+        assert_eq!(fields1.len(), fields2.len());
+        Self {
+            s1,
+            s2,
+            fields1,
+            fields2,
+            in_second: false,
+        }
+    }
+
+    /// The name of the field in the scan the record is from.
+    fn field_of_current(&self, fldname: &str) -> Result<String, std::io::Error> {
+        match self.fields1.iter().position(|f| f == fldname) {
+            Some(i) if self.in_second => Ok(self.fields2[i].clone()),
+            Some(_) => Ok(fldname.to_string()),
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("field {} not found", fldname),
+            )),
+        }
+    }
+}
+
+impl<S1: Scan, S2: Scan> Scan for UnionScan<S1, S2> {
+    fn before_first(&mut self, tx: &mut Transaction) -> Result<(), std::io::Error> {
+        self.in_second = false;
+        self.s1.before_first(tx)?;
+        self.s2.before_first(tx)
+    }
+
+    fn next(&mut self, tx: &mut Transaction) -> Result<bool, std::io::Error> {
+        if !self.in_second {
+            if self.s1.next(tx)? {
+                return Ok(true);
+            }
+            self.in_second = true;
+        }
+        self.s2.next(tx)
+    }
+
+    fn get_int(&mut self, tx: &mut Transaction, fldname: &str) -> Result<i64, std::io::Error> {
+        let fldname = self.field_of_current(fldname)?;
+        if self.in_second {
+            self.s2.get_int(tx, &fldname)
+        } else {
+            self.s1.get_int(tx, &fldname)
+        }
+    }
+
+    fn get_string(
+        &mut self,
+        tx: &mut Transaction,
+        fldname: &str,
+    ) -> Result<String, std::io::Error> {
+        let fldname = self.field_of_current(fldname)?;
+        if self.in_second {
+            self.s2.get_string(tx, &fldname)
+        } else {
+            self.s1.get_string(tx, &fldname)
+        }
+    }
+
+    fn get_val(&mut self, tx: &mut Transaction, fldname: &str) -> Result<Constant, std::io::Error> {
+        let fldname = self.field_of_current(fldname)?;
+        if self.in_second {
+            self.s2.get_val(tx, &fldname)
+        } else {
+            self.s1.get_val(tx, &fldname)
+        }
+    }
+
+    fn has_field(&self, fldname: &str) -> bool {
+        self.fields1.iter().any(|f| f == fldname)
+    }
+
+    fn close(&mut self, tx: &mut Transaction) {
+        self.s1.close(tx);
+        self.s2.close(tx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        layout::Layout,
+        scan::Scan,
+        schema::Schema,
+        simpledb::SimpleDB,
+        table_scan::TableScan,
+        transaction::Transaction,
+    };
+
+    use super::UnionScan;
+
+    #[test]
+    fn test_union_scan() {
+        let _ = std::fs::remove_dir_all("uniontest");
+        let mut db = SimpleDB::new("uniontest", 400, 8).unwrap();
+        let mut tx = Transaction::new(
+            &db.file_manager,
+            &mut db.log_manager,
+            &mut db.buffer_manager,
+        );
+
+        let mut layouts = Vec::new();
+        for (tblname, fldname, values) in [("T", "A", vec![1, 2]), ("U", "C", vec![2, 3, 4])] {
+            let mut schema = Schema::new();
+            schema.add_int_field(fldname);
+            let layout = Layout::new(schema);
+            let mut ts = TableScan::new(&mut tx, tblname, layout.clone()).unwrap();
+            for value in values {
+                ts.insert(&mut tx).unwrap();
+                ts.set_int(&mut tx, fldname, value).unwrap();
+            }
+            ts.close(&mut tx);
+            layouts.push(layout);
+        }
+
+        let t = TableScan::new(&mut tx, "T", layouts[0].clone()).unwrap();
+        let u = TableScan::new(&mut tx, "U", layouts[1].clone()).unwrap();
+        let mut scan = UnionScan::new(t, u, vec!["A".to_string()], vec!["C".to_string()]);
+        assert!(scan.has_field("A"));
+        assert!(!scan.has_field("C"));
+        for _ in 0..2 {
+            let mut values = Vec::new();
+            scan.before_first(&mut tx).unwrap();
+            while scan.next(&mut tx).unwrap() {
+                values.push(scan.get_int(&mut tx, "A").unwrap());
+                assert!(scan.get_val(&mut tx, "C").is_err());
+            }
+            assert_eq!(values, [1, 2, 2, 3, 4]);
+        }
+        scan.close(&mut tx);
+        tx.commit().unwrap();
+    }
+}
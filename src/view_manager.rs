@@ -0,0 +1,225 @@
+use std::cell::RefCell;
+
+use crate::{
+    layout::Layout,
+    parser::parse_query,
+    query_data::QueryData,
+    schema::Schema,
+    table_manager::{TableManager, MAX_NAME},
+    table_scan::TableScan,
+    transaction::Transaction,
+};
+
+/// Keeps view definitions in the `viewcat` catalog table. The definition is
+/// the text of the view's query, stored as TEXT so it can be of any length.
+pub(crate) struct ViewManager {
+    vcat_layout: Layout,
+}
+
+impl ViewManager {
+    pub(crate) fn new(
+        is_new: bool,
+        table_manager: &TableManager,
+        tx: &mut Transaction,
+    ) -> Result<Self, std::io::Error> {
+        let mut schema = Schema::new();
+        schema.add_string_field("viewname", MAX_NAME);
+        schema.add_text_field("viewdef");
+        if is_new {
+            table_manager.create_table("viewcat", &schema, tx)?;
+        }
+
+        Ok(Self {
+            vcat_layout: Layout::new(schema),
+        })
+    }
+
+    pub(crate) fn create_view(
+        &self,
+        viewname: &str,
+        viewdef: &str,
+        tx: &mut Transaction,
+    ) -> Result<(), std::io::Error> {
+        let mut vcat = TableScan::new(tx, "viewcat", self.vcat_layout.clone())?;
+        vcat.insert(tx)?;
+        vcat.set_string(tx, "viewname", viewname)?;
+        vcat.set_string(tx, "viewdef", viewdef)?;
+        vcat.close(tx);
+        Ok(())
+    }
+
+    pub(crate) fn get_view_def(
+        &self,
+        viewname: &str,
+        tx: &mut Transaction,
+    ) -> Result<Option<String>, std::io::Error> {
+        let mut result = None;
+        let mut vcat = TableScan::new(tx, "viewcat", self.vcat_layout.clone())?;
+        while vcat.next(tx)? {
+            if vcat.get_string(tx, "viewname")? == viewname {
+                result = Some(vcat.get_string(tx, "viewdef")?);
+                break;
+            }
+        }
+        vcat.close(tx);
+        Ok(result)
+    }
+
+    /// Deletes the view's row from `viewcat`, returning whether there was one.
+    pub(crate) fn drop_view(
+        &self,
+        viewname: &str,
+        tx: &mut Transaction,
+    ) -> Result<bool, std::io::Error> {
+        let mut found = false;
+        let mut vcat = TableScan::new(tx, "viewcat", self.vcat_layout.clone())?;
+        while vcat.next(tx)? {
+            if vcat.get_string(tx, "viewname")? == viewname {
+                vcat.delete(tx)?;
+                found = true;
+            }
+        }
+        vcat.close(tx);
+        Ok(found)
+    }
+
+    /// The views that read from the table or view `name`, wherever it shows
+    /// up in the FROM clauses of their query.
+    pub(crate) fn views_referencing(
+        &self,
+        name: &str,
+        tx: &mut Transaction,
+    ) -> Result<Vec<String>, std::io::Error> {
+        self.views_where(tx, |query| {
+            let mut tables = Vec::new();
+            collect_tables(query, &mut tables);
+            tables.iter().any(|t| t == name)
+        })
+    }
+
+    /// The views that read from `tblname` and mention the field `fldname`,
+    /// qualified or not, anywhere in their query.
+    pub(crate) fn views_using_field(
+        &self,
+        tblname: &str,
+        fldname: &str,
+        tx: &mut Transaction,
+    ) -> Result<Vec<String>, std::io::Error> {
+        self.views_where(tx, |query| {
+            let mut tables = Vec::new();
+            collect_tables(query, &mut tables);
+            let fields = RefCell::new(Vec::new());
+            collect_fields(query, &fields);
+            let is_field = |f: &String| f.rsplit('.').next() == Some(fldname);
+            tables.iter().any(|t| t == tblname) && fields.borrow().iter().any(is_field)
+        })
+    }
+
+    fn views_where(
+        &self,
+        tx: &mut Transaction,
+        pred: impl Fn(&QueryData) -> bool,
+    ) -> Result<Vec<String>, std::io::Error> {
+        let mut result = Vec::new();
+        let mut vcat = TableScan::new(tx, "viewcat", self.vcat_layout.clone())?;
+        while vcat.next(tx)? {
+            let viewname = vcat.get_string(tx, "viewname")?;
+            let viewdef = vcat.get_string(tx, "viewdef")?;
+            let query = parse_query(&viewdef).map_err(|e| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("definition of view {} does not parse: {}", viewname, e),
+                )
+            })?;
+            if pred(&query) {
+                result.push(viewname);
+            }
+        }
+        vcat.close(tx);
+        Ok(result)
+    }
+}
+
+// The tables and views named in the FROM clauses, those of nested queries
+// included
+fn collect_tables(query: &QueryData, tables: &mut Vec<String>) {
+    for table in query.tables() {
+        if table.subquery.is_none() {
+            tables.push(table.tblname.clone());
+        }
+    }
+    for subquery in query.subqueries() {
+        collect_tables(subquery, tables);
+    }
+}
+
+// Every field reference, as written
+fn collect_fields(query: &QueryData, fields: &RefCell<Vec<String>>) {
+    query.map_fields(&|f| {
+        fields.borrow_mut().push(f.to_string());
+        None
+    });
+    fields.borrow_mut().extend(
+        query
+            .group_by
+            .iter()
+            .chain(query.order_by.iter().map(|k| &k.field))
+            .cloned(),
+    );
+    fields
+        .borrow_mut()
+        .extend(query.aggregates.iter().filter_map(|a| a.field.clone()));
+    for subquery in query.subqueries() {
+        collect_fields(subquery, fields);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{simpledb::SimpleDB, transaction::Transaction};
+
+    #[test]
+    fn test_view_manager() {
+        let _ = std::fs::remove_dir_all("viewmgrtest");
+        let viewdef = format!(
+            "select sname, gradyear from student where {}",
+            (0..40)
+                .map(|i| format!("majorid = {}", i))
+                .collect::<Vec<_>>()
+                .join(" or ")
+        );
+
+        {
+            let mut db = SimpleDB::new("viewmgrtest", 400, 8).unwrap();
+            let mut tx = Transaction::new(
+                &db.file_manager,
+                &mut db.log_manager,
+                &mut db.buffer_manager,
+            );
+            db.metadata_manager
+                .create_view("students", &viewdef, &mut tx)
+                .unwrap();
+            tx.commit().unwrap();
+        }
+
+        let mut db = SimpleDB::new("viewmgrtest", 400, 8).unwrap();
+        let mut tx = Transaction::new(
+            &db.file_manager,
+            &mut db.log_manager,
+            &mut db.buffer_manager,
+        );
+        assert_eq!(
+            db.metadata_manager
+                .get_view_def("students", &mut tx)
+                .unwrap(),
+            Some(viewdef)
+        );
+        assert_eq!(
+            db.metadata_manager
+                .get_view_def("missing", &mut tx)
+                .unwrap(),
+            None
+        );
+        tx.commit().unwrap();
+    }
+}